@@ -0,0 +1,56 @@
+/*!
+ * Throughput of the SIMD-accelerated exports on a 10k-point stroke
+ *
+ * Natively this measures the scalar loops. For the scalar/SIMD
+ * comparison, run it on a WASI runtime once as is and once with the
+ * simd128 kernels (see the README); the group name says which build
+ * ran.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use creovox_engine::{free_buffer, simd_available, smooth_stroke_v2, transform_stroke_in_place};
+
+const POINTS: usize = 10_000;
+
+fn stroke() -> Vec<f32> {
+    (0..POINTS)
+        .flat_map(|i| {
+            let x = i as f32 * 0.5;
+            [x, 80.0 * (x / 60.0).sin(), 0.5 + 0.4 * (x / 200.0).cos()]
+        })
+        .collect()
+}
+
+fn kernels(c: &mut Criterion) {
+    let points = stroke();
+    let build = if simd_available() { "simd128" } else { "scalar" };
+    let mut group = c.benchmark_group(format!("10k points ({build})"));
+    group.throughput(Throughput::Elements(POINTS as u64));
+
+    group.bench_function("smooth_stroke_v2", |b| {
+        b.iter(|| {
+            let result = smooth_stroke_v2(black_box(points.as_ptr()), points.len());
+            free_buffer(result.ptr(), result.byte_len());
+        })
+    });
+
+    // Alternately scaling up and back keeps the values in range across iterations
+    let mut transformed = points.clone();
+    let mut scale = 2.0f32;
+    group.bench_function("transform_stroke_in_place", |b| {
+        b.iter(|| {
+            let count = transformed.len();
+            transform_stroke_in_place(transformed.as_mut_ptr(), count, scale, 0.0, 1.0, 0.0, scale, -1.0, true);
+            scale = 1.0 / scale;
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, kernels);
+criterion_main!(benches);
@@ -0,0 +1,246 @@
+/*!
+ * Arc-length queries
+ *
+ * Stamp spacing, trimming, and playback all measure distance along the
+ * stroke. They share the routines here so a length reported by one
+ * feature lands on the same point in another.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "std")]
+use crate::error::{require_finite, run_export, run_scalar, CreoVexError, EngineError};
+use crate::float::math;
+#[cfg(feature = "std")]
+use crate::{read_points, serialize_points, serialize_records, EngineBuffer};
+
+/**
+ * Total x/y length of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Returns the sum of segment lengths in canvas units (0 for a single
+ * point). Equals the last entry of build_arclength_table.
+ *
+ * Errors: the read_points errors. On failure the result is NaN and the
+ * error is available through last_error_code()/last_error_message().
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_length(points_ptr: *const f32, points_len: usize) -> f32 {
+    run_scalar(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        Ok(polyline_length(points, 3) as f32)
+    })
+}
+
+/**
+ * Build the cumulative arc-length table of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: entry count (u32, one per input point)
+ * - N * 4 bytes: f32 distance from the first point to point i
+ *
+ * The first entry is always 0 and entries never decrease; duplicate
+ * points repeat the previous distance.
+ *
+ * Errors: the read_points errors.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn build_arclength_table(points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+
+        let table: Vec<f32> = cumulative_lengths(points, 3).into_iter().map(|s| s as f32).collect();
+        serialize_records(table.len() as u32, &table)
+    })
+}
+
+/**
+ * Interpolate the stroke at a distance along it
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: one point, [x, y, p] (see serialize_points)
+ *
+ * `s` is measured from the first point in canvas units. Position and
+ * pressure are interpolated linearly within the segment containing `s`;
+ * distances past the end clamp to the last point and negative distances
+ * to the first. A distance landing on duplicate points returns the last
+ * copy.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue for a NaN or
+ * infinite `s`.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn point_at_length(points_ptr: *const f32, points_len: usize, s: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let s = require_finite("s", s)?;
+
+        let table = cumulative_lengths(points, 3);
+        serialize_points(&interpolate_at_length(points, 3, &table, s as f64))
+    })
+}
+
+/**
+ * Trim distances off both ends of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: [x, y, p] points (see serialize_points)
+ *
+ * `trim_start` and `trim_end` are arc lengths in canvas units. The new
+ * ends are interpolated as in point_at_length, and the input points
+ * strictly between them are kept. A negative value extends that end
+ * instead, straight on along the nearest segment of nonzero length, with
+ * the end's pressure.
+ *
+ * When the trims meet or pass each other nothing is left, and the result
+ * is an empty stroke (a point count of 0) rather than an error. Zero on
+ * both ends returns the stroke with its end duplicates collapsed.
+ *
+ * Errors: the read_points errors, NonFiniteValue for a NaN or infinite
+ * trim, and InvalidParameter for extending a stroke of zero length (it
+ * has no direction).
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn trim_stroke(points_ptr: *const f32, points_len: usize, trim_start: f32, trim_end: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let trim_start = require_finite("trim_start", trim_start)? as f64;
+        let trim_end = require_finite("trim_end", trim_end)? as f64;
+
+        let table = cumulative_lengths(points, 3);
+        let total = table[table.len() - 1];
+        let (from, to) = (trim_start, total - trim_end);
+        if to < from || (to == from && (trim_start != 0.0 || trim_end != 0.0)) {
+            return serialize_points(&[]);
+        }
+        if total == 0.0 && (from < 0.0 || to > total) {
+            return Err(EngineError::new(CreoVexError::InvalidParameter, "cannot extend a stroke of zero length"));
+        }
+
+        // Distance along the stroke, extended past either end
+        let first = &points[..3];
+        let last = &points[points.len() - 3..];
+        let at = |s: f64| {
+            if s < 0.0 {
+                let next = points.chunks_exact(3).find(|point| point[..2] != first[..2]);
+                extend_past(first, next.expect("stroke has length"), -s)
+            } else if s > total {
+                let previous = points.chunks_exact(3).rev().find(|point| point[..2] != last[..2]);
+                extend_past(last, previous.expect("stroke has length"), s - total)
+            } else {
+                interpolate_at_length(points, 3, &table, s)
+            }
+        };
+        let mut trimmed = at(from);
+        if to > from {
+            for (point, &walked) in points.chunks_exact(3).zip(&table) {
+                if walked > from && walked < to {
+                    trimmed.extend_from_slice(point);
+                }
+            }
+            trimmed.extend(at(to));
+        }
+        serialize_points(&trimmed)
+    })
+}
+
+/**
+ * `end` moved `distance` further along the direction from `from`, with
+ * its pressure
+ */
+#[cfg(feature = "std")]
+fn extend_past(end: &[f32], from: &[f32], distance: f64) -> Vec<f32> {
+    let scale = distance / segment_length(from, end);
+    let x = end[0] as f64 + (end[0] - from[0]) as f64 * scale;
+    let y = end[1] as f64 + (end[1] - from[1]) as f64 * scale;
+    vec![x as f32, y as f32, end[2]]
+}
+
+/**
+ * Total x/y length of the polyline (f64 so long strokes stay exact enough)
+ */
+pub(crate) fn polyline_length(points: &[f32], stride: usize) -> f64 {
+    points
+        .chunks_exact(stride)
+        .zip(points.chunks_exact(stride).skip(1))
+        .map(|(a, b)| segment_length(a, b))
+        .sum()
+}
+
+/**
+ * Distance from the first point to each point, in the same summation
+ * order as polyline_length (so the last entry equals it exactly)
+ */
+pub(crate) fn cumulative_lengths(points: &[f32], stride: usize) -> Vec<f64> {
+    let mut table = Vec::with_capacity(points.len() / stride);
+    let mut walked = 0.0f64;
+    table.push(walked);
+    for (a, b) in points.chunks_exact(stride).zip(points.chunks_exact(stride).skip(1)) {
+        walked += segment_length(a, b);
+        table.push(walked);
+    }
+    table
+}
+
+/**
+ * Point at distance `s` using a cumulative_lengths table, clamped to the
+ * ends
+ *
+ * Points are `stride` floats wide; every channel after x/y is
+ * interpolated like pressure.
+ */
+pub(crate) fn interpolate_at_length(points: &[f32], stride: usize, table: &[f64], s: f64) -> Vec<f32> {
+    let total = table[table.len() - 1];
+    if s <= 0.0 || table.len() == 1 {
+        return points[..stride].to_vec();
+    }
+    if s >= total {
+        return points[points.len() - stride..].to_vec();
+    }
+
+    // First point strictly beyond s; its segment has positive length
+    let end = table.partition_point(|&walked| walked <= s);
+    let a = &points[(end - 1) * stride..end * stride];
+    let b = &points[end * stride..(end + 1) * stride];
+    let t = ((s - table[end - 1]) / (table[end] - table[end - 1])) as f32;
+    a.iter().zip(b).map(|(&a, &b)| a + (b - a) * t).collect()
+}
+
+/**
+ * Part of the stroke between distances `from` and `to` (from <= to),
+ * using a cumulative_lengths table
+ *
+ * Both ends are interpolated as in interpolate_at_length, with the input
+ * points strictly between them kept. Equal distances give one point.
+ */
+pub(crate) fn slice_at_lengths(points: &[f32], stride: usize, table: &[f64], from: f64, to: f64) -> Vec<f32> {
+    let mut slice = interpolate_at_length(points, stride, table, from);
+    if to > from {
+        for (point, &walked) in points.chunks_exact(stride).zip(table) {
+            if walked > from && walked < to {
+                slice.extend_from_slice(point);
+            }
+        }
+        slice.extend(interpolate_at_length(points, stride, table, to));
+    }
+    slice
+}
+
+pub(crate) fn segment_length(a: &[f32], b: &[f32]) -> f64 {
+    let dx = (b[0] - a[0]) as f64;
+    let dy = (b[1] - a[1]) as f64;
+    math::sqrt(dx * dx + dy * dy)
+}
@@ -0,0 +1,483 @@
+/*!
+ * Cubic Bézier curve fitting and flattening
+ *
+ * fit_curve turns strokes into Bézier segments; flatten_beziers turns
+ * segments (SVG import, the pen tool, or fit_curve's own output) back
+ * into stroke points for the rest of the pipeline.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Curve Fitting, 0.5px max error)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_positive, run_export, CreoVexError, EngineError};
+use crate::resample::MAX_RESAMPLED_POINTS;
+use crate::validate::validate_floats;
+use crate::{batch_stroke_values, read_packed, read_points, serialize_records, serialize_strokes, EngineBuffer};
+
+/**
+ * Floats per fitted segment: four control points plus averaged pressure
+ */
+const SEGMENT_FLOATS: usize = 9;
+
+/**
+ * Newton-Raphson reparameterization passes before falling back to a split
+ */
+const MAX_REPARAMETERIZE: usize = 4;
+
+/**
+ * Floats per flatten_beziers input segment: four control points plus
+ * start and end pressure
+ */
+const FLATTEN_SEGMENT_FLOATS: usize = 10;
+
+/**
+ * Deepest de Casteljau subdivision flatten_beziers applies to one segment
+ * (at most 2^16 pieces), so cusps and tolerances below f32 precision
+ * still terminate
+ */
+const MAX_FLATTEN_DEPTH: u32 = 16;
+
+type Vec2 = (f32, f32);
+
+/**
+ * Fit stroke to a sequence of cubic Bézier segments
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: segment count (u32)
+ * - N * 36 bytes: f32 records [x0, y0, x1, y1, x2, y2, x3, y3, pressure]
+ *
+ * Each record holds the start point, two handles, and end point of one
+ * segment, followed by the mean pressure of the input points it covers.
+ * Consecutive segments share their joining control point.
+ *
+ * Algorithm: Schneider least-squares fitting with Newton-Raphson
+ * reparameterization, recursively splitting at the point of maximum
+ * deviation until every input point lies within `max_error` canvas units.
+ *
+ * Inputs with fewer than three distinct points produce a single degenerate
+ * segment (a straight line, or all four control points at one position).
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a NaN or non-positive max_error.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn fit_curve(points_ptr: *const f32, points_len: usize, max_error: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let max_error = require_positive("max_error", max_error)?;
+
+        let segments = fit_cubic_segments(points, max_error);
+        serialize_records((segments.len() / SEGMENT_FLOATS) as u32, &segments)
+    })
+}
+
+/**
+ * Flatten cubic Bézier paths into strokes
+ *
+ * Input format: a batch container (see read_batch) holding one list per
+ * path, with 10-float segment records
+ * [x0, y0, x1, y1, x2, y2, x3, y3, p0, p3]: start point, two handles, end
+ * point, and the pressure at the start and end of the segment
+ *
+ * Output format: the batch container holding one stroke per path, in
+ * order, without a status array
+ *
+ * Every segment is subdivided at its midpoint (de Casteljau) until both
+ * handles lie within `tolerance` canvas units of the piece's chord, so
+ * the polyline stays within `tolerance` of the true curve; straight
+ * segments become one line. Subdivision stops after MAX_FLATTEN_DEPTH
+ * levels, so cusps and segments with coincident control points always
+ * terminate. Pressure interpolates linearly in the curve parameter from
+ * p0 to p3. A segment that does not start where the previous one ended
+ * is joined to it by a straight line; a path with no segments gives an
+ * empty stroke.
+ *
+ * Errors: NullInput/InvalidLength for a null or malformed container,
+ * NonFiniteValue for a NaN or infinite value, NonFiniteValue/
+ * InvalidParameter for a NaN or non-positive tolerance, and
+ * InvalidParameter when the strokes would exceed MAX_RESAMPLED_POINTS
+ * points.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn flatten_beziers(segments_ptr: *const u8, segments_len: usize, tolerance: f32) -> EngineBuffer {
+    run_export(|| {
+        let tolerance = require_positive("tolerance", tolerance)?;
+
+        let mut strokes = Vec::new();
+        let mut total_points = 0;
+        for (index, bytes) in read_packed("segments", segments_ptr, segments_len, FLATTEN_SEGMENT_FLOATS)?
+            .into_iter()
+            .enumerate()
+        {
+            let values = batch_stroke_values(bytes);
+            let values = validate_floats(&values, FLATTEN_SEGMENT_FLOATS)?;
+            if values.iter().any(|value| !value.is_finite()) {
+                return Err(EngineError::new(
+                    CreoVexError::NonFiniteValue,
+                    format!("path {index} has a non-finite value"),
+                ));
+            }
+
+            let stroke = flatten_path(&values, tolerance);
+            total_points += stroke.len() / 3;
+            if total_points > MAX_RESAMPLED_POINTS {
+                return Err(EngineError::new(
+                    CreoVexError::InvalidParameter,
+                    format!("flattened paths exceed {MAX_RESAMPLED_POINTS} points; raise tolerance"),
+                ));
+            }
+            strokes.push(stroke);
+        }
+        serialize_strokes(&strokes)
+    })
+}
+
+/**
+ * [x, y, p] points of one flatten_beziers path
+ */
+fn flatten_path(segments: &[f32], tolerance: f32) -> Vec<f32> {
+    let mut points: Vec<f32> = Vec::new();
+    for segment in segments.chunks_exact(FLATTEN_SEGMENT_FLOATS) {
+        let curve = [
+            (segment[0], segment[1]),
+            (segment[2], segment[3]),
+            (segment[4], segment[5]),
+            (segment[6], segment[7]),
+        ];
+        let (p0, p3) = (segment[8], segment[9]);
+        if points.len() < 3 || points[points.len() - 3..points.len() - 1] != [curve[0].0, curve[0].1] {
+            points.extend_from_slice(&[curve[0].0, curve[0].1, p0]);
+        }
+
+        // Explicit work stack (left half processed first) keeps the
+        // points ordered without recursing on the small wasm stack
+        let mut stack = vec![(curve, 0.0f32, 1.0f32, 0u32)];
+        while let Some((curve, t0, t1, depth)) = stack.pop() {
+            let flat = chord_distance(curve[1], curve[0], curve[3]).max(chord_distance(curve[2], curve[0], curve[3]));
+            if flat <= tolerance || depth == MAX_FLATTEN_DEPTH {
+                let pressure = if t1 == 1.0 { p3 } else { p0 + (p3 - p0) * t1 };
+                points.extend_from_slice(&[curve[3].0, curve[3].1, pressure]);
+                continue;
+            }
+
+            let (left, right) = split_curve(&curve);
+            let middle = (t0 + t1) / 2.0;
+            stack.push((right, middle, t1, depth + 1));
+            stack.push((left, t0, middle, depth + 1));
+        }
+    }
+    points
+}
+
+/**
+ * The two halves of a cubic at t = 0.5 (de Casteljau)
+ */
+fn split_curve(curve: &[Vec2; 4]) -> ([Vec2; 4], [Vec2; 4]) {
+    let [a, b, c, d] = *curve;
+    let (ab, bc, cd) = (lerp(a, b, 0.5), lerp(b, c, 0.5), lerp(c, d, 0.5));
+    let (abc, bcd) = (lerp(ab, bc, 0.5), lerp(bc, cd, 0.5));
+    let middle = lerp(abc, bcd, 0.5);
+    ([a, ab, abc, middle], [middle, bcd, cd, d])
+}
+
+/**
+ * Distance from `p` to the segment from `a` to `b`
+ */
+fn chord_distance(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let chord = sub(b, a);
+    let along = length_sq(chord);
+    let t = if along > 0.0 { (dot(sub(p, a), chord) / along).clamp(0.0, 1.0) } else { 0.0 };
+    length_sq(sub(p, lerp(a, b, t))).sqrt()
+}
+
+/**
+ * Fit the whole stroke, returning flattened segment records
+ */
+pub(crate) fn fit_cubic_segments(points: &[f32], max_error: f32) -> Vec<f32> {
+    let (positions, pressures) = distinct_positions(points);
+    let count = positions.len();
+    let mut segments = Vec::new();
+
+    if count == 1 {
+        let p = positions[0];
+        push_segment(&mut segments, [p, p, p, p], pressures[0]);
+        return segments;
+    }
+
+    let left_tangent = normalize(sub(positions[1], positions[0]));
+    let right_tangent = normalize(sub(positions[count - 2], positions[count - 1]));
+
+    // Explicit work stack (left half processed first) keeps output ordered
+    // without recursing on the small wasm stack.
+    let mut stack = vec![(0usize, count - 1, left_tangent, right_tangent)];
+    let error_sq = max_error * max_error;
+
+    while let Some((first, last, t1, t2)) = stack.pop() {
+        let pressure = mean(&pressures[first..=last]);
+
+        if last - first == 1 {
+            let curve = heuristic_curve(positions[first], positions[last], t1, t2);
+            push_segment(&mut segments, curve, pressure);
+            continue;
+        }
+
+        let span = &positions[first..=last];
+        let mut u = chord_length_parameterize(span);
+        let mut curve = generate_bezier(span, &u, t1, t2);
+        let (mut worst_sq, mut split) = max_deviation(span, &curve, &u);
+
+        if worst_sq < error_sq {
+            push_segment(&mut segments, curve, pressure);
+            continue;
+        }
+
+        // Close misses are often fixed by a better parameterization
+        if worst_sq < error_sq * 4.0 {
+            for _ in 0..MAX_REPARAMETERIZE {
+                u = reparameterize(span, &u, &curve);
+                curve = generate_bezier(span, &u, t1, t2);
+                (worst_sq, split) = max_deviation(span, &curve, &u);
+                if worst_sq < error_sq {
+                    break;
+                }
+            }
+            if worst_sq < error_sq {
+                push_segment(&mut segments, curve, pressure);
+                continue;
+            }
+        }
+
+        // Split at the worst point (always strictly interior)
+        let split = (first + split).clamp(first + 1, last - 1);
+        let (left_end, right_start) = split_tangents(&positions, split);
+        stack.push((split, last, right_start, t2));
+        stack.push((first, split, t1, left_end));
+    }
+
+    segments
+}
+
+/**
+ * Split triplets into positions and pressures, dropping consecutive
+ * duplicate positions (they break chord-length parameterization)
+ */
+fn distinct_positions(points: &[f32]) -> (Vec<Vec2>, Vec<f32>) {
+    let mut positions: Vec<Vec2> = Vec::with_capacity(points.len() / 3);
+    let mut pressures = Vec::with_capacity(points.len() / 3);
+
+    for point in points.chunks_exact(3) {
+        let p = (point[0], point[1]);
+        if let Some(&last) = positions.last() {
+            if length_sq(sub(p, last)) == 0.0 {
+                continue;
+            }
+        }
+        positions.push(p);
+        pressures.push(point[2]);
+    }
+
+    (positions, pressures)
+}
+
+fn push_segment(segments: &mut Vec<f32>, curve: [Vec2; 4], pressure: f32) {
+    for (x, y) in curve {
+        segments.push(x);
+        segments.push(y);
+    }
+    segments.push(pressure);
+}
+
+/**
+ * Handles at one third of the chord along each tangent
+ */
+fn heuristic_curve(p0: Vec2, p3: Vec2, t1: Vec2, t2: Vec2) -> [Vec2; 4] {
+    let dist = length_sq(sub(p3, p0)).sqrt() / 3.0;
+    [p0, add(p0, scale(t1, dist)), add(p3, scale(t2, dist)), p3]
+}
+
+fn chord_length_parameterize(span: &[Vec2]) -> Vec<f32> {
+    let mut u = Vec::with_capacity(span.len());
+    u.push(0.0);
+    for i in 1..span.len() {
+        let d = length_sq(sub(span[i], span[i - 1])).sqrt();
+        u.push(u[i - 1] + d);
+    }
+
+    let total = u[span.len() - 1];
+    for value in u.iter_mut() {
+        *value /= total;
+    }
+    u
+}
+
+/**
+ * Least-squares handle lengths for fixed endpoint tangents
+ */
+fn generate_bezier(span: &[Vec2], u: &[f32], t1: Vec2, t2: Vec2) -> [Vec2; 4] {
+    let p0 = span[0];
+    let p3 = span[span.len() - 1];
+
+    let mut c = [[0.0f32; 2]; 2];
+    let mut x = [0.0f32; 2];
+
+    for (&point, &t) in span.iter().zip(u) {
+        let [b0, b1, b2, b3] = bernstein(t);
+        let a0 = scale(t1, b1);
+        let a1 = scale(t2, b2);
+
+        c[0][0] += dot(a0, a0);
+        c[0][1] += dot(a0, a1);
+        c[1][1] += dot(a1, a1);
+
+        let base = add(scale(p0, b0 + b1), scale(p3, b2 + b3));
+        let residual = sub(point, base);
+        x[0] += dot(a0, residual);
+        x[1] += dot(a1, residual);
+    }
+    c[1][0] = c[0][1];
+
+    let det_c0_c1 = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let det_c0_x = c[0][0] * x[1] - c[1][0] * x[0];
+    let det_x_c1 = x[0] * c[1][1] - x[1] * c[0][1];
+
+    let (alpha_l, alpha_r) = if det_c0_c1 == 0.0 {
+        (0.0, 0.0)
+    } else {
+        (det_x_c1 / det_c0_c1, det_c0_x / det_c0_c1)
+    };
+
+    // Degenerate or backwards handles: fall back to the chord heuristic
+    let seg_length = length_sq(sub(p3, p0)).sqrt();
+    let epsilon = 1e-6 * seg_length;
+    if !(alpha_l >= epsilon && alpha_r >= epsilon) {
+        return heuristic_curve(p0, p3, t1, t2);
+    }
+
+    [p0, add(p0, scale(t1, alpha_l)), add(p3, scale(t2, alpha_r)), p3]
+}
+
+/**
+ * Largest squared deviation and its index within the span
+ */
+fn max_deviation(span: &[Vec2], curve: &[Vec2; 4], u: &[f32]) -> (f32, usize) {
+    let mut worst = 0.0f32;
+    let mut index = span.len() / 2;
+    for i in 1..span.len() - 1 {
+        let d = length_sq(sub(evaluate(curve, u[i]), span[i]));
+        if d >= worst {
+            worst = d;
+            index = i;
+        }
+    }
+    (worst, index)
+}
+
+/**
+ * One Newton-Raphson step per point towards its closest curve parameter
+ */
+fn reparameterize(span: &[Vec2], u: &[f32], curve: &[Vec2; 4]) -> Vec<f32> {
+    let d1 = [
+        scale(sub(curve[1], curve[0]), 3.0),
+        scale(sub(curve[2], curve[1]), 3.0),
+        scale(sub(curve[3], curve[2]), 3.0),
+    ];
+    let d2 = [scale(sub(d1[1], d1[0]), 2.0), scale(sub(d1[2], d1[1]), 2.0)];
+
+    span.iter()
+        .zip(u)
+        .map(|(&point, &t)| {
+            let q = evaluate(curve, t);
+            let q1 = evaluate_quadratic(&d1, t);
+            let q2 = lerp(d2[0], d2[1], t);
+            let diff = sub(q, point);
+            let numerator = dot(diff, q1);
+            let denominator = dot(q1, q1) + dot(diff, q2);
+            if denominator == 0.0 || !denominator.is_finite() {
+                t
+            } else {
+                (t - numerator / denominator).clamp(0.0, 1.0)
+            }
+        })
+        .collect()
+}
+
+/**
+ * Tangents at an interior split point
+ *
+ * Returns the end tangent of the left piece (pointing backwards) and the
+ * start tangent of the right piece. They are opposite unless the stroke
+ * doubles back onto itself, in which case each side follows its own
+ * neighboring segment so the cusp stays sharp.
+ */
+fn split_tangents(positions: &[Vec2], index: usize) -> (Vec2, Vec2) {
+    let chord = sub(positions[index - 1], positions[index + 1]);
+    if length_sq(chord) > 0.0 {
+        let center = normalize(chord);
+        (center, (-center.0, -center.1))
+    } else {
+        (
+            normalize(sub(positions[index - 1], positions[index])),
+            normalize(sub(positions[index + 1], positions[index])),
+        )
+    }
+}
+
+fn bernstein(t: f32) -> [f32; 4] {
+    let mt = 1.0 - t;
+    [mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t]
+}
+
+fn evaluate(curve: &[Vec2; 4], t: f32) -> Vec2 {
+    let [b0, b1, b2, b3] = bernstein(t);
+    (
+        curve[0].0 * b0 + curve[1].0 * b1 + curve[2].0 * b2 + curve[3].0 * b3,
+        curve[0].1 * b0 + curve[1].1 * b1 + curve[2].1 * b2 + curve[3].1 * b3,
+    )
+}
+
+fn evaluate_quadratic(curve: &[Vec2; 3], t: f32) -> Vec2 {
+    let mt = 1.0 - t;
+    let (b0, b1, b2) = (mt * mt, 2.0 * mt * t, t * t);
+    (
+        curve[0].0 * b0 + curve[1].0 * b1 + curve[2].0 * b2,
+        curve[0].1 * b0 + curve[1].1 * b1 + curve[2].1 * b2,
+    )
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+fn add(a: Vec2, b: Vec2) -> Vec2 {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: Vec2, b: Vec2) -> Vec2 {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(a: Vec2, s: f32) -> Vec2 {
+    (a.0 * s, a.1 * s)
+}
+
+fn dot(a: Vec2, b: Vec2) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn lerp(a: Vec2, b: Vec2, t: f32) -> Vec2 {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn length_sq(a: Vec2) -> f32 {
+    dot(a, a)
+}
+
+fn normalize(a: Vec2) -> Vec2 {
+    let len = length_sq(a).sqrt();
+    (a.0 / len, a.1 / len)
+}
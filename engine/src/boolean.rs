@@ -0,0 +1,546 @@
+/*!
+ * Boolean operations on closed outlines
+ *
+ * Merge-selected-shapes and the vector eraser on filled shapes combine
+ * outline polygons. Both shapes are cut into one planar graph (every
+ * crossing, touch, and shared stretch becomes a vertex), each edge of
+ * the graph is classified by what lies on either side of it, and the
+ * edges separating kept area from dropped area are linked back into
+ * rings. Shared edges and vertices lying on edges need no special case:
+ * they are just graph edges with both shapes on one side.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Editing)
+ */
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{run_export, CreoVexError, EngineError, EngineResult};
+use crate::intersect::{collect_hits, Segment, INTERSECTION_EPSILON};
+use crate::validate::validate_floats;
+use crate::{batch_stroke_values, read_packed, serialize_packed, EngineBuffer};
+
+/**
+ * How polygon_boolean combines its two shapes
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BooleanOp {
+    /** Area inside either shape */
+    Union = 0,
+    /** Area inside A but not B */
+    Subtract = 1,
+    /** Area inside both shapes */
+    Intersect = 2,
+}
+
+impl BooleanOp {
+    fn keeps(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            BooleanOp::Union => in_a || in_b,
+            BooleanOp::Subtract => in_a && !in_b,
+            BooleanOp::Intersect => in_a && in_b,
+        }
+    }
+}
+
+/**
+ * Combine two shapes made of closed rings
+ *
+ * Input format: A then B, each in the stroke_outline output layout:
+ * - 4 bytes: ring count R (u32)
+ * - R * 4 bytes: vertex count of each ring (u32)
+ * - f32 [x, y] pairs of every ring, concatenated in order
+ *
+ * Output format: the same layout
+ *
+ * Rings are implicitly closed (repeating the first vertex is allowed)
+ * and a shape covers the points its rings wind around a nonzero number
+ * of times, so stroke_outline results and earlier polygon_boolean
+ * results can be passed straight in. Either shape may have no rings.
+ *
+ * Result rings do not cross or overlap each other or themselves, though
+ * they may touch at a vertex (two squares meeting at a corner). Outer
+ * rings have positive signed area (x0 * y1 - x1 * y0 summed) and holes
+ * negative, so the result fills correctly with either fill rule.
+ * Vertices closer than INTERSECTION_EPSILON merge, shared edges and
+ * vertices touching an edge are resolved exactly, and vertices left on a
+ * straight run are dropped.
+ *
+ * Errors: NullInput/InvalidLength for a null or malformed container;
+ * NonFiniteValue for a NaN or infinite coordinate.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn polygon_boolean(a_ptr: *const u8, a_len: usize, b_ptr: *const u8, b_len: usize, op: BooleanOp) -> EngineBuffer {
+    run_export(|| {
+        let a = read_rings("a", a_ptr, a_len)?;
+        let b = read_rings("b", b_ptr, b_len)?;
+
+        let rings: Vec<Vec<f32>> = combine(&[a, b], op)
+            .into_iter()
+            .map(|ring| ring.iter().flat_map(|vertex| [vertex[0] as f32, vertex[1] as f32]).collect())
+            .collect();
+        serialize_packed(&rings, 2)
+    })
+}
+
+fn read_rings(name: &str, rings_ptr: *const u8, rings_len: usize) -> EngineResult<Vec<Vec<[f64; 2]>>> {
+    let mut rings = Vec::new();
+    for (index, bytes) in read_packed(name, rings_ptr, rings_len, 2)?.into_iter().enumerate() {
+        let values = batch_stroke_values(bytes);
+        let values = validate_floats(&values, 2)?;
+        if values.iter().any(|value| !value.is_finite()) {
+            return Err(EngineError::new(
+                CreoVexError::NonFiniteValue,
+                format!("{name} ring {index} has a non-finite coordinate"),
+            ));
+        }
+        rings.push(values.chunks_exact(2).map(|pair| [pair[0] as f64, pair[1] as f64]).collect());
+    }
+    Ok(rings)
+}
+
+/**
+ * Rings bounding the area `op` keeps of `shapes` (A, B)
+ */
+fn combine(shapes: &[Vec<Vec<[f64; 2]>>; 2], op: BooleanOp) -> Vec<Vec<[f64; 2]>> {
+    let graph = PlanarGraph::build(shapes);
+
+    // Directed so the kept area is on the left; an edge with the same
+    // answer on both sides is not on the boundary
+    let mut boundary = Vec::new();
+    for (&(lo, hi), net) in &graph.edges {
+        let (a_left, a_right) = graph.windings(0, lo, hi, net[0]);
+        let (b_left, b_right) = graph.windings(1, lo, hi, net[1]);
+        let keep_left = op.keeps(a_left != 0, b_left != 0);
+        if keep_left != op.keeps(a_right != 0, b_right != 0) {
+            boundary.push(if keep_left { (lo, hi) } else { (hi, lo) });
+        }
+    }
+
+    link_rings(&graph.vertices, &boundary)
+}
+
+/**
+ * Both shapes' edges split at every point where they meet, with the
+ * split points merged into shared vertices
+ */
+struct PlanarGraph {
+    vertices: Vec<[f64; 2]>,
+    /** Split edges of each shape, directed as in their ring */
+    pieces: [Vec<(usize, usize)>; 2],
+    /**
+     * Undirected edges (lower vertex first) with the net number of
+     * times each shape runs along them from lower to higher vertex
+     */
+    edges: BTreeMap<(usize, usize), [i32; 2]>,
+}
+
+impl PlanarGraph {
+    fn build(shapes: &[Vec<Vec<[f64; 2]>>; 2]) -> Self {
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut owners = Vec::new();
+        for (shape, rings) in shapes.iter().enumerate() {
+            for ring in rings {
+                for (i, &start) in ring.iter().enumerate() {
+                    let end = ring[(i + 1) % ring.len()];
+                    if start != end {
+                        segments.push([start, end]);
+                        owners.push(shape);
+                    }
+                }
+            }
+        }
+
+        // Every segment is cut at its ends and wherever another touches it
+        let mut cuts: Vec<Vec<(f64, [f64; 2])>> =
+            segments.iter().map(|&[start, end]| vec![(0.0, start), (1.0, end)]).collect();
+        for hit in collect_hits(&segments, &segments, |i, j| j > i) {
+            cuts[hit.seg_a].push((hit.t_a, [hit.x, hit.y]));
+            cuts[hit.seg_b].push((hit.t_b, [hit.x, hit.y]));
+        }
+
+        let mut welder = Welder::default();
+        let mut pieces = [Vec::new(), Vec::new()];
+        let mut edges = BTreeMap::new();
+        for (mut points, &shape) in cuts.into_iter().zip(&owners) {
+            points.sort_by(|p, q| p.0.total_cmp(&q.0));
+            let ids: Vec<usize> = points.iter().map(|&(_, point)| welder.weld(point)).collect();
+            for pair in ids.windows(2) {
+                let (from, to) = (pair[0], pair[1]);
+                if from == to {
+                    continue;
+                }
+                pieces[shape].push((from, to));
+                let net: &mut [i32; 2] = edges.entry((from.min(to), from.max(to))).or_default();
+                net[shape] += if from < to { 1 } else { -1 };
+            }
+        }
+
+        PlanarGraph { vertices: welder.vertices, pieces, edges }
+    }
+
+    /**
+     * Winding number of `shape` just left and just right of edge
+     * (`lo`, `hi`), read in the lo -> hi direction
+     *
+     * The winding at the edge's midpoint with the edge itself removed is
+     * found by casting a ray along whichever axis crosses the edge more
+     * steeply. That is the winding on the side whose own ray would miss
+     * the edge; the other side's ray also crosses it, adding the shape's
+     * net crossings of the edge.
+     */
+    fn windings(&self, shape: usize, lo: usize, hi: usize, net: i32) -> (i32, i32) {
+        let (p, q) = (self.vertices[lo], self.vertices[hi]);
+        let mid = [(p[0] + q[0]) / 2.0, (p[1] + q[1]) / 2.0];
+        let (dx, dy) = (q[0] - p[0], q[1] - p[1]);
+        let along_x = dy.abs() >= dx.abs();
+
+        let mut winding = 0;
+        for &(from, to) in &self.pieces[shape] {
+            if (from.min(to), from.max(to)) == (lo, hi) {
+                continue;
+            }
+            let (a, b) = (self.vertices[from], self.vertices[to]);
+            winding += if along_x {
+                // Ray towards +x; counter-clockwise loops cross it upwards
+                if (a[1] > mid[1]) != (b[1] > mid[1])
+                    && a[0] + (mid[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) > mid[0]
+                {
+                    if b[1] > a[1] { 1 } else { -1 }
+                } else {
+                    0
+                }
+            } else if (a[0] > mid[0]) != (b[0] > mid[0])
+                && a[1] + (mid[0] - a[0]) / (b[0] - a[0]) * (b[1] - a[1]) > mid[1]
+            {
+                // Ray towards +y; counter-clockwise loops cross it leftwards
+                if b[0] < a[0] { 1 } else { -1 }
+            } else {
+                0
+            };
+        }
+
+        // Crossing from the right of an edge to its left adds its net
+        let left_ray_crosses = if along_x { dy > 0.0 } else { dx < 0.0 };
+        if left_ray_crosses {
+            (winding + net, winding)
+        } else {
+            (winding, winding - net)
+        }
+    }
+}
+
+/**
+ * Merges points closer than INTERSECTION_EPSILON into one vertex
+ */
+#[derive(Default)]
+struct Welder {
+    vertices: Vec<[f64; 2]>,
+    cells: BTreeMap<(i64, i64), Vec<usize>>,
+}
+
+impl Welder {
+    fn weld(&mut self, point: [f64; 2]) -> usize {
+        let cell = |value: f64| (value / INTERSECTION_EPSILON).floor() as i64;
+        let (cx, cy) = (cell(point[0]), cell(point[1]));
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                let nearby = self.cells.get(&(x, y)).into_iter().flatten();
+                if let Some(&id) = nearby.into_iter().find(|&&id| {
+                    let (dx, dy) = (self.vertices[id][0] - point[0], self.vertices[id][1] - point[1]);
+                    dx * dx + dy * dy < INTERSECTION_EPSILON * INTERSECTION_EPSILON
+                }) {
+                    return id;
+                }
+            }
+        }
+        self.vertices.push(point);
+        self.cells.entry((cx, cy)).or_default().push(self.vertices.len() - 1);
+        self.vertices.len() - 1
+    }
+}
+
+/**
+ * Chain directed boundary edges into rings
+ *
+ * Where several rings meet at one vertex, each ring takes the outgoing
+ * edge turning furthest to the right, which keeps the kept area on the
+ * left tight against the ring so rings touching at a point stay apart.
+ */
+fn link_rings(vertices: &[[f64; 2]], boundary: &[(usize, usize)]) -> Vec<Vec<[f64; 2]>> {
+    let mut outgoing: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (index, &(from, _)) in boundary.iter().enumerate() {
+        outgoing.entry(from).or_default().push(index);
+    }
+    let angle = |from: usize, to: usize| {
+        let (a, b) = (vertices[from], vertices[to]);
+        (b[1] - a[1]).atan2(b[0] - a[0])
+    };
+
+    let mut used = vec![false; boundary.len()];
+    let mut rings = Vec::new();
+    for first in 0..boundary.len() {
+        if used[first] {
+            continue;
+        }
+        let mut ring = Vec::new();
+        let mut edge = first;
+        loop {
+            used[edge] = true;
+            let (from, to) = boundary[edge];
+            ring.push(from);
+
+            // Smallest clockwise turn from the way back
+            let back = angle(to, from);
+            let turn = |&&candidate: &&usize| {
+                let clockwise = (back - angle(to, boundary[candidate].1)).rem_euclid(std::f64::consts::TAU);
+                if clockwise == 0.0 { std::f64::consts::TAU } else { clockwise }
+            };
+            let choices = outgoing.get(&to).map(Vec::as_slice).unwrap_or_default();
+            match choices.iter().min_by(|p, q| turn(p).total_cmp(&turn(q))) {
+                Some(&next) if !used[next] => edge = next,
+                _ => break,
+            }
+        }
+
+        let ring = drop_straight_runs(vertices, &ring);
+        if ring.len() >= 3 {
+            rings.push(ring);
+        }
+    }
+    rings
+}
+
+/**
+ * Ring positions without the vertices that sit exactly on a straight
+ * line between their neighbors
+ */
+fn drop_straight_runs(vertices: &[[f64; 2]], ring: &[usize]) -> Vec<[f64; 2]> {
+    let count = ring.len();
+    (0..count)
+        .filter(|&i| {
+            let a = vertices[ring[(i + count - 1) % count]];
+            let (b, c) = (vertices[ring[i]], vertices[ring[(i + 1) % count]]);
+            let (u, v) = ([b[0] - a[0], b[1] - a[1]], [c[0] - b[0], c[1] - b[1]]);
+            u[0] * v[1] - u[1] * v[0] != 0.0 || u[0] * v[0] + u[1] * v[1] < 0.0
+        })
+        .map(|i| vertices[ring[i]])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outline::stroke_outline;
+    use crate::test_support::{error_of, take_strokes, Noise};
+    use BooleanOp::{Intersect, Subtract, Union};
+
+    const OPS: [BooleanOp; 3] = [Union, Subtract, Intersect];
+
+    /**
+     * Rings of flat [x, y] pairs in the stroke_outline layout
+     */
+    fn pack_rings(rings: &[Vec<f32>]) -> Vec<u8> {
+        let mut bytes = (rings.len() as u32).to_le_bytes().to_vec();
+        for ring in rings {
+            bytes.extend_from_slice(&((ring.len() / 2) as u32).to_le_bytes());
+        }
+        for value in rings.iter().flatten() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn combine(a: &[Vec<f32>], b: &[Vec<f32>], op: BooleanOp) -> Vec<Vec<f32>> {
+        let (a, b) = (pack_rings(a), pack_rings(b));
+        take_strokes(polygon_boolean(a.as_ptr(), a.len(), b.as_ptr(), b.len(), op), 2)
+    }
+
+    fn square(x: f32, y: f32, size: f32) -> Vec<f32> {
+        vec![x, y, x + size, y, x + size, y + size, x, y + size]
+    }
+
+    /**
+     * Signed area of one ring
+     */
+    fn area(ring: &[f32]) -> f64 {
+        let count = ring.len() / 2;
+        let point = |i: usize| [ring[i % count * 2] as f64, ring[i % count * 2 + 1] as f64];
+        (0..count).map(|i| point(i)[0] * point(i + 1)[1] - point(i + 1)[0] * point(i)[1]).sum::<f64>() / 2.0
+    }
+
+    fn total_area(rings: &[Vec<f32>]) -> f64 {
+        rings.iter().map(|ring| area(ring)).sum()
+    }
+
+    /**
+     * How many times `rings` wind around (x, y)
+     */
+    fn winding(rings: &[Vec<f32>], x: f64, y: f64) -> i32 {
+        let mut winding = 0;
+        for ring in rings {
+            let count = ring.len() / 2;
+            for i in 0..count {
+                let j = (i + 1) % count;
+                let (ax, ay) = (ring[i * 2] as f64, ring[i * 2 + 1] as f64);
+                let (bx, by) = (ring[j * 2] as f64, ring[j * 2 + 1] as f64);
+                if (ay > y) != (by > y) && ax + (y - ay) / (by - ay) * (bx - ax) > x {
+                    winding += if by > ay { 1 } else { -1 };
+                }
+            }
+        }
+        winding
+    }
+
+    /**
+     * Sample points around both shapes and check the result covers
+     * exactly the ones `op` keeps, each once
+     */
+    fn assert_covers(a: &[Vec<f32>], b: &[Vec<f32>], result: &[Vec<f32>], op: BooleanOp, seed: u64) {
+        let values = || a.iter().chain(b).flat_map(|ring| ring.iter().copied());
+        let (low, high) = values().fold((f32::MAX, f32::MIN), |(low, high), v| (low.min(v), high.max(v)));
+        let (center, reach) = ((low + high) as f64 / 2.0, (high - low) as f64 / 2.0 + 1.0);
+        let mut noise = Noise::new(seed);
+        for _ in 0..3000 {
+            let (x, y) = (center + noise.next() as f64 * reach, center + noise.next() as f64 * reach);
+            let kept = op.keeps(winding(a, x, y) != 0, winding(b, x, y) != 0);
+            let covered = winding(result, x, y);
+            assert!(covered == 0 || covered == 1, "{op:?}: ({x}, {y}) is covered {covered} times");
+            assert_eq!(covered == 1, kept, "{op:?}: wrong coverage at ({x}, {y})");
+        }
+        assert!(result.iter().all(|ring| ring.len() >= 6), "{op:?}: a ring has fewer than three vertices");
+    }
+
+    #[test]
+    fn overlapping_squares_combine() {
+        let (a, b) = (vec![square(0.0, 0.0, 2.0)], vec![square(1.0, 1.0, 2.0)]);
+        for (op, expected) in OPS.into_iter().zip([7.0, 3.0, 1.0]) {
+            let result = combine(&a, &b, op);
+            assert_eq!(total_area(&result), expected, "{op:?}");
+            assert_covers(&a, &b, &result, op, 1);
+        }
+    }
+
+    #[test]
+    fn shared_edges_and_touching_vertices_resolve() {
+        let a = vec![square(0.0, 0.0, 2.0)];
+
+        // A whole shared edge: the union is one rectangle with its corners only
+        let b = vec![square(2.0, 0.0, 2.0)];
+        let union = combine(&a, &b, Union);
+        assert_eq!((union.len(), union[0].len()), (1, 8));
+        assert_eq!(area(&union[0]), 8.0);
+        assert!(combine(&a, &b, Intersect).is_empty());
+        assert_eq!(total_area(&combine(&a, &b, Subtract)), 4.0);
+
+        // Part of an edge shared, with B wound the other way
+        let b = vec![square(2.0, 1.0, 2.0).chunks_exact(2).rev().flatten().copied().collect()];
+        for op in OPS {
+            assert_covers(&a, &b, &combine(&a, &b, op), op, 2);
+        }
+        assert_eq!(total_area(&combine(&a, &b, Union)), 8.0);
+
+        // Identical shapes
+        assert_eq!(total_area(&combine(&a, &a, Union)), 4.0);
+        assert!(combine(&a, &a, Subtract).is_empty());
+        assert_eq!(combine(&a, &a, Intersect).len(), 1);
+
+        // A vertex of B touching the middle of A's top edge
+        let b = vec![vec![1.0, 2.0, 2.0, 3.0, 0.0, 3.0]];
+        for op in OPS {
+            assert_covers(&a, &b, &combine(&a, &b, op), op, 3);
+        }
+
+        // Squares meeting at a corner stay two rings
+        let union = combine(&a, &[square(2.0, 2.0, 1.0)], Union);
+        assert_eq!(union.len(), 2);
+        assert_eq!(total_area(&union), 5.0);
+    }
+
+    #[test]
+    fn holes_are_wound_backwards() {
+        let a = vec![square(0.0, 0.0, 2.0)];
+        let inner = vec![square(0.5, 0.5, 1.0)];
+        let holed = combine(&a, &inner, Subtract);
+        assert_eq!(holed.len(), 2);
+        assert_eq!(total_area(&holed), 3.0);
+        assert!(holed.iter().any(|ring| area(ring) < 0.0));
+        assert_covers(&a, &inner, &holed, Subtract, 4);
+
+        // Cutouts touching the outline at a vertex, and along an edge
+        let notch = vec![vec![0.0, 1.0, 1.0, 0.5, 1.0, 1.5]];
+        assert_covers(&a, &notch, &combine(&a, &notch, Subtract), Subtract, 5);
+        let bay = vec![vec![0.0, 0.5, 1.0, 0.5, 1.0, 1.5, 0.0, 1.5]];
+        let result = combine(&a, &bay, Subtract);
+        assert_eq!(result.len(), 1);
+        assert_covers(&a, &bay, &result, Subtract, 6);
+
+        // Results go straight back in: an island inside the hole
+        let island = vec![square(0.75, 0.75, 0.5)];
+        let result = combine(&holed, &island, Union);
+        assert_eq!(result.len(), 3);
+        assert_covers(&holed, &island, &result, Union, 7);
+    }
+
+    #[test]
+    fn random_shapes_match_point_sampling() {
+        let mut noise = Noise::new(777);
+        let mut star = |x: f32, y: f32, points: usize, on_grid: bool| -> Vec<f32> {
+            (0..points)
+                .flat_map(|i| {
+                    let angle = i as f32 / points as f32 * std::f32::consts::TAU;
+                    let radius = 3.5 + 1.5 * noise.next();
+                    let vertex = [x + radius * angle.cos(), y + radius * angle.sin()];
+                    if on_grid { vertex.map(f32::round) } else { vertex }
+                })
+                .collect()
+        };
+        for trial in 0..60 {
+            // Grid-snapped vertices give plenty of shared edges and touches
+            let on_grid = trial % 2 == 0;
+            let a = vec![star(0.0, 0.0, 12, on_grid)];
+            let b = vec![star(1.0, 0.5, 9, on_grid), star(-1.0, -1.0, 6, on_grid)];
+            for op in OPS {
+                assert_covers(&a, &b, &combine(&a, &b, op), op, trial * 3 + 11);
+            }
+        }
+
+        // Stroke outlines overlap themselves and rely on the nonzero rule
+        let wave: Vec<f32> = (0..80).flat_map(|i| [i as f32, (i as f32 * 0.3).sin() * 20.0, 1.0]).collect();
+        let lissajous: Vec<f32> = (0..80)
+            .flat_map(|i| {
+                let t = i as f32 * 0.1;
+                [40.0 + 30.0 * t.cos(), 30.0 * (t * 1.3).sin(), 0.8]
+            })
+            .collect();
+        let a = take_strokes(stroke_outline(wave.as_ptr(), wave.len(), 6.0), 2);
+        let b = take_strokes(stroke_outline(lissajous.as_ptr(), lissajous.len(), 4.0), 2);
+        for op in OPS {
+            assert_covers(&a, &b, &combine(&a, &b, op), op, 99);
+        }
+    }
+
+    #[test]
+    fn empty_shapes_and_bad_input() {
+        let a = vec![square(0.0, 0.0, 2.0)];
+        assert!(combine(&[], &[], Union).is_empty());
+        assert_eq!(combine(&a, &[], Union), a);
+        assert!(combine(&[], &a, Subtract).is_empty());
+
+        let packed = pack_rings(&a);
+        let short = [1u8, 0, 0, 0, 5, 0, 0, 0];
+        let nan = pack_rings(&[vec![0.0, 0.0, f32::NAN, 1.0, 1.0, 1.0]]);
+        let cases = [
+            (polygon_boolean(short.as_ptr(), short.len(), packed.as_ptr(), packed.len(), Union), CreoVexError::InvalidLength),
+            (polygon_boolean(std::ptr::null(), 0, packed.as_ptr(), packed.len(), Union), CreoVexError::NullInput),
+            (polygon_boolean(packed.as_ptr(), packed.len(), nan.as_ptr(), nan.len(), Union), CreoVexError::NonFiniteValue),
+        ];
+        for (result, code) in cases {
+            assert_eq!(error_of(result), code as u32);
+        }
+    }
+}
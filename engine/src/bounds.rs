@@ -0,0 +1,519 @@
+/*!
+ * Stroke bounding boxes and hulls
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Culling)
+ */
+
+use alloc::format;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_finite, require_non_negative, CreoVexError, EngineError, EngineResult};
+#[cfg(feature = "std")]
+use crate::error::{run_export, run_vec};
+use crate::float::Float;
+#[cfg(feature = "std")]
+use crate::validate::{validate_floats, with_float_validation, FloatValidation};
+#[cfg(feature = "std")]
+use crate::{
+    batch_stroke_values, read_batch, read_points, serialize_indices, serialize_polygon, serialize_records,
+    serialize_records_f64, EngineBuffer,
+};
+
+/**
+ * Axis-aligned bounding box in canvas units (f64 for stroke_bounds_f64)
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Bounds<F = f32> {
+    pub(crate) min_x: F,
+    pub(crate) min_y: F,
+    pub(crate) max_x: F,
+    pub(crate) max_y: F,
+}
+
+impl<F: Float> Bounds<F> {
+    /**
+     * Degenerate box at the origin (used for empty strokes)
+     */
+    pub(crate) const EMPTY: Bounds<F> = Bounds { min_x: F::ZERO, min_y: F::ZERO, max_x: F::ZERO, max_y: F::ZERO };
+
+    /**
+     * Centerline box of [x, y, p] points grown by `base_width * max
+     * pressure` (negative pressure counts as zero); None when empty
+     */
+    pub(crate) fn of_stroke(points: &[F], base_width: F) -> Option<Bounds<F>> {
+        if points.is_empty() {
+            return None;
+        }
+
+        let mut bounds = Bounds {
+            min_x: F::INFINITY,
+            min_y: F::INFINITY,
+            max_x: F::NEG_INFINITY,
+            max_y: F::NEG_INFINITY,
+        };
+        let mut max_pressure = F::ZERO;
+        for point in points.chunks_exact(3) {
+            bounds.min_x = bounds.min_x.min(point[0]);
+            bounds.min_y = bounds.min_y.min(point[1]);
+            bounds.max_x = bounds.max_x.max(point[0]);
+            bounds.max_y = bounds.max_y.max(point[1]);
+            max_pressure = max_pressure.max(point[2]);
+        }
+
+        Some(bounds.expand(base_width * max_pressure))
+    }
+
+    pub(crate) fn union(self, other: Bounds<F>) -> Bounds<F> {
+        Bounds {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    pub(crate) fn expand(self, margin: F) -> Bounds<F> {
+        Bounds {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+
+    pub(crate) fn to_array(self) -> [F; 4] {
+        [self.min_x, self.min_y, self.max_x, self.max_y]
+    }
+
+    /**
+     * Whether the boxes overlap or touch; a box with min > max (or a NaN
+     * bound) is empty and overlaps nothing
+     */
+    pub(crate) fn intersects(self, other: Bounds<F>) -> bool {
+        self.min_x <= self.max_x
+            && self.min_y <= self.max_y
+            && self.min_x <= other.max_x
+            && other.min_x <= self.max_x
+            && self.min_y <= other.max_y
+            && other.min_y <= self.max_y
+    }
+}
+
+/**
+ * Compute the painted bounding box of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: record count (u32, always 1)
+ * - 16 bytes: f32 [min_x, min_y, max_x, max_y]
+ *
+ * The centerline box is expanded by `max_width * max(pressure)`, the
+ * largest pressure-scaled half-width for that base width, so it covers the
+ * painted area of a round-joined stroke. (Miter joins from
+ * tessellate_stroke can reach past it on sharp turns.) An empty stroke
+ * (length 0) gives the degenerate box [0, 0, 0, 0]; a single point gives
+ * a square around it (or the point itself at zero width).
+ *
+ * Errors: NullInput/InvalidLength for a bad non-empty buffer, plus
+ * NonFiniteValue/InvalidParameter for a NaN or negative width.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_bounds(points_ptr: *const f32, points_len: usize, max_width: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points_or_empty(points_ptr, points_len)?;
+        serialize_records(1, &painted_bounds(points, max_width)?)
+    })
+}
+
+/**
+ * stroke_bounds from a Float32Array, returning [min_x, min_y, max_x,
+ * max_y] as a Float32Array
+ *
+ * The default for pointer-up processing (see smooth_stroke_js); both
+ * forms run painted_bounds.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: as stroke_bounds. A failure returns an empty array and sets
+ * last_error_code() / last_error_message().
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_bounds_js(points: &[f32], max_width: f32, validation: Option<FloatValidation>) -> Vec<f32> {
+    with_float_validation(validation, || {
+        run_vec(|| Ok(painted_bounds(&read_points_or_empty(points.as_ptr(), points.len())?, max_width)?.to_vec()))
+    })
+}
+
+/**
+ * stroke_bounds on validated points (shared with stroke_bounds_js)
+ */
+pub(crate) fn painted_bounds(points: &[f32], max_width: f32) -> EngineResult<[f32; 4]> {
+    let max_width = require_non_negative("max_width", max_width)?;
+    Ok(Bounds::of_stroke(points, max_width).unwrap_or(Bounds::EMPTY).to_array())
+}
+
+/**
+ * Compute the painted bounding box of a stroke in f64 precision
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, ...] as f64 values
+ *
+ * Output format:
+ * - 4 bytes: record count (u32, always 1)
+ * - 4 bytes: element size (u32, 8)
+ * - 32 bytes: f64 [min_x, min_y, max_x, max_y]
+ *
+ * stroke_bounds for the *_f64 pipeline, with the same width and
+ * empty-stroke rules.
+ *
+ * Errors: as stroke_bounds.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_bounds_f64(points_ptr: *const f64, points_len: usize, max_width: f64) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points_or_empty(points_ptr, points_len)?;
+        let max_width = require_non_negative("max_width", max_width)?;
+
+        let bounds = Bounds::of_stroke(points, max_width).unwrap_or(Bounds::EMPTY);
+        serialize_records_f64(1, &bounds.to_array())
+    })
+}
+
+/**
+ * Compute the region that changed between two revisions of a stroke
+ *
+ * Input format: two [x, y, p, ...] buffers, old then new (either may be
+ * empty)
+ *
+ * Output format:
+ * - 4 bytes: record count (u32, 0 when nothing changed, otherwise 1)
+ * - 16 bytes (if count is 1): f32 [min_x, min_y, max_x, max_y]
+ *
+ * The revisions are compared point by point; the points they share from
+ * the start (bitwise equal) are unchanged. Everything after that shared
+ * prefix in either revision, plus the last shared point (its outgoing
+ * segment moved), is dirty, and the rect is the union of the stroke_bounds
+ * of those two tails using `width`.
+ *
+ * Appending points dirties only the new tail and its junction; a
+ * re-smoothed tail (StrokeBuilder::smoothed_tail) dirties from the first
+ * point that moved. A shorter new revision (undo) dirties the removed
+ * tail, and strokes that differ from the first point give the union of
+ * both full bounds.
+ *
+ * Errors: NullInput/InvalidLength for a bad non-empty buffer, plus
+ * NonFiniteValue/InvalidParameter for a NaN or negative width.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_dirty_rect(
+    old_ptr: *const f32,
+    old_len: usize,
+    new_ptr: *const f32,
+    new_len: usize,
+    width: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let old = &read_points_or_empty(old_ptr, old_len)?;
+        let new = &read_points_or_empty(new_ptr, new_len)?;
+        let width = require_non_negative("width", width)?;
+
+        let shared = old
+            .chunks_exact(3)
+            .zip(new.chunks_exact(3))
+            .take_while(|(a, b)| a.iter().zip(b.iter()).all(|(a, b)| a.to_bits() == b.to_bits()))
+            .count();
+        if shared * 3 == old.len() && shared * 3 == new.len() {
+            return serialize_records(0, &[]);
+        }
+
+        let start = shared.saturating_sub(1) * 3;
+        let dirty = match (Bounds::of_stroke(&old[start..], width), Bounds::of_stroke(&new[start..], width)) {
+            (Some(a), Some(b)) => a.union(b),
+            (Some(bounds), None) | (None, Some(bounds)) => bounds,
+            (None, None) => unreachable!("revisions differ, so one has points past the shared prefix"),
+        };
+        serialize_records(1, &dirty.to_array())
+    })
+}
+
+/**
+ * Find the strokes of a batch visible in a view rect
+ *
+ * Input format: the batch container (see read_batch); the view rect in
+ * canvas units
+ *
+ * Output format:
+ * - 4 bytes: visible count (u32)
+ * - N * 4 bytes: indices of the visible strokes (u32), ascending
+ *
+ * A stroke is visible when its centerline box overlaps or touches the
+ * view rect grown by `pad` on every side. Pass at least the largest
+ * painted half-width as `pad` (base width times max pressure, as for
+ * stroke_bounds) so wide strokes just outside the view are kept. Strokes
+ * with no points, or that are invalid on their own, are never visible.
+ * Use cull_bounds instead when the document already has the bounds.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container, plus the
+ * view rect errors of cull_bounds.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn cull_strokes(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    view_min_x: f32,
+    view_min_y: f32,
+    view_max_x: f32,
+    view_max_y: f32,
+    pad: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let strokes = read_batch(batch_ptr, batch_len)?;
+        let view = read_view(view_min_x, view_min_y, view_max_x, view_max_y, pad)?;
+
+        let visible: Vec<u32> = strokes
+            .iter()
+            .enumerate()
+            .filter(|(_, stroke)| {
+                let values = batch_stroke_values(stroke);
+                read_points(values.as_ptr(), values.len())
+                    .is_ok_and(|points| Bounds::of_stroke(&points, 0.0).is_some_and(|bounds| bounds.intersects(view)))
+            })
+            .map(|(index, _)| index as u32)
+            .collect();
+        serialize_indices(&visible)
+    })
+}
+
+/**
+ * Find the strokes visible in a view rect from precomputed bounds
+ *
+ * Input format: f32 [min_x, min_y, max_x, max_y] per stroke (e.g. kept
+ * from stroke_bounds for unchanged strokes); `bounds_len` 0 is an empty
+ * document (the pointer is not read)
+ *
+ * Output format: as cull_strokes, indices into the rect list
+ *
+ * Same test and `pad` as cull_strokes, against the given rects. A rect
+ * with min above max or a NaN bound is never visible: store one such as
+ * [1, 1, 0, 0] for strokes with no points (stroke_bounds gives [0, 0, 0,
+ * 0] for them, which would be visible at the origin).
+ *
+ * Errors: NullInput for a null pointer with a non-zero `bounds_len`;
+ * InvalidLength when `bounds_len` is not a multiple of 4;
+ * NonFiniteValue for a NaN or infinite view bound; InvalidParameter for
+ * a view max below its min; NonFiniteValue/InvalidParameter for a NaN or
+ * negative `pad`.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn cull_bounds(
+    bounds_ptr: *const f32,
+    bounds_len: usize,
+    view_min_x: f32,
+    view_min_y: f32,
+    view_max_x: f32,
+    view_max_y: f32,
+    pad: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let rects: &[f32] = if bounds_len == 0 {
+            &[]
+        } else if bounds_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "bounds_ptr is null"));
+        } else {
+            unsafe { core::slice::from_raw_parts(bounds_ptr, bounds_len) }
+        };
+        if !bounds_len.is_multiple_of(4) {
+            return Err(EngineError::new(
+                CreoVexError::InvalidLength,
+                format!("bounds_len {bounds_len} is not a multiple of 4 (min_x, min_y, max_x, max_y)"),
+            ));
+        }
+        let view = read_view(view_min_x, view_min_y, view_max_x, view_max_y, pad)?;
+
+        let visible: Vec<u32> = rects
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, rect)| {
+                Bounds { min_x: rect[0], min_y: rect[1], max_x: rect[2], max_y: rect[3] }.intersects(view)
+            })
+            .map(|(index, _)| index as u32)
+            .collect();
+        serialize_indices(&visible)
+    })
+}
+
+/**
+ * Convex hull of a stroke's points
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: hull vertex count N (u32)
+ * - 4 bytes: f32 hull area
+ * - N * 8 bytes: f32 [x, y] per hull vertex
+ *
+ * Uses the x/y channels only (centerline, no width). Vertices run
+ * counter-clockwise in a y-up frame (positive signed area; clockwise on
+ * screen with y down), starting from the lowest x (then lowest y), and
+ * points on a hull edge are left out. Degenerate input degrades instead
+ * of failing: collinear points give the two extreme points, a single
+ * distinct point gives that point, and both have area 0. Points with a
+ * NaN or infinite coordinate are ignored.
+ *
+ * Errors: the read_points errors.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn convex_hull(points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+
+        let hull = hull_of(points.chunks_exact(3).map(|point| [point[0], point[1]]).collect());
+        serialize_polygon(&hull, polygon_area(&hull))
+    })
+}
+
+/**
+ * One convex hull around every stroke of a group
+ *
+ * Input format: the batch container (see read_batch)
+ * Output format: see convex_hull; 0 vertices when the batch has no points
+ *
+ * The hull of all points of all strokes together, as convex_hull
+ * computes it for one stroke.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn convex_hull_batch(batch_ptr: *const u8, batch_len: usize, validation: Option<FloatValidation>) -> EngineBuffer {
+    with_float_validation(validation, || {
+        run_export(|| {
+            let strokes = read_batch(batch_ptr, batch_len)?;
+
+            let mut points = Vec::new();
+            for stroke in strokes {
+                let values = batch_stroke_values(stroke);
+                let values = validate_floats(&values, 3)?;
+                points.extend(values.chunks_exact(3).map(|point| [point[0], point[1]]));
+            }
+            let hull = hull_of(points);
+            serialize_polygon(&hull, polygon_area(&hull))
+        })
+    })
+}
+
+/**
+ * Andrew's monotone chain, dropping non-finite and collinear points
+ */
+fn hull_of(mut points: Vec<[f32; 2]>) -> Vec<[f32; 2]> {
+    points.retain(|point| point[0].is_finite() && point[1].is_finite());
+    points.sort_by(|p, q| p[0].total_cmp(&q[0]).then(p[1].total_cmp(&q[1])));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    // Positive when o -> a -> b turns counter-clockwise
+    let turn = |o: [f32; 2], a: [f32; 2], b: [f32; 2]| {
+        let (ax, ay) = (a[0] as f64 - o[0] as f64, a[1] as f64 - o[1] as f64);
+        let (bx, by) = (b[0] as f64 - o[0] as f64, b[1] as f64 - o[1] as f64);
+        ax * by - ay * bx
+    };
+
+    // Lower chain left to right, then upper chain back; each drops its
+    // last point, the other chain's first
+    let chain = |ordered: &mut dyn Iterator<Item = &[f32; 2]>| {
+        let mut chain: Vec<[f32; 2]> = Vec::new();
+        for &point in ordered {
+            while chain.len() >= 2 && turn(chain[chain.len() - 2], chain[chain.len() - 1], point) <= 0.0 {
+                chain.pop();
+            }
+            chain.push(point);
+        }
+        chain.pop();
+        chain
+    };
+    let mut hull = chain(&mut points.iter());
+    hull.extend(chain(&mut points.iter().rev()));
+    hull
+}
+
+/**
+ * Shoelace area of a closed polygon, positive when counter-clockwise in
+ * a y-up frame
+ */
+fn polygon_area(vertices: &[[f32; 2]]) -> f32 {
+    let Some(&origin) = vertices.first() else {
+        return 0.0;
+    };
+    let relative = |vertex: [f32; 2]| [vertex[0] as f64 - origin[0] as f64, vertex[1] as f64 - origin[1] as f64];
+    let doubled: f64 = vertices
+        .windows(2)
+        .map(|pair| {
+            let (a, b) = (relative(pair[0]), relative(pair[1]));
+            a[0] * b[1] - a[1] * b[0]
+        })
+        .sum();
+    (doubled / 2.0) as f32
+}
+
+/**
+ * Validate a view rect and grow it by `pad`
+ */
+fn read_view(min_x: f32, min_y: f32, max_x: f32, max_y: f32, pad: f32) -> EngineResult<Bounds> {
+    let view = Bounds {
+        min_x: require_finite("view_min_x", min_x)?,
+        min_y: require_finite("view_min_y", min_y)?,
+        max_x: require_finite("view_max_x", max_x)?,
+        max_y: require_finite("view_max_y", max_y)?,
+    };
+    if view.max_x < view.min_x || view.max_y < view.min_y {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("view rect max is below its min ({:?})", view.to_array()),
+        ));
+    }
+    Ok(view.expand(require_non_negative("pad", pad)?))
+}
+
+/**
+ * read_points, except that a zero length is an empty stroke (pointer unused)
+ */
+#[cfg(feature = "std")]
+fn read_points_or_empty<'a, F: Float>(points_ptr: *const F, points_len: usize) -> EngineResult<Cow<'a, [F]>> {
+    if points_len == 0 {
+        return Ok(Cow::Borrowed(&[]));
+    }
+    read_points(points_ptr, points_len)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_support::take_records_f64;
+
+    #[test]
+    fn f64_bounds_keep_fractions_ten_million_units_out() {
+        let far = [1.0e7 + 0.25, 2.0e7, 0.5, 1.0e7 + 3.5, 2.0e7 - 0.125, 1.0];
+        let bounds = take_records_f64(stroke_bounds_f64(far.as_ptr(), far.len(), 1.0), 4);
+        assert_eq!(bounds, [1.0e7 - 0.75, 2.0e7 - 1.125, 1.0e7 + 4.5, 2.0e7 + 1.0]);
+        assert_eq!(take_records_f64(stroke_bounds_f64(std::ptr::null(), 0, 1.0), 4), [0.0; 4]);
+    }
+}
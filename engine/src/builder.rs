@@ -0,0 +1,351 @@
+/*!
+ * Incremental stroke building for live input
+ *
+ * Batch exports re-smooth the whole stroke on every pointermove, which is
+ * quadratic over a stroke's lifetime. StrokeBuilder keeps the smoothed
+ * result between calls and only recomputes the unstable tail.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Live Stroke Pipeline)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{run_export, run_status, CreoVexError, EngineError};
+use crate::filter::{OneEuroPointFilter, DEFAULT_SAMPLE_RATE_HZ};
+use crate::error::EngineResult;
+use crate::smooth::{feed_levels, finish_levels, ChaikinLevel, DEFAULT_ITERATIONS, MAX_ITERATIONS};
+use crate::validate::{float_validation, validate_floats, FloatValidation};
+use crate::{read_points, read_strided_points, serialize_points, EngineBuffer};
+
+/**
+ * Floats per ingest_events record: x, y, pressure, t_ms, is_predicted
+ */
+pub(crate) const EVENT_STRIDE: usize = 5;
+
+/**
+ * Construction options for StrokeBuilder
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug)]
+pub struct StrokeBuilderOptions {
+    /** Chaikin passes (clamped to 0-6; default 2, as smooth_stroke_v2) */
+    pub iterations: u32,
+    /** 1-Euro prefilter min cutoff in Hz; 0 disables the prefilter (default) */
+    pub min_cutoff: f32,
+    /** 1-Euro prefilter speed coefficient (used when min_cutoff > 0) */
+    pub beta: f32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl StrokeBuilderOptions {
+    /** Defaults: smooth_stroke_v2 smoothing, no prefilter */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> StrokeBuilderOptions {
+        StrokeBuilderOptions { iterations: DEFAULT_ITERATIONS, min_cutoff: 0.0, beta: 0.0 }
+    }
+}
+
+impl Default for StrokeBuilderOptions {
+    fn default() -> Self {
+        StrokeBuilderOptions::new()
+    }
+}
+
+/**
+ * Stateful builder for one live stroke
+ *
+ * Points are fed as they arrive; each Chaikin level runs as a streaming
+ * filter, so a new point finalizes a fixed number of smoothed points and
+ * leaves only a short provisional tail (the pinned end and the cuts that
+ * depend on it). finish() produces exactly what smooth_stroke_ex gives
+ * for the whole stroke with the same iteration count (at strength 1).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct StrokeBuilder {
+    levels: Vec<ChaikinLevel>,
+    prefilter: Option<OneEuroPointFilter>,
+    /** Smoothed points that no later input can change */
+    stable: Vec<f32>,
+    /** Raw points of the last ingest_events predicted events, never committed */
+    predicted: Vec<f32>,
+    /** [t_ms, x, y] of the last real event ingest_events appended */
+    last_event: Option<[f32; 3]>,
+    /** Last raw point added, which FloatValidation::Repair fills add_point's bad values from */
+    last_point: Option<[f32; 3]>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl StrokeBuilder {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(options: &StrokeBuilderOptions) -> StrokeBuilder {
+        let iterations = options.iterations.min(MAX_ITERATIONS) as usize;
+        let prefilter = (options.min_cutoff.is_finite() && options.min_cutoff > 0.0)
+            .then(|| OneEuroPointFilter::new(options.min_cutoff, options.beta.max(0.0)));
+        StrokeBuilder {
+            levels: vec![ChaikinLevel::default(); iterations],
+            prefilter,
+            stable: Vec::new(),
+            predicted: Vec::new(),
+            last_event: None,
+            last_point: None,
+        }
+    }
+
+    /**
+     * Append one raw input point (dropping any predicted events)
+     *
+     * Returns 1 (the number of points added), or a negative CreoVexError
+     * discriminant when the point is not added: NonFiniteValue for a NaN
+     * or infinite value under FloatValidation::Reject. Under Repair a bad
+     * value takes the previous point's (its only neighbour yet), and
+     * the stroke's first point, which has none, is refused the same way.
+     */
+    pub fn add_point(&mut self, x: f32, y: f32, pressure: f32) -> i32 {
+        run_status(|| {
+            let point = self.checked_point([x, y, pressure])?;
+            self.push_point(point);
+            Ok(1)
+        })
+    }
+
+    /**
+     * Append raw input points ([x, y, p] triplets)
+     *
+     * Returns the number of points added, or a negative CreoVexError
+     * discriminant when the buffer is invalid (nothing is added then).
+     */
+    pub fn add_points(&mut self, points_ptr: *const f32, points_len: usize) -> i32 {
+        run_status(|| {
+            let points = &read_points(points_ptr, points_len)?;
+            for point in points.chunks_exact(3) {
+                self.push_point([point[0], point[1], point[2]]);
+            }
+            Ok(points.len() / 3)
+        })
+    }
+
+    /**
+     * Merge a batch of browser pointer events, coalesced and predicted
+     *
+     * Input format: [x, y, pressure, t_ms, is_predicted] records
+     * (is_predicted 0 or 1), in any order, for example getCoalescedEvents()
+     * followed by getPredictedEvents().
+     *
+     * Real events are appended in timestamp order. One with the timestamp
+     * and position of the previous real event is a duplicate and one
+     * older than it arrived too late (the points it would change may be
+     * final); both are dropped. Predicted events replace the previous
+     * batch's: they extend the provisional tail but are never committed,
+     * and those not after the last real event are discarded.
+     *
+     * Returns how many points at the end of the smoothed stroke changed:
+     * smoothed_tail of that many is what to re-upload, and
+     * smoothed_count the new total (it shrinks when a prediction is
+     * withdrawn). A negative CreoVexError discriminant means the buffer
+     * is invalid (length a multiple of 5, NonFiniteValue for a NaN or
+     * infinite timestamp) and nothing was ingested.
+     */
+    pub fn ingest_events(&mut self, events_ptr: *const f32, events_len: usize) -> i32 {
+        run_status(|| {
+            let events = &read_strided_points(events_ptr, events_len, EVENT_STRIDE)?;
+            if let Some(i) = events.chunks_exact(EVENT_STRIDE).position(|event| !event[3].is_finite()) {
+                return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("timestamp {i} is not finite")));
+            }
+            let stable_before = self.stable.len();
+            let tail_before = self.provisional_tail();
+
+            let (mut real, mut predicted): (Vec<&[f32]>, Vec<&[f32]>) =
+                events.chunks_exact(EVENT_STRIDE).partition(|event| event[4] == 0.0);
+            real.sort_by(|a, b| a[3].total_cmp(&b[3]));
+            for event in real {
+                if let Some([t, x, y]) = self.last_event {
+                    if event[3] < t || (event[3] == t && event[0] == x && event[1] == y) {
+                        continue;
+                    }
+                }
+                self.last_event = Some([event[3], event[0], event[1]]);
+                self.last_point = Some([event[0], event[1], event[2]]);
+                let point = prefiltered(&mut self.prefilter, [event[0], event[1], event[2]]);
+                feed_levels(&mut self.levels, point, &mut self.stable);
+            }
+
+            predicted.retain(|event| self.last_event.is_none_or(|[t, _, _]| event[3] > t));
+            predicted.sort_by(|a, b| a[3].total_cmp(&b[3]));
+            self.predicted = predicted.iter().flat_map(|event| [event[0], event[1], event[2]]).collect();
+
+            // Everything after the points shared with the stroke before the batch changed
+            let mut after: Vec<f32> = self.stable[stable_before..].to_vec();
+            after.extend_from_slice(&self.provisional_tail());
+            let kept = tail_before.chunks_exact(3).zip(after.chunks_exact(3)).take_while(|(a, b)| a == b).count();
+            Ok(after.len() / 3 - kept)
+        })
+    }
+
+    /**
+     * Number of points in the current smoothed stroke, provisional tail
+     * included
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn smoothed_count(&self) -> usize {
+        (self.stable.len() + self.provisional_tail().len()) / 3
+    }
+
+    /**
+     * Number of smoothed points that are final
+     *
+     * Points before this index never change; everything after it is the
+     * provisional tail that smoothed_tail reports.
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn stable_count(&self) -> usize {
+        self.stable.len() / 3
+    }
+
+    /**
+     * Last `n` points of the current smoothed stroke
+     *
+     * Includes the provisional tail as if the stroke ended now, predicted
+     * events included. Pass
+     * `total - stable_count` (or a little more) to redraw only what
+     * changed. Same serialized layout as smooth_stroke_v2.
+     */
+    pub fn smoothed_tail(&self, n: usize) -> EngineBuffer {
+        run_export(|| {
+            let tail = self.provisional_tail();
+            let total = self.stable.len() + tail.len();
+            let start = total.saturating_sub(n.saturating_mul(3));
+
+            let mut result = Vec::with_capacity(total - start);
+            if start < self.stable.len() {
+                result.extend_from_slice(&self.stable[start..]);
+                result.extend_from_slice(&tail);
+            } else {
+                result.extend_from_slice(&tail[start - self.stable.len()..]);
+            }
+            serialize_points(&result)
+        })
+    }
+
+    /**
+     * Full smoothed stroke (same layout as smooth_stroke_v2)
+     *
+     * Predicted events are left out. The builder is reset afterwards and
+     * can start a new stroke with the same options.
+     */
+    pub fn finish(&mut self) -> EngineBuffer {
+        run_export(|| {
+            finish_levels(&mut self.levels, &mut self.stable);
+            let stroke = std::mem::take(&mut self.stable);
+            self.levels.fill(ChaikinLevel::default());
+            self.predicted.clear();
+            self.last_event = None;
+            self.last_point = None;
+            if let Some(filter) = &mut self.prefilter {
+                filter.reset();
+            }
+            serialize_points(&stroke)
+        })
+    }
+}
+
+impl StrokeBuilder {
+    /**
+     * `point` under the FloatValidation mode, repaired from the previous
+     * point
+     */
+    fn checked_point(&self, point: [f32; 3]) -> EngineResult<[f32; 3]> {
+        match self.last_point.filter(|_| float_validation() == FloatValidation::Repair) {
+            Some([x, y, pressure]) => {
+                let pair = [x, y, pressure, point[0], point[1], point[2]];
+                let pair = validate_floats(&pair, 3)?;
+                Ok([pair[3], pair[4], pair[5]])
+            }
+            None => {
+                validate_floats(&point, 3)?;
+                Ok(point)
+            }
+        }
+    }
+
+    /**
+     * Append a checked raw point (dropping any predicted events)
+     */
+    fn push_point(&mut self, point: [f32; 3]) {
+        self.predicted.clear();
+        self.last_point = Some(point);
+        let point = prefiltered(&mut self.prefilter, point);
+        feed_levels(&mut self.levels, point, &mut self.stable);
+    }
+
+    /**
+     * Points finish() would append right now followed by the predicted
+     * events' smoothing, without consuming state
+     */
+    fn provisional_tail(&self) -> Vec<f32> {
+        let mut levels = self.levels.clone();
+        let mut prefilter = self.prefilter;
+        let mut tail = Vec::new();
+        for point in self.predicted.chunks_exact(3) {
+            let point = prefiltered(&mut prefilter, [point[0], point[1], point[2]]);
+            feed_levels(&mut levels, point, &mut tail);
+        }
+        finish_levels(&mut levels, &mut tail);
+        tail
+    }
+}
+
+/**
+ * `point` through the 1-Euro prefilter, if there is one
+ */
+fn prefiltered(prefilter: &mut Option<OneEuroPointFilter>, point: [f32; 3]) -> [f32; 3] {
+    match prefilter {
+        Some(filter) => filter.push(point, 1.0 / DEFAULT_SAMPLE_RATE_HZ),
+        None => point,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smooth::smooth_stroke_v2;
+    use crate::test_support::take_points;
+    use crate::validate::set_float_validation;
+
+    const NON_FINITE: i32 = -(CreoVexError::NonFiniteValue as i32);
+
+    fn built(points: &[[f32; 3]]) -> (Vec<i32>, Vec<f32>) {
+        let mut builder = StrokeBuilder::new(&StrokeBuilderOptions::new());
+        let statuses = points.iter().map(|&[x, y, pressure]| builder.add_point(x, y, pressure)).collect();
+        (statuses, take_points(builder.finish()))
+    }
+
+    fn smoothed(points: &[f32]) -> Vec<f32> {
+        take_points(smooth_stroke_v2(points.as_ptr(), points.len()))
+    }
+
+    #[test]
+    fn non_finite_points_follow_the_validation_mode() {
+        let points = [[0.0, 0.0, 1.0], [f32::NAN, 1.0, 1.0], [10.0, 0.0, 1.0]];
+
+        set_float_validation(FloatValidation::Reject);
+        assert_eq!(built(&points), (vec![1, NON_FINITE, 1], smoothed(&[0.0, 0.0, 1.0, 10.0, 0.0, 1.0])));
+        let mut builder = StrokeBuilder::new(&StrokeBuilderOptions::new());
+        let batch = [0.0, 0.0, 1.0, 1.0, f32::INFINITY, 1.0];
+        assert_eq!(builder.add_points(batch.as_ptr(), batch.len()), NON_FINITE);
+        assert_eq!(builder.smoothed_count(), 0);
+
+        // Repair holds the previous point's value; the first point has none
+        set_float_validation(FloatValidation::Repair);
+        assert_eq!(built(&points), (vec![1, 1, 1], smoothed(&[0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 10.0, 0.0, 1.0])));
+        let (statuses, _) = built(&[[0.0, f32::NAN, 1.0], [1.0, 1.0, 1.0]]);
+        assert_eq!(statuses, [NON_FINITE, 1]);
+
+        set_float_validation(FloatValidation::Passthrough);
+        let (statuses, stroke) = built(&points);
+        assert_eq!(statuses, [1, 1, 1]);
+        assert!(stroke.iter().any(|value| value.is_nan()));
+        set_float_validation(FloatValidation::Reject);
+    }
+}
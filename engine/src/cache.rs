@@ -0,0 +1,570 @@
+/*!
+ * Engine-side stroke cache
+ *
+ * Redrawing a document re-smooths and re-tessellates strokes that did not
+ * change, and every call copies the points across the boundary again.
+ * StrokeCache keeps the points in engine memory behind a handle and
+ * memoizes the results of the common operations per handle, within a
+ * byte budget.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing), ffi_contract.md (Memory Ownership Rules)
+ */
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{
+    record_error, require_finite, require_positive, run_export, run_status, CreoVexError, EngineError, EngineResult,
+};
+use crate::pool::AlignedBuffer;
+use crate::tessellate::{StripOptions, TessellationOptions, MAX_ROUND_SEGMENTS};
+use crate::{hand_off, output_buffer, read_points, CapStyle, EngineBuffer, JoinStyle};
+use crate::{simplify_stroke, smooth_stroke_v2, stroke_bounds, tessellate_stroke, tessellate_stroke_ex};
+
+/**
+ * Zoom buckets per doubling of zoom, unless set otherwise
+ */
+const DEFAULT_BUCKETS_PER_OCTAVE: u32 = 2;
+
+/**
+ * Finest bucket granularity zoom_buckets_per_octave accepts
+ */
+const MAX_BUCKETS_PER_OCTAVE: u32 = 16;
+
+/**
+ * Memoized operation and the bits of its f32 arguments
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Operation {
+    Smooth,
+    Simplify { epsilon: u32, corner_angle_deg: u32 },
+    Tessellate { base_width: u32 },
+    Bounds { max_width: u32 },
+    /** get_or_tessellate; `bucket` is 0 for meshes that do not depend on zoom */
+    Mesh { bucket: i32, base_width: u32, join: u32, miter_limit: u32, round_segments: u32, caps: [u32; 2] },
+}
+
+impl Operation {
+    fn is_tessellation(self) -> bool {
+        matches!(self, Operation::Tessellate { .. } | Operation::Mesh { .. })
+    }
+
+    fn is_mesh(self) -> bool {
+        matches!(self, Operation::Mesh { .. })
+    }
+}
+
+/**
+ * One stored stroke and the serialized results computed from it
+ */
+struct Entry {
+    points: Vec<f32>,
+    results: BTreeMap<Operation, AlignedBuffer>,
+    /** Access tick for LRU eviction */
+    last_used: u64,
+    /** Cleared by mark_visible for strokes outside the view */
+    visible: bool,
+}
+
+impl Entry {
+    fn result_bytes(&self) -> usize {
+        self.results.values().map(AlignedBuffer::len).sum()
+    }
+
+    /**
+     * Drop the results `drop` selects; returns the bytes freed
+     */
+    fn drop_results(&mut self, drop: impl Fn(Operation) -> bool) -> usize {
+        let before = self.result_bytes();
+        self.results.retain(|&operation, _| !drop(operation));
+        before - self.result_bytes()
+    }
+}
+
+/**
+ * Stroke store with per-handle memoization and LRU eviction
+ *
+ * store() copies a stroke into engine memory and returns a handle; the
+ * operation methods take the handle and return exactly what the matching
+ * export returns for the stored points (a new EngineBuffer each call, to
+ * be freed as usual). The first call per operation and arguments
+ * computes the result; later calls copy the memoized bytes.
+ *
+ * get_or_tessellate memoizes meshes per zoom bucket, so zooming reuses
+ * them until the zoom moves to another bucket; its hit/miss counters and
+ * tessellation_bytes() show how well the bucket granularity works.
+ *
+ * memory_usage() counts the stored points and memoized results. Whenever
+ * it exceeds the byte budget, the cache drops, least recently used first
+ * within each step, the get_or_tessellate meshes of strokes mark_visible
+ * reported off-screen, then all memoized results, then the strokes
+ * themselves, until it fits; the stroke being accessed is kept either
+ * way. Handles are never reused, so a removed or evicted handle
+ * stays invalid and operations on it fail with InvalidParameter.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct StrokeCache {
+    entries: BTreeMap<u32, Entry>,
+    next_handle: u32,
+    tick: u64,
+    usage: usize,
+    byte_budget: usize,
+    zoom_buckets_per_octave: u32,
+    tessellation_hits: usize,
+    tessellation_misses: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl StrokeCache {
+    /**
+     * Empty cache; `byte_budget` 0 means no limit
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(byte_budget: usize) -> StrokeCache {
+        StrokeCache {
+            entries: BTreeMap::new(),
+            next_handle: 1,
+            tick: 0,
+            usage: 0,
+            byte_budget,
+            zoom_buckets_per_octave: DEFAULT_BUCKETS_PER_OCTAVE,
+            tessellation_hits: 0,
+            tessellation_misses: 0,
+        }
+    }
+
+    /**
+     * Byte budget (0 = no limit); lowering it evicts immediately
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn byte_budget(&self) -> usize {
+        self.byte_budget
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_byte_budget(&mut self, byte_budget: usize) {
+        self.byte_budget = byte_budget;
+        self.evict(None);
+    }
+
+    /**
+     * Zoom buckets per doubling of zoom (default 2, clamped to
+     * 1-MAX_BUCKETS_PER_OCTAVE); changing it drops the get_or_tessellate
+     * meshes
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn zoom_buckets_per_octave(&self) -> u32 {
+        self.zoom_buckets_per_octave
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_zoom_buckets_per_octave(&mut self, buckets: u32) {
+        let buckets = buckets.clamp(1, MAX_BUCKETS_PER_OCTAVE);
+        if buckets != self.zoom_buckets_per_octave {
+            self.zoom_buckets_per_octave = buckets;
+            for entry in self.entries.values_mut() {
+                self.usage -= entry.drop_results(Operation::is_mesh);
+            }
+        }
+    }
+
+    /**
+     * get_or_tessellate calls answered from the cache
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn tessellation_hits(&self) -> usize {
+        self.tessellation_hits
+    }
+
+    /**
+     * get_or_tessellate calls that had to tessellate
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn tessellation_misses(&self) -> usize {
+        self.tessellation_misses
+    }
+
+    /**
+     * Bytes held for get_or_tessellate meshes (part of memory_usage())
+     */
+    pub fn tessellation_bytes(&self) -> usize {
+        self.entries
+            .values()
+            .flat_map(|entry| entry.results.iter())
+            .filter(|(operation, _)| operation.is_mesh())
+            .map(|(_, bytes)| bytes.len())
+            .sum()
+    }
+
+    /**
+     * Zero the hit and miss counters
+     */
+    pub fn reset_tessellation_stats(&mut self) {
+        self.tessellation_hits = 0;
+        self.tessellation_misses = 0;
+    }
+
+    /**
+     * Number of strokes in the cache
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /**
+     * Whether the cache holds no strokes
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /**
+     * Bytes held for stored points and memoized results
+     */
+    pub fn memory_usage(&self) -> usize {
+        self.usage
+    }
+
+    /**
+     * Copy a stroke into the cache and return its handle
+     *
+     * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...], read as
+     * read_points does (FloatValidation::Repair stores the repaired
+     * points).
+     *
+     * Returns 0 on failure; last_error_code() / last_error_message() then
+     * describe it. Storing may evict other strokes to fit the budget.
+     *
+     * Errors: the read_points errors; InvalidParameter once 2^32 - 1
+     * handles have been issued.
+     */
+    pub fn store(&mut self, points_ptr: *const f32, points_len: usize) -> u32 {
+        let stored = read_points(points_ptr, points_len).and_then(|points| {
+            if self.next_handle == u32::MAX {
+                return Err(EngineError::new(CreoVexError::InvalidParameter, "stroke cache handles exhausted"));
+            }
+            Ok(points.into_owned())
+        });
+        let points = match stored {
+            Ok(points) => points,
+            Err(error) => {
+                record_error(error);
+                return 0;
+            }
+        };
+
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.tick += 1;
+        self.usage += points.len() * 4;
+        self.entries.insert(handle, Entry { points, results: BTreeMap::new(), last_used: self.tick, visible: true });
+        self.evict(Some(handle));
+        handle
+    }
+
+    /**
+     * Whether `handle` still refers to a stored stroke
+     */
+    pub fn contains(&self, handle: u32) -> bool {
+        self.entries.contains_key(&handle)
+    }
+
+    /**
+     * Drop the memoized results of a stroke, keeping its points
+     *
+     * Returns whether the handle was valid.
+     */
+    pub fn invalidate(&mut self, handle: u32) -> bool {
+        let Some(entry) = self.entries.get_mut(&handle) else {
+            return false;
+        };
+        self.usage -= entry.result_bytes();
+        entry.results.clear();
+        true
+    }
+
+    /**
+     * Drop the tessellate and get_or_tessellate results of a stroke, e.g.
+     * after its brush changed; returns whether the handle was valid
+     */
+    pub fn invalidate_tessellation(&mut self, handle: u32) -> bool {
+        let Some(entry) = self.entries.get_mut(&handle) else {
+            return false;
+        };
+        self.usage -= entry.drop_results(Operation::is_tessellation);
+        true
+    }
+
+    /**
+     * Tell the cache which strokes are on screen
+     *
+     * Input format: [h0, h1, ...] u32 handles of the visible strokes (e.g.
+     * from cull_strokes); every other stroke counts as off-screen until
+     * the next call. Strokes are visible when stored. Handles not in the
+     * cache are ignored.
+     *
+     * Returns the number of cached strokes marked visible, or a negative
+     * CreoVexError discriminant (visibility is unchanged then).
+     *
+     * Errors: NullInput for a null `handles_ptr` with a non-zero
+     * `handles_len`.
+     */
+    pub fn mark_visible(&mut self, handles_ptr: *const u32, handles_len: usize) -> i32 {
+        run_status(|| {
+            if handles_len > 0 && handles_ptr.is_null() {
+                return Err(EngineError::new(CreoVexError::NullInput, "handles_ptr is null"));
+            }
+            let handles: &[u32] =
+                if handles_len == 0 { &[] } else { unsafe { std::slice::from_raw_parts(handles_ptr, handles_len) } };
+
+            for entry in self.entries.values_mut() {
+                entry.visible = false;
+            }
+            let mut marked = 0;
+            for handle in handles {
+                if let Some(entry) = self.entries.get_mut(handle) {
+                    marked += usize::from(!entry.visible);
+                    entry.visible = true;
+                }
+            }
+            Ok(marked)
+        })
+    }
+
+    /**
+     * Remove a stroke and its results; returns whether the handle was valid
+     */
+    pub fn remove(&mut self, handle: u32) -> bool {
+        let Some(entry) = self.entries.remove(&handle) else {
+            return false;
+        };
+        self.usage -= entry.points.len() * 4 + entry.result_bytes();
+        true
+    }
+
+    /**
+     * Remove every stroke (all handles become invalid)
+     */
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.usage = 0;
+    }
+
+    /**
+     * smooth_stroke_v2 of the stored stroke
+     *
+     * Errors: InvalidParameter for an invalid handle, otherwise as
+     * smooth_stroke_v2.
+     */
+    pub fn smooth(&mut self, handle: u32) -> EngineBuffer {
+        self.memoized(handle, Operation::Smooth, |points| smooth_stroke_v2(points.as_ptr(), points.len()))
+    }
+
+    /**
+     * simplify_stroke of the stored stroke
+     *
+     * Errors: InvalidParameter for an invalid handle, otherwise as
+     * simplify_stroke.
+     */
+    pub fn simplify(&mut self, handle: u32, epsilon: f32, corner_angle_deg: f32) -> EngineBuffer {
+        let operation =
+            Operation::Simplify { epsilon: epsilon.to_bits(), corner_angle_deg: corner_angle_deg.to_bits() };
+        self.memoized(handle, operation, |points| {
+            simplify_stroke(points.as_ptr(), points.len(), epsilon, corner_angle_deg)
+        })
+    }
+
+    /**
+     * tessellate_stroke of the stored stroke
+     *
+     * Errors: InvalidParameter for an invalid handle, otherwise as
+     * tessellate_stroke.
+     */
+    pub fn tessellate(&mut self, handle: u32, base_width: f32) -> EngineBuffer {
+        let operation = Operation::Tessellate { base_width: base_width.to_bits() };
+        self.memoized(handle, operation, |points| tessellate_stroke(points.as_ptr(), points.len(), base_width))
+    }
+
+    /**
+     * Mesh of the stored stroke for drawing at `zoom`, memoized per zoom
+     * bucket
+     *
+     * Output format as tessellate_stroke_ex, for the stroke in canvas
+     * units. Zoom only changes round joins and caps: the chord error of a
+     * fan grows with the on-screen radius over the squared segment count,
+     * so `options.round_segments` (the count at zoom 1) is scaled by the
+     * square root of the bucket's zoom, clamped to 1-MAX_ROUND_SEGMENTS.
+     * Buckets are log2(zoom) * zoom_buckets_per_octave rounded; every zoom
+     * in a bucket gets the mesh of the bucket's center, so at zoom 1 the
+     * result equals tessellate_stroke_ex. Meshes without round joins or
+     * caps are the same at every zoom and share one entry.
+     *
+     * Errors: InvalidParameter for an invalid handle; NonFiniteValue/
+     * InvalidParameter for a zoom that is not positive and finite;
+     * otherwise as tessellate_stroke_ex.
+     */
+    pub fn get_or_tessellate(
+        &mut self,
+        handle: u32,
+        zoom: f32,
+        base_width: f32,
+        options: &TessellationOptions,
+    ) -> EngineBuffer {
+        run_export(|| {
+            let zoom = require_positive("zoom", require_finite("zoom", zoom)?)? as f64;
+            let strip = options.strip_options()?;
+
+            let per_octave = self.zoom_buckets_per_octave as f64;
+            let bucket = if uses_round(&strip) { (zoom.log2() * per_octave).round() as i32 } else { 0 };
+            let scale = (bucket as f64 / per_octave).exp2().sqrt();
+            let round_segments =
+                (strip.round_segments as f64 * scale).ceil().clamp(1.0, MAX_ROUND_SEGMENTS as f64) as u32;
+
+            let operation = Operation::Mesh {
+                bucket,
+                base_width: base_width.to_bits(),
+                join: strip.join as u32,
+                miter_limit: strip.miter_limit.to_bits(),
+                round_segments: strip.round_segments,
+                caps: [strip.start_cap as u32, strip.end_cap as u32],
+            };
+            Ok(self.memoized(handle, operation, |points| {
+                tessellate_stroke_ex(
+                    points.as_ptr(),
+                    points.len(),
+                    base_width,
+                    strip.join,
+                    strip.miter_limit,
+                    round_segments,
+                    strip.start_cap,
+                    strip.end_cap,
+                )
+            }))
+        })
+    }
+
+    /**
+     * stroke_bounds of the stored stroke
+     *
+     * Errors: InvalidParameter for an invalid handle, otherwise as
+     * stroke_bounds.
+     */
+    pub fn bounds(&mut self, handle: u32, max_width: f32) -> EngineBuffer {
+        let operation = Operation::Bounds { max_width: max_width.to_bits() };
+        self.memoized(handle, operation, |points| stroke_bounds(points.as_ptr(), points.len(), max_width))
+    }
+}
+
+impl StrokeCache {
+    /**
+     * Return the memoized result of `operation` for `handle`, computing
+     * and keeping it on the first call
+     *
+     * Failed results are returned as is and not kept; the returned buffer
+     * is always the caller's own allocation.
+     */
+    fn memoized(
+        &mut self,
+        handle: u32,
+        operation: Operation,
+        compute: impl FnOnce(&[f32]) -> EngineBuffer,
+    ) -> EngineBuffer {
+        run_export(|| {
+            self.tick += 1;
+            let entry = self.entries.get_mut(&handle).ok_or_else(|| stale_handle(handle))?;
+            entry.last_used = self.tick;
+            if let Some(bytes) = entry.results.get(&operation) {
+                if operation.is_mesh() {
+                    self.tessellation_hits = self.tessellation_hits.saturating_add(1);
+                }
+                return copy_out(bytes);
+            }
+            if operation.is_mesh() {
+                self.tessellation_misses = self.tessellation_misses.saturating_add(1);
+            }
+
+            let result = compute(&entry.points);
+            if result.error_code() != 0 {
+                return Ok(result);
+            }
+            // Not being able to keep a copy only costs the memoization
+            let Ok(mut kept) = output_buffer(result.byte_len()) else {
+                return Ok(result);
+            };
+            kept.extend_from_slice(unsafe { std::slice::from_raw_parts(result.ptr(), result.byte_len()) });
+            self.usage += kept.len();
+            entry.results.insert(operation, kept);
+            self.evict(Some(handle));
+            Ok(result)
+        })
+    }
+
+    /**
+     * Shrink to the byte budget, least recently used first, sparing `keep`
+     *
+     * Off-screen meshes go first, then any memoized results, then
+     * strokes.
+     */
+    fn evict(&mut self, keep: Option<u32>) {
+        if self.byte_budget == 0 || self.usage <= self.byte_budget {
+            return;
+        }
+        let mut by_age: Vec<(u64, u32)> = self
+            .entries
+            .iter()
+            .filter(|&(&handle, _)| Some(handle) != keep)
+            .map(|(&handle, entry)| (entry.last_used, handle))
+            .collect();
+        by_age.sort_unstable();
+
+        for &(_, handle) in &by_age {
+            if self.usage <= self.byte_budget {
+                return;
+            }
+            let entry = self.entries.get_mut(&handle).expect("handle from entries");
+            if !entry.visible {
+                self.usage -= entry.drop_results(Operation::is_mesh);
+            }
+        }
+        for &(_, handle) in &by_age {
+            if self.usage <= self.byte_budget {
+                return;
+            }
+            self.invalidate(handle);
+        }
+        for &(_, handle) in &by_age {
+            if self.usage <= self.byte_budget {
+                return;
+            }
+            self.remove(handle);
+        }
+    }
+}
+
+/**
+ * Whether the zoom-scaled round segment count affects the mesh
+ */
+fn uses_round(strip: &StripOptions) -> bool {
+    strip.join == JoinStyle::Round || strip.start_cap == CapStyle::Round || strip.end_cap == CapStyle::Round
+}
+
+fn stale_handle(handle: u32) -> EngineError {
+    EngineError::new(
+        CreoVexError::InvalidParameter,
+        format!("stroke cache handle {handle} is not valid (never issued, removed, or evicted)"),
+    )
+}
+
+/**
+ * Hand JS a fresh copy of memoized result bytes
+ */
+fn copy_out(bytes: &[u8]) -> EngineResult<EngineBuffer> {
+    let mut buffer = output_buffer(bytes.len())?;
+    buffer.extend_from_slice(bytes);
+    Ok(hand_off(buffer))
+}
@@ -0,0 +1,522 @@
+/*!
+ * Viewport camera
+ *
+ * The renderer draws the canvas through one view matrix built from pan,
+ * zoom, and rotation. Computing it here keeps the matrix the GPU uses and
+ * the screen/world conversions used for picking on the same math.
+ *
+ * Coordinate spaces:
+ * - world: canvas units, as stored in strokes
+ * - screen: viewport pixels, origin top-left, y down
+ * - clip: WebGL clip space, [-1, 1] with y up
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Rendering)
+ */
+
+use std::borrow::Cow;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_finite, require_positive, run_export, run_status, CreoVexError, EngineError, EngineResult};
+use crate::validate::{with_float_validation, FloatValidation};
+use crate::{read_strided_points, read_u32, serialize_records, EngineBuffer, MAX_STRIDE};
+
+/**
+ * Which way transform_points_batch maps points
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraDirection {
+    /** World units to viewport pixels (world_to_screen) */
+    WorldToScreen = 0,
+    /** Viewport pixels to world units (screen_to_world) */
+    ScreenToWorld = 1,
+}
+
+/**
+ * f32 values in a camera buffer: the 3x3 matrix, then the viewport size
+ */
+const CAMERA_FLOATS: usize = 11;
+
+/**
+ * Byte length of a camera buffer (record count plus CAMERA_FLOATS)
+ */
+const CAMERA_BYTES: usize = 4 + CAMERA_FLOATS * 4;
+
+/**
+ * World-to-clip transform plus the viewport it was built for
+ *
+ * `m` is the affine part row-major, [m00, m01, m02, m10, m11, m12] (the
+ * third row is always [0, 0, 1]); kept in f64 so conversions through
+ * the matrix and its inverse round-trip at extreme zoom.
+ */
+#[derive(Clone, Copy, Debug)]
+struct Camera {
+    m: [f64; 6],
+    viewport_w: f64,
+    viewport_h: f64,
+}
+
+impl Camera {
+    /**
+     * Camera mapping world (x, y) to screen
+     * anchor + zoom * R(rotation) * ((x, y) + pan - anchor), then to clip
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn from_view(
+        pan_x: f64,
+        pan_y: f64,
+        zoom: f64,
+        rotation: f64,
+        anchor_x: f64,
+        anchor_y: f64,
+        viewport_w: f64,
+        viewport_h: f64,
+    ) -> Self {
+        let (sin, cos) = rotation.sin_cos();
+        let (l00, l01, l10, l11) = (zoom * cos, -zoom * sin, zoom * sin, zoom * cos);
+        let (dx, dy) = (pan_x - anchor_x, pan_y - anchor_y);
+        let tx = anchor_x + l00 * dx + l01 * dy;
+        let ty = anchor_y + l10 * dx + l11 * dy;
+
+        // Screen pixels to clip: x' = 2x/w - 1, y' = 1 - 2y/h
+        let (sx, sy) = (2.0 / viewport_w, -2.0 / viewport_h);
+        Camera {
+            m: [sx * l00, sx * l01, sx * tx - 1.0, sy * l10, sy * l11, sy * ty + 1.0],
+            viewport_w,
+            viewport_h,
+        }
+    }
+
+    /**
+     * Parse and check a camera buffer (see compute_camera_matrix)
+     */
+    fn read(camera_ptr: *const u8, camera_len: usize) -> EngineResult<Self> {
+        if camera_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "camera_ptr is null"));
+        }
+        let bytes: &[u8] = unsafe { std::slice::from_raw_parts(camera_ptr, camera_len) };
+        if bytes.len() != CAMERA_BYTES || read_u32(bytes, 0) != Some(1) {
+            return Err(EngineError::new(
+                CreoVexError::InvalidLength,
+                format!("camera buffer must be a {CAMERA_BYTES} byte compute_camera_matrix result (got {camera_len})"),
+            ));
+        }
+
+        let mut values = [0.0f64; CAMERA_FLOATS];
+        for (index, value) in values.iter_mut().enumerate() {
+            let b = &bytes[4 + index * 4..8 + index * 4];
+            let raw = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            if !raw.is_finite() {
+                return Err(EngineError::new(
+                    CreoVexError::NonFiniteValue,
+                    format!("camera value {index} is not finite ({raw})"),
+                )
+                .at_index(index));
+            }
+            *value = raw as f64;
+        }
+
+        // Column-major: [m00, m10, 0, m01, m11, 0, m02, m12, 1]
+        let [m00, m10, z0, m01, m11, z1, m02, m12, one, viewport_w, viewport_h] = values;
+        if z0 != 0.0 || z1 != 0.0 || one != 1.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                "camera matrix must be affine (third row [0, 0, 1])",
+            ));
+        }
+        if viewport_w <= 0.0 || viewport_h <= 0.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("camera viewport must be positive (got {viewport_w} x {viewport_h})"),
+            ));
+        }
+        Ok(Camera { m: [m00, m01, m02, m10, m11, m12], viewport_w, viewport_h })
+    }
+
+    /**
+     * The clip-to-world camera (same viewport)
+     */
+    fn inverse(&self) -> EngineResult<Self> {
+        let [m00, m01, m02, m10, m11, m12] = self.m;
+        let det = m00 * m11 - m01 * m10;
+        let inverse_det = 1.0 / det;
+        if det == 0.0 || !inverse_det.is_finite() {
+            return Err(EngineError::new(CreoVexError::InvalidParameter, "camera matrix is singular"));
+        }
+        let (i00, i01, i10, i11) = (m11 * inverse_det, -m01 * inverse_det, -m10 * inverse_det, m00 * inverse_det);
+        Ok(Camera {
+            m: [i00, i01, -(i00 * m02 + i01 * m12), i10, i11, -(i10 * m02 + i11 * m12)],
+            ..*self
+        })
+    }
+
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let [m00, m01, m02, m10, m11, m12] = self.m;
+        (m00 * x + m01 * y + m02, m10 * x + m11 * y + m12)
+    }
+
+    fn screen_to_clip(&self, x: f64, y: f64) -> (f64, f64) {
+        (2.0 * x / self.viewport_w - 1.0, 1.0 - 2.0 * y / self.viewport_h)
+    }
+
+    fn clip_to_screen(&self, x: f64, y: f64) -> (f64, f64) {
+        ((x + 1.0) * 0.5 * self.viewport_w, (1.0 - y) * 0.5 * self.viewport_h)
+    }
+
+    /**
+     * Map one point in `direction`; ScreenToWorld expects the inverse
+     * camera
+     */
+    #[inline(always)]
+    fn map(&self, direction: CameraDirection, x: f64, y: f64) -> (f64, f64) {
+        match direction {
+            CameraDirection::WorldToScreen => {
+                let (clip_x, clip_y) = self.apply(x, y);
+                self.clip_to_screen(clip_x, clip_y)
+            }
+            CameraDirection::ScreenToWorld => {
+                let (clip_x, clip_y) = self.screen_to_clip(x, y);
+                self.apply(clip_x, clip_y)
+            }
+        }
+    }
+
+    /**
+     * Map the x/y of every `stride`-float point in place
+     *
+     * Inlined so that a literal stride at the call site makes this a
+     * plain fixed-step pass over contiguous memory, which the compiler
+     * unrolls and vectorizes.
+     */
+    #[inline(always)]
+    fn map_points(&self, direction: CameraDirection, points: &mut [f32], stride: usize) {
+        for point in points.chunks_exact_mut(stride) {
+            let (x, y) = self.map(direction, point[0] as f64, point[1] as f64);
+            point[0] = x as f32;
+            point[1] = y as f32;
+        }
+    }
+
+    /**
+     * Camera buffer: record count, column-major 3x3 matrix, viewport size
+     */
+    fn serialize(&self) -> EngineResult<EngineBuffer> {
+        let [m00, m01, m02, m10, m11, m12] = self.m.map(|value| value as f32);
+        let values =
+            [m00, m10, 0.0, m01, m11, 0.0, m02, m12, 1.0, self.viewport_w as f32, self.viewport_h as f32];
+        if values.iter().any(|value| !value.is_finite()) {
+            return Err(EngineError::new(CreoVexError::InvalidParameter, "camera matrix overflows f32"));
+        }
+        serialize_records(1, &values)
+    }
+}
+
+/**
+ * Compute the view matrix for a panned, zoomed, and rotated viewport
+ *
+ * A world point (x, y) lands on screen at
+ * anchor + zoom * R(rotation) * ((x, y) + pan - anchor), where R rotates
+ * by `rotation` radians from +x toward +y (clockwise on the y-down
+ * screen). The world point anchor - pan therefore stays under the anchor
+ * at every zoom and rotation: pinch-zoom and rotate about the fingers by
+ * keeping the anchor and pan fixed while zoom and rotation change. Pan
+ * is in world units, the anchor in screen pixels.
+ *
+ * Output format (little-endian):
+ * - 4 bytes: record count (u32, always 1)
+ * - 36 bytes: f32 3x3 world-to-clip matrix, column-major, ready for
+ *   `gl.uniformMatrix3fv(location, false, view)` on the 9 floats at byte
+ *   offset 4 (for a mat4 uniform, pad to [m00, m10, 0, 0, m01, m11, 0, 0,
+ *   0, 0, 1, 0, m02, m12, 0, 1])
+ * - 8 bytes: f32 viewport width and height in pixels
+ *
+ * Pass the whole buffer (ptr, byte_len) to invert_camera_matrix,
+ * screen_to_world, and world_to_screen.
+ *
+ * Errors: NonFiniteValue for a NaN or infinite argument;
+ * InvalidParameter for a zoom or viewport size that is not positive, or
+ * a view whose matrix overflows f32.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn compute_camera_matrix(
+    pan_x: f32,
+    pan_y: f32,
+    zoom: f32,
+    rotation: f32,
+    anchor_x: f32,
+    anchor_y: f32,
+    viewport_w: f32,
+    viewport_h: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let finite = |name: &str, value: f32| require_finite(name, value).map(f64::from);
+        let positive = |name: &str, value: f32| require_positive(name, require_finite(name, value)?).map(f64::from);
+        Camera::from_view(
+            finite("pan_x", pan_x)?,
+            finite("pan_y", pan_y)?,
+            positive("zoom", zoom)?,
+            finite("rotation", rotation)?,
+            finite("anchor_x", anchor_x)?,
+            finite("anchor_y", anchor_y)?,
+            positive("viewport_w", viewport_w)?,
+            positive("viewport_h", viewport_h)?,
+        )
+        .serialize()
+    })
+}
+
+/**
+ * Invert a camera matrix, mapping clip space back to world
+ *
+ * Input format: a compute_camera_matrix buffer, exactly `camera_len`
+ * bytes
+ *
+ * Output format: the same layout, holding the clip-to-world matrix and
+ * the unchanged viewport size. Multiply clip coordinates
+ * (2 x / width - 1, 1 - 2 y / height) by it to pick in world space, for
+ * example in a shader; screen_to_world does the same for one point.
+ *
+ * Errors: NullInput for a null pointer; InvalidLength for a buffer that
+ * is not a 48-byte camera; NonFiniteValue for a NaN/infinite entry;
+ * InvalidParameter for a non-affine or singular matrix or a viewport
+ * that is not positive.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn invert_camera_matrix(camera_ptr: *const u8, camera_len: usize) -> EngineBuffer {
+    run_export(|| Camera::read(camera_ptr, camera_len)?.inverse()?.serialize())
+}
+
+/**
+ * Convert a screen pixel position to world coordinates
+ *
+ * Input format: a compute_camera_matrix buffer, exactly `camera_len`
+ * bytes; (x, y) in viewport pixels
+ *
+ * Output format:
+ * - 4 bytes: record count (u32, always 1)
+ * - 8 bytes: f32 [x, y] in world units
+ *
+ * The exact inverse of world_to_screen: both apply the buffer's matrix
+ * (or its inverse) in double precision, so points round-trip to f32
+ * precision at any zoom.
+ *
+ * Errors: as invert_camera_matrix, plus NonFiniteValue for a NaN or
+ * infinite coordinate.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn screen_to_world(camera_ptr: *const u8, camera_len: usize, x: f32, y: f32) -> EngineBuffer {
+    run_export(|| {
+        let camera = Camera::read(camera_ptr, camera_len)?;
+        let (x, y) = (require_finite("x", x)? as f64, require_finite("y", y)? as f64);
+        let (world_x, world_y) = camera.inverse()?.map(CameraDirection::ScreenToWorld, x, y);
+        serialize_records(1, &[world_x as f32, world_y as f32])
+    })
+}
+
+/**
+ * Convert a world position to screen pixels
+ *
+ * Input format: a compute_camera_matrix buffer, exactly `camera_len`
+ * bytes; (x, y) in world units
+ *
+ * Output format:
+ * - 4 bytes: record count (u32, always 1)
+ * - 8 bytes: f32 [x, y] in viewport pixels (origin top-left, y down)
+ *
+ * The point the GPU draws (x, y) at: the buffer's matrix applied to
+ * (x, y, 1), then clip space mapped to the viewport.
+ *
+ * Errors: as invert_camera_matrix, except that a singular matrix is
+ * accepted, plus NonFiniteValue for a NaN or infinite coordinate.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn world_to_screen(camera_ptr: *const u8, camera_len: usize, x: f32, y: f32) -> EngineBuffer {
+    run_export(|| {
+        let camera = Camera::read(camera_ptr, camera_len)?;
+        let (x, y) = (require_finite("x", x)? as f64, require_finite("y", y)? as f64);
+        let (screen_x, screen_y) = camera.map(CameraDirection::WorldToScreen, x, y);
+        serialize_records(1, &[screen_x as f32, screen_y as f32])
+    })
+}
+
+/**
+ * Map a whole point buffer between world and screen in place
+ *
+ * Input format: `stride` floats per point, [x, y, extra...], at
+ * `points_ptr`; a compute_camera_matrix buffer, exactly `camera_len`
+ * bytes
+ *
+ * Only x and y are rewritten: with a stride of 3 or more, pressure and
+ * any further channels are left untouched (stride 2 is a plain x/y
+ * buffer). Each point gives exactly the world_to_screen /
+ * screen_to_world result for it. The camera, including its inverse for
+ * ScreenToWorld, is checked before the buffer is touched, so a failed
+ * call never leaves it half-transformed or filled with NaN.
+ *
+ * Returns the number of points transformed, or a negative CreoVexError
+ * discriminant on failure. Under FloatValidation::Repair the repaired
+ * values are written back too.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: InvalidParameter for a stride outside 2-8; the read_points
+ * errors with `points_len` a multiple of `stride`; the
+ * invert_camera_matrix errors for the camera, except that world-to-screen
+ * accepts a singular matrix.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn transform_points_batch(
+    points_ptr: *mut f32,
+    points_len: usize,
+    stride: usize,
+    camera_ptr: *const u8,
+    camera_len: usize,
+    direction: CameraDirection,
+    validation: Option<FloatValidation>,
+) -> i32 {
+    with_float_validation(validation, || {
+        run_status(|| {
+            // Unlike require_stride, x/y-only buffers are allowed: no channel past y is read
+            if !(2..=MAX_STRIDE).contains(&stride) {
+                return Err(EngineError::new(
+                    CreoVexError::InvalidParameter,
+                    format!("stride must be 2-{MAX_STRIDE} (got {stride})"),
+                ));
+            }
+            let repaired = match read_strided_points(points_ptr, points_len, stride)? {
+                Cow::Owned(values) => Some(values),
+                Cow::Borrowed(_) => None,
+            };
+            let camera = Camera::read(camera_ptr, camera_len)?;
+            let camera = match direction {
+                CameraDirection::WorldToScreen => camera,
+                CameraDirection::ScreenToWorld => camera.inverse()?,
+            };
+
+            let points = unsafe { std::slice::from_raw_parts_mut(points_ptr, points_len) };
+            if let Some(values) = repaired {
+                points.copy_from_slice(&values);
+            }
+            match stride {
+                2 => camera.map_points(direction, points, 2),
+                3 => camera.map_points(direction, points, 3),
+                _ => camera.map_points(direction, points, stride),
+            }
+            Ok(points_len / stride)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{error_of, take_bytes, take_records, Noise};
+
+    const VIEWPORT: (f32, f32) = (1920.0, 1080.0);
+
+    fn camera(pan: [f32; 2], zoom: f32, rotation: f32, anchor: [f32; 2]) -> Vec<u8> {
+        take_bytes(compute_camera_matrix(pan[0], pan[1], zoom, rotation, anchor[0], anchor[1], VIEWPORT.0, VIEWPORT.1))
+    }
+
+    fn value(camera: &[u8], index: usize) -> f32 {
+        f32::from_le_bytes(camera[4 + index * 4..8 + index * 4].try_into().unwrap())
+    }
+
+    fn to_screen(camera: &[u8], x: f32, y: f32) -> [f32; 2] {
+        let point = take_records(world_to_screen(camera.as_ptr(), camera.len(), x, y), 2);
+        [point[0], point[1]]
+    }
+
+    fn to_world(camera: &[u8], x: f32, y: f32) -> [f32; 2] {
+        let point = take_records(screen_to_world(camera.as_ptr(), camera.len(), x, y), 2);
+        [point[0], point[1]]
+    }
+
+    fn close(a: [f32; 2], b: [f32; 2], tolerance: f32) -> bool {
+        (a[0] - b[0]).abs() <= tolerance && (a[1] - b[1]).abs() <= tolerance
+    }
+
+    #[test]
+    fn unit_view_maps_world_onto_pixels() {
+        let view = camera([0.0, 0.0], 1.0, 0.0, [0.0, 0.0]);
+        assert_eq!(view.len(), CAMERA_BYTES);
+        // The screen origin is clip (-1, 1)
+        assert_eq!((value(&view, 6), value(&view, 7), value(&view, 8)), (-1.0, 1.0, 1.0));
+        assert_eq!((value(&view, 9), value(&view, 10)), VIEWPORT);
+        assert!(close(to_screen(&view, 100.0, 50.0), [100.0, 50.0], 1e-3));
+
+        // Rotating a quarter turn takes world +x to screen +y
+        let turned = camera([0.0, 0.0], 2.0, std::f32::consts::FRAC_PI_2, [0.0, 0.0]);
+        assert!(close(to_screen(&turned, 10.0, 0.0), [0.0, 20.0], 1e-3));
+    }
+
+    #[test]
+    fn points_round_trip_at_extreme_zoom() {
+        let mut noise = Noise::new(74);
+        for zoom in [0.01f32, 0.1, 1.0, 10.0, 100.0] {
+            for _ in 0..100 {
+                let pan = [noise.next() * 1000.0, noise.next() * 1000.0];
+                let anchor = [(noise.next() + 1.0) * 960.0, (noise.next() + 1.0) * 540.0];
+                let view = camera(pan, zoom, noise.next() * 6.0, anchor);
+
+                // The anchor stays put under zoom and rotation
+                let pinned = to_screen(&view, anchor[0] - pan[0], anchor[1] - pan[1]);
+                assert!(close(pinned, anchor, 0.05 * zoom.max(1.0)), "zoom {zoom}: {pinned:?} vs {anchor:?}");
+
+                let world = [noise.next() * 2000.0, noise.next() * 2000.0];
+                let screen = to_screen(&view, world[0], world[1]);
+                let back = to_world(&view, screen[0], screen[1]);
+                let tolerance = (2e-3 * world[0].abs().max(world[1].abs())).max(1e-2 / zoom);
+                assert!(close(back, world, tolerance), "zoom {zoom}: {world:?} came back as {back:?}");
+
+                let pixel = [(noise.next() + 1.0) * 960.0, (noise.next() + 1.0) * 540.0];
+                let picked = to_world(&view, pixel[0], pixel[1]);
+                let back = to_screen(&view, picked[0], picked[1]);
+                let tolerance = (2e-3f32).max(4.0 * zoom * picked[0].abs().max(picked[1].abs()) * f32::EPSILON);
+                assert!(close(back, pixel, tolerance), "zoom {zoom}: {pixel:?} came back as {back:?}");
+
+                // The inverse matrix applied to clip coordinates picks the same point
+                let inverse = take_bytes(invert_camera_matrix(view.as_ptr(), view.len()));
+                let m = |index: usize| value(&inverse, index) as f64;
+                let clip = [2.0 * pixel[0] as f64 / 1920.0 - 1.0, 1.0 - 2.0 * pixel[1] as f64 / 1080.0];
+                let shader = [m(0) * clip[0] + m(3) * clip[1] + m(6), m(1) * clip[0] + m(4) * clip[1] + m(7)];
+                for axis in 0..2 {
+                    let expected = picked[axis] as f64;
+                    assert!((shader[axis] - expected).abs() <= 1e-3 * expected.abs().max(1.0), "zoom {zoom}: {shader:?} vs {picked:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bad_views_and_buffers_are_rejected() {
+        let build = |zoom: f32, rotation: f32, width: f32| error_of(compute_camera_matrix(0.0, 0.0, zoom, rotation, 0.0, 0.0, width, 10.0));
+        assert_eq!(build(0.0, 0.0, 10.0), CreoVexError::InvalidParameter as u32);
+        assert_eq!(build(f32::NAN, 0.0, 10.0), CreoVexError::NonFiniteValue as u32);
+        assert_eq!(build(1.0, 0.0, 0.0), CreoVexError::InvalidParameter as u32);
+        assert_eq!(build(1.0, f32::INFINITY, 10.0), CreoVexError::NonFiniteValue as u32);
+
+        let view = camera([0.0, 0.0], 1.0, 0.0, [0.0, 0.0]);
+        assert_eq!(error_of(screen_to_world(view.as_ptr(), CAMERA_BYTES - 1, 0.0, 0.0)), CreoVexError::InvalidLength as u32);
+        assert_eq!(error_of(screen_to_world(std::ptr::null(), CAMERA_BYTES, 0.0, 0.0)), CreoVexError::NullInput as u32);
+        assert_eq!(error_of(world_to_screen(view.as_ptr(), CAMERA_BYTES, f32::NAN, 1.0)), CreoVexError::NonFiniteValue as u32);
+
+        // A projective third row is not a camera
+        let mut projective = view.clone();
+        projective[36..40].copy_from_slice(&2.0f32.to_le_bytes());
+        assert_eq!(error_of(invert_camera_matrix(projective.as_ptr(), CAMERA_BYTES)), CreoVexError::InvalidParameter as u32);
+
+        // A singular matrix cannot pick, but can still draw
+        let mut singular = view.clone();
+        for index in [0, 1, 3, 4] {
+            singular[4 + index * 4..8 + index * 4].copy_from_slice(&0.0f32.to_le_bytes());
+        }
+        assert_eq!(error_of(invert_camera_matrix(singular.as_ptr(), CAMERA_BYTES)), CreoVexError::InvalidParameter as u32);
+        assert_eq!(take_records(world_to_screen(singular.as_ptr(), CAMERA_BYTES, 1.0, 1.0), 2).len(), 2);
+    }
+}
@@ -0,0 +1,391 @@
+/*!
+ * Typed stroke data model and native stroke API
+ *
+ * The wasm exports exchange flat little-endian buffers (ffi_contract.md);
+ * these are the values behind them, for native Rust callers and for the
+ * engine's own buffer conversions. Point has the same [x, y, pressure]
+ * f32 layout as a stroke buffer, so point data is viewed as points
+ * without copying. With the `serde` feature the types implement
+ * Serialize/Deserialize, so strokes can be stored as JSON or bincode
+ * without going through the wasm ABI.
+ *
+ * The Stroke methods are the engine's algorithms without wasm: each runs
+ * the same function over `&[f32]` as its export, which only adds pointer
+ * validation and serialization, so both give the same points. Building
+ * with `default-features = false` drops the exports and the wasm-bindgen
+ * dependency, leaving this API for desktop builds and native tests; with
+ * the `std` feature off as well it builds as no_std + alloc, without the
+ * batch and tessellation methods (their buffers come from the pool).
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Data Formats)
+ */
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+use crate::bounds::painted_bounds;
+use crate::error::EngineResult;
+#[cfg(feature = "std")]
+use crate::error::require_non_negative;
+#[cfg(feature = "std")]
+use crate::pool::AlignedBuffer;
+use crate::resample::{resample_count_points, resample_points};
+use crate::simplify::simplify_points;
+use crate::smooth::{smooth_points, DEFAULT_ITERATIONS};
+#[cfg(feature = "std")]
+use crate::tessellate::{extrude_strip, index_strip, StripOptions};
+use crate::transform::transform_points;
+#[cfg(feature = "std")]
+use crate::validate::validate_floats;
+use crate::{read_points, CreoVexError};
+#[cfg(feature = "std")]
+use crate::{batch_stroke_values, output_buffer, pack_lists, split_packed};
+
+/**
+ * One stroke sample: canvas position and pen pressure (0-1)
+ *
+ * `#[repr(C)]`: identical to one [x, y, p] triplet of a stroke buffer.
+ */
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+    pub pressure: f32,
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32, pressure: f32) -> Self {
+        Point { x, y, pressure }
+    }
+
+    /**
+     * View [x0, y0, p0, x1, ...] values as points, without copying
+     *
+     * A trailing partial point (fewer than three values) is left out.
+     */
+    pub fn from_triplets(values: &[f32]) -> &[Point] {
+        // Point is repr(C) with three f32 fields: same size and alignment as [f32; 3]
+        unsafe { core::slice::from_raw_parts(values.as_ptr().cast::<Point>(), values.len() / 3) }
+    }
+
+    /**
+     * View points as [x0, y0, p0, x1, ...] values, without copying
+     */
+    pub fn as_triplets(points: &[Point]) -> &[f32] {
+        unsafe { core::slice::from_raw_parts(points.as_ptr().cast::<f32>(), points.len() * 3) }
+    }
+}
+
+/**
+ * An ordered list of samples, as passed to every stroke export
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stroke {
+    pub points: Vec<Point>,
+}
+
+impl Stroke {
+    /**
+     * Copy a stroke buffer ([x0, y0, p0, x1, ...]) into a stroke
+     */
+    pub fn from_triplets(values: &[f32]) -> Self {
+        Stroke { points: Point::from_triplets(values).to_vec() }
+    }
+
+    /**
+     * The points in stroke buffer layout, without copying
+     */
+    pub fn triplets(&self) -> &[f32] {
+        Point::as_triplets(&self.points)
+    }
+
+    /**
+     * Read strokes from the batch container layout (see read_batch)
+     *
+     * Errors: InvalidLength for a malformed container.
+     */
+    #[cfg(feature = "std")]
+    pub fn read_batch(bytes: &[u8]) -> Result<Vec<Stroke>, CreoVexError> {
+        let strokes = split_packed("batch", bytes, 3).map_err(|error| error.code)?;
+        Ok(strokes.into_iter().map(|stroke| Stroke::from_triplets(&batch_stroke_values(stroke))).collect())
+    }
+
+    /**
+     * Write strokes in the batch container layout, ready for a *_batch
+     * export
+     *
+     * Errors: AllocationFailed when the buffer cannot be allocated.
+     */
+    #[cfg(feature = "std")]
+    pub fn write_batch(strokes: &[Stroke]) -> Result<Vec<u8>, CreoVexError> {
+        let lists: Vec<&[f32]> = strokes.iter().map(Stroke::triplets).collect();
+        pack_lists(&lists, 3).map(|buffer| buffer.to_vec()).map_err(|error| error.code)
+    }
+
+    /**
+     * Tessellate into an indexed triangle mesh, as
+     * tessellate_stroke_indexed with miter joins (limit 4) and butt caps
+     *
+     * Non-finite values are handled per the FloatValidation mode.
+     *
+     * Errors: NonFiniteValue/InvalidParameter for a NaN or negative
+     * `base_width`; NonFiniteValue for a NaN/infinite point under
+     * FloatValidation::Reject.
+     */
+    #[cfg(feature = "std")]
+    pub fn tessellate(&self, base_width: f32) -> Result<TessellationResult, CreoVexError> {
+        let tessellate = || -> EngineResult<TessellationResult> {
+            let points = validate_floats(self.triplets(), 3)?;
+            let base_width = require_non_negative("base_width", base_width)?;
+            Ok(TessellationResult::indexed_strip(&extrude_strip(&points, base_width, &StripOptions::DEFAULT), 2))
+        };
+        tessellate().map_err(|error| error.code)
+    }
+
+    /**
+     * Chaikin-smoothed copy, as smooth_stroke_v2
+     *
+     * Like every method below, the points are checked as the exports
+     * check them (read_points): non-finite values are handled per the
+     * FloatValidation mode.
+     *
+     * Errors: TooFewPoints for an empty stroke; NonFiniteValue for a
+     * NaN/infinite point under FloatValidation::Reject.
+     */
+    pub fn smooth(&self) -> Result<Stroke, CreoVexError> {
+        self.smooth_ex(DEFAULT_ITERATIONS, 1.0, 0.0, 1.0, false)
+    }
+
+    /**
+     * Smoothed copy with explicit parameters, as smooth_stroke_ex
+     *
+     * Errors: as smooth, plus the parameter errors of smooth_stroke_ex.
+     */
+    pub fn smooth_ex(
+        &self,
+        iterations: u32,
+        tension: f32,
+        corner_angle_deg: f32,
+        strength: f32,
+        sanitize: bool,
+    ) -> Result<Stroke, CreoVexError> {
+        self.process(|points| smooth_points(points, iterations, tension, corner_angle_deg, strength, sanitize))
+    }
+
+    /**
+     * Ramer-Douglas-Peucker simplified copy, as simplify_stroke
+     *
+     * Errors: as smooth, plus NonFiniteValue/InvalidParameter for a NaN
+     * or negative epsilon or corner angle.
+     */
+    pub fn simplify(&self, epsilon: f32, corner_angle_deg: f32) -> Result<Stroke, CreoVexError> {
+        self.process(|points| simplify_points(points, epsilon, corner_angle_deg))
+    }
+
+    /**
+     * Copy resampled at a fixed arc-length spacing, as resample_stroke
+     *
+     * Errors: as smooth, plus the spacing errors of resample_stroke.
+     */
+    pub fn resample(&self, spacing: f32) -> Result<Stroke, CreoVexError> {
+        self.process(|points| resample_points(points, spacing))
+    }
+
+    /**
+     * Copy resampled to exactly `count` points, as resample_to_count
+     *
+     * Errors: as smooth, plus the count errors of resample_to_count.
+     */
+    pub fn resample_to_count(&self, count: u32) -> Result<Stroke, CreoVexError> {
+        self.process(|points| resample_count_points(points, 3, count))
+    }
+
+    /**
+     * Transformed copy through the 2x3 matrix [m00, m01, m02, m10, m11,
+     * m12], as transform_stroke
+     *
+     * Errors: as smooth, plus NonFiniteValue for a NaN or infinite matrix
+     * entry.
+     */
+    pub fn transform(&self, m: [f32; 6], scale_pressure: bool) -> Result<Stroke, CreoVexError> {
+        self.process(|points| transform_points(points, m, scale_pressure))
+    }
+
+    /**
+     * Painted bounding box [min_x, min_y, max_x, max_y], as stroke_bounds
+     * (an empty stroke gives [0, 0, 0, 0])
+     *
+     * Errors: NonFiniteValue/InvalidParameter for a NaN or negative
+     * `max_width`; NonFiniteValue for a NaN/infinite point under
+     * FloatValidation::Reject.
+     */
+    pub fn bounds(&self, max_width: f32) -> Result<[f32; 4], CreoVexError> {
+        let bounds = || -> EngineResult<[f32; 4]> {
+            let points = if self.points.is_empty() { Cow::Borrowed(&[][..]) } else { self.validated()? };
+            painted_bounds(&points, max_width)
+        };
+        bounds().map_err(|error| error.code)
+    }
+
+    /**
+     * The points as an export reads them (see read_points)
+     */
+    fn validated(&self) -> EngineResult<Cow<'_, [f32]>> {
+        let values = self.triplets();
+        read_points(values.as_ptr(), values.len())
+    }
+
+    /**
+     * A stroke-to-stroke algorithm over the validated points
+     */
+    fn process(&self, algorithm: impl FnOnce(&[f32]) -> EngineResult<Vec<f32>>) -> Result<Stroke, CreoVexError> {
+        let values = self.validated().and_then(|points| algorithm(&points)).map_err(|error| error.code)?;
+        Ok(Stroke::from_triplets(&values))
+    }
+}
+
+/**
+ * Indexed triangle mesh produced by the tessellation exports
+ *
+ * `vertices` holds `floats_per_vertex` f32 values per vertex, [x, y]
+ * first (the extra floats are documented at each export); `indices` holds
+ * three vertex indices per triangle.
+ */
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TessellationResult {
+    pub vertices: Vec<f32>,
+    pub floats_per_vertex: usize,
+    pub indices: Vec<u32>,
+}
+
+#[cfg(feature = "std")]
+impl TessellationResult {
+    /**
+     * Weld a triangle strip into an indexed mesh (see index_strip)
+     */
+    pub(crate) fn indexed_strip(strip: &[f32], floats_per_vertex: usize) -> Self {
+        let (vertices, indices) = index_strip(strip, floats_per_vertex);
+        TessellationResult { vertices, floats_per_vertex, indices }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len().checked_div(self.floats_per_vertex).unwrap_or(0)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /**
+     * The mesh in the serialize_mesh buffer layout
+     *
+     * Errors: AllocationFailed when the buffer cannot be allocated.
+     */
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CreoVexError> {
+        self.mesh_bytes().map(|buffer| buffer.to_vec()).map_err(|error| error.code)
+    }
+
+    pub(crate) fn mesh_bytes(&self) -> EngineResult<AlignedBuffer> {
+        let mut buffer = output_buffer(8 + (self.vertices.len() * 4) + (self.indices.len() * 4))?;
+        buffer.extend_from_slice(&(self.vertex_count() as u32).to_le_bytes());
+        buffer.extend_from_slice(&(self.indices.len() as u32).to_le_bytes());
+        for &value in &self.vertices {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        for &index in &self.indices {
+            buffer.extend_from_slice(&index.to_le_bytes());
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bounds::stroke_bounds;
+    use crate::resample::{resample_stroke, resample_to_count};
+    use crate::simplify::simplify_stroke;
+    use crate::smooth::{smooth_stroke_ex, smooth_stroke_v2};
+    use crate::tessellate::{tessellate_stroke_indexed, CapStyle, JoinStyle};
+    use crate::test_support::{pack_batch, sine_stroke, take_bytes, take_points, take_records};
+    use crate::transform::transform_stroke;
+    use crate::EngineBuffer;
+
+    fn bits(values: &[f32]) -> Vec<u32> {
+        values.iter().map(|value| value.to_bits()).collect()
+    }
+
+    #[test]
+    fn points_view_stroke_buffers_in_place() {
+        let values = [1.0, 2.0, 0.5, 3.0, 4.0, 1.0, 9.0];
+        let points = Point::from_triplets(&values);
+        assert_eq!(points, [Point::new(1.0, 2.0, 0.5), Point::new(3.0, 4.0, 1.0)]);
+        assert_eq!(points.as_ptr().cast::<f32>(), values.as_ptr());
+        assert_eq!(Point::as_triplets(points), &values[..6]);
+
+        let stroke = Stroke::from_triplets(&values);
+        assert_eq!(stroke.points, points);
+        assert_eq!(stroke.triplets(), &values[..6]);
+        assert_eq!(Stroke::default().triplets(), &[] as &[f32]);
+    }
+
+    #[test]
+    fn methods_match_their_exports() {
+        let values = sine_stroke(40, 1.5, 12.0, 6.0);
+        let (ptr, len) = (values.as_ptr(), values.len());
+        let stroke = Stroke::from_triplets(&values);
+        let same = |method: Result<Stroke, CreoVexError>, export: EngineBuffer| {
+            assert_eq!(bits(method.unwrap().triplets()), bits(&take_points(export)));
+        };
+
+        same(stroke.smooth(), smooth_stroke_v2(ptr, len));
+        same(stroke.smooth_ex(3, 0.5, 30.0, 0.8, true), smooth_stroke_ex(ptr, len, 3, 0.5, 30.0, 0.8, true));
+        same(stroke.simplify(0.5, 45.0), simplify_stroke(ptr, len, 0.5, 45.0));
+        same(stroke.resample(2.5), resample_stroke(ptr, len, 2.5));
+        same(stroke.resample_to_count(17), resample_to_count(ptr, len, 17));
+        let m = [0.5, -0.25, 10.0, 0.25, 2.0, -3.0];
+        same(stroke.transform(m, true), transform_stroke(ptr, len, m[0], m[1], m[2], m[3], m[4], m[5], true));
+        assert_eq!(bits(&stroke.bounds(3.0).unwrap()), bits(&take_records(stroke_bounds(ptr, len, 3.0), 4)));
+
+        let mesh = stroke.tessellate(2.0).unwrap();
+        let export = tessellate_stroke_indexed(ptr, len, 2.0, JoinStyle::Miter, 4.0, 8, CapStyle::Butt, CapStyle::Butt);
+        assert_eq!(mesh.to_bytes().unwrap(), take_bytes(export));
+        assert_eq!((mesh.floats_per_vertex, mesh.vertex_count() * 2), (2, mesh.vertices.len()));
+        assert_eq!(mesh.triangle_count() * 3, mesh.indices.len());
+    }
+
+    #[test]
+    fn batches_round_trip() {
+        let strokes = [vec![1.0, 2.0, 0.5], vec![], sine_stroke(5, 1.0, 2.0, 3.0)];
+        let bytes = Stroke::write_batch(&strokes.iter().map(|values| Stroke::from_triplets(values)).collect::<Vec<_>>()).unwrap();
+        assert_eq!(bytes, pack_batch(&strokes));
+        let read = Stroke::read_batch(&bytes).unwrap();
+        assert_eq!(read.iter().map(|stroke| stroke.triplets().to_vec()).collect::<Vec<_>>(), strokes);
+        assert_eq!(Stroke::read_batch(&bytes[..bytes.len() - 1]), Err(CreoVexError::InvalidLength));
+    }
+
+    #[test]
+    fn errors_are_the_export_codes() {
+        let empty = Stroke::default();
+        assert_eq!(empty.smooth(), Err(CreoVexError::TooFewPoints));
+        assert_eq!(empty.bounds(1.0), Ok([0.0; 4]));
+
+        let stroke = Stroke::from_triplets(&[0.0, 0.0, 1.0, f32::NAN, 1.0, 1.0]);
+        crate::set_float_validation(crate::FloatValidation::Reject);
+        assert_eq!(stroke.smooth(), Err(CreoVexError::NonFiniteValue));
+        assert_eq!(stroke.bounds(1.0), Err(CreoVexError::NonFiniteValue));
+        assert_eq!(stroke.tessellate(1.0), Err(CreoVexError::NonFiniteValue));
+
+        let stroke = Stroke::from_triplets(&[0.0, 0.0, 1.0, 5.0, 1.0, 1.0]);
+        assert_eq!(stroke.simplify(-1.0, 0.0), Err(CreoVexError::InvalidParameter));
+        assert_eq!(stroke.bounds(-1.0), Err(CreoVexError::InvalidParameter));
+        assert_eq!(stroke.tessellate(f32::NAN), Err(CreoVexError::NonFiniteValue));
+        assert_eq!(stroke.transform([f32::INFINITY, 0.0, 0.0, 0.0, 1.0, 0.0], false), Err(CreoVexError::NonFiniteValue));
+    }
+}
@@ -0,0 +1,130 @@
+/*!
+ * Corner detection
+ *
+ * Finds deliberate sharp corners (lettering, technical sketches) so that
+ * smoothing can keep them as fixed anchors and the UI can show handles.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Smoothing)
+ */
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "std")]
+use crate::error::{require_non_negative, run_export};
+use crate::float::Float;
+#[cfg(feature = "std")]
+use crate::{read_points, serialize_indices, EngineBuffer};
+
+/**
+ * Minimum arm length (canvas units) on each side of a corner
+ *
+ * The angle at a vertex is measured to the nearest neighbors at least
+ * this far away, so sub-pixel jitter on densely sampled input does not
+ * register as a corner.
+ */
+pub(crate) const CORNER_MIN_SEGMENT: f32 = 2.0;
+
+/**
+ * How many neighbors to search on each side for an arm endpoint
+ *
+ * Bounds the cost on clusters of near-identical points (pen held still).
+ */
+const CORNER_SEARCH_WINDOW: usize = 32;
+
+/**
+ * Detect sharp corners in a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: corner count (u32)
+ * - N * 4 bytes: point indices (u32), ascending
+ *
+ * A point is a corner when the angle between its two arms is below
+ * `angle_deg` (180° = straight line, 90° = the elbow of an "L").
+ * Endpoints are never corners, and candidates closer together than
+ * CORNER_MIN_SEGMENT count as one corner at the sharpest point. `angle_deg` = 0
+ * detects nothing.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a NaN or negative angle.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn detect_corners(points_ptr: *const f32, points_len: usize, angle_deg: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let angle_deg = require_non_negative("angle_deg", angle_deg)?;
+
+        let corners: Vec<u32> = find_corners(points, 3, angle_deg.to_radians()).into_iter().map(|i| i as u32).collect();
+        serialize_indices(&corners)
+    })
+}
+
+/**
+ * Indices of corner points, ascending (see detect_corners)
+ *
+ * Points are `stride` floats wide; only x/y are read.
+ */
+pub(crate) fn find_corners<F: Float>(points: &[F], stride: usize, angle_threshold_rad: F) -> Vec<usize> {
+    let count = points.len() / stride;
+    let position = |i: usize| (points[i * stride], points[i * stride + 1]);
+    let min_sq = F::from_f32(CORNER_MIN_SEGMENT * CORNER_MIN_SEGMENT);
+
+    let mut corners: Vec<usize> = Vec::new();
+    // Angle of the kept point in the current run of candidates, if any
+    let mut run_angle: Option<F> = None;
+
+    for i in 1..count.saturating_sub(1) {
+        let v = position(i);
+        let far_enough = |j: usize| distance_sq(position(j), v) >= min_sq;
+
+        let before = (i.saturating_sub(CORNER_SEARCH_WINDOW)..i).rev().find(|&j| far_enough(j));
+        let after = (i + 1..count.min(i + 1 + CORNER_SEARCH_WINDOW)).find(|&k| far_enough(k));
+        let angle = match (before, after) {
+            (Some(j), Some(k)) => arm_angle(position(j), v, position(k)),
+            _ => F::INFINITY,
+        };
+
+        if angle >= angle_threshold_rad {
+            run_angle = None;
+            continue;
+        }
+
+        // Consecutive candidates closer than one arm length belong to
+        // the same corner: keep the sharpest
+        match (run_angle, corners.last_mut()) {
+            (Some(best), Some(last)) if distance_sq(position(*last), v) < min_sq => {
+                if angle < best {
+                    *last = i;
+                    run_angle = Some(angle);
+                }
+            }
+            _ => {
+                corners.push(i);
+                run_angle = Some(angle);
+            }
+        }
+    }
+
+    corners
+}
+
+/**
+ * Angle at `v` between the arms towards `a` and `b`, in radians (0-π)
+ */
+fn arm_angle<F: Float>(a: (F, F), v: (F, F), b: (F, F)) -> F {
+    let (ax, ay) = (a.0 - v.0, a.1 - v.1);
+    let (bx, by) = (b.0 - v.0, b.1 - v.1);
+    (ax * by - ay * bx).abs().atan2(ax * bx + ay * by)
+}
+
+fn distance_sq<F: Float>(a: (F, F), b: (F, F)) -> F {
+    let dx = b.0 - a.0;
+    let dy = b.1 - a.1;
+    dx * dx + dy * dy
+}
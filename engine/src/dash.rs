@@ -0,0 +1,160 @@
+/*!
+ * Dash patterns
+ *
+ * Guides and selected paths are drawn dashed or dotted. The pattern is
+ * laid along the arc length and only its "on" stretches are returned, as
+ * separate strokes, so they render through the normal stroke path.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::{cumulative_lengths, slice_at_lengths};
+use crate::error::{require_finite, run_export, CreoVexError, EngineError, EngineResult};
+use crate::{read_points, serialize_strokes, EngineBuffer};
+
+/**
+ * Most entries a dash pattern may have
+ */
+pub(crate) const MAX_DASH_ENTRIES: usize = 64;
+
+/**
+ * Most dashes one call may produce
+ *
+ * Guards against a tiny pattern on a long stroke allocating millions of
+ * pieces.
+ */
+pub(crate) const MAX_DASH_PIECES: usize = 1 << 20;
+
+/**
+ * Split a stroke into the "on" pieces of a dash pattern
+ *
+ * Input format:
+ * - points: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * - pattern: [on0, off0, on1, off1, ...] lengths in canvas units
+ *
+ * Output format: the batch container (see read_batch) holding the dashes
+ * in stroke order, without a status array; 0 strokes when no dash falls
+ * on the stroke
+ *
+ * The pattern repeats from the first point, shifted by `phase`: the
+ * stroke starts `phase` units into the pattern, so increasing it moves
+ * the dashes backwards along the stroke (marching ants). Any finite
+ * phase is allowed, negative included. As in SVG, a pattern with an odd
+ * number of entries is repeated once to make the on/off pairs.
+ *
+ * Dash ends are cut exactly at their arc length, with position and
+ * pressure interpolated; input points inside a dash are kept. A
+ * zero-length "on" entry gives a single-point stroke (a dot, for a round
+ * cap to draw). A stroke with no length is returned whole if its start
+ * is inside a dash.
+ *
+ * Errors: the read_points errors; NullInput, InvalidLength (0 or more
+ * than MAX_DASH_ENTRIES entries), NonFiniteValue, or InvalidParameter
+ * (a negative entry or a zero total) for the pattern; NonFiniteValue for
+ * a NaN or infinite phase; InvalidParameter when the stroke would split
+ * into more than MAX_DASH_PIECES dashes.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn dash_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    pattern_ptr: *const f32,
+    pattern_len: usize,
+    phase: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let pattern = read_pattern(pattern_ptr, pattern_len)?;
+        let phase = require_finite("phase", phase)?;
+
+        serialize_strokes(&dash_polyline(points, 3, &pattern, phase as f64)?)
+    })
+}
+
+/**
+ * Validate the dash pattern and return it as f64 with an even length
+ */
+fn read_pattern(pattern_ptr: *const f32, pattern_len: usize) -> EngineResult<Vec<f64>> {
+    if pattern_ptr.is_null() {
+        return Err(EngineError::new(CreoVexError::NullInput, "pattern_ptr is null"));
+    }
+    if pattern_len == 0 || pattern_len > MAX_DASH_ENTRIES {
+        return Err(EngineError::new(
+            CreoVexError::InvalidLength,
+            format!("pattern_len must be 1-{MAX_DASH_ENTRIES} (got {pattern_len})"),
+        ));
+    }
+    let raw: &[f32] = unsafe { std::slice::from_raw_parts(pattern_ptr, pattern_len) };
+
+    for (index, &value) in raw.iter().enumerate() {
+        if !value.is_finite() {
+            return Err(EngineError::new(
+                CreoVexError::NonFiniteValue,
+                format!("pattern entry {index} is not finite"),
+            ));
+        }
+        if value < 0.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("pattern entry {index} is negative ({value})"),
+            ));
+        }
+    }
+
+    let mut pattern: Vec<f64> = raw.iter().map(|&value| value as f64).collect();
+    if pattern.iter().sum::<f64>() == 0.0 {
+        return Err(EngineError::new(CreoVexError::InvalidParameter, "pattern lengths sum to zero"));
+    }
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_within(..);
+    }
+    Ok(pattern)
+}
+
+/**
+ * "On" pieces of `points` under an even-length, positive-sum `pattern`
+ */
+fn dash_polyline(points: &[f32], stride: usize, pattern: &[f64], phase: f64) -> EngineResult<Vec<Vec<f32>>> {
+    let table = cumulative_lengths(points, stride);
+    let total = table[table.len() - 1];
+    let period: f64 = pattern.iter().sum();
+
+    // Where in the pattern the first point falls
+    let mut offset = phase.rem_euclid(period);
+    let mut index = 0;
+    while offset > pattern[index] {
+        offset -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+
+    if total == 0.0 {
+        return Ok(if index % 2 == 0 { vec![points.to_vec()] } else { Vec::new() });
+    }
+
+    let estimate = ((total / period).ceil() as usize).saturating_mul(pattern.len() / 2).saturating_add(1);
+    if estimate > MAX_DASH_PIECES {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("pattern would cut the stroke into more than {MAX_DASH_PIECES} dashes"),
+        ));
+    }
+
+    let mut dashes = Vec::new();
+    let mut start = -offset;
+    while start <= total {
+        let end = start + pattern[index];
+        if index % 2 == 0 {
+            let (from, to) = (start.max(0.0), end.min(total));
+            if from < to || pattern[index] == 0.0 {
+                dashes.push(slice_at_lengths(points, stride, &table, from, to));
+            }
+        }
+        start = end;
+        index = (index + 1) % pattern.len();
+    }
+    Ok(dashes)
+}
@@ -0,0 +1,364 @@
+/*!
+ * Stroke document format
+ *
+ * The engine owns the on-disk representation of a stroke collection, so
+ * every frontend reads and writes the same bytes. Each stroke carries a
+ * small metadata record (brush, color, flags) and its points as a
+ * compress_stroke blob.
+ *
+ * Compatibility rules: the version changes only when old readers could
+ * misread a document, and readers refuse versions they do not know.
+ * Within a version, metadata records may grow: the header gives their
+ * size, and readers skip fields past the ones they understand.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Data Formats)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{run_export, CreoVexError, EngineError, EngineResult};
+use crate::quantize::{compress_points, decompress_points};
+use crate::validate::validate_floats;
+use crate::{batch_stroke_values, hand_off, output_buffer, read_batch, read_u32, EngineBuffer};
+
+/**
+ * First bytes of every serialized document
+ */
+const DOCUMENT_MAGIC: [u8; 4] = *b"CVXD";
+
+/**
+ * Document format version written by serialize_document
+ */
+const DOCUMENT_VERSION: u32 = 1;
+
+/**
+ * Bytes before the first stroke: magic, version, stroke count, metadata size
+ */
+const DOCUMENT_HEADER_BYTES: usize = 16;
+
+/**
+ * u32 metadata fields per stroke in version 1: brush id, color, flags
+ */
+const METADATA_FIELDS: usize = 3;
+
+/**
+ * Serialize a stroke collection to the versioned document format
+ *
+ * Input format:
+ * - the batch container (see read_batch)
+ * - `metadata_len` u32 values, [brush_id, color, flags] per stroke in
+ *   batch order; `metadata_len` 0 writes zeros for every stroke (the
+ *   pointer is not read)
+ *
+ * Output format (little-endian):
+ * - 4 bytes: magic "CVXD"
+ * - 4 bytes: format version (u32, 1)
+ * - 4 bytes: stroke count S (u32)
+ * - 4 bytes: metadata record size M in bytes (u32, 12 in version 1)
+ * - per stroke:
+ *   - M bytes: u32 brush id, u32 color, u32 flags, then any fields added later
+ *   - 4 bytes: compressed point data length L (u32, 0 for no points)
+ *   - L bytes: the compress_stroke blob of its points
+ *
+ * Points round-trip as through compress_stroke (within half a
+ * quantization step of each stroke's own bounds).
+ *
+ * Errors: NullInput/InvalidLength for a malformed container; NullInput
+ * for a null metadata pointer with a non-zero `metadata_len`;
+ * InvalidLength when `metadata_len` is not 0 or 3 per stroke; the
+ * compress_stroke errors for any stroke (the message names it).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn serialize_document(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    metadata_ptr: *const u32,
+    metadata_len: usize,
+) -> EngineBuffer {
+    run_export(|| {
+        let strokes = read_batch(batch_ptr, batch_len)?;
+        let metadata: &[u32] = if metadata_len == 0 {
+            &[]
+        } else if metadata_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "metadata_ptr is null"));
+        } else {
+            unsafe { std::slice::from_raw_parts(metadata_ptr, metadata_len) }
+        };
+        if !metadata.is_empty() && metadata.len() != strokes.len() * METADATA_FIELDS {
+            return Err(EngineError::new(
+                CreoVexError::InvalidLength,
+                format!(
+                    "metadata_len {metadata_len} does not match {} strokes ({METADATA_FIELDS} values each)",
+                    strokes.len()
+                ),
+            ));
+        }
+
+        let blobs: Vec<Vec<u8>> = strokes
+            .iter()
+            .enumerate()
+            .map(|(index, stroke)| {
+                let values = batch_stroke_values(stroke);
+                if values.is_empty() {
+                    return Ok(Vec::new());
+                }
+                let values = validate_floats(&values, 3).map_err(|error| in_stroke(index, error))?;
+                compress_points(&values).map_err(|error| in_stroke(index, error))
+            })
+            .collect::<EngineResult<_>>()?;
+
+        let metadata_bytes = METADATA_FIELDS * 4;
+        let total = DOCUMENT_HEADER_BYTES + blobs.iter().map(|blob| metadata_bytes + 4 + blob.len()).sum::<usize>();
+        let mut buffer = output_buffer(total)?;
+        buffer.extend_from_slice(&DOCUMENT_MAGIC);
+        for field in [DOCUMENT_VERSION, strokes.len() as u32, metadata_bytes as u32] {
+            buffer.extend_from_slice(&field.to_le_bytes());
+        }
+        for (index, blob) in blobs.iter().enumerate() {
+            for field in 0..METADATA_FIELDS {
+                let value = metadata.get(index * METADATA_FIELDS + field).copied().unwrap_or(0);
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            buffer.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(blob);
+        }
+        Ok(hand_off(buffer))
+    })
+}
+
+/**
+ * Read a serialize_document buffer back into strokes and metadata
+ *
+ * Input format: a serialized document, exactly `document_len` bytes
+ *
+ * Output format: the batch container (see read_batch) holding the
+ * strokes, without a status array, followed by
+ * - S * 12 bytes: u32 [brush_id, color, flags] per stroke
+ *
+ * Metadata fields past the three version 1 defines are skipped. The
+ * whole document is checked before anything is returned, so a damaged
+ * file never yields partial strokes.
+ *
+ * Errors: NullInput for a null pointer; InvalidParameter for a wrong
+ * magic; UnsupportedVersion for any version other than 1; InvalidLength
+ * for a truncated document, trailing bytes, or a metadata size below 12;
+ * the decompress_stroke errors for a damaged stroke (the message names
+ * it).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn deserialize_document(document_ptr: *const u8, document_len: usize) -> EngineBuffer {
+    run_export(|| {
+        if document_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "document_ptr is null"));
+        }
+        let bytes: &[u8] = unsafe { std::slice::from_raw_parts(document_ptr, document_len) };
+        let malformed =
+            |detail: String| EngineError::new(CreoVexError::InvalidLength, format!("malformed document: {detail}"));
+
+        if bytes.len() < DOCUMENT_HEADER_BYTES {
+            return Err(malformed(format!(
+                "{document_len} bytes is shorter than the {DOCUMENT_HEADER_BYTES} byte header"
+            )));
+        }
+        if bytes[..4] != DOCUMENT_MAGIC {
+            return Err(EngineError::new(CreoVexError::InvalidParameter, "not a stroke document (wrong magic)"));
+        }
+        let version = read_u32(bytes, 4).unwrap_or(0);
+        if version != DOCUMENT_VERSION {
+            return Err(EngineError::new(
+                CreoVexError::UnsupportedVersion,
+                format!("unsupported document version {version} (this engine reads {DOCUMENT_VERSION})"),
+            ));
+        }
+        let stroke_count = read_u32(bytes, 8).unwrap_or(0) as usize;
+        let metadata_bytes = read_u32(bytes, 12).unwrap_or(0) as usize;
+        if metadata_bytes < METADATA_FIELDS * 4 {
+            return Err(malformed(format!("metadata size {metadata_bytes} is below {}", METADATA_FIELDS * 4)));
+        }
+        // Every stroke takes at least its metadata and length field
+        let minimum = stroke_count.checked_mul(metadata_bytes.saturating_add(4));
+        if minimum.is_none_or(|n| n > bytes.len() - DOCUMENT_HEADER_BYTES) {
+            return Err(malformed(format!("{stroke_count} strokes do not fit in {document_len} bytes")));
+        }
+
+        let mut strokes = Vec::with_capacity(stroke_count);
+        let mut metadata = Vec::with_capacity(stroke_count * METADATA_FIELDS);
+        let mut offset = DOCUMENT_HEADER_BYTES;
+        for index in 0..stroke_count {
+            let truncated = || malformed(format!("stroke {index} is truncated"));
+            let record = bytes.get(offset..offset.saturating_add(metadata_bytes)).ok_or_else(truncated)?;
+            metadata.extend((0..METADATA_FIELDS).map(|field| read_u32(record, field * 4).unwrap_or(0)));
+            offset += metadata_bytes;
+
+            let blob_len = read_u32(bytes, offset).ok_or_else(truncated)? as usize;
+            offset += 4;
+            let blob = bytes.get(offset..offset.saturating_add(blob_len)).ok_or_else(truncated)?;
+            offset += blob_len;
+            strokes.push(if blob.is_empty() {
+                Vec::new()
+            } else {
+                decompress_points(blob).map_err(|error| in_stroke(index, error))?
+            });
+        }
+        if offset != bytes.len() {
+            return Err(malformed(format!("{} bytes after the last stroke", bytes.len() - offset)));
+        }
+
+        let data_floats: usize = strokes.iter().map(Vec::len).sum();
+        let mut buffer = output_buffer(4 + stroke_count * (4 + METADATA_FIELDS * 4) + data_floats * 4)?;
+        buffer.extend_from_slice(&(stroke_count as u32).to_le_bytes());
+        for stroke in &strokes {
+            buffer.extend_from_slice(&((stroke.len() / 3) as u32).to_le_bytes());
+        }
+        for &value in strokes.iter().flatten() {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in metadata {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        Ok(hand_off(buffer))
+    })
+}
+
+/**
+ * Prefix an error about one stroke's points with the stroke's index
+ */
+fn in_stroke(index: usize, error: EngineError) -> EngineError {
+    EngineError { message: format!("stroke {index}: {}", error.message), ..error }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{error_of, pack_batch, take_bytes, Noise};
+
+    /**
+     * Strokes and metadata values of a deserialize_document result
+     */
+    fn take_document(buffer: EngineBuffer) -> (Vec<Vec<f32>>, Vec<u32>) {
+        let bytes = take_bytes(buffer);
+        let word = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let count = word(0) as usize;
+        let mut offset = 4 + count * 4;
+        let mut strokes = Vec::with_capacity(count);
+        for i in 0..count {
+            let values = word(4 + i * 4) as usize * 3;
+            strokes.push((0..values).map(|k| f32::from_bits(word(offset + k * 4))).collect());
+            offset += values * 4;
+        }
+        let metadata = (0..count * METADATA_FIELDS).map(|k| word(offset + k * 4)).collect();
+        assert_eq!(offset + count * METADATA_FIELDS * 4, bytes.len());
+        (strokes, metadata)
+    }
+
+    fn serialize(strokes: &[Vec<f32>], metadata: &[u32]) -> Vec<u8> {
+        let batch = pack_batch(strokes);
+        take_bytes(serialize_document(batch.as_ptr(), batch.len(), metadata.as_ptr(), metadata.len()))
+    }
+
+    /**
+     * Serialize and read back, checking the points match compress_stroke's
+     * own round trip exactly and the metadata survives
+     */
+    fn round_trip(strokes: &[Vec<f32>], metadata: &[u32]) -> Vec<u8> {
+        let document = serialize(strokes, metadata);
+        let (read, read_metadata) = take_document(deserialize_document(document.as_ptr(), document.len()));
+        assert_eq!(read.len(), strokes.len());
+        for (stroke, read) in strokes.iter().zip(&read) {
+            let expected = if stroke.is_empty() { vec![] } else { decompress_points(&compress_points(stroke).unwrap()).unwrap() };
+            assert_eq!(expected.iter().map(|v| v.to_bits()).collect::<Vec<_>>(), read.iter().map(|v| v.to_bits()).collect::<Vec<_>>());
+        }
+        if metadata.is_empty() {
+            assert!(read_metadata.iter().all(|&value| value == 0));
+        } else {
+            assert_eq!(read_metadata, metadata);
+        }
+        document
+    }
+
+    /**
+     * `document` rewritten with `extra` bytes appended to every metadata
+     * record, as a later format revision would write it
+     */
+    fn grow_metadata(document: &[u8], extra: &[u8]) -> Vec<u8> {
+        let word = |offset: usize| u32::from_le_bytes(document[offset..offset + 4].try_into().unwrap()) as usize;
+        let record = METADATA_FIELDS * 4;
+        let mut grown = document[..12].to_vec();
+        grown.extend_from_slice(&((record + extra.len()) as u32).to_le_bytes());
+        let mut offset = DOCUMENT_HEADER_BYTES;
+        for _ in 0..word(8) {
+            grown.extend_from_slice(&document[offset..offset + record]);
+            grown.extend_from_slice(extra);
+            let end = offset + record + 4 + word(offset + record);
+            grown.extend_from_slice(&document[offset + record..end]);
+            offset = end;
+        }
+        assert_eq!(offset, document.len());
+        grown
+    }
+
+    #[test]
+    fn documents_round_trip() {
+        assert_eq!(round_trip(&[], &[]).len(), DOCUMENT_HEADER_BYTES);
+        round_trip(&[vec![3.0, 4.0, 0.5]], &[7, 0xFF0000FF, 1]);
+        round_trip(&[vec![], vec![3.0, 4.0, 0.5], vec![]], &[]);
+
+        let mut noise = Noise::new(5);
+        let strokes: Vec<Vec<f32>> = (0..10_000)
+            .map(|_| {
+                let points = (noise.next() * 10.0 + 10.0) as usize;
+                let (x, y) = (noise.next() * 1000.0, noise.next() * 1000.0);
+                (0..points).flat_map(|i| [x + i as f32, y + noise.next() * 5.0, noise.next() * 0.5 + 0.5]).collect()
+            })
+            .collect();
+        let metadata: Vec<u32> = (0..30_000).map(|i| i * 7).collect();
+        round_trip(&strokes, &metadata);
+    }
+
+    #[test]
+    fn longer_metadata_records_are_skipped_past() {
+        let strokes: Vec<Vec<f32>> = (0..10).map(|i| vec![i as f32, 2.0, 0.5, 3.0, i as f32 * 4.0, 1.0]).collect();
+        let metadata: Vec<u32> = (0..30).collect();
+        let document = round_trip(&strokes, &metadata);
+        let grown = grow_metadata(&document, &[9; 8]);
+        assert_eq!(take_document(deserialize_document(grown.as_ptr(), grown.len())), take_document(deserialize_document(document.as_ptr(), document.len())));
+    }
+
+    #[test]
+    fn damaged_documents_are_refused() {
+        let document = round_trip(&[vec![1.0, 2.0, 0.5, 3.0, 4.0, 1.0], vec![5.0, 6.0, 0.25]], &[1, 2, 3, 4, 5, 6]);
+        let read = |bytes: &[u8]| error_of(deserialize_document(bytes.as_ptr(), bytes.len()));
+        let edited = |offset: usize, value: u32| {
+            let mut edited = document.clone();
+            edited[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            edited
+        };
+
+        for version in [0, 2, u32::MAX] {
+            assert_eq!(read(&edited(4, version)), CreoVexError::UnsupportedVersion as u32);
+        }
+        assert_eq!(read(&edited(0, 0)), CreoVexError::InvalidParameter as u32);
+        assert_eq!(read(&edited(12, 8)), CreoVexError::InvalidLength as u32);
+        for cut in 0..document.len() {
+            assert_eq!(read(&document[..cut]), CreoVexError::InvalidLength as u32, "{cut} bytes");
+        }
+        let mut trailing = document.clone();
+        trailing.push(0);
+        assert_eq!(read(&trailing), CreoVexError::InvalidLength as u32);
+        assert_eq!(error_of(deserialize_document(std::ptr::null(), 0)), CreoVexError::NullInput as u32);
+    }
+
+    #[test]
+    fn bad_collections_are_refused() {
+        let batch = pack_batch(&[vec![1.0, 2.0, 0.5]]);
+        let short = serialize_document(batch.as_ptr(), batch.len(), [1u32].as_ptr(), 1);
+        assert_eq!(error_of(short), CreoVexError::InvalidLength as u32);
+        let null = serialize_document(batch.as_ptr(), batch.len(), std::ptr::null(), 3);
+        assert_eq!(error_of(null), CreoVexError::NullInput as u32);
+        let batch = pack_batch(&[vec![f32::NAN, 2.0, 0.5]]);
+        let nan = serialize_document(batch.as_ptr(), batch.len(), std::ptr::null(), 0);
+        assert_eq!(error_of(nan), CreoVexError::NonFiniteValue as u32);
+    }
+}
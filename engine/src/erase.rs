@@ -0,0 +1,229 @@
+/*!
+ * Vector erasing
+ *
+ * Cuts strokes by a moving round eraser. The eraser path sweeps a chain
+ * of capsules; each target segment loses the parameter range inside any
+ * of them, and what remains is split into separate strokes.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Editing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::polyline_length;
+use crate::error::{require_finite, require_non_negative, run_export};
+use crate::{read_points, serialize_strokes, EngineBuffer};
+
+/**
+ * Shortest surviving fragment kept, in canvas units
+ *
+ * Slivers left where the eraser grazes a stroke would otherwise become
+ * invisible one-pixel strokes in the document.
+ */
+pub(crate) const ERASE_MIN_FRAGMENT_LENGTH: f64 = 0.5;
+
+type Vec2 = [f64; 2];
+
+/**
+ * Erase the parts of a stroke covered by an eraser path
+ *
+ * Input format: target stroke and eraser path, both [x, y, p, ...]
+ *
+ * Output format: the batch container (see read_batch) holding the
+ * surviving pieces in stroke order, without a status array; 0 strokes
+ * when everything was erased
+ *
+ * Everything closer than `radius` to any segment of the eraser path
+ * (or to its only point) is removed; eraser pressure is ignored. Cut
+ * points are interpolated exactly where the stroke leaves the eraser,
+ * position and pressure alike. Pieces shorter than
+ * ERASE_MIN_FRAGMENT_LENGTH are dropped, except that a single-point target
+ * outside the eraser survives unchanged.
+ *
+ * Errors: the read_points errors for either buffer, plus
+ * NonFiniteValue/InvalidParameter for a non-finite or negative radius.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn erase_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    eraser_ptr: *const f32,
+    eraser_len: usize,
+    radius: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let eraser = &read_points(eraser_ptr, eraser_len)?;
+        let radius = require_non_negative("radius", require_finite("radius", radius)?)?;
+
+        let pieces = erase_polyline(points, 3, eraser, 3, radius as f64);
+        serialize_strokes(&pieces)
+    })
+}
+
+/**
+ * Surviving pieces of `points` after erasing along `eraser`
+ *
+ * Points are `stride` floats wide (per buffer); channels after x/y are
+ * interpolated at cut points like pressure.
+ */
+pub(crate) fn erase_polyline(
+    points: &[f32],
+    stride: usize,
+    eraser: &[f32],
+    eraser_stride: usize,
+    radius: f64,
+) -> Vec<Vec<f32>> {
+    let position = |p: &[f32]| -> Vec2 { [p[0] as f64, p[1] as f64] };
+    let mut capsules: Vec<[Vec2; 2]> = eraser
+        .chunks_exact(eraser_stride)
+        .zip(eraser.chunks_exact(eraser_stride).skip(1))
+        .map(|(e0, e1)| [position(e0), position(e1)])
+        .collect();
+    if capsules.is_empty() {
+        let only = position(&eraser[..eraser_stride]);
+        capsules.push([only, only]);
+    }
+
+    if points.len() == stride {
+        let p = position(points);
+        let erased = capsules.iter().any(|&[e0, e1]| capsule_interval(p, [0.0, 0.0], e0, e1, radius).is_some());
+        return if erased { Vec::new() } else { vec![points.to_vec()] };
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<f32> = Vec::new();
+    for (a, b) in points.chunks_exact(stride).zip(points.chunks_exact(stride).skip(1)) {
+        let p0 = position(a);
+        let d = [b[0] as f64 - p0[0], b[1] as f64 - p0[1]];
+        let mut erased: Vec<(f64, f64)> = capsules
+            .iter()
+            .filter_map(|&[e0, e1]| capsule_interval(p0, d, e0, e1, radius))
+            .filter_map(|(lo, hi)| {
+                let (lo, hi) = (lo.max(0.0), hi.min(1.0));
+                (lo < hi).then_some((lo, hi))
+            })
+            .collect();
+        erased.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+        // Kept ranges are the gaps between the (overlapping) erased ones
+        let mut kept = Vec::new();
+        let mut cursor = 0.0f64;
+        for (lo, hi) in erased {
+            if lo > cursor {
+                kept.push((cursor, lo));
+            }
+            cursor = cursor.max(hi);
+        }
+        if cursor < 1.0 {
+            kept.push((cursor, 1.0));
+        }
+
+        let point_at = |t: f64| -> Vec<f32> {
+            match t {
+                t if t <= 0.0 => a.to_vec(),
+                t if t >= 1.0 => b.to_vec(),
+                t => a.iter().zip(b).map(|(&a, &b)| a + (b - a) * t as f32).collect(),
+            }
+        };
+
+        // The open piece continues only if this segment is kept from its start
+        if kept.first().is_none_or(|&(start, _)| start > 0.0) {
+            finish_piece(&mut current, &mut pieces, stride);
+        }
+        for (start, end) in kept {
+            if current.is_empty() {
+                current.extend_from_slice(&point_at(start));
+            }
+            current.extend_from_slice(&point_at(end));
+            if end < 1.0 {
+                finish_piece(&mut current, &mut pieces, stride);
+            }
+        }
+    }
+    finish_piece(&mut current, &mut pieces, stride);
+
+    pieces
+}
+
+/**
+ * Close the open piece, keeping it only if it is long enough
+ */
+fn finish_piece(current: &mut Vec<f32>, pieces: &mut Vec<Vec<f32>>, stride: usize) {
+    let piece = std::mem::take(current);
+    if piece.len() >= 2 * stride && polyline_length(&piece, stride) >= ERASE_MIN_FRAGMENT_LENGTH {
+        pieces.push(piece);
+    }
+}
+
+/**
+ * Parameter range of p0 + t * d strictly inside the capsule of `radius`
+ * around segment e0-e1, if any (unclamped)
+ *
+ * The capsule is convex, so the range is one interval: the union of the
+ * ranges inside the two end discs and the rectangle between them. A
+ * zero-length `d` gives the whole line or nothing.
+ */
+fn capsule_interval(p0: Vec2, d: Vec2, e0: Vec2, e1: Vec2, radius: f64) -> Option<(f64, f64)> {
+    let mut lo = f64::INFINITY;
+    let mut hi = f64::NEG_INFINITY;
+    let mut include = |range: Option<(f64, f64)>| {
+        if let Some((a, b)) = range {
+            lo = lo.min(a);
+            hi = hi.max(b);
+        }
+    };
+
+    include(disc_interval(p0, d, e0, radius));
+    include(disc_interval(p0, d, e1, radius));
+
+    let axis = [e1[0] - e0[0], e1[1] - e0[1]];
+    let length = (axis[0] * axis[0] + axis[1] * axis[1]).sqrt();
+    if length > 0.0 {
+        let u = [axis[0] / length, axis[1] / length];
+        let f = [p0[0] - e0[0], p0[1] - e0[1]];
+        // Along the axis within [0, length], across it within (-radius, radius)
+        let along = slab(f[0] * u[0] + f[1] * u[1], d[0] * u[0] + d[1] * u[1], 0.0, length);
+        let across = slab(u[0] * f[1] - u[1] * f[0], u[0] * d[1] - u[1] * d[0], -radius, radius);
+        if let (Some(a), Some(b)) = (along, across) {
+            let range = (a.0.max(b.0), a.1.min(b.1));
+            include((range.0 < range.1).then_some(range));
+        }
+    }
+
+    (lo < hi).then_some((lo, hi))
+}
+
+/**
+ * Parameter range of p0 + t * d strictly inside the disc around `center`
+ */
+fn disc_interval(p0: Vec2, d: Vec2, center: Vec2, radius: f64) -> Option<(f64, f64)> {
+    let f = [p0[0] - center[0], p0[1] - center[1]];
+    let a = d[0] * d[0] + d[1] * d[1];
+    let c = f[0] * f[0] + f[1] * f[1] - radius * radius;
+    if a == 0.0 {
+        return (c < 0.0).then_some((f64::NEG_INFINITY, f64::INFINITY));
+    }
+
+    let b = f[0] * d[0] + f[1] * d[1];
+    let discriminant = b * b - a * c;
+    if discriminant <= 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    Some(((-b - root) / a, (-b + root) / a))
+}
+
+/**
+ * Parameter range where `offset + t * rate` lies within [min, max]
+ */
+fn slab(offset: f64, rate: f64, min: f64, max: f64) -> Option<(f64, f64)> {
+    if rate == 0.0 {
+        return (offset > min && offset < max).then_some((f64::NEG_INFINITY, f64::INFINITY));
+    }
+    let t0 = (min - offset) / rate;
+    let t1 = (max - offset) / rate;
+    Some((t0.min(t1), t0.max(t1)))
+}
@@ -0,0 +1,441 @@
+/*!
+ * Engine error model
+ *
+ * Every export reports failure through a CreoVexError code (carried on
+ * EngineBuffer or recorded for scalar exports) plus a human-readable
+ * message retrievable with last_error_message().
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Error Handling)
+ */
+
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::cell::RefCell;
+#[cfg(feature = "std")]
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "std")]
+use std::sync::Once;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::float::Float;
+#[cfg(feature = "std")]
+use crate::EngineBuffer;
+
+/**
+ * Failure categories reported across the FFI boundary
+ *
+ * Discriminants are stable: JS compares against them directly, and 0 is
+ * reserved for success (EngineBuffer.error_code on a successful call).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreoVexError {
+    /** A required pointer argument was null */
+    NullInput = 1,
+    /** A length argument does not match the expected layout */
+    InvalidLength = 2,
+    /** The input does not contain enough points for the operation */
+    TooFewPoints = 3,
+    /** The engine could not allocate the output buffer */
+    AllocationFailed = 4,
+    /** A NaN or infinite value where a finite number is required */
+    NonFiniteValue = 5,
+    /** A parameter is outside its documented range */
+    InvalidParameter = 6,
+    /** Internal engine failure (a bug, not bad input) */
+    Internal = 7,
+    /** A caller-provided output buffer is too small for the result */
+    BufferTooSmall = 8,
+    /** Serialized data was written by a format version this engine does not read */
+    UnsupportedVersion = 9,
+    /** free_buffer got a pointer that is not live or the wrong size (allocation tracking only) */
+    InvalidFree = 10,
+}
+
+/**
+ * Error value used inside the engine
+ *
+ * Carries the code that crosses the boundary plus the message stored for
+ * last_error_message().
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct EngineError {
+    pub(crate) code: CreoVexError,
+    pub(crate) message: String,
+    /** Input value the error is about, for last_error_index() */
+    pub(crate) index: Option<usize>,
+}
+
+impl EngineError {
+    pub(crate) fn new(code: CreoVexError, message: impl Into<String>) -> Self {
+        EngineError { code, message: message.into(), index: None }
+    }
+
+    pub(crate) fn at_index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+}
+
+pub(crate) type EngineResult<T> = Result<T, EngineError>;
+
+#[cfg(feature = "std")]
+thread_local! {
+    static LAST_ERROR: RefCell<Option<EngineError>> = const { RefCell::new(None) };
+}
+
+/**
+ * Remember an error for last_error_message() / last_error_code()
+ */
+#[cfg(feature = "std")]
+pub(crate) fn record_error(error: EngineError) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+/**
+ * Run a buffer-producing export body and convert failure into the FFI
+ * error path
+ *
+ * On error the message is recorded and a null EngineBuffer carrying the
+ * error code is returned. A panic inside the body is caught and reported
+ * as CreoVexError::Internal instead of unwinding across the FFI boundary.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn run_export(body: impl FnOnce() -> EngineResult<EngineBuffer>) -> EngineBuffer {
+    let result = panic::catch_unwind(AssertUnwindSafe(body))
+        .unwrap_or_else(|payload| Err(EngineError::new(CreoVexError::Internal, panic_message(payload))));
+
+    match result {
+        Ok(buffer) => buffer,
+        Err(error) => {
+            let code = error.code;
+            record_error(error);
+            EngineBuffer::failed(code)
+        }
+    }
+}
+
+/**
+ * Run an export that writes into caller memory and returns a byte count
+ *
+ * Success returns the count; failure records the message and returns the
+ * negated CreoVexError discriminant. Panics are caught as in run_export.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn run_status(body: impl FnOnce() -> EngineResult<usize>) -> i32 {
+    let result = panic::catch_unwind(AssertUnwindSafe(body))
+        .unwrap_or_else(|payload| Err(EngineError::new(CreoVexError::Internal, panic_message(payload))));
+
+    let result = result.and_then(|written| {
+        i32::try_from(written)
+            .map_err(|_| EngineError::new(CreoVexError::InvalidLength, format!("{written} bytes exceed i32 range")))
+    });
+
+    match result {
+        Ok(written) => written,
+        Err(error) => {
+            let code = error.code;
+            record_error(error);
+            -(code as i32)
+        }
+    }
+}
+
+/**
+ * Run an export that returns a single number
+ *
+ * Failure records the message and returns NaN; callers check
+ * last_error_code() when they see it. Panics are caught as in run_export.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn run_scalar(body: impl FnOnce() -> EngineResult<f32>) -> f32 {
+    let result = panic::catch_unwind(AssertUnwindSafe(body))
+        .unwrap_or_else(|payload| Err(EngineError::new(CreoVexError::Internal, panic_message(payload))));
+
+    match result {
+        Ok(value) => value,
+        Err(error) => {
+            record_error(error);
+            f32::NAN
+        }
+    }
+}
+
+/**
+ * Run an export that returns a u64 (a BigInt in JS), such as a hash
+ *
+ * Failure records the message and returns 0, which callers tell apart
+ * from a real 0 through last_error_code(). Panics are caught as in
+ * run_export.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn run_u64(body: impl FnOnce() -> EngineResult<u64>) -> u64 {
+    let result = panic::catch_unwind(AssertUnwindSafe(body))
+        .unwrap_or_else(|payload| Err(EngineError::new(CreoVexError::Internal, panic_message(payload))));
+
+    match result {
+        Ok(value) => value,
+        Err(error) => {
+            record_error(error);
+            0
+        }
+    }
+}
+
+/**
+ * Run an export that returns an f32 array (a Float32Array in JS)
+ *
+ * Failure records the message and returns an empty array; callers check
+ * last_error_code() when they get one. Panics are caught as in
+ * run_export.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn run_vec(body: impl FnOnce() -> EngineResult<Vec<f32>>) -> Vec<f32> {
+    let result = panic::catch_unwind(AssertUnwindSafe(body))
+        .unwrap_or_else(|payload| Err(EngineError::new(CreoVexError::Internal, panic_message(payload))));
+
+    match result {
+        Ok(values) => values,
+        Err(error) => {
+            record_error(error);
+            Vec::new()
+        }
+    }
+}
+
+/**
+ * Describe a caught panic payload
+ *
+ * panic!() payloads are either &str or String; anything else gets a
+ * generic message. The location was already logged by the panic hook.
+ */
+#[cfg(feature = "std")]
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    let detail = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => (*message).to_string(),
+            Err(_) => "unknown panic payload".to_string(),
+        },
+    };
+    format!("engine panicked: {detail}")
+}
+
+#[cfg(feature = "std")]
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+/**
+ * Initialize the engine
+ *
+ * Installs a panic hook that logs the panic message and source location
+ * to the JS console (stderr on native builds). Call once after the wasm
+ * module loads; repeated calls are no-ops.
+ *
+ * Note: wasm32-unknown-unknown builds with panic=abort, so a panic still
+ * traps the instance there; the hook makes the cause visible. Builds with
+ * unwinding (native, or wasm with exception handling) recover through
+ * run_export and report CreoVexError::Internal.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn init_engine() {
+    INSTALL_PANIC_HOOK.call_once(|| {
+        // Without the wasm feature Rust's default hook already prints both to stderr
+        #[cfg(feature = "wasm")]
+        panic::set_hook(Box::new(console_error_panic_hook::hook));
+    });
+}
+
+/**
+ * Reject NaN/infinite parameters
+ */
+pub(crate) fn require_finite<F: Float>(name: &str, value: F) -> EngineResult<F> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(EngineError::new(
+            CreoVexError::NonFiniteValue,
+            format!("{name} must be finite (got {value})"),
+        ))
+    }
+}
+
+/**
+ * Reject NaN and negative parameters (positive infinity is allowed)
+ */
+pub(crate) fn require_non_negative<F: Float>(name: &str, value: F) -> EngineResult<F> {
+    if value.is_nan() {
+        return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("{name} must not be NaN")));
+    }
+    if value < F::ZERO {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("{name} must be >= 0 (got {value})"),
+        ));
+    }
+    Ok(value)
+}
+
+/**
+ * Reject NaN, zero, and negative parameters
+ */
+pub(crate) fn require_positive<F: Float>(name: &str, value: F) -> EngineResult<F> {
+    let value = require_non_negative(name, value)?;
+    if value == F::ZERO {
+        return Err(EngineError::new(CreoVexError::InvalidParameter, format!("{name} must be > 0")));
+    }
+    Ok(value)
+}
+
+/**
+ * Human-readable description of the most recent engine error
+ *
+ * Empty when no error has occurred yet. Errors are not cleared by later
+ * successful calls, so check the error code first.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn last_error_message() -> String {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map(|e| e.message.clone()).unwrap_or_default())
+}
+
+/**
+ * Code of the most recent engine error (0 when none has occurred)
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn last_error_code() -> u32 {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(0, |e| e.code as u32))
+}
+
+/**
+ * Input index of the most recent engine error, or -1
+ *
+ * Set when an error is about one value of an input buffer: the f32
+ * offset of the first NaN/infinite value FloatValidation::Reject found
+ * (divide by the stride for the point), or the byte offset of the bad
+ * token in svg_path_to_stroke input. -1 for errors without one.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn last_error_index() -> i32 {
+    LAST_ERROR.with(|last| {
+        last.borrow().as_ref().and_then(|e| e.index).and_then(|index| i32::try_from(index).ok()).unwrap_or(-1)
+    })
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_support::error_of;
+    use crate::{
+        alloc, apply_pressure, fit_curve, free_buffer, radial_symmetry_into, radial_symmetry_output_size,
+        set_allocation_tracking, simplify_stroke, smooth_stroke_adaptive, smooth_stroke_v2, stroke_length,
+        PressureCurve,
+    };
+
+    const STROKE: [f32; 6] = [0.0, 0.0, 1.0, 1.0, 1.0, 1.0];
+
+    /**
+     * The code an EngineBuffer failure carries, checking it was recorded
+     * with a message too
+     */
+    fn reported(buffer: EngineBuffer) -> u32 {
+        let code = error_of(buffer);
+        assert_eq!(last_error_code(), code);
+        assert!(!last_error_message().is_empty());
+        code
+    }
+
+    #[test]
+    fn input_errors_carry_their_code() {
+        assert_eq!(reported(fit_curve(core::ptr::null(), 6, 1.0)), CreoVexError::NullInput as u32);
+        assert_eq!(reported(simplify_stroke(STROKE.as_ptr(), 5, 0.3, 0.0)), CreoVexError::InvalidLength as u32);
+        assert_eq!(reported(fit_curve(STROKE.as_ptr(), 0, 1.0)), CreoVexError::TooFewPoints as u32);
+
+        let gamma = apply_pressure(STROKE.as_ptr(), 6, PressureCurve::Gamma, f32::INFINITY, core::ptr::null(), 0);
+        assert_eq!(reported(gamma), CreoVexError::NonFiniteValue as u32);
+        assert!(last_error_message().contains("gamma"));
+        let lut = apply_pressure(STROKE.as_ptr(), 6, PressureCurve::Lut, 1.0, core::ptr::null(), 0);
+        assert_eq!(reported(lut), CreoVexError::NullInput as u32);
+
+        assert_eq!(reported(simplify_stroke(STROKE.as_ptr(), 6, -1.0, 0.0)), CreoVexError::InvalidParameter as u32);
+        assert_eq!(reported(smooth_stroke_adaptive(STROKE.as_ptr(), 6, 30.0, 2, 1)), CreoVexError::InvalidParameter as u32);
+    }
+
+    #[test]
+    fn errors_stay_until_the_next_one() {
+        assert_eq!(reported(fit_curve(core::ptr::null(), 6, 1.0)), CreoVexError::NullInput as u32);
+        let result = smooth_stroke_v2(STROKE.as_ptr(), STROKE.len());
+        assert_eq!(result.error_code(), 0);
+        free_buffer(result.ptr(), result.byte_len());
+        assert_eq!(last_error_code(), CreoVexError::NullInput as u32);
+        assert_eq!(last_error_index(), -1);
+    }
+
+    #[test]
+    fn every_export_shape_reports_failure() {
+        // Scalars give NaN, status exports the negated code
+        assert!(stroke_length(STROKE.as_ptr(), 4).is_nan());
+        assert_eq!(last_error_code(), CreoVexError::InvalidLength as u32);
+
+        let needed = radial_symmetry_output_size(STROKE.len(), 3, false);
+        let mut out = vec![0u8; needed - 1];
+        let written = radial_symmetry_into(STROKE.as_ptr(), 6, 0.0, 0.0, 3, false, out.as_mut_ptr(), out.len());
+        assert_eq!(written, -(CreoVexError::BufferTooSmall as i32));
+        assert_eq!(u32::from_le_bytes(out[..4].try_into().unwrap()) as usize, needed);
+
+        assert!(alloc(usize::MAX).is_null());
+        assert_eq!(last_error_code(), CreoVexError::AllocationFailed as u32);
+    }
+
+    #[test]
+    fn double_frees_are_reported_under_tracking() {
+        set_allocation_tracking(true);
+        let ptr = alloc(16);
+        free_buffer(ptr, 16);
+        assert_eq!(last_error_code(), 0);
+        free_buffer(ptr, 16);
+        assert_eq!(last_error_code(), CreoVexError::InvalidFree as u32);
+        set_allocation_tracking(false);
+    }
+
+    /**
+     * Stands in for an engine bug
+     */
+    fn broken(points: &[f32]) -> EngineResult<usize> {
+        let index = points.len();
+        Ok(points[index] as usize)
+    }
+
+    #[test]
+    fn panics_surface_as_internal_errors() {
+        init_engine();
+        let internal = CreoVexError::Internal as u32;
+
+        let result = run_export(|| {
+            broken(&STROKE)?;
+            unreachable!()
+        });
+        assert_eq!(reported(result), internal);
+        assert!(last_error_message().starts_with("engine panicked: index out of bounds"));
+
+        assert_eq!(run_status(|| broken(&STROKE)), -(internal as i32));
+        assert!(run_scalar(|| panic!("scalar {}", 1)).is_nan());
+        assert_eq!(last_error_message(), "engine panicked: scalar 1");
+        assert_eq!(run_u64(|| Ok(broken(&STROKE)? as u64)), 0);
+        assert!(last_error_message().starts_with("engine panicked: index out of bounds"));
+        assert!(run_vec(|| std::panic::panic_any(7u8)).is_empty());
+        assert_eq!(last_error_message(), "engine panicked: unknown panic payload");
+        assert_eq!(last_error_code(), internal);
+
+        // The engine keeps working afterwards
+        let result = smooth_stroke_v2(STROKE.as_ptr(), STROKE.len());
+        assert_eq!(result.error_code(), 0);
+        free_buffer(result.ptr(), result.byte_len());
+    }
+}
@@ -0,0 +1,165 @@
+/*!
+ * Paint-bucket fill
+ *
+ * Fills work on the canvas pixels rather than on strokes: the bucket
+ * floods the connected region around a click whose color is close to
+ * the clicked pixel's. Running it here keeps large canvases off the main
+ * thread's JS loop.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Rendering)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{run_export, CreoVexError, EngineError, EngineResult};
+use crate::{serialize_records, EngineBuffer};
+
+/**
+ * Flood-fill the region around a seed pixel of an RGBA8 image
+ *
+ * Input format: `width * height * 4` bytes at `pixels_ptr` (allocate
+ * with alloc), row-major from the top row, R, G, B, A per pixel
+ *
+ * Output format:
+ * - 4 bytes: record count (u32, always 1)
+ * - 16 bytes: f32 [min_x, min_y, max_x, max_y] of the filled pixels, in
+ *   pixels with the max edges exclusive (a partial texture upload of
+ *   `max_x - min_x` by `max_y - min_y` pixels at (min_x, min_y))
+ *
+ * The region is every pixel 4-connected to (`seed_x`, `seed_y`) whose
+ * channels all differ from the seed pixel's by at most `tolerance`
+ * (0 = exact match, 255 = every pixel). It is found one horizontal span
+ * at a time with an explicit stack, so memory use does not grow with
+ * recursion depth.
+ *
+ * `fill_rgba` is the color as 0xRRGGBBAA (CSS hex order); it is written
+ * to every pixel of the region. With a non-null `mask_ptr` the image is
+ * left untouched instead: the `width * height` byte mask is cleared and
+ * the region set to 255 ("fill as selection"), and `fill_rgba` is
+ * ignored.
+ *
+ * Errors: NullInput for a null `pixels_ptr`; InvalidParameter for an
+ * empty or overflowing image size or a seed outside it; InvalidLength
+ * when `pixels_len` is not `width * height * 4` or a mask's `mask_len`
+ * is not `width * height`.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn flood_fill(
+    pixels_ptr: *mut u8,
+    pixels_len: usize,
+    width: u32,
+    height: u32,
+    seed_x: u32,
+    seed_y: u32,
+    fill_rgba: u32,
+    tolerance: u8,
+    mask_ptr: *mut u8,
+    mask_len: usize,
+) -> EngineBuffer {
+    run_export(|| {
+        if pixels_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "pixels_ptr is null"));
+        }
+        let (w, h) = (width as usize, height as usize);
+        let pixel_count = w.checked_mul(h).filter(|&count| count > 0 && count.checked_mul(4).is_some());
+        let pixel_count = pixel_count.ok_or_else(|| {
+            EngineError::new(CreoVexError::InvalidParameter, format!("{width}x{height} is not a usable image size"))
+        })?;
+        if seed_x >= width || seed_y >= height {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("seed ({seed_x}, {seed_y}) is outside the {width}x{height} image"),
+            ));
+        }
+        require_len("pixels_len", pixels_len, pixel_count * 4)?;
+        if !mask_ptr.is_null() {
+            require_len("mask_len", mask_len, pixel_count)?;
+        }
+
+        let pixels = unsafe { std::slice::from_raw_parts_mut(pixels_ptr, pixels_len) };
+        let mut scratch = Vec::new();
+        let region: &mut [u8] = if mask_ptr.is_null() {
+            scratch.resize(pixel_count, 0);
+            &mut scratch
+        } else {
+            let mask = unsafe { std::slice::from_raw_parts_mut(mask_ptr, mask_len) };
+            mask.fill(0);
+            mask
+        };
+
+        let rect = fill_region(pixels, w, h, seed_y as usize * w + seed_x as usize, tolerance, region);
+        if mask_ptr.is_null() {
+            let color = fill_rgba.to_be_bytes();
+            for y in rect[1]..rect[3] {
+                for x in rect[0]..rect[2] {
+                    if region[y * w + x] != 0 {
+                        pixels[(y * w + x) * 4..][..4].copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+
+        serialize_records(1, &rect.map(|edge| edge as f32))
+    })
+}
+
+fn require_len(name: &str, len: usize, expected: usize) -> EngineResult<()> {
+    if len != expected {
+        return Err(EngineError::new(
+            CreoVexError::InvalidLength,
+            format!("{name} must be {expected} (got {len})"),
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * Set `region` to 255 over the pixels connected to `seed` within
+ * `tolerance` of its color; returns [min_x, min_y, max_x, max_y), the
+ * bounds of what was set
+ *
+ * `region` must start all zero; it doubles as the visited set.
+ */
+fn fill_region(pixels: &[u8], w: usize, h: usize, seed: usize, tolerance: u8, region: &mut [u8]) -> [usize; 4] {
+    let target: [u8; 4] = pixels[seed * 4..][..4].try_into().unwrap();
+    let matches = |index: usize| {
+        pixels[index * 4..][..4].iter().zip(&target).all(|(&channel, &wanted)| channel.abs_diff(wanted) <= tolerance)
+    };
+
+    let mut rect = [seed % w, seed / w, seed % w + 1, seed / w + 1];
+    let mut stack = vec![seed];
+    while let Some(start) = stack.pop() {
+        if region[start] != 0 {
+            continue;
+        }
+        let (row, y) = (start - start % w, start / w);
+
+        // Widen to the whole matching span on this row
+        let (mut left, mut right) = (start, start + 1);
+        while left > row && region[left - 1] == 0 && matches(left - 1) {
+            left -= 1;
+        }
+        while right < row + w && region[right] == 0 && matches(right) {
+            right += 1;
+        }
+        region[left..right].fill(255);
+        rect = [rect[0].min(left - row), rect[1].min(y), rect[2].max(right - row), rect[3].max(y + 1)];
+
+        // One seed per run of unvisited matching pixels above and below
+        for neighbor in [y.checked_sub(1), Some(y + 1).filter(|&below| below < h)].into_iter().flatten() {
+            let first = neighbor * w + (left - row);
+            let mut in_run = false;
+            for (index, &visited) in (first..).zip(&region[first..first + (right - left)]) {
+                let open = visited == 0 && matches(index);
+                if open && !in_run {
+                    stack.push(index);
+                }
+                in_run = open;
+            }
+        }
+    }
+    rect
+}
@@ -0,0 +1,523 @@
+/*!
+ * Live-input filters (same point count in and out)
+ *
+ * Unlike Chaikin subdivision these never add points, so they can run on
+ * every input sample with negligible latency.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Input Stabilization)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::cumulative_lengths;
+use crate::error::{require_finite, require_non_negative, require_positive, run_export, CreoVexError, EngineError};
+use crate::{read_points, serialize_points, EngineBuffer};
+
+/**
+ * Sample rate assumed when points carry no timestamps
+ *
+ * Typical stylus digitizer rate; only the ratio to the cutoff frequencies
+ * matters, so a wrong guess shifts the filter response uniformly.
+ */
+pub(crate) const DEFAULT_SAMPLE_RATE_HZ: f32 = 120.0;
+
+/**
+ * Cutoff (Hz) for the 1-Euro derivative low-pass
+ */
+const ONE_EURO_DERIVATIVE_CUTOFF: f32 = 1.0;
+
+/**
+ * Maximum distance of a Kalman output from its raw sample, in measurement
+ * standard deviations
+ *
+ * A constant-velocity model keeps moving past sharp corners; clamping to
+ * this gate bounds corner overshoot at 3σ of the measurement noise.
+ */
+pub(crate) const KALMAN_OVERSHOOT_SIGMAS: f32 = 3.0;
+
+/**
+ * Angle increment the ruler snaps its direction to, in degrees
+ */
+pub(crate) const RULER_SNAP_DEGREES: f64 = 15.0;
+
+/**
+ * Smooth stroke with a 1-Euro filter
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, same point count
+ *
+ * x, y, and pressure are filtered independently with the same
+ * parameters, assuming uniform sampling at DEFAULT_SAMPLE_RATE_HZ.
+ * `min_cutoff` (Hz, > 0) sets smoothing at rest: lower removes more
+ * jitter. `beta` (>= 0) raises the cutoff with speed: higher reduces lag
+ * on fast movements. The first point passes through unchanged.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a non-positive min_cutoff or a negative beta.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_one_euro(points_ptr: *const f32, points_len: usize, min_cutoff: f32, beta: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let min_cutoff = require_positive("min_cutoff", min_cutoff)?;
+        let beta = require_non_negative("beta", beta)?;
+
+        let dt = 1.0 / DEFAULT_SAMPLE_RATE_HZ;
+        let mut filter = OneEuroPointFilter::new(min_cutoff, beta);
+        let mut filtered = Vec::with_capacity(points.len());
+        for point in points.chunks_exact(3) {
+            filtered.extend_from_slice(&filter.push([point[0], point[1], point[2]], dt));
+        }
+
+        serialize_points(&filtered)
+    })
+}
+
+/**
+ * Smooth stroke with an exponential moving average ("lazy brush")
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, same point count
+ *
+ * Each output position is `alpha * raw + (1 - alpha) * previous output`,
+ * so `alpha` = 1 leaves the stroke untouched and smaller values trail
+ * further behind the pen. The first point is kept exactly. Pressure is
+ * averaged too when `smooth_pressure` is set, otherwise copied. With
+ * `pin_end` the last point is replaced by its raw input so the stroke
+ * ends where the pen lifted.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for an alpha outside [0, 1].
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_ema(
+    points_ptr: *const f32,
+    points_len: usize,
+    alpha: f32,
+    smooth_pressure: bool,
+    pin_end: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let alpha = require_non_negative("alpha", alpha)?;
+        if alpha > 1.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("alpha must be in [0, 1] (got {alpha})"),
+            ));
+        }
+
+        let mut filter = EmaFilter::new(alpha, smooth_pressure);
+        let mut filtered = Vec::with_capacity(points.len());
+        for point in points.chunks_exact(3) {
+            filtered.extend_from_slice(&filter.push([point[0], point[1], point[2]]));
+        }
+
+        if pin_end {
+            let start = points.len() - 3;
+            filtered[start..].copy_from_slice(&points[start..]);
+        }
+
+        serialize_points(&filtered)
+    })
+}
+
+/**
+ * Exponential moving average over [x, y, pressure] points
+ */
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct EmaFilter {
+    alpha: f32,
+    smooth_pressure: bool,
+    prev: Option<[f32; 3]>,
+}
+
+impl EmaFilter {
+    pub(crate) fn new(alpha: f32, smooth_pressure: bool) -> Self {
+        EmaFilter { alpha, smooth_pressure, prev: None }
+    }
+
+    pub(crate) fn push(&mut self, point: [f32; 3]) -> [f32; 3] {
+        let filtered = match self.prev {
+            None => point,
+            Some(prev) => [
+                lerp(prev[0], point[0], self.alpha),
+                lerp(prev[1], point[1], self.alpha),
+                if self.smooth_pressure { lerp(prev[2], point[2], self.alpha) } else { point[2] },
+            ],
+        };
+        self.prev = Some(filtered);
+        filtered
+    }
+}
+
+/**
+ * Stabilize stroke with a constant-velocity Kalman filter
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, same point count
+ *
+ * x and y are filtered independently, assuming uniform sampling at
+ * DEFAULT_SAMPLE_RATE_HZ; pressure is copied unchanged.
+ * `process_noise` (>= 0) is the acceleration noise density: higher
+ * follows direction changes faster. `measurement_noise` (> 0) is the
+ * variance of stylus jitter in canvas units²: higher smooths harder.
+ *
+ * Overshoot bound: every output point lies within
+ * KALMAN_OVERSHOOT_SIGMAS * sqrt(measurement_noise) canvas units of its
+ * raw sample, so overshoot at a sharp corner never exceeds that distance.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a negative process_noise or a non-positive measurement_noise.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stabilize_stroke_kalman(
+    points_ptr: *const f32,
+    points_len: usize,
+    process_noise: f32,
+    measurement_noise: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let process_noise = require_non_negative("process_noise", process_noise)?;
+        let measurement_noise = require_positive("measurement_noise", measurement_noise)?;
+
+        let dt = 1.0 / DEFAULT_SAMPLE_RATE_HZ;
+        let mut filter = KalmanPointFilter::new(process_noise, measurement_noise);
+        let mut filtered = Vec::with_capacity(points.len());
+        for point in points.chunks_exact(3) {
+            filtered.extend_from_slice(&filter.push([point[0], point[1], point[2]], dt));
+        }
+
+        serialize_points(&filtered)
+    })
+}
+
+/**
+ * One axis of a constant-velocity Kalman filter
+ *
+ * State is [position, velocity] with a white-noise acceleration model.
+ */
+#[derive(Clone, Copy, Debug)]
+struct KalmanAxis {
+    position: f32,
+    velocity: f32,
+    /** Covariance [[pp, pv], [pv, vv]] */
+    covariance: [f32; 3],
+}
+
+impl KalmanAxis {
+    fn new(position: f32, measurement_noise: f32, dt: f32) -> Self {
+        // Velocity is unknown at the first sample: one sample of position
+        // noise per time step.
+        KalmanAxis { position, velocity: 0.0, covariance: [measurement_noise, 0.0, measurement_noise / (dt * dt)] }
+    }
+
+    fn update(&mut self, measurement: f32, q: f32, r: f32, dt: f32) {
+        // Predict
+        let [pp, pv, vv] = self.covariance;
+        self.position += self.velocity * dt;
+        let dt2 = dt * dt;
+        let pp = pp + 2.0 * dt * pv + dt2 * vv + q * dt2 * dt2 / 4.0;
+        let pv = pv + dt * vv + q * dt2 * dt / 2.0;
+        let vv = vv + q * dt2;
+
+        // Correct
+        let s = pp + r;
+        let k_position = pp / s;
+        let k_velocity = pv / s;
+        let innovation = measurement - self.position;
+        self.position += k_position * innovation;
+        self.velocity += k_velocity * innovation;
+        self.covariance = [(1.0 - k_position) * pp, (1.0 - k_position) * pv, vv - k_velocity * pv];
+    }
+}
+
+/**
+ * Kalman filters for the x and y channels of a stroke
+ */
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct KalmanPointFilter {
+    process_noise: f32,
+    measurement_noise: f32,
+    axes: Option<[KalmanAxis; 2]>,
+}
+
+impl KalmanPointFilter {
+    pub(crate) fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        KalmanPointFilter { process_noise, measurement_noise, axes: None }
+    }
+
+    pub(crate) fn push(&mut self, point: [f32; 3], dt: f32) -> [f32; 3] {
+        let (q, r) = (self.process_noise, self.measurement_noise);
+        let axes = match &mut self.axes {
+            Some(axes) => {
+                axes[0].update(point[0], q, r, dt);
+                axes[1].update(point[1], q, r, dt);
+                axes
+            }
+            None => {
+                self.axes = Some([KalmanAxis::new(point[0], r, dt), KalmanAxis::new(point[1], r, dt)]);
+                return point;
+            }
+        };
+
+        // Overshoot gate: pull the estimate back towards the measurement
+        // and keep the filter state consistent with the clamped output.
+        let limit = KALMAN_OVERSHOOT_SIGMAS * r.sqrt();
+        let dx = axes[0].position - point[0];
+        let dy = axes[1].position - point[1];
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance > limit {
+            let shrink = limit / distance;
+            axes[0].position = point[0] + dx * shrink;
+            axes[1].position = point[1] + dy * shrink;
+        }
+
+        [axes[0].position, axes[1].position, point[2]]
+    }
+}
+
+/**
+ * Single-channel 1-Euro filter (Casiez et al. 2012)
+ *
+ * State is explicit so incremental callers (the stroke builder) can keep
+ * one filter alive across input events.
+ */
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct OneEuroFilter {
+    min_cutoff: f32,
+    beta: f32,
+    /** Previous filtered value and filtered derivative */
+    state: Option<(f32, f32)>,
+}
+
+impl OneEuroFilter {
+    pub(crate) fn new(min_cutoff: f32, beta: f32) -> Self {
+        OneEuroFilter { min_cutoff, beta, state: None }
+    }
+
+    /**
+     * Filter one sample taken `dt` seconds after the previous one
+     */
+    pub(crate) fn filter(&mut self, value: f32, dt: f32) -> f32 {
+        let (prev, prev_derivative) = match self.state {
+            Some(state) => state,
+            None => {
+                self.state = Some((value, 0.0));
+                return value;
+            }
+        };
+
+        let derivative = (value - prev) / dt;
+        let derivative = lerp(prev_derivative, derivative, smoothing_factor(ONE_EURO_DERIVATIVE_CUTOFF, dt));
+
+        let cutoff = self.min_cutoff + self.beta * derivative.abs();
+        let filtered = lerp(prev, value, smoothing_factor(cutoff, dt));
+
+        self.state = Some((filtered, derivative));
+        filtered
+    }
+}
+
+/**
+ * 1-Euro filters for the x, y, and pressure channels of a stroke
+ */
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct OneEuroPointFilter {
+    channels: [OneEuroFilter; 3],
+}
+
+impl OneEuroPointFilter {
+    pub(crate) fn new(min_cutoff: f32, beta: f32) -> Self {
+        OneEuroPointFilter { channels: [OneEuroFilter::new(min_cutoff, beta); 3] }
+    }
+
+    /**
+     * Forget all state (the next point passes through unchanged)
+     */
+    pub(crate) fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.state = None;
+        }
+    }
+
+    pub(crate) fn push(&mut self, point: [f32; 3], dt: f32) -> [f32; 3] {
+        [
+            self.channels[0].filter(point[0], dt),
+            self.channels[1].filter(point[1], dt),
+            self.channels[2].filter(point[2], dt),
+        ]
+    }
+}
+
+/**
+ * Exponential smoothing factor for a first-order low-pass at `cutoff` Hz
+ */
+fn smoothing_factor(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+    1.0 / (1.0 + tau / dt)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/**
+ * Constrain a stroke to a straight line from its first point (ruler assist)
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, same point count
+ *
+ * The line runs from the first point along (dir_x, dir_y), or along the
+ * chord to the last point when that direction is (0, 0). With
+ * `snap_angle` the direction is rounded to the nearest multiple of
+ * RULER_SNAP_DEGREES. Point i is placed at the same fraction of the line
+ * as its arc length is of the whole input, so spacing (and therefore
+ * stamp rhythm) follows the real pen motion and points never move
+ * backwards. The line ends at the last point's projection onto it (the
+ * last point itself in chord mode). Pressure is copied unchanged.
+ *
+ * A stroke that has not moved, or a zero chord, collapses onto the first
+ * point, which tessellate_stroke accepts like any other duplicate points.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue for a NaN or
+ * infinite direction.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn constrain_to_line(
+    points_ptr: *const f32,
+    points_len: usize,
+    dir_x: f32,
+    dir_y: f32,
+    snap_angle: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let dir_x = require_finite("dir_x", dir_x)? as f64;
+        let dir_y = require_finite("dir_y", dir_y)? as f64;
+
+        let start = [points[0] as f64, points[1] as f64];
+        let last = &points[points.len() - 3..];
+        let chord = [last[0] as f64 - start[0], last[1] as f64 - start[1]];
+        let direction = if dir_x == 0.0 && dir_y == 0.0 { chord } else { [dir_x, dir_y] };
+        let extent = ruler_extent(direction, chord, snap_angle);
+
+        let table = cumulative_lengths(points, 3);
+        let total = table[table.len() - 1];
+        let mut constrained = Vec::with_capacity(points.len());
+        for (point, &walked) in points.chunks_exact(3).zip(&table) {
+            let t = if total > 0.0 { walked / total } else { 0.0 };
+            constrained.extend_from_slice(&[
+                (start[0] + extent[0] * t) as f32,
+                (start[1] + extent[1] * t) as f32,
+                point[2],
+            ]);
+        }
+
+        serialize_points(&constrained)
+    })
+}
+
+/**
+ * Vector from the first point to the end of the ruler line: `chord`
+ * projected onto `direction` (optionally snapped), or zero when
+ * `direction` is zero
+ */
+fn ruler_extent(direction: [f64; 2], chord: [f64; 2], snap_angle: bool) -> [f64; 2] {
+    let length = direction[0].hypot(direction[1]);
+    if length == 0.0 {
+        return [0.0, 0.0];
+    }
+
+    let mut unit = [direction[0] / length, direction[1] / length];
+    if snap_angle {
+        let step = RULER_SNAP_DEGREES.to_radians();
+        let angle = (unit[1].atan2(unit[0]) / step).round() * step;
+        unit = [angle.cos(), angle.sin()];
+    }
+    let along = chord[0] * unit[0] + chord[1] * unit[1];
+    [unit[0] * along, unit[1] * along]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{error_of, take_points, Noise};
+
+    /**
+     * Mean absolute second difference of y: how much a stroke wiggles
+     */
+    fn jitter(points: &[f32]) -> f32 {
+        let ys: Vec<f32> = points.chunks_exact(3).map(|point| point[1]).collect();
+        ys.windows(3).map(|w| (w[0] - 2.0 * w[1] + w[2]).abs()).sum::<f32>() / ys.len() as f32
+    }
+
+    #[test]
+    fn one_euro_removes_most_jitter_from_a_noisy_sine() {
+        let mut noise = Noise::new(10);
+        let (mut noisy, mut clean) = (Vec::new(), Vec::new());
+        for i in 0..400 {
+            let (x, n) = (i as f32 * 0.5, noise.next());
+            let y = 20.0 * (x * 0.05).sin();
+            noisy.extend_from_slice(&[x, y + n, 0.5 + n * 0.05]);
+            clean.extend_from_slice(&[x, y, 0.5]);
+        }
+
+        let filtered = take_points(smooth_stroke_one_euro(noisy.as_ptr(), noisy.len(), 1.0, 0.007));
+        assert_eq!(filtered.len(), noisy.len());
+        assert_eq!(filtered[..3], noisy[..3]);
+        assert!(jitter(&filtered) < jitter(&noisy) * 0.3, "{} filtered against {} raw", jitter(&filtered), jitter(&noisy));
+        // ... while lagging the underlying curve by well under its amplitude
+        let drift = filtered.chunks_exact(3).zip(clean.chunks_exact(3)).map(|(f, c)| (f[1] - c[1]).abs()).fold(0.0, f32::max);
+        assert!(drift < 5.0, "filtered stroke strays {drift} from the clean sine");
+
+        let points = &noisy[..6];
+        assert_eq!(error_of(smooth_stroke_one_euro(points.as_ptr(), 6, 0.0, 0.0)), CreoVexError::InvalidParameter as u32);
+        assert_eq!(error_of(smooth_stroke_one_euro(points.as_ptr(), 6, 1.0, -1.0)), CreoVexError::InvalidParameter as u32);
+    }
+
+    #[test]
+    fn kalman_corner_overshoot_stays_within_the_gate() {
+        // Three sides of a 100-unit square in 2-unit steps, with ±0.5 of jitter
+        let mut noise = Noise::new(12);
+        let mut shake = || noise.next() * 0.5;
+        let mut square = Vec::new();
+        for i in 0..50 {
+            square.extend_from_slice(&[i as f32 * 2.0 + shake(), shake(), 0.5]);
+        }
+        for i in 0..50 {
+            square.extend_from_slice(&[100.0 + shake(), i as f32 * 2.0 + shake(), 0.5]);
+        }
+        for i in 0..50 {
+            square.extend_from_slice(&[100.0 - i as f32 * 2.0 + shake(), 100.0 + shake(), 0.5]);
+        }
+
+        let measurement_noise = 0.25f32;
+        let gate = KALMAN_OVERSHOOT_SIGMAS * measurement_noise.sqrt();
+        for process_noise in [0.0, 1.0e3, 1.0e6] {
+            let stabilized = take_points(stabilize_stroke_kalman(square.as_ptr(), square.len(), process_noise, measurement_noise));
+            assert_eq!(stabilized.len(), square.len());
+            for (output, raw) in stabilized.chunks_exact(3).zip(square.chunks_exact(3)) {
+                let distance = ((output[0] - raw[0]).powi(2) + (output[1] - raw[1]).powi(2)).sqrt();
+                assert!(distance <= gate + 1e-4, "process noise {process_noise}: {distance} from the raw sample");
+                assert_eq!(output[2], raw[2]);
+            }
+            // Past the corners: at most the jitter plus the gate
+            let beyond = stabilized.chunks_exact(3).map(|point| point[0].max(point[1]) - 100.0).fold(f32::MIN, f32::max);
+            assert!(beyond <= 0.5 + gate + 1e-3, "process noise {process_noise}: {beyond} past the corner");
+        }
+    }
+
+    #[test]
+    fn kalman_steadies_a_shaky_line() {
+        let mut noise = Noise::new(12);
+        let line: Vec<f32> = (0..200).flat_map(|i| [i as f32, noise.next() * 0.5, 1.0]).collect();
+        let stabilized = take_points(stabilize_stroke_kalman(line.as_ptr(), line.len(), 1.0e3, 0.25));
+        // After the filter settles, y stays closer to the line
+        let wobble = |points: &[f32]| points.chunks_exact(3).skip(20).map(|point| point[1].abs()).sum::<f32>();
+        assert!(wobble(&stabilized) < wobble(&line) * 0.6);
+    }
+}
@@ -0,0 +1,175 @@
+/*!
+ * Float element types
+ *
+ * The core algorithms (smoothing, simplification, bounds, transforms)
+ * are written once over Float and run on f32, the default everywhere, or
+ * f64 for the *_f64 exports: millions of units from the origin an f32
+ * coordinate step is larger than a pixel, and smoothed strokes wobble.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Data Formats)
+ */
+
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display};
+use core::ops::{Add, Div, Mul, MulAssign, Neg, Sub};
+
+use crate::smooth::chaikin_cuts;
+use crate::transform::affine_scalar;
+
+/**
+ * f32 or f64, with the operations the shared algorithms use
+ *
+ * Every method is the inherent method of the same name, so an f32
+ * instantiation computes exactly what the f32-only code did. The kernel
+ * methods default to the scalar loops; f32 overrides them with the SIMD
+ * kernels in simd128 builds.
+ */
+pub(crate) trait Float:
+    Copy
+    + PartialOrd
+    + Debug
+    + Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + MulAssign
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const INFINITY: Self;
+    const NEG_INFINITY: Self;
+
+    fn from_f32(value: f32) -> Self;
+    fn from_usize(value: usize) -> Self;
+    fn is_finite(self) -> bool;
+    fn is_nan(self) -> bool;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+
+    /** Kernel of chaikin_uniform (see smooth::chaikin_cuts) */
+    fn chaikin_cuts(points: &[Self], stride: usize, cut: Self, out: &mut Vec<Self>) {
+        chaikin_cuts(points, stride, cut, out);
+    }
+
+    /** Kernel of Affine::apply (see transform::affine_scalar) */
+    fn affine(points: &mut [Self], stride: usize, m: [Self; 6], pressure_scale: Self) {
+        affine_scalar(points, stride, m, pressure_scale);
+    }
+}
+
+/**
+ * The float functions core does not provide, for Float and the core
+ * modules: the inherent methods with std, libm in no_std builds
+ */
+#[cfg(feature = "std")]
+pub(crate) mod math {
+    pub(crate) fn sqrtf(value: f32) -> f32 {
+        value.sqrt()
+    }
+
+    pub(crate) fn atan2f(y: f32, x: f32) -> f32 {
+        y.atan2(x)
+    }
+
+    pub(crate) fn acosf(value: f32) -> f32 {
+        value.acos()
+    }
+
+    pub(crate) fn sqrt(value: f64) -> f64 {
+        value.sqrt()
+    }
+
+    pub(crate) fn atan2(y: f64, x: f64) -> f64 {
+        y.atan2(x)
+    }
+
+    pub(crate) fn sincos(value: f64) -> (f64, f64) {
+        value.sin_cos()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) mod math {
+    pub(crate) use libm::{acosf, atan2, atan2f, sincos, sqrt, sqrtf};
+}
+
+macro_rules! impl_float {
+    ($float:ident, $sqrt:ident, $atan2:ident { $($kernels:item)* }) => {
+        impl Float for $float {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            const INFINITY: Self = $float::INFINITY;
+            const NEG_INFINITY: Self = $float::NEG_INFINITY;
+
+            fn from_f32(value: f32) -> Self {
+                value as $float
+            }
+
+            fn from_usize(value: usize) -> Self {
+                value as $float
+            }
+
+            fn is_finite(self) -> bool {
+                $float::is_finite(self)
+            }
+
+            fn is_nan(self) -> bool {
+                $float::is_nan(self)
+            }
+
+            fn min(self, other: Self) -> Self {
+                $float::min(self, other)
+            }
+
+            fn max(self, other: Self) -> Self {
+                $float::max(self, other)
+            }
+
+            fn clamp(self, min: Self, max: Self) -> Self {
+                $float::clamp(self, min, max)
+            }
+
+            fn abs(self) -> Self {
+                $float::abs(self)
+            }
+
+            fn sqrt(self) -> Self {
+                math::$sqrt(self)
+            }
+
+            fn atan2(self, other: Self) -> Self {
+                math::$atan2(self, other)
+            }
+            $($kernels)*
+        }
+    };
+}
+
+impl_float!(f32, sqrtf, atan2f {
+    #[cfg(target_feature = "simd128")]
+    fn chaikin_cuts(points: &[f32], stride: usize, cut: f32, out: &mut Vec<f32>) {
+        if stride == 3 {
+            crate::simd::chaikin_cuts_xyz(points, cut, out);
+        } else {
+            chaikin_cuts(points, stride, cut, out);
+        }
+    }
+
+    #[cfg(target_feature = "simd128")]
+    fn affine(points: &mut [f32], stride: usize, m: [f32; 6], pressure_scale: f32) {
+        if stride == 3 {
+            crate::simd::affine_xyz(points, m, pressure_scale);
+        } else {
+            affine_scalar(points, stride, m, pressure_scale);
+        }
+    }
+});
+impl_float!(f64, sqrt, atan2 {});
@@ -0,0 +1,206 @@
+/*!
+ * Per-point differential geometry
+ *
+ * Tangent direction, curvature, and turning angle along a stroke, for
+ * adaptive subdivision, calligraphy nibs, and the corner handles UI.
+ * Raw vertex neighbours make these estimates explode on jittery or
+ * duplicated input, so every point is measured against neighbours a
+ * minimum arc length away instead, and curvature is averaged over a short
+ * arc-length window.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::cumulative_lengths;
+use crate::error::run_export;
+use crate::{read_points, serialize_records, EngineBuffer};
+
+/**
+ * Size of one stroke_geometry record in f32 values
+ *
+ * [tx, ty, curvature, turning angle]
+ */
+pub(crate) const GEOMETRY_RECORD_FLOATS: usize = 4;
+
+/**
+ * Arc length (canvas units) from a point to the neighbours it is
+ * measured against
+ *
+ * A few samples of typical stylus input: enough that sub-pixel jitter
+ * does not swamp the curvature of gentle arcs, short enough that corners
+ * stay sharp at screen scale.
+ */
+pub(crate) const GEOMETRY_MIN_ARM: f64 = 4.0;
+
+/**
+ * Arc length (canvas units) on each side of a point over which the
+ * circle curvatures are averaged
+ *
+ * Averaging the estimates rather than the positions leaves a clean arc's
+ * curvature exact, ends included.
+ */
+pub(crate) const GEOMETRY_SMOOTHING: f64 = 4.0;
+
+type Vec2 = [f64; 2];
+
+/**
+ * Tangent, curvature, and turning angle at every point of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: record count (u32, one per input point)
+ * - N * 16 bytes: f32 records [tx, ty, curvature, turning angle]
+ *
+ * Each point is measured against its arm neighbours: the nearest points
+ * at least GEOMETRY_MIN_ARM of arc length before and after it, or the
+ * stroke's first and last points when those are closer. Curvature is
+ * then averaged over GEOMETRY_SMOOTHING of arc length on either side. So
+ * the estimates follow a window a few samples wide rather than every
+ * sample; on heavily noisy input, smooth first as well.
+ *
+ * - (tx, ty): unit tangent, the central difference between the arm
+ *   neighbours, or the one-sided difference to the only neighbour at an
+ *   endpoint
+ * - curvature: 1 / radius of the circle through the point and its arm
+ *   neighbours (an endpoint uses the circle through itself, its
+ *   neighbour, and that neighbour's next arm neighbour), averaged;
+ *   positive when the stroke turns from +x toward +y
+ * - turning angle: signed heading change in radians (-π to π) between
+ *   the arms, 0 at the endpoints
+ *
+ * Duplicate points are measured like their neighbours and give no
+ * infinities: a point with no neighbour at a distinct position (a single
+ * point, or every point the same) reports a zero tangent and zero
+ * curvature, and a point where the stroke doubles back on itself has
+ * curvature 0 and a turning angle of ±π.
+ *
+ * Errors: the read_points errors.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_geometry(points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let records = point_geometry(points, 3);
+        serialize_records((records.len() / GEOMETRY_RECORD_FLOATS) as u32, &records)
+    })
+}
+
+/**
+ * stroke_geometry records for every point (see stroke_geometry)
+ *
+ * Points are `stride` floats wide; only x/y are read.
+ */
+pub(crate) fn point_geometry(points: &[f32], stride: usize) -> Vec<f32> {
+    let count = points.len() / stride;
+    let position = |i: usize| [points[i * stride] as f64, points[i * stride + 1] as f64];
+    let table = cumulative_lengths(points, stride);
+
+    // Arm neighbours at a distinct position, if any
+    let distinct = |i: usize, j: usize| (position(i) != position(j)).then_some(j);
+    let before = |i: usize| {
+        let reach = table.partition_point(|&walked| walked <= table[i] - GEOMETRY_MIN_ARM);
+        let j = if reach > 0 { reach - 1 } else { 0 };
+        distinct(i, j)
+    };
+    let after = |i: usize| {
+        let reach = table.partition_point(|&walked| walked < table[i] + GEOMETRY_MIN_ARM);
+        let j = if reach < count { reach } else { count - 1 };
+        distinct(i, j)
+    };
+
+    let mut estimates = Vec::with_capacity(count);
+    for i in 0..count {
+        let here = position(i);
+        estimates.push(match (before(i), after(i)) {
+            (Some(p), Some(n)) => {
+                let (a, b) = (sub(here, position(p)), sub(position(n), here));
+                let chord = sub(position(n), position(p));
+                let tangent = if chord == [0.0, 0.0] { a } else { chord };
+                (unit(tangent), circle_curvature(a, b), cross(a, b).atan2(dot(a, b)))
+            }
+            (None, Some(n)) => {
+                let arm = sub(position(n), here);
+                let curvature = after(n).map_or(0.0, |m| circle_curvature(arm, sub(position(m), position(n))));
+                (unit(arm), curvature, 0.0)
+            }
+            (Some(p), None) => {
+                let arm = sub(here, position(p));
+                let curvature = before(p).map_or(0.0, |m| circle_curvature(sub(position(p), position(m)), arm));
+                (unit(arm), curvature, 0.0)
+            }
+            (None, None) => ([0.0, 0.0], 0.0, 0.0),
+        });
+    }
+
+    let curvatures: Vec<f64> = estimates.iter().map(|&(_, curvature, _)| curvature).collect();
+    let mut records = Vec::with_capacity(count * GEOMETRY_RECORD_FLOATS);
+    for ((tangent, _, turn), curvature) in estimates.into_iter().zip(window_means(&curvatures, &table)) {
+        records.extend_from_slice(&[tangent[0] as f32, tangent[1] as f32, curvature as f32, turn as f32]);
+    }
+    records
+}
+
+/**
+ * `values` (one per point) averaged over the points within
+ * GEOMETRY_SMOOTHING of arc length either side
+ */
+fn window_means(values: &[f64], table: &[f64]) -> Vec<f64> {
+    let mut sums = Vec::with_capacity(values.len() + 1);
+    sums.push(0.0);
+    for &value in values {
+        sums.push(sums[sums.len() - 1] + value);
+    }
+
+    // Both window edges only move forward
+    let (mut first, mut end) = (0, 0);
+    let mut means = Vec::with_capacity(values.len());
+    for &walked in table {
+        while table[first] < walked - GEOMETRY_SMOOTHING {
+            first += 1;
+        }
+        while end < table.len() && table[end] <= walked + GEOMETRY_SMOOTHING {
+            end += 1;
+        }
+        means.push((sums[end] - sums[first]) / (end - first) as f64);
+    }
+    means
+}
+
+/**
+ * Signed curvature of the circle through three points, from the two
+ * nonzero arms `a` (first to second) and `b` (second to third); 0 when
+ * they are collinear
+ */
+fn circle_curvature(a: Vec2, b: Vec2) -> f64 {
+    let chord = length([a[0] + b[0], a[1] + b[1]]);
+    if chord == 0.0 {
+        return 0.0;
+    }
+    2.0 * cross(a, b) / (length(a) * length(b) * chord)
+}
+
+fn sub(a: Vec2, b: Vec2) -> Vec2 {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn dot(a: Vec2, b: Vec2) -> f64 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: Vec2, b: Vec2) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn length(v: Vec2) -> f64 {
+    dot(v, v).sqrt()
+}
+
+fn unit(v: Vec2) -> Vec2 {
+    let length = length(v);
+    [v[0] / length, v[1] / length]
+}
@@ -0,0 +1,178 @@
+/*!
+ * Stroke content hashing and comparison
+ *
+ * Collaborative sync and undo need a cheap identity for stroke content:
+ * equal hashes mean the strokes are (almost certainly) bit-identical, on
+ * every platform. strokes_equal covers the cases where bit-exactness is
+ * too strict, such as after a lossy quantization round trip.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Editing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_non_negative, run_export, run_status, run_u64, EngineResult};
+use crate::validate::{with_float_validation, FloatValidation};
+use crate::{batch_stroke_values, hand_off, output_buffer, read_batch, read_points, EngineBuffer};
+
+const PRIME_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+/**
+ * XXH64 of `bytes` with seed 0 (the reference algorithm, so hashes match
+ * other xxHash implementations over the same bytes)
+ */
+fn xxh64(bytes: &[u8]) -> u64 {
+    let read_u64 = |b: &[u8]| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
+    let round =
+        |acc: u64, lane: u64| acc.wrapping_add(lane.wrapping_mul(PRIME_2)).rotate_left(31).wrapping_mul(PRIME_1);
+    let merge = |acc: u64, lane: u64| (acc ^ round(0, lane)).wrapping_mul(PRIME_1).wrapping_add(PRIME_4);
+
+    let stripes = bytes.chunks_exact(32);
+    let mut rest = stripes.remainder();
+    let mut hash = if bytes.len() >= 32 {
+        let mut lanes = [PRIME_1.wrapping_add(PRIME_2), PRIME_2, 0, PRIME_1.wrapping_neg()];
+        for stripe in stripes {
+            for (lane, word) in lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+                *lane = round(*lane, read_u64(word));
+            }
+        }
+        let [v1, v2, v3, v4] = lanes;
+        let hash = v1
+            .rotate_left(1)
+            .wrapping_add(v2.rotate_left(7))
+            .wrapping_add(v3.rotate_left(12))
+            .wrapping_add(v4.rotate_left(18));
+        lanes.into_iter().fold(hash, merge)
+    } else {
+        PRIME_5
+    };
+    hash = hash.wrapping_add(bytes.len() as u64);
+
+    while rest.len() >= 8 {
+        hash = (hash ^ round(0, read_u64(rest))).rotate_left(27).wrapping_mul(PRIME_1).wrapping_add(PRIME_4);
+        rest = &rest[8..];
+    }
+    if rest.len() >= 4 {
+        let word = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as u64;
+        hash = (hash ^ word.wrapping_mul(PRIME_1)).rotate_left(23).wrapping_mul(PRIME_2).wrapping_add(PRIME_3);
+        rest = &rest[4..];
+    }
+    for &byte in rest {
+        hash = (hash ^ (byte as u64).wrapping_mul(PRIME_5)).rotate_left(11).wrapping_mul(PRIME_1);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME_3);
+    hash ^ (hash >> 32)
+}
+
+/**
+ * Hash of a point buffer's f32 bit patterns (little-endian)
+ */
+fn hash_points(points: &[f32]) -> u64 {
+    let bytes: Vec<u8> = points.iter().flat_map(|value| value.to_le_bytes()).collect();
+    xxh64(&bytes)
+}
+
+/**
+ * Content hash of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Returns the XXH64 (seed 0) of the points' f32 bit patterns as
+ * little-endian bytes, i.e. of the buffer exactly as JS holds it, so the
+ * value is the same on every platform and in other xxHash
+ * implementations. Being bit-exact, 0.0 and -0.0 hash differently; use
+ * strokes_equal to compare by value. The points are read as read_points
+ * reads them, so FloatValidation::Repair hashes the repaired points.
+ *
+ * Returns 0 on failure; last_error_code() / last_error_message() then
+ * describe it. (A stroke can also hash to 0, with odds of 2^-64.)
+ *
+ * Errors: the read_points errors.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn hash_stroke(points_ptr: *const f32, points_len: usize) -> u64 {
+    run_u64(|| Ok(hash_points(&read_points(points_ptr, points_len)?)))
+}
+
+/**
+ * Content hash of every stroke in a batch
+ *
+ * Input format: the batch container (see read_batch)
+ *
+ * Output format:
+ * - 4 bytes: stroke count S (u32)
+ * - S * 8 bytes: hash of each stroke (u64, as hash_stroke; 0 for a
+ *   failed stroke)
+ * - S * 4 bytes: per-stroke status (u32): 0 = ok, otherwise the
+ *   CreoVexError discriminant (an empty stroke reports TooFewPoints)
+ *
+ * The hashes start at byte offset 4, so read them with
+ * DataView.getBigUint64(offset, true) rather than a BigUint64Array.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn hash_strokes_batch(batch_ptr: *const u8, batch_len: usize, validation: Option<FloatValidation>) -> EngineBuffer {
+    with_float_validation(validation, || {
+        run_export(|| {
+            let strokes = read_batch(batch_ptr, batch_len)?;
+            let hashes: Vec<EngineResult<u64>> = strokes
+                .iter()
+                .map(|stroke| {
+                    let values = batch_stroke_values(stroke);
+                    Ok(hash_points(&read_points(values.as_ptr(), values.len())?))
+                })
+                .collect();
+
+            let mut buffer = output_buffer(4 + hashes.len() * 12)?;
+            buffer.extend_from_slice(&(hashes.len() as u32).to_le_bytes());
+            for hash in &hashes {
+                buffer.extend_from_slice(&hash.as_ref().map_or(0, |&hash| hash).to_le_bytes());
+            }
+            for hash in &hashes {
+                let status = hash.as_ref().map_or_else(|error| error.code as u32, |_| 0);
+                buffer.extend_from_slice(&status.to_le_bytes());
+            }
+            Ok(hand_off(buffer))
+        })
+    })
+}
+
+/**
+ * Compare two strokes within a tolerance
+ *
+ * Input format: two [x, y, p] point buffers
+ *
+ * Returns 1 when both have the same number of points and every x, y, and
+ * pressure differs by at most `epsilon` from its counterpart, 0
+ * otherwise, or a negated CreoVexError discriminant on failure. With
+ * `epsilon` 0 the strokes must be equal by value (0.0 equals -0.0).
+ * Pressure uses the same tolerance as the coordinates, so pick one that
+ * covers the coarser of the two quantization steps.
+ *
+ * Errors: the read_points errors for either buffer;
+ * NonFiniteValue/InvalidParameter for a NaN or negative epsilon.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn strokes_equal(a_ptr: *const f32, a_len: usize, b_ptr: *const f32, b_len: usize, epsilon: f32) -> i32 {
+    run_status(|| {
+        let a = &read_points(a_ptr, a_len)?;
+        let b = &read_points(b_ptr, b_len)?;
+        let epsilon = require_non_negative("epsilon", epsilon)?;
+
+        Ok(usize::from(a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() <= epsilon)))
+    })
+}
@@ -0,0 +1,744 @@
+/*!
+ * InkML trace import
+ *
+ * Note-taking apps export handwriting as InkML: XML with `<trace>`
+ * elements whose text lists one point per comma-separated group, in the
+ * channel order of a `<traceFormat>`. Only what is needed to recover
+ * strokes is read here: traces, trace formats (including formats shared
+ * through contexts and ink sources), and the channel properties that
+ * give their units. Every other element is skipped.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Data Formats)
+ */
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{run_export, CreoVexError, EngineError, EngineResult};
+use crate::resample::MAX_RESAMPLED_POINTS;
+use crate::{serialize_strokes, EngineBuffer};
+
+/**
+ * Elements in other namespaces (vendor extensions, embedded MathML) are
+ * skipped even when their local names match InkML's
+ */
+const INKML_NAMESPACE: &str = "http://www.w3.org/2003/InkML";
+
+/**
+ * Extract the traces of an InkML document as strokes
+ *
+ * Input format: `bytes_len` bytes of UTF-8 InkML
+ *
+ * Output format: the batch container (see read_batch) holding one stroke
+ * per non-empty `<trace>` in document order, without a status array
+ *
+ * Each trace is read with its trace format: the one of its `contextRef`,
+ * else of the enclosing `<traceGroup>`'s context, else of the last
+ * `<context>` seen outside `<definitions>`, else the InkML default (X,
+ * Y). Contexts resolve their format through `traceFormatRef`,
+ * `inkSourceRef`, `contextRef`, or an inline `<traceFormat>` /
+ * `<inkSource>`; references to unknown ids are ignored.
+ *
+ * Point values follow the InkML grammar, including `!` (explicit), `'`
+ * (first difference), and `"` (second difference) qualifiers, `*` (same
+ * difference again), `?` (unknown: previous value), `T`/`F`, and
+ * intermittent channels (previous value when omitted).
+ *
+ * X and Y are converted to CSS pixels (96 per inch) from the channel's
+ * `units` (in, cm, mm, himetric, pt, pc, px; other units are taken as
+ * pixels), or from a `resolution` channel property ("value" raw units per
+ * "1/cm" etc.) when one is given. The F (force) channel becomes pressure:
+ * `(F - min) / (max - min)` with the channel's `min` (default 0) and
+ * `max`, or F itself without a `max`, clamped to [0, 1]. Pressure is 1.0
+ * when the format has no F channel.
+ *
+ * Errors: NullInput for a null pointer with `bytes_len` > 0;
+ * InvalidParameter for input that is not UTF-8, malformed XML (an
+ * unterminated tag, comment, or attribute, or a mismatched end tag),
+ * trace data that does not fit its format, a trace format without X and
+ * Y, or more than MAX_RESAMPLED_POINTS points in total.
+ * last_error_index() gives the byte offset of the problem.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_inkml_traces(bytes_ptr: *const u8, bytes_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let bytes: &[u8] = if bytes_len == 0 {
+            &[]
+        } else if bytes_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "bytes_ptr is null"));
+        } else {
+            unsafe { std::slice::from_raw_parts(bytes_ptr, bytes_len) }
+        };
+        let text = std::str::from_utf8(bytes).map_err(|error| {
+            let offset = error.valid_up_to();
+            EngineError::new(CreoVexError::InvalidParameter, format!("input is not UTF-8 at offset {offset}"))
+                .at_index(offset)
+        })?;
+
+        serialize_strokes(&InkDocument::default().read(text)?)
+    })
+}
+
+fn syntax_error(offset: usize, problem: impl std::fmt::Display) -> EngineError {
+    EngineError::new(CreoVexError::InvalidParameter, format!("{problem} at offset {offset}")).at_index(offset)
+}
+
+/**
+ * CSS pixels per InkML length unit (unknown units count as pixels)
+ */
+fn pixels_per_unit(units: &str) -> f64 {
+    match units {
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "himetric" => 96.0 / 2540.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        _ => 1.0,
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Channel {
+    name: String,
+    units: Option<String>,
+    min: Option<f64>,
+    max: Option<f64>,
+    /** `resolution` channel property: raw units per its unit ("1/cm" and so on) */
+    resolution: Option<(f64, Option<String>)>,
+    intermittent: bool,
+}
+
+impl Channel {
+    /**
+     * CSS pixels per raw X/Y unit
+     */
+    fn pixel_scale(&self) -> f64 {
+        match &self.resolution {
+            Some((per_unit, units)) if *per_unit > 0.0 => {
+                let units = units.as_deref().or(self.units.as_deref()).unwrap_or("");
+                pixels_per_unit(units.trim_start_matches("1/")) / per_unit
+            }
+            _ => self.units.as_deref().map_or(1.0, pixels_per_unit),
+        }
+    }
+
+    fn pressure(&self, force: f64) -> f32 {
+        let pressure = match self.max {
+            Some(max) => {
+                let min = self.min.unwrap_or(0.0);
+                if max > min { (force - min) / (max - min) } else { 1.0 }
+            }
+            None => force,
+        };
+        pressure.clamp(0.0, 1.0) as f32
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TraceFormat {
+    channels: Vec<Channel>,
+}
+
+impl TraceFormat {
+    fn default_format() -> Self {
+        let channel = |name: &str| Channel { name: name.to_string(), ..Channel::default() };
+        TraceFormat { channels: vec![channel("X"), channel("Y")] }
+    }
+}
+
+/**
+ * An element that can own a trace format: `<context>` or `<inkSource>`
+ */
+struct FormatOwner {
+    is_context: bool,
+    id: Option<String>,
+    format: Option<usize>,
+    properties: Vec<(String, String, f64, Option<String>)>,
+}
+
+struct OpenElement<'a> {
+    qualified: &'a str,
+    /** InkML local name, "" for elements of other namespaces */
+    name: &'a str,
+    /** xmlns / xmlns:prefix attributes, as (prefix, namespace) */
+    declarations: Vec<(&'a str, String)>,
+}
+
+/**
+ * How the next value of one channel is encoded
+ */
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Explicit,
+    FirstDifference,
+    SecondDifference,
+}
+
+#[derive(Default)]
+struct InkDocument {
+    /** Index 0 is the InkML default format */
+    formats: Vec<TraceFormat>,
+    format_ids: BTreeMap<String, usize>,
+    contexts: BTreeMap<String, usize>,
+    ink_sources: BTreeMap<String, usize>,
+    current_context: usize,
+    total_points: usize,
+}
+
+impl InkDocument {
+    fn read(mut self, text: &str) -> EngineResult<Vec<Vec<f32>>> {
+        self.formats.push(TraceFormat::default_format());
+        let mut reader = XmlReader { text, offset: 0 };
+        let mut open: Vec<OpenElement> = Vec::new();
+        let mut definitions = 0usize;
+        let mut owners: Vec<FormatOwner> = Vec::new();
+        let mut building: Option<(Option<String>, TraceFormat)> = None;
+        let mut intermittent = false;
+        let mut groups: Vec<Option<usize>> = Vec::new();
+        let mut trace: Option<(usize, usize, String)> = None;
+        let mut strokes = Vec::new();
+
+        while let Some(event) = reader.next_event()? {
+            match event {
+                XmlEvent::Start { name: qualified, attributes, self_closing, offset } => {
+                    let attribute = |key: &str| attributes.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+                    let declarations: Vec<(&str, String)> = attributes
+                        .iter()
+                        .filter_map(|(key, value)| {
+                            let prefix = if *key == "xmlns" { "" } else { key.strip_prefix("xmlns:")? };
+                            Some((prefix, value.clone()))
+                        })
+                        .collect();
+                    let (prefix, local) = qualified.split_once(':').unwrap_or(("", qualified));
+                    let namespace = declarations
+                        .iter()
+                        .chain(open.iter().rev().flat_map(|element| &element.declarations))
+                        .find(|(declared, _)| *declared == prefix)
+                        .map(|(_, namespace)| namespace.as_str());
+                    let is_inkml =
+                        namespace.map_or(prefix.is_empty() || prefix == "inkml", |uri| uri == INKML_NAMESPACE);
+                    let name = if is_inkml { local } else { "" };
+                    let id = attribute("xml:id").or(attribute("id")).map(str::to_string);
+                    match name {
+                        "definitions" => definitions += 1,
+                        "context" | "inkSource" => {
+                            let mut format = None;
+                            if name == "context" {
+                                format = attribute("contextRef")
+                                    .and_then(|reference| self.contexts.get(strip_ref(reference)).copied());
+                                if let Some(source) = attribute("inkSourceRef")
+                                    .and_then(|reference| self.ink_sources.get(strip_ref(reference)))
+                                {
+                                    format = Some(*source);
+                                }
+                            }
+                            if let Some(shared) = attribute("traceFormatRef")
+                                .and_then(|reference| self.format_ids.get(strip_ref(reference)))
+                            {
+                                format = Some(*shared);
+                            }
+                            let is_context = name == "context";
+                            owners.push(FormatOwner { is_context, id, format, properties: Vec::new() });
+                        }
+                        "traceFormat" => building = Some((id, TraceFormat { channels: Vec::new() })),
+                        "intermittentChannels" => intermittent = true,
+                        "channel" => {
+                            if let Some((_, format)) = building.as_mut() {
+                                let number = |key: &str| attribute(key).and_then(|value| value.trim().parse().ok());
+                                format.channels.push(Channel {
+                                    name: attribute("name").unwrap_or_default().to_string(),
+                                    units: attribute("units").map(str::to_string),
+                                    min: number("min"),
+                                    max: number("max"),
+                                    resolution: None,
+                                    intermittent,
+                                });
+                            }
+                        }
+                        "channelProperty" => {
+                            let value = attribute("value").and_then(|value| value.trim().parse::<f64>().ok());
+                            if let (Some(owner), Some(channel), Some(property), Some(value)) =
+                                (owners.last_mut(), attribute("channel"), attribute("name"), value)
+                            {
+                                let units = attribute("units").map(str::to_string);
+                                owner.properties.push((channel.to_string(), property.to_string(), value, units));
+                            }
+                        }
+                        "traceGroup" => {
+                            let context = attribute("contextRef")
+                                .and_then(|reference| self.contexts.get(strip_ref(reference)).copied());
+                            groups.push(context.or(groups.last().copied().flatten()));
+                        }
+                        "trace" => {
+                            let format = attribute("contextRef")
+                                .and_then(|reference| self.contexts.get(strip_ref(reference)).copied())
+                                .or(groups.last().copied().flatten())
+                                .unwrap_or(self.current_context);
+                            trace = Some((format, offset, String::new()));
+                        }
+                        _ => {}
+                    }
+                    if self_closing {
+                        self.end_element(name, &mut definitions, &mut owners, &mut building, &mut intermittent)?;
+                        if name == "traceGroup" {
+                            groups.pop();
+                        }
+                        if name == "trace" {
+                            trace = None;
+                        }
+                    } else {
+                        open.push(OpenElement { qualified, name, declarations });
+                    }
+                }
+                XmlEvent::End { name: qualified, offset } => {
+                    let name = match open.pop() {
+                        Some(element) if element.qualified == qualified => element.name,
+                        _ => return Err(syntax_error(offset, format_args!("unexpected end tag </{qualified}>"))),
+                    };
+                    self.end_element(name, &mut definitions, &mut owners, &mut building, &mut intermittent)?;
+                    match name {
+                        "traceGroup" => {
+                            groups.pop();
+                        }
+                        "trace" => {
+                            if let Some((format, start, data)) = trace.take() {
+                                let points = self.read_trace(&data, start, format)?;
+                                if !points.is_empty() {
+                                    strokes.push(points);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                XmlEvent::Text(text) => {
+                    if let Some((_, _, data)) = trace.as_mut() {
+                        data.push_str(&text);
+                    }
+                }
+            }
+        }
+        if let Some(element) = open.last() {
+            return Err(syntax_error(text.len(), format_args!("<{}> is not closed", element.qualified)));
+        }
+        Ok(strokes)
+    }
+
+    fn end_element(
+        &mut self,
+        name: &str,
+        definitions: &mut usize,
+        owners: &mut Vec<FormatOwner>,
+        building: &mut Option<(Option<String>, TraceFormat)>,
+        intermittent: &mut bool,
+    ) -> EngineResult<()> {
+        match name {
+            "definitions" => *definitions = definitions.saturating_sub(1),
+            "intermittentChannels" => *intermittent = false,
+            "traceFormat" => {
+                if let Some((id, format)) = building.take() {
+                    self.formats.push(format);
+                    let index = self.formats.len() - 1;
+                    if let Some(id) = id {
+                        self.format_ids.insert(id, index);
+                    }
+                    if let Some(owner) = owners.last_mut() {
+                        owner.format = Some(index);
+                    }
+                }
+            }
+            "inkSource" | "context" => {
+                let Some(mut owner) = owners.pop() else {
+                    return Ok(());
+                };
+                if !owner.properties.is_empty() {
+                    // Properties apply to a copy: the format may be shared
+                    let mut format = self.formats[owner.format.unwrap_or(0)].clone();
+                    for (channel, property, value, units) in owner.properties.drain(..) {
+                        if property == "resolution" {
+                            if let Some(channel) = format.channels.iter_mut().find(|c| c.name == channel) {
+                                channel.resolution = Some((value, units));
+                            }
+                        }
+                    }
+                    self.formats.push(format);
+                    owner.format = Some(self.formats.len() - 1);
+                }
+
+                let format = owner.format.unwrap_or(0);
+                if !owner.is_context {
+                    if let Some(id) = owner.id {
+                        self.ink_sources.insert(id, format);
+                    }
+                    // An inline ink source gives its context its format
+                    if let Some(parent) = owners.last_mut().filter(|parent| parent.is_context) {
+                        parent.format = Some(format);
+                    }
+                    return Ok(());
+                }
+                if let Some(id) = owner.id {
+                    self.contexts.insert(id, format);
+                }
+                if *definitions == 0 {
+                    self.current_context = format;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /**
+     * [x, y, p] points of one trace's text, which starts at byte `start`
+     */
+    fn read_trace(&mut self, data: &str, start: usize, format: usize) -> EngineResult<Vec<f32>> {
+        let format = &self.formats[format];
+        let find = |name: &str| format.channels.iter().position(|channel| channel.name == name);
+        let (Some(x), Some(y)) = (find("X"), find("Y")) else {
+            return Err(syntax_error(start, "trace format has no X and Y channels"));
+        };
+        let force = find("F");
+        let required = format.channels.iter().filter(|channel| !channel.intermittent).count();
+        let scale = [format.channels[x].pixel_scale(), format.channels[y].pixel_scale()];
+
+        let count = format.channels.len();
+        let mut encodings = vec![Encoding::Explicit; count];
+        let mut values = vec![0.0f64; count];
+        let mut differences = vec![0.0f64; count];
+        let mut points = Vec::new();
+
+        // A trailing comma is tolerated
+        let data = data.trim();
+        let data = data.strip_suffix(',').unwrap_or(data);
+        if data.is_empty() {
+            return Ok(points);
+        }
+        for (index, group) in data.split(',').enumerate() {
+            let tokens = trace_tokens(group)
+                .map_err(|_| syntax_error(start, format_args!("trace point {index} has an invalid value")))?;
+            if tokens.len() < required || tokens.len() > count {
+                let channels = if required == count { count.to_string() } else { format!("{required}-{count}") };
+                return Err(syntax_error(
+                    start,
+                    format_args!("trace point {index} has {} values for {channels} channels", tokens.len()),
+                ));
+            }
+
+            for (channel, (qualifier, value)) in tokens.into_iter().enumerate() {
+                match qualifier {
+                    Some('!') => encodings[channel] = Encoding::Explicit,
+                    Some('\'') => encodings[channel] = Encoding::FirstDifference,
+                    Some('"') => encodings[channel] = Encoding::SecondDifference,
+                    _ => {}
+                }
+                let next = match (value, encodings[channel]) {
+                    (TraceValue::Unknown, _) => values[channel],
+                    (TraceValue::Repeat, Encoding::Explicit) => values[channel],
+                    (TraceValue::Repeat, _) => values[channel] + differences[channel],
+                    (TraceValue::Number(n), Encoding::Explicit) => n,
+                    (TraceValue::Number(n), Encoding::FirstDifference) => values[channel] + n,
+                    (TraceValue::Number(n), Encoding::SecondDifference) => {
+                        values[channel] + differences[channel] + n
+                    }
+                };
+                differences[channel] = next - values[channel];
+                values[channel] = next;
+            }
+
+            self.total_points += 1;
+            if self.total_points > MAX_RESAMPLED_POINTS {
+                return Err(syntax_error(start, format_args!("document has more than {MAX_RESAMPLED_POINTS} points")));
+            }
+            let pressure = force.map_or(1.0, |f| format.channels[f].pressure(values[f]));
+            points.extend([(values[x] * scale[0]) as f32, (values[y] * scale[1]) as f32, pressure]);
+        }
+        Ok(points)
+    }
+}
+
+fn strip_ref(reference: &str) -> &str {
+    reference.trim().trim_start_matches('#')
+}
+
+enum TraceValue {
+    Number(f64),
+    /** `*`: continue with the same difference */
+    Repeat,
+    /** `?`: value not known */
+    Unknown,
+}
+
+/**
+ * The qualified values of one trace point; values only need whitespace
+ * between them where a qualifier or sign does not separate them
+ */
+fn trace_tokens(group: &str) -> Result<Vec<(Option<char>, TraceValue)>, ()> {
+    let bytes = group.as_bytes();
+    let mut tokens = Vec::new();
+    let mut at = 0;
+    loop {
+        while bytes.get(at).is_some_and(u8::is_ascii_whitespace) {
+            at += 1;
+        }
+        let Some(&first) = bytes.get(at) else {
+            return Ok(tokens);
+        };
+        let mut qualifier = None;
+        if matches!(first, b'!' | b'\'' | b'"') {
+            qualifier = Some(first as char);
+            at += 1;
+            while bytes.get(at).is_some_and(u8::is_ascii_whitespace) {
+                at += 1;
+            }
+        }
+
+        let value = match bytes.get(at) {
+            Some(b'*') => TraceValue::Repeat,
+            Some(b'?') => TraceValue::Unknown,
+            Some(b'T') => TraceValue::Number(1.0),
+            Some(b'F') => TraceValue::Number(0.0),
+            _ => {
+                let start = at;
+                if bytes.get(at).is_some_and(|&byte| byte == b'+' || byte == b'-') {
+                    at += 1;
+                }
+                while bytes.get(at).is_some_and(|&byte| byte.is_ascii_digit() || byte == b'.') {
+                    at += 1;
+                }
+                if bytes.get(at).is_some_and(|&byte| byte == b'e' || byte == b'E') {
+                    at += 1;
+                    if bytes.get(at).is_some_and(|&byte| byte == b'+' || byte == b'-') {
+                        at += 1;
+                    }
+                    while bytes.get(at).is_some_and(u8::is_ascii_digit) {
+                        at += 1;
+                    }
+                }
+                let number: f64 = group[start..at].parse().map_err(|_| ())?;
+                if !number.is_finite() {
+                    return Err(());
+                }
+                tokens.push((qualifier, TraceValue::Number(number)));
+                continue;
+            }
+        };
+        at += 1;
+        tokens.push((qualifier, value));
+    }
+}
+
+enum XmlEvent<'a> {
+    Start { name: &'a str, attributes: Vec<(&'a str, String)>, self_closing: bool, offset: usize },
+    End { name: &'a str, offset: usize },
+    Text(String),
+}
+
+/**
+ * Just enough XML: tags, attributes, text, and the constructs to skip
+ * (declarations, comments, doctype); CDATA is text. Names are reported
+ * as written, with any namespace prefix.
+ */
+struct XmlReader<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> XmlReader<'a> {
+    fn next_event(&mut self) -> EngineResult<Option<XmlEvent<'a>>> {
+        loop {
+            let rest = &self.text[self.offset..];
+            if rest.is_empty() {
+                return Ok(None);
+            }
+            let start = self.offset;
+            if !rest.starts_with('<') {
+                let end = rest.find('<').unwrap_or(rest.len());
+                self.offset += end;
+                return Ok(Some(XmlEvent::Text(decode_entities(&rest[..end]))));
+            }
+
+            for (open, close) in [("<!--", "-->"), ("<?", "?>")] {
+                if rest.starts_with(open) {
+                    self.skip_past(close, start, "unterminated comment or declaration")?;
+                }
+            }
+            if self.offset > start {
+                continue;
+            }
+            if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let end = cdata.find("]]>").ok_or_else(|| syntax_error(start, "unterminated CDATA section"))?;
+                self.offset += 9 + end + 3;
+                return Ok(Some(XmlEvent::Text(cdata[..end].to_string())));
+            }
+            if rest.starts_with("<!") {
+                self.skip_past(">", start, "unterminated declaration")?;
+                continue;
+            }
+            return self.tag(start).map(Some);
+        }
+    }
+
+    fn skip_past(&mut self, close: &str, start: usize, problem: &str) -> EngineResult<()> {
+        let end = self.text[self.offset..].find(close).ok_or_else(|| syntax_error(start, problem))?;
+        self.offset += end + close.len();
+        Ok(())
+    }
+
+    fn tag(&mut self, start: usize) -> EngineResult<XmlEvent<'a>> {
+        let text = self.text;
+        let bytes = text.as_bytes();
+        let mut at = start + 1;
+        let closing = bytes.get(at) == Some(&b'/');
+        if closing {
+            at += 1;
+        }
+        let name_start = at;
+        while bytes.get(at).is_some_and(|&byte| !byte.is_ascii_whitespace() && !b"/>=".contains(&byte)) {
+            at += 1;
+        }
+        if at == name_start {
+            return Err(syntax_error(start, "tag has no name"));
+        }
+        let name = &text[name_start..at];
+
+        let mut attributes = Vec::new();
+        loop {
+            while bytes.get(at).is_some_and(u8::is_ascii_whitespace) {
+                at += 1;
+            }
+            match bytes.get(at) {
+                None => return Err(syntax_error(start, format_args!("unterminated tag <{name}>"))),
+                Some(b'>') => {
+                    self.offset = at + 1;
+                    return Ok(if closing {
+                        XmlEvent::End { name, offset: start }
+                    } else {
+                        XmlEvent::Start { name, attributes, self_closing: false, offset: start }
+                    });
+                }
+                Some(b'/') if !closing && bytes.get(at + 1) == Some(&b'>') => {
+                    self.offset = at + 2;
+                    return Ok(XmlEvent::Start { name, attributes, self_closing: true, offset: start });
+                }
+                Some(_) if closing => return Err(syntax_error(at, format_args!("malformed end tag </{name}>"))),
+                Some(_) => {}
+            }
+
+            let key_start = at;
+            while bytes.get(at).is_some_and(|&byte| !byte.is_ascii_whitespace() && !b"/>=".contains(&byte)) {
+                at += 1;
+            }
+            let key = &text[key_start..at];
+            while bytes.get(at).is_some_and(u8::is_ascii_whitespace) {
+                at += 1;
+            }
+            if key.is_empty() || bytes.get(at) != Some(&b'=') {
+                return Err(syntax_error(key_start, format_args!("malformed attribute in <{name}>")));
+            }
+            at += 1;
+            while bytes.get(at).is_some_and(u8::is_ascii_whitespace) {
+                at += 1;
+            }
+            let quote = match bytes.get(at) {
+                Some(&quote @ (b'"' | b'\'')) => quote,
+                _ => return Err(syntax_error(at, format_args!("attribute {key} is not quoted"))),
+            };
+            let value_start = at + 1;
+            let length = text[value_start..]
+                .find(quote as char)
+                .ok_or_else(|| syntax_error(at, format_args!("unterminated value of attribute {key}")))?;
+            at = value_start + length + 1;
+            attributes.push((key, decode_entities(&text[value_start..value_start + length])));
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::take_strokes;
+
+    const CROHME: &[u8] = include_bytes!("../fixtures/inkml/crohme_expression.inkml");
+    const TABLET: &[u8] = include_bytes!("../fixtures/inkml/tablet_force.inkml");
+
+    fn traces(document: &[u8]) -> Vec<Vec<f32>> {
+        take_strokes(parse_inkml_traces(document.as_ptr(), document.len()), 3)
+    }
+
+    fn with_inserted(document: &[u8], before: &str, inserted: &str) -> Vec<u8> {
+        let text = std::str::from_utf8(document).unwrap();
+        assert!(text.contains(before));
+        text.replacen(before, &format!("{inserted}{before}"), 1).into_bytes()
+    }
+
+    #[test]
+    fn crohme_traces_are_read_in_pixels_with_full_pressure() {
+        let strokes = traces(CROHME);
+        assert_eq!(strokes.iter().map(|stroke| stroke.len() / 3).collect::<Vec<_>>(), [7, 7, 10]);
+        // No units: coordinates as written
+        let first = [12.4, 40.1, 13.0, 39.2, 14.1, 37.8, 15.6, 35.9, 17.2, 33.7, 18.9, 31.6, 20.3, 29.8];
+        let read: Vec<f32> = strokes[0].chunks_exact(3).flat_map(|point| [point[0], point[1]]).collect();
+        assert_eq!(read, first);
+        assert_eq!(strokes[2][27..], [28.3, 29.4, 1.0]);
+        assert!(strokes.iter().flatten().skip(2).step_by(3).all(|&pressure| pressure == 1.0));
+    }
+
+    #[test]
+    fn tablet_traces_scale_himetric_and_force() {
+        let strokes = traces(TABLET);
+        assert_eq!(strokes.iter().map(|stroke| stroke.len() / 3).collect::<Vec<_>>(), [7, 5]);
+        assert_eq!(strokes[0][..3], [192.0, 96.0, 8192.0 / 32767.0]);
+
+        // The first trace switches to first (') then second (") differences,
+        // which carry on for the unqualified points after them
+        let raw_first = [
+            [5080.0, 2540.0, 8192.0],
+            [5106.0, 2527.0, 9216.0],
+            [5137.0, 2517.0, 10752.0],
+            [5170.0, 2508.0, 12544.0],
+            [5204.0, 2499.0, 14336.0],
+            [5235.0, 2488.0, 15616.0],
+            [5262.0, 2474.0, 15872.0],
+        ];
+        let raw_second = [7620.0, 2540.0, 16384.0, 7620.0, 3175.0, 20480.0, 7620.0, 3810.0, 24576.0, 7620.0, 4445.0, 20480.0, 7620.0, 5080.0, 12288.0];
+        let raw: Vec<f64> = raw_first.iter().flatten().copied().collect();
+        for (stroke, raw) in strokes.iter().zip([&raw[..], &raw_second[..]]) {
+            for (point, raw) in stroke.chunks_exact(3).zip(raw.chunks_exact(3)) {
+                let expected = [raw[0] * 96.0 / 2540.0, raw[1] * 96.0 / 2540.0, raw[2] / 32767.0];
+                for (&value, expected) in point.iter().zip(expected) {
+                    assert!((value as f64 - expected).abs() < 1e-4, "{point:?} for raw {raw:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn foreign_elements_are_skipped() {
+        // MathML inside annotationXML, even with InkML's local names
+        let mathml = with_inserted(CROHME, "</math>", "<trace>1 2, 3 4</trace><traceFormat><channel name=\"Y\"/></traceFormat>");
+        assert_eq!(traces(&mathml), traces(CROHME));
+
+        // Windows Ink msink: elements
+        let vendor = "<msink:trace>1 1 1</msink:trace><msink:context><msink:traceFormat><msink:channel name=\"Y\"/><msink:channel name=\"X\"/></msink:traceFormat></msink:context>";
+        let tablet = with_inserted(TABLET, "<inkml:trace ", vendor);
+        assert_eq!(traces(&tablet), traces(TABLET));
+        let declared = vendor.replacen("<msink:trace>", "<msink:trace xmlns:msink=\"http://schemas.microsoft.com/ink/2010/main\">", 1);
+        let crohme = with_inserted(CROHME, "<trace id=\"0\">", &declared);
+        assert_eq!(traces(&crohme), traces(CROHME));
+    }
+}
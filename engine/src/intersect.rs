@@ -0,0 +1,440 @@
+/*!
+ * Stroke intersection
+ *
+ * Crossing points between strokes (knife/slice tool) and within one
+ * stroke (fill and shape validation). Segment tests run in f64; large
+ * strokes are bucketed in a uniform grid so only nearby segment pairs
+ * are tested.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Editing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{run_export, EngineResult};
+use crate::{read_points, serialize_records, EngineBuffer};
+
+/**
+ * Distance (canvas units) under which two crossings are the same point,
+ * or a segment counts as lying on another's line
+ *
+ * Absorbs floating-point noise: a crossing through a shared vertex is
+ * found on both segments meeting there, and near-parallel segments
+ * produce nearly identical parameters.
+ */
+pub(crate) const INTERSECTION_EPSILON: f64 = 1e-4;
+
+/**
+ * Sine of the angle below which two segments are treated as parallel
+ */
+const PARALLEL_EPSILON: f64 = 1e-9;
+
+/**
+ * Segment-pair count above which candidates come from a SegmentGrid
+ * instead of testing every pair
+ */
+const GRID_MIN_PAIRS: usize = 1 << 16;
+
+/**
+ * Upper bound on grid cells per axis (keeps the grid small for strokes
+ * with extreme aspect ratios)
+ */
+const GRID_MAX_CELLS_PER_AXIS: usize = 1024;
+
+/**
+ * Size of one intersection record in 4-byte fields
+ */
+const INTERSECTION_FIELDS: usize = 6;
+
+pub(crate) type Segment = [[f64; 2]; 2];
+
+/**
+ * One crossing between segment `seg_a` of stroke A and `seg_b` of stroke B
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Intersection {
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) seg_a: usize,
+    pub(crate) t_a: f64,
+    pub(crate) seg_b: usize,
+    pub(crate) t_b: f64,
+}
+
+impl Intersection {
+    /**
+     * Record layout shared by the intersection exports; segment indices
+     * are stored as raw u32 bits
+     */
+    fn to_record(self) -> [f32; INTERSECTION_FIELDS] {
+        [
+            self.x as f32,
+            self.y as f32,
+            f32::from_bits(self.seg_a as u32),
+            self.t_a as f32,
+            f32::from_bits(self.seg_b as u32),
+            self.t_b as f32,
+        ]
+    }
+}
+
+/**
+ * Find every crossing between two strokes
+ *
+ * Input format: two [x, y, p, ...] buffers, stroke A then stroke B
+ *
+ * Output format:
+ * - 4 bytes: intersection count (u32, may be 0)
+ * - N * 24 bytes: f32 x, y, segment index on A (u32), f32 t on A,
+ *   segment index on B (u32), f32 t on B
+ *
+ * Records are sorted along stroke A (segment, then t). Segment i runs
+ * from point i to point i + 1 and t is 0 at its start, 1 at its end.
+ * Touching counts as crossing. Collinear overlapping segments report the
+ * two ends of their overlap, and the same crossing found on neighboring
+ * segments (a crossing through a vertex, or floating-point noise on
+ * near-parallel segments) is reported once. Zero-length segments are
+ * ignored.
+ *
+ * Errors: the read_points errors for either stroke.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn intersect_strokes(a_ptr: *const f32, a_len: usize, b_ptr: *const f32, b_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let a = &read_points(a_ptr, a_len)?;
+        let b = &read_points(b_ptr, b_len)?;
+
+        let hits = intersect_polylines(a, b, 3);
+        serialize_intersections(&hits)
+    })
+}
+
+/**
+ * Find the places where a stroke crosses itself
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format: the intersect_strokes records, with the first segment
+ * index always lower than the second (count 0 when the stroke does not
+ * cross itself)
+ *
+ * Records are sorted along the stroke by their first segment. The vertex
+ * two consecutive segments share is not reported, but a stroke that
+ * doubles back reports the overlapping stretch by its ends, and a stroke
+ * ending on its start point reports that point. Intersection rules and
+ * deduplication are those of intersect_strokes; long strokes use the
+ * same grid, so a scribble of a few thousand points stays near linear.
+ *
+ * Errors: the read_points errors.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn find_self_intersections(points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+
+        let hits = self_intersections(points, 3);
+        serialize_intersections(&hits)
+    })
+}
+
+/**
+ * Crossings between two polylines, sorted along `a` and deduplicated
+ *
+ * Points are `stride` floats wide; only x/y are read.
+ */
+pub(crate) fn intersect_polylines(a: &[f32], b: &[f32], stride: usize) -> Vec<Intersection> {
+    let a_segments = segments(a, stride);
+    let b_segments = segments(b, stride);
+
+    let hits = collect_hits(&a_segments, &b_segments, |_, _| true);
+    sort_and_dedup(hits)
+}
+
+/**
+ * Crossings of a polyline with itself, sorted along it and deduplicated
+ *
+ * Every record has seg_a < seg_b. The vertex shared by two consecutive
+ * segments (ignoring zero-length segments between them) is not a
+ * crossing, but anything else those two segments share is, so a stroke
+ * that doubles back on itself still reports the overlap.
+ */
+pub(crate) fn self_intersections(points: &[f32], stride: usize) -> Vec<Intersection> {
+    let segments = segments(points, stride);
+
+    // Next positive-length segment after each index: the one sharing its end vertex
+    let mut next_solid = vec![usize::MAX; segments.len()];
+    let mut following = usize::MAX;
+    for (i, segment) in segments.iter().enumerate().rev() {
+        next_solid[i] = following;
+        if segment[0] != segment[1] {
+            following = i;
+        }
+    }
+
+    let mut hits = collect_hits(&segments, &segments, |i, j| j > i);
+    let epsilon_sq = INTERSECTION_EPSILON * INTERSECTION_EPSILON;
+    hits.retain(|hit| {
+        let joint = segments[hit.seg_a][1];
+        let dx = hit.x - joint[0];
+        let dy = hit.y - joint[1];
+        next_solid[hit.seg_a] != hit.seg_b || dx * dx + dy * dy >= epsilon_sq
+    });
+    sort_and_dedup(hits)
+}
+
+/**
+ * Test every accepted segment pair (a[i], b[j]) and collect the points
+ * they share, unsorted
+ *
+ * Candidate pairs come from a SegmentGrid over `b` once the pair count
+ * exceeds GRID_MIN_PAIRS.
+ */
+pub(crate) fn collect_hits(a: &[Segment], b: &[Segment], accept: impl Fn(usize, usize) -> bool) -> Vec<Intersection> {
+    let mut hits = Vec::new();
+    let mut test = |i: usize, j: usize| {
+        if !accept(i, j) {
+            return;
+        }
+        segment_hits(a[i], b[j], |t_a, t_b| {
+            let [p0, p1] = a[i];
+            hits.push(Intersection {
+                x: p0[0] + (p1[0] - p0[0]) * t_a,
+                y: p0[1] + (p1[1] - p0[1]) * t_a,
+                seg_a: i,
+                t_a,
+                seg_b: j,
+                t_b,
+            });
+        });
+    };
+
+    if a.len().saturating_mul(b.len()) > GRID_MIN_PAIRS {
+        let grid = SegmentGrid::new(b);
+        let mut candidates = Vec::new();
+        for (i, segment) in a.iter().enumerate() {
+            grid.candidates(segment, &mut candidates);
+            for &j in &candidates {
+                test(i, j);
+            }
+        }
+    } else {
+        for i in 0..a.len() {
+            for j in 0..b.len() {
+                test(i, j);
+            }
+        }
+    }
+
+    hits
+}
+
+/**
+ * x/y segments of a polyline in f64
+ */
+fn segments(points: &[f32], stride: usize) -> Vec<Segment> {
+    let position = |p: &[f32]| [p[0] as f64, p[1] as f64];
+    points
+        .chunks_exact(stride)
+        .zip(points.chunks_exact(stride).skip(1))
+        .map(|(p0, p1)| [position(p0), position(p1)])
+        .collect()
+}
+
+/**
+ * Report (t_a, t_b) for each point shared by two segments
+ *
+ * Crossing segments report one point; collinear overlapping segments
+ * report both ends of the overlap (one if they only touch). Parameters
+ * are clamped to [0, 1].
+ */
+fn segment_hits(a: Segment, b: Segment, mut emit: impl FnMut(f64, f64)) {
+    let r = sub(a[1], a[0]);
+    let s = sub(b[1], b[0]);
+    let r_len_sq = dot(r, r);
+    let s_len_sq = dot(s, s);
+    if r_len_sq == 0.0 || s_len_sq == 0.0 {
+        return;
+    }
+
+    let qp = sub(b[0], a[0]);
+    let denom = cross(r, s);
+    let r_len = r_len_sq.sqrt();
+    let s_len = s_len_sq.sqrt();
+
+    if denom.abs() <= PARALLEL_EPSILON * r_len * s_len {
+        // Parallel: only collinear segments can share points
+        if (cross(qp, r) / r_len).abs() > INTERSECTION_EPSILON {
+            return;
+        }
+        let t0 = dot(qp, r) / r_len_sq;
+        let t1 = t0 + dot(s, r) / r_len_sq;
+        let start = t0.min(t1).max(0.0);
+        let end = t0.max(t1).min(1.0);
+        if (start - end) * r_len > INTERSECTION_EPSILON {
+            return;
+        }
+
+        let t_b = |t_a: f64| {
+            let point = [a[0][0] + r[0] * t_a, a[0][1] + r[1] * t_a];
+            (dot(sub(point, b[0]), s) / s_len_sq).clamp(0.0, 1.0)
+        };
+        emit(start.min(end), t_b(start.min(end)));
+        if (end - start) * r_len > INTERSECTION_EPSILON {
+            emit(end, t_b(end));
+        }
+        return;
+    }
+
+    let t = cross(qp, s) / denom;
+    let u = cross(qp, r) / denom;
+    // Allow misses within INTERSECTION_EPSILON of an endpoint
+    let t_slack = INTERSECTION_EPSILON / r_len;
+    let u_slack = INTERSECTION_EPSILON / s_len;
+    if t < -t_slack || t > 1.0 + t_slack || u < -u_slack || u > 1.0 + u_slack {
+        return;
+    }
+    emit(t.clamp(0.0, 1.0), u.clamp(0.0, 1.0));
+}
+
+/**
+ * Sort along stroke A and drop crossings within INTERSECTION_EPSILON of
+ * an already kept one on the same or neighboring segments of both strokes
+ *
+ * Distinct segment pairs that happen to cross at nearly the same point
+ * (three strokes through one spot) are all kept.
+ */
+fn sort_and_dedup(mut hits: Vec<Intersection>) -> Vec<Intersection> {
+    hits.sort_by(|p, q| {
+        (p.seg_a, p.t_a, p.seg_b, p.t_b)
+            .partial_cmp(&(q.seg_a, q.t_a, q.seg_b, q.t_b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let epsilon_sq = INTERSECTION_EPSILON * INTERSECTION_EPSILON;
+    let mut kept: Vec<Intersection> = Vec::with_capacity(hits.len());
+    for hit in hits {
+        // Duplicates sit at (nearly) the same place along A: the same
+        // segment, or the end of the previous one
+        let duplicate = kept
+            .iter()
+            .rev()
+            .take_while(|k| k.seg_a + 1 >= hit.seg_a)
+            .any(|k| {
+                let dx = k.x - hit.x;
+                let dy = k.y - hit.y;
+                k.seg_b.abs_diff(hit.seg_b) <= 1 && dx * dx + dy * dy < epsilon_sq
+            });
+        if !duplicate {
+            kept.push(hit);
+        }
+    }
+    kept
+}
+
+fn serialize_intersections(hits: &[Intersection]) -> EngineResult<EngineBuffer> {
+    let mut records = Vec::with_capacity(hits.len() * INTERSECTION_FIELDS);
+    for hit in hits {
+        records.extend_from_slice(&hit.to_record());
+    }
+    serialize_records(hits.len() as u32, &records)
+}
+
+/**
+ * Uniform grid of segment indices keyed by the cells their bounding boxes
+ * overlap
+ */
+struct SegmentGrid {
+    min: [f64; 2],
+    cell: f64,
+    cols: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+}
+
+impl SegmentGrid {
+    /**
+     * Grid over `segments` with roughly one cell per segment
+     */
+    fn new(segments: &[Segment]) -> Self {
+        let mut min = [f64::INFINITY; 2];
+        let mut max = [f64::NEG_INFINITY; 2];
+        for point in segments.iter().flatten() {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(point[axis]);
+                max[axis] = max[axis].max(point[axis]);
+            }
+        }
+        let width = (max[0] - min[0]).max(0.0);
+        let height = (max[1] - min[1]).max(0.0);
+
+        let count = segments.len().max(1) as f64;
+        let longest_side = width.max(height);
+        let cell = (width * height / count)
+            .sqrt()
+            .max(longest_side / GRID_MAX_CELLS_PER_AXIS as f64)
+            .max(f64::MIN_POSITIVE);
+        let cols = ((width / cell) as usize + 1).min(GRID_MAX_CELLS_PER_AXIS);
+        let rows = ((height / cell) as usize + 1).min(GRID_MAX_CELLS_PER_AXIS);
+
+        let mut grid = SegmentGrid { min, cell, cols, rows, cells: vec![Vec::new(); cols * rows] };
+        for (index, segment) in segments.iter().enumerate() {
+            let (c0, c1, r0, r1) = grid.cell_range(segment);
+            for row in r0..=r1 {
+                for col in c0..=c1 {
+                    grid.cells[row * cols + col].push(index);
+                }
+            }
+        }
+        grid
+    }
+
+    /**
+     * Indices of segments sharing a cell with `segment`, ascending and
+     * unique
+     */
+    fn candidates(&self, segment: &Segment, out: &mut Vec<usize>) {
+        out.clear();
+        let (c0, c1, r0, r1) = self.cell_range(segment);
+        for row in r0..=r1 {
+            for col in c0..=c1 {
+                out.extend_from_slice(&self.cells[row * self.cols + col]);
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+    }
+
+    /**
+     * Inclusive cell columns and rows covered by the segment's bounding
+     * box (grown by INTERSECTION_EPSILON), clamped to the grid
+     */
+    fn cell_range(&self, segment: &Segment) -> (usize, usize, usize, usize) {
+        let cell_of = |value: f64, axis: usize, limit: usize| {
+            let index = ((value - self.min[axis]) / self.cell).floor();
+            (index.max(0.0) as usize).min(limit - 1)
+        };
+        let [p0, p1] = segment;
+        let lo = |axis: usize| p0[axis].min(p1[axis]) - INTERSECTION_EPSILON;
+        let hi = |axis: usize| p0[axis].max(p1[axis]) + INTERSECTION_EPSILON;
+        (
+            cell_of(lo(0), 0, self.cols),
+            cell_of(hi(0), 0, self.cols),
+            cell_of(lo(1), 1, self.rows),
+            cell_of(hi(1), 1, self.rows),
+        )
+    }
+}
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn dot(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
@@ -0,0 +1,218 @@
+/*!
+ * Stroke reversal and joining
+ *
+ * The connect-strokes tool merges two separately drawn strokes into one
+ * path: the closest pair of endpoints is bridged, with the strokes
+ * reversed as needed so the result runs continuously.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Editing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::segment_length;
+use crate::error::{require_finite, require_non_negative, run_export, CreoVexError, EngineError};
+use crate::{read_points, read_strided_points, require_stride, serialize_points, serialize_strided_points, EngineBuffer};
+
+/**
+ * How join_strokes connects the two strokes
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BridgeMode {
+    /** One straight segment between the endpoints */
+    Straight = 0,
+    /** A cubic Hermite connector leaving and entering along the end tangents */
+    Smooth = 1,
+}
+
+/**
+ * Spacing of the points inserted along a smooth bridge, in canvas units
+ */
+pub(crate) const BRIDGE_SAMPLE_SPACING: f64 = 2.0;
+
+/**
+ * Most points inserted along a smooth bridge
+ */
+pub(crate) const BRIDGE_MAX_SAMPLES: usize = 64;
+
+/**
+ * Reverse the point order of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, last point first
+ *
+ * Errors: the read_points errors.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn reverse_stroke(points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        serialize_points(&reverse_polyline(points, 3))
+    })
+}
+
+/**
+ * Reverse the point order of a stroke with extra per-point channels
+ *
+ * Input format: `stride` floats per point, [x, y, pressure, extra...]
+ * Output format: as smooth_stroke_n (point count, stride, points)
+ *
+ * Every channel travels with its point, so tilt and rotation stay where
+ * they were drawn.
+ *
+ * Errors: InvalidParameter for a stride outside 3-8, then the
+ * read_points errors with `points_len` a multiple of `stride`.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn reverse_stroke_n(points_ptr: *const f32, points_len: usize, stride: usize) -> EngineBuffer {
+    run_export(|| {
+        let stride = require_stride(stride)?;
+        let points = &read_strided_points(points_ptr, points_len, stride)?;
+        serialize_strided_points(&reverse_polyline(points, stride), stride)
+    })
+}
+
+/**
+ * Join two strokes into one across their closest endpoints
+ *
+ * Input format: two strokes [x, y, p, ...]
+ * Output format: the joined stroke (see serialize_points)
+ *
+ * Of the four endpoint pairings the one with the smallest gap wins (a's
+ * end to b's start on ties, then a's end to b's end, a's start to b's
+ * end, a's start to b's start). The result keeps a's direction: b is
+ * reversed when its matching endpoint is the wrong one, and comes first
+ * when it meets a's start.
+ *
+ * Straight bridges are the segment between the two endpoints. Smooth
+ * bridges insert points every BRIDGE_SAMPLE_SPACING (at most
+ * BRIDGE_MAX_SAMPLES) along a cubic Hermite curve whose end tangents are
+ * the strokes' own end directions scaled to the gap; a single-point
+ * stroke uses the gap's direction. Pressure across the bridge
+ * interpolates linearly between the two endpoint pressures. Endpoints
+ * that coincide are merged into one point, keeping the first stroke's.
+ *
+ * Errors: the read_points errors for either stroke,
+ * NonFiniteValue/InvalidParameter for a NaN, infinite, or negative
+ * max_gap, and InvalidParameter when the smallest gap exceeds max_gap
+ * (the join is refused).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn join_strokes(
+    a_ptr: *const f32,
+    a_len: usize,
+    b_ptr: *const f32,
+    b_len: usize,
+    max_gap: f32,
+    bridge_mode: BridgeMode,
+) -> EngineBuffer {
+    run_export(|| {
+        let a = &read_points(a_ptr, a_len)?;
+        let b = &read_points(b_ptr, b_len)?;
+        let max_gap = require_non_negative("max_gap", require_finite("max_gap", max_gap)?)?;
+
+        let (first, second, gap) = pair_endpoints(a, b, 3);
+        if gap > max_gap as f64 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("strokes are {gap} apart, more than max_gap {max_gap}"),
+            ));
+        }
+        serialize_points(&join_polylines(&first, &second, 3, bridge_mode))
+    })
+}
+
+/**
+ * Points of `points` in reverse order, each point's channels intact
+ */
+pub(crate) fn reverse_polyline(points: &[f32], stride: usize) -> Vec<f32> {
+    points.chunks_exact(stride).rev().flatten().copied().collect()
+}
+
+/**
+ * `a` and `b` ordered and oriented so the first's end meets the second's
+ * start at the closest endpoint pairing, with that gap
+ */
+fn pair_endpoints(a: &[f32], b: &[f32], stride: usize) -> (Vec<f32>, Vec<f32>, f64) {
+    let (a_start, a_end) = (&a[..stride], &a[a.len() - stride..]);
+    let (b_start, b_end) = (&b[..stride], &b[b.len() - stride..]);
+    let gaps = [
+        segment_length(a_end, b_start),
+        segment_length(a_end, b_end),
+        segment_length(b_end, a_start),
+        segment_length(b_start, a_start),
+    ];
+    let mut best = 0;
+    for (pairing, &gap) in gaps.iter().enumerate() {
+        if gap < gaps[best] {
+            best = pairing;
+        }
+    }
+
+    let (first, second) = match best {
+        0 => (a.to_vec(), b.to_vec()),
+        1 => (a.to_vec(), reverse_polyline(b, stride)),
+        2 => (b.to_vec(), a.to_vec()),
+        _ => (reverse_polyline(b, stride), a.to_vec()),
+    };
+    (first, second, gaps[best])
+}
+
+/**
+ * `first` followed by `second`, bridged per `mode`
+ *
+ * Points are `stride` floats wide; every channel after x/y interpolates
+ * across a smooth bridge like pressure.
+ */
+pub(crate) fn join_polylines(first: &[f32], second: &[f32], stride: usize, mode: BridgeMode) -> Vec<f32> {
+    let start = &first[first.len() - stride..];
+    let end = &second[..stride];
+    let gap = segment_length(start, end);
+    let mut joined = Vec::with_capacity(first.len() + second.len() + BRIDGE_MAX_SAMPLES * stride);
+    joined.extend_from_slice(first);
+    if gap == 0.0 {
+        joined.extend_from_slice(&second[stride..]);
+        return joined;
+    }
+
+    if mode == BridgeMode::Smooth {
+        let chord = [(end[0] - start[0]) as f64 / gap, (end[1] - start[1]) as f64 / gap];
+        // Leaving along first's last distinct segment, entering along second's first
+        let leaving = first.chunks_exact(stride).rev().find(|point| point[..2] != start[..2]);
+        let entering = second.chunks_exact(stride).find(|point| point[..2] != end[..2]);
+        let t0 = leaving.map_or(chord, |from| direction(from, start));
+        let t1 = entering.map_or(chord, |to| direction(end, to));
+
+        let samples = ((gap / BRIDGE_SAMPLE_SPACING).ceil() as usize).saturating_sub(1).clamp(1, BRIDGE_MAX_SAMPLES);
+        for k in 1..=samples {
+            let u = k as f64 / (samples + 1) as f64;
+            let (u2, u3) = (u * u, u * u * u);
+            let h00 = 2.0 * u3 - 3.0 * u2 + 1.0;
+            let h10 = u3 - 2.0 * u2 + u;
+            let h01 = 3.0 * u2 - 2.0 * u3;
+            let h11 = u3 - u2;
+            for axis in 0..2 {
+                let value = h00 * start[axis] as f64
+                    + h10 * gap * t0[axis]
+                    + h01 * end[axis] as f64
+                    + h11 * gap * t1[axis];
+                joined.push(value as f32);
+            }
+            let u = u as f32;
+            joined.extend(start[2..].iter().zip(&end[2..]).map(|(&a, &b)| a + (b - a) * u));
+        }
+    }
+    joined.extend_from_slice(second);
+    joined
+}
+
+/**
+ * Unit x/y direction from `a` to `b` (distinct positions)
+ */
+fn direction(a: &[f32], b: &[f32]) -> [f64; 2] {
+    let length = segment_length(a, b);
+    [(b[0] - a[0]) as f64 / length, (b[1] - a[1]) as f64 / length]
+}
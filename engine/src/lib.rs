@@ -1,4 +1,4 @@
-/**
+/*!
  * CreoVox Rust/Wasm Engine
  * 
  * Performance-critical stroke processing operations.
@@ -7,169 +7,897 @@
  * Authority: drawing_engine_architecture.md, ffi_contract.md
  */
 
+// Exports receive raw pointers into wasm linear memory by contract
+// (ffi_contract.md); every export validates them before dereferencing.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+#![cfg_attr(not(feature = "std"), no_std)]
+// Without std the exports are gone, and with them the callers of the
+// helpers only they use (LOD levels, adaptive and chunked smoothing, ...)
+#![cfg_attr(not(feature = "std"), allow(dead_code))]
+
+extern crate alloc;
+
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::ToString;
+#[cfg(feature = "std")]
+use std::panic::Location;
+
+use error::{EngineError, EngineResult};
+#[cfg(feature = "std")]
+use error::{record_error, run_export};
+use float::Float;
+#[cfg(feature = "std")]
+use pool::{hand_out, reclaim, take_aligned_buffer, take_buffer, AlignedBuffer};
+#[cfg(feature = "std")]
+use threads::map_indexed;
+#[cfg(feature = "std")]
+use tracking::{track_allocation, track_release, tracking_enabled, Origin};
+use validate::validate_floats;
+
 /**
- * Smooth stroke using Chaikin subdivision algorithm
+ * Items that need std: the FFI layer (buffers, error registry, exports)
+ * and every module beyond the geometry core
+ *
+ * Without the default `std` feature the crate is `#![no_std]` + alloc
+ * and keeps the modules declared outside with_std! blocks (Chaikin
+ * smoothing, corners, simplification, resampling, arc length, bounds,
+ * transforms, sanitizing, float validation) and the Stroke API, for
+ * embedded targets:
+ *
+ * ```text
+ * cargo check --no-default-features --target thumbv7em-none-eabihf
+ * ```
+ *
+ * Float functions core lacks (sqrt, atan2, ...) come from libm there.
+ */
+macro_rules! with_std {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "std")]
+            $item
+        )*
+    };
+}
+
+mod arclength;
+mod bounds;
+mod core;
+mod corners;
+mod error;
+mod float;
+mod resample;
+mod sanitize;
+mod simd;
+mod simplify;
+mod smooth;
+mod transform;
+mod validate;
+
+with_std! {
+    mod bezier;
+    mod boolean;
+    mod builder;
+    mod cache;
+    mod camera;
+    mod dash;
+    mod document;
+    mod erase;
+    mod fill;
+    mod filter;
+    mod geometry;
+    mod hash;
+    mod inkml;
+    mod intersect;
+    mod join;
+    mod morph;
+    mod outline;
+    mod pool;
+    mod predict;
+    mod pressure;
+    mod quantize;
+    mod query;
+    mod raster;
+    mod rng;
+    mod roughen;
+    mod shapes;
+    mod spatial;
+    mod spline;
+    mod split;
+    mod stamp;
+    mod svg;
+    mod tessellate;
+    mod threads;
+    mod tiles;
+    mod tracking;
+    mod triangulate;
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test_support;
+
+pub use crate::core::{Point, Stroke};
+pub use error::CreoVexError;
+pub use sanitize::NonFinitePolicy;
+pub use validate::{float_validation, set_float_validation, FloatValidation};
+
+with_std! {
+    pub use arclength::{build_arclength_table, point_at_length, stroke_length, trim_stroke};
+    pub use bezier::{fit_curve, flatten_beziers};
+    pub use boolean::{polygon_boolean, BooleanOp};
+    pub use bounds::{
+        convex_hull, convex_hull_batch, cull_bounds, cull_strokes, stroke_bounds, stroke_bounds_f64, stroke_bounds_js,
+        stroke_dirty_rect,
+    };
+    pub use builder::{StrokeBuilder, StrokeBuilderOptions};
+    pub use cache::StrokeCache;
+    pub use camera::{
+        compute_camera_matrix, invert_camera_matrix, screen_to_world, transform_points_batch, world_to_screen,
+        CameraDirection,
+    };
+    pub use corners::detect_corners;
+    pub use crate::core::TessellationResult;
+    pub use dash::dash_stroke;
+    pub use document::{deserialize_document, serialize_document};
+    pub use erase::erase_stroke;
+    pub use error::{init_engine, last_error_code, last_error_index, last_error_message};
+    pub use fill::flood_fill;
+    pub use filter::{constrain_to_line, smooth_stroke_ema, smooth_stroke_one_euro, stabilize_stroke_kalman};
+    pub use geometry::stroke_geometry;
+    pub use hash::{hash_stroke, hash_strokes_batch, strokes_equal};
+    pub use inkml::parse_inkml_traces;
+    pub use intersect::{find_self_intersections, intersect_strokes};
+    pub use join::{join_strokes, reverse_stroke, reverse_stroke_n, BridgeMode};
+    pub use morph::{average_strokes, morph_sequence, morph_strokes};
+    pub use outline::{stroke_outline, stroke_outline_ex};
+    pub use pool::{pool_stats, recycle_buffer, set_pool_capacity, trim_pool, PoolStats};
+    pub use predict::predict_points;
+    pub use pressure::{
+        apply_pressure, apply_taper, normalize_pressure, smooth_pressure, synthesize_pressure_from_velocity,
+        PressureCurve, PressureNormalization, PressureSmoothing, TaperProfile,
+    };
+    pub use quantize::{
+        compress_stroke, decompress_stroke, dequantize_stroke, dequantize_strokes_batch, quantize_stroke,
+        quantize_strokes_batch,
+    };
+    pub use query::{
+        hit_test_stroke, hit_test_strokes_batch, nearest_point_on_stroke, nearest_points_on_stroke, stroke_in_polygon,
+        strokes_in_polygon_batch, LassoMode,
+    };
+    pub use raster::{rasterize_stroke, stroke_sdf};
+    pub use resample::{resample_stroke, resample_stroke_js, resample_to_count, resample_to_count_n};
+    pub use roughen::roughen_stroke;
+    pub use sanitize::sanitize_stroke;
+    pub use shapes::{recognize_shape, shape_to_stroke, ShapeKind};
+    pub use simd::simd_available;
+    pub use simplify::{
+        generate_lod, recommend_lod_level, simplify_between_anchors, simplify_stroke, simplify_stroke_f64,
+        simplify_stroke_js, simplify_stroke_to_count, simplify_stroke_vw, SimplifyMethod,
+    };
+    pub use smooth::{
+        chunk_context_len, chunk_overlap_points, overlap_context_len, smooth_output_size,
+        smooth_stroke_adaptive, smooth_stroke_chunked, smooth_stroke_closed, smooth_stroke_ex, smooth_stroke_f64,
+        smooth_stroke_into, smooth_stroke_js, smooth_stroke_n, smooth_stroke_overlap, smooth_stroke_timed,
+        smooth_stroke_v2, smooth_strokes_batch,
+    };
+    // The legacy export stays reachable; callers get the deprecation warning
+    #[allow(deprecated)]
+    pub use smooth::smooth_stroke;
+    pub use spatial::SpatialIndex;
+    pub use spline::{interpolate_stroke_catmull_rom, smooth_stroke_bspline};
+    pub use split::{split_stroke, split_stroke_at_params};
+    pub use stamp::{compute_scatter, compute_stamps};
+    pub use svg::{stroke_to_svg_path, strokes_to_svg_paths_batch, svg_path_to_stroke};
+    pub use tessellate::{
+        tessellate_stroke, tessellate_stroke_alpha, tessellate_stroke_ex, tessellate_stroke_feathered,
+        tessellate_stroke_indexed, tessellate_stroke_uv, tessellate_strokes_batch, CapStyle, JoinStyle,
+        TessellationOptions,
+    };
+    pub use threads::{init_thread_pool, thread_pool_size};
+    #[cfg(feature = "threads")]
+    pub use threads::thread_pool_worker;
+    pub use tiles::{strokes_to_dirty_tiles, DirtyTileTracker};
+    pub use tracking::{
+        allocation_tracking, dump_allocations, live_allocation_bytes, live_allocation_count, set_allocation_tracking,
+    };
+    pub use transform::{
+        mirror_stroke, radial_symmetry, radial_symmetry_into, radial_symmetry_output_size, smooth_and_mirror_stroke,
+        transform_stroke, transform_stroke_f64, transform_stroke_in_place, transform_strokes_batch,
+        transform_strokes_batch_in_place,
+    };
+    pub use triangulate::triangulate_polygon;
+}
+
+/**
+ * Engine-owned output buffer returned across the FFI boundary
  * 
- * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
- * Output format: same structure, smoothed
+ * Every producing export returns one of these instead of a bare pointer.
+ * `byte_len` is the exact size of the data, so JS never has to derive it
+ * from the buffer header: read the data at `ptr`, then call
+ * `free_buffer(ptr, byte_len)` (or recycle_buffer) with these two values.
+ * `ptr` is at least 4-byte aligned, so the f32 and u32 sections of every
+ * layout can be read through typed-array views without copying.
  * 
- * Algorithm: 2 iterations of Chaikin subdivision for noticeable smoothing
- * Performance: O(n) where n = point count
+ * A failed call returns a null `ptr` (with `byte_len` 0) and a non-zero
+ * `error_code` (a CreoVexError discriminant); last_error_message() then
+ * describes the failure. The JS wrapper object itself is released with
+ * `.free()` as usual for wasm_bindgen structs; that does not free the
+ * data buffer.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug)]
+pub struct EngineBuffer {
+    ptr: *mut u8,
+    byte_len: usize,
+    error: u32,
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl EngineBuffer {
+    /** Start of the buffer in wasm memory (null on failure) */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    /** Exact allocation size in bytes, to be passed to free_buffer */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn byte_len(&self) -> usize {
+        self.byte_len
+    }
+
+    /** 0 on success, otherwise a CreoVexError discriminant */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn error_code(&self) -> u32 {
+        self.error
+    }
+}
+
+#[cfg(feature = "std")]
+impl EngineBuffer {
+    pub(crate) fn failed(code: CreoVexError) -> Self {
+        EngineBuffer { ptr: std::ptr::null_mut(), byte_len: 0, error: code as u32 }
+    }
+}
+
+/**
+ * Read a point buffer passed in from JS
  * 
- * Pressure is preserved and smoothed alongside position data.
+ * Input format: [x0, y0, p0, x1, y1, p1, ...] with `points_len` counting
+ * f32 values (f64 for the *_f64 exports). Rejects null pointers, empty buffers, and lengths that are
+ * not whole triplets, then applies the FloatValidation mode (borrowed
+ * unless Repair replaced values).
+ */
+pub(crate) fn read_points<'a, F: Float>(points_ptr: *const F, points_len: usize) -> EngineResult<Cow<'a, [F]>> {
+    read_strided_points(points_ptr, points_len, 3)
+}
+
+/**
+ * Read a point buffer with `stride` floats per point
  * 
- * TODO: Add adaptive subdivision (more smoothing for jagged sections)
- * TODO: Support chunked processing for large strokes (>1000 points)
- * TODO: Add custom pressure curve application (per brush type)
+ * Same checks as read_points, with `points_len` required to be a whole
+ * number of `stride`-sized points.
  */
-#[wasm_bindgen]
-pub fn smooth_stroke(points_ptr: *const f32, points_len: usize) -> *mut u8 {
-    // Validate input (must be triplets: x, y, pressure)
-    if points_ptr.is_null() || points_len == 0 || points_len % 3 != 0 {
-        return std::ptr::null_mut();
+pub(crate) fn read_strided_points<'a, F: Float>(
+    points_ptr: *const F,
+    points_len: usize,
+    stride: usize,
+) -> EngineResult<Cow<'a, [F]>> {
+    validate_floats(borrow_strided_points(points_ptr, points_len, stride)?, stride)
+}
+
+/**
+ * read_strided_points without FloatValidation, for exports with their own
+ * non-finite handling
+ */
+pub(crate) fn borrow_strided_points<'a, F: Float>(
+    points_ptr: *const F,
+    points_len: usize,
+    stride: usize,
+) -> EngineResult<&'a [F]> {
+    if points_ptr.is_null() {
+        return Err(EngineError::new(CreoVexError::NullInput, "points_ptr is null"));
+    }
+    if points_len == 0 {
+        return Err(EngineError::new(CreoVexError::TooFewPoints, "stroke has no points"));
+    }
+    if !points_len.is_multiple_of(stride) {
+        let layout = if stride == 3 { "(x, y, pressure)".to_string() } else { format!("(stride {stride})") };
+        return Err(EngineError::new(
+            CreoVexError::InvalidLength,
+            format!("points_len {points_len} is not a multiple of {stride} {layout}"),
+        ));
     }
 
-    // Convert raw pointer to slice
-    let points = unsafe { std::slice::from_raw_parts(points_ptr, points_len) };
-    
-    // Need at least 2 points (6 floats) to smooth
-    if points.len() < 6 {
-        // Return copy of original points
-        return serialize_points(points);
+    Ok(unsafe { ::core::slice::from_raw_parts(points_ptr, points_len) })
+}
+
+/**
+ * Serialize points to buffer that JS can read
+ * 
+ * Output format:
+ * - 4 bytes: point count (u32)
+ * - N * 12 bytes: f32 triplets [x0, y0, p0, x1, y1, p1, ...]
+ * 
+ * The returned EngineBuffer carries the exact byte length
+ * (4 + N * 12) to hand back to free_buffer.
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_points(points: &[f32]) -> EngineResult<EngineBuffer> {
+    serialize_records((points.len() / 3) as u32, points)
+}
+
+/**
+ * Serialize f64 points to a buffer JS can read (the *_f64 exports)
+ * 
+ * Output format: serialize_records_f64 with the point count, i.e.
+ * N * 24 bytes of f64 triplets [x0, y0, p0, x1, y1, p1, ...] after the
+ * 8-byte header.
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_points_f64(points: &[f64]) -> EngineResult<EngineBuffer> {
+    serialize_records_f64((points.len() / 3) as u32, points)
+}
+
+/**
+ * Serialize a record count plus f64 payload to a buffer JS can read
+ * 
+ * Output format:
+ * - 4 bytes: record count (u32)
+ * - 4 bytes: element size in bytes (u32, always 8); the f32 layouts
+ *   have no such field, so a reader can tell the two apart
+ * - values.len() * 8 bytes: f64 payload, little-endian
+ * 
+ * The buffer is 8-byte aligned, so JS can read the payload as a
+ * Float64Array at `ptr + 8` in place.
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_records_f64(count: u32, values: &[f64]) -> EngineResult<EngineBuffer> {
+    let mut buffer = take_aligned_buffer(8 + (values.len() * 8), 8)?;
+    buffer.extend_from_slice(&count.to_le_bytes());
+    buffer.extend_from_slice(&8u32.to_le_bytes());
+    for &value in values {
+        buffer.extend_from_slice(&value.to_le_bytes());
     }
+    Ok(hand_off(buffer))
+}
 
-    // Apply Chaikin subdivision (2 iterations for visible smoothing)
-    let smoothed = chaikin_subdivide(points, 2);
-    
-    // Serialize result
-    serialize_points(&smoothed)
+/**
+ * Packed multi-stroke container used by the *_batch exports
+ * 
+ * Input layout (little-endian):
+ * - 4 bytes: stroke count S (u32)
+ * - S * 4 bytes: point count of each stroke (u32)
+ * - f32 triplets [x, y, p] of every stroke, concatenated in order
+ * 
+ * Result layout: the same container holding the processed strokes (the
+ * per-stroke counts are record counts: points, or vertices for
+ * tessellation), followed by
+ * - S * 4 bytes: per-stroke status (u32): 0 = ok, otherwise the
+ *   CreoVexError discriminant; failed strokes hold 0 records
+ * 
+ * Strokes are processed independently, so one invalid stroke does not
+ * fail the batch. Only a malformed container fails the whole call.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn read_batch<'a>(batch_ptr: *const u8, batch_len: usize) -> EngineResult<Vec<&'a [u8]>> {
+    read_packed("batch", batch_ptr, batch_len, 3)
+}
+
+/**
+ * Split a container in the serialize_packed layout into the raw bytes of
+ * each list (`record_floats` f32 values per record); `name` prefixes the
+ * argument names in error messages
+ */
+#[cfg(feature = "std")]
+pub(crate) fn read_packed<'a>(
+    name: &str,
+    batch_ptr: *const u8,
+    batch_len: usize,
+    record_floats: usize,
+) -> EngineResult<Vec<&'a [u8]>> {
+    if batch_ptr.is_null() {
+        return Err(EngineError::new(CreoVexError::NullInput, format!("{name}_ptr is null")));
+    }
+    split_packed(name, unsafe { std::slice::from_raw_parts(batch_ptr, batch_len) }, record_floats)
+}
+
+/**
+ * read_packed on a byte slice (Stroke::read_batch for native callers)
+ */
+#[cfg(feature = "std")]
+pub(crate) fn split_packed<'a>(name: &str, bytes: &'a [u8], record_floats: usize) -> EngineResult<Vec<&'a [u8]>> {
+    let batch_len = bytes.len();
+    let malformed =
+        |detail: String| EngineError::new(CreoVexError::InvalidLength, format!("malformed {name}: {detail}"));
+
+    let stroke_count = read_u32(bytes, 0).ok_or_else(|| malformed("missing stroke count".into()))? as usize;
+    let header_len = stroke_count
+        .checked_mul(4)
+        .and_then(|n| n.checked_add(4))
+        .filter(|&n| n <= bytes.len())
+        .ok_or_else(|| malformed(format!("{stroke_count} stroke counts do not fit in {batch_len} bytes")))?;
+
+    let mut strokes = Vec::with_capacity(stroke_count);
+    let mut offset = header_len;
+    for i in 0..stroke_count {
+        let points = read_u32(bytes, 4 + i * 4).unwrap_or(0) as usize;
+        let end = points
+            .checked_mul(record_floats * 4)
+            .and_then(|n| n.checked_add(offset))
+            .filter(|&n| n <= bytes.len())
+            .ok_or_else(|| malformed(format!("stroke {i} ({points} points) runs past the end")))?;
+        strokes.push(&bytes[offset..end]);
+        offset = end;
+    }
+    if offset != bytes.len() {
+        return Err(malformed(format!("{} trailing bytes", bytes.len() - offset)));
+    }
+
+    Ok(strokes)
+}
+
+/**
+ * Decode one stroke of a batch into f32 values (any alignment)
+ */
+#[cfg(feature = "std")]
+pub(crate) fn batch_stroke_values(stroke: &[u8]) -> Vec<f32> {
+    stroke.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+/**
+ * Serialize strokes in the batch input layout (no status array)
+ * 
+ * Used by exports that produce several strokes from one, so the result
+ * can be passed straight back to a *_batch export.
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_strokes(strokes: &[Vec<f32>]) -> EngineResult<EngineBuffer> {
+    serialize_packed(strokes, 3)
 }
 
 /**
- * Chaikin subdivision algorithm with pressure preservation
+ * Serialize several record lists in the batch container layout
  * 
- * Each iteration:
- * - For each line segment, create 2 new points at 1/4 and 3/4 positions
- * - Interpolate pressure values at the same ratios
- * - Remove original intermediate points (keep endpoints)
+ * Output format:
+ * - 4 bytes: list count S (u32)
+ * - S * 4 bytes: record count of each list (u32)
+ * - the f32 records of every list, concatenated in order
  * 
- * This creates a corner-cutting effect that smooths the curve while
- * maintaining natural pressure transitions.
+ * `record_floats` is the number of f32 values per record (3 for strokes,
+ * 2 for x/y paths).
  */
-fn chaikin_subdivide(points: &[f32], iterations: usize) -> Vec<f32> {
-    let mut current = points.to_vec();
-    
-    for _ in 0..iterations {
-        if current.len() < 6 {
-            break;
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_packed(strokes: &[Vec<f32>], record_floats: usize) -> EngineResult<EngineBuffer> {
+    Ok(hand_off(pack_lists(strokes, record_floats)?))
+}
+
+/**
+ * serialize_packed into a plain byte vector (Stroke::write_batch for
+ * native callers)
+ */
+#[cfg(feature = "std")]
+pub(crate) fn pack_lists<T: AsRef<[f32]>>(strokes: &[T], record_floats: usize) -> EngineResult<AlignedBuffer> {
+    let data_floats: usize = strokes.iter().map(|stroke| stroke.as_ref().len()).sum();
+    let mut buffer = output_buffer(4 + strokes.len() * 4 + data_floats * 4)?;
+
+    buffer.extend_from_slice(&(strokes.len() as u32).to_le_bytes());
+    for stroke in strokes {
+        buffer.extend_from_slice(&((stroke.as_ref().len() / record_floats) as u32).to_le_bytes());
+    }
+    for stroke in strokes {
+        for &value in stroke.as_ref() {
+            buffer.extend_from_slice(&value.to_le_bytes());
         }
-        
-        let mut next = Vec::with_capacity(current.len() * 2);
-        
-        // Always keep first point (x, y, pressure)
-        next.push(current[0]);
-        next.push(current[1]);
-        next.push(current[2]);
-        
-        // Process each segment
-        for i in 0..(current.len() / 3 - 1) {
-            let x0 = current[i * 3];
-            let y0 = current[i * 3 + 1];
-            let p0 = current[i * 3 + 2];
-            let x1 = current[(i + 1) * 3];
-            let y1 = current[(i + 1) * 3 + 1];
-            let p1 = current[(i + 1) * 3 + 2];
-            
-            // Point at 1/4 along segment (position + pressure)
-            let q_x = 0.75 * x0 + 0.25 * x1;
-            let q_y = 0.75 * y0 + 0.25 * y1;
-            let q_p = 0.75 * p0 + 0.25 * p1;  // Interpolate pressure
-            
-            // Point at 3/4 along segment (position + pressure)
-            let r_x = 0.25 * x0 + 0.75 * x1;
-            let r_y = 0.25 * y0 + 0.75 * y1;
-            let r_p = 0.25 * p0 + 0.75 * p1;  // Interpolate pressure
-            
-            next.push(q_x);
-            next.push(q_y);
-            next.push(q_p);
-            next.push(r_x);
-            next.push(r_y);
-            next.push(r_p);
+    }
+
+    Ok(buffer)
+}
+
+/**
+ * Serialize per-stroke results in the batch result layout
+ * 
+ * `record_floats` is the number of f32 values per output record.
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_batch(results: &[EngineResult<Vec<f32>>], record_floats: usize) -> EngineResult<EngineBuffer> {
+    let data_floats: usize = results.iter().map(|r| r.as_ref().map_or(0, Vec::len)).sum();
+    let mut buffer = output_buffer(4 + results.len() * 8 + data_floats * 4)?;
+
+    buffer.extend_from_slice(&(results.len() as u32).to_le_bytes());
+    for result in results {
+        let records = result.as_ref().map_or(0, |values| values.len() / record_floats);
+        buffer.extend_from_slice(&(records as u32).to_le_bytes());
+    }
+    for values in results.iter().flatten() {
+        for &value in values {
+            buffer.extend_from_slice(&value.to_le_bytes());
         }
-        
-        // Always keep last point (x, y, pressure)
-        let last_idx = current.len() - 3;
-        next.push(current[last_idx]);
-        next.push(current[last_idx + 1]);
-        next.push(current[last_idx + 2]);
-        
-        current = next;
     }
-    
-    current
+    for result in results {
+        let status = result.as_ref().map_or_else(|error| error.code as u32, |_| 0);
+        buffer.extend_from_slice(&status.to_le_bytes());
+    }
+
+    Ok(hand_off(buffer))
 }
 
 /**
- * Serialize points to buffer that JS can read
+ * Process every stroke of a batch with `process` and serialize the results
+ * 
+ * Each stroke is validated like a single-stroke export (an empty stroke
+ * reports TooFewPoints). Strokes are decoded and validated on the
+ * calling thread (FloatValidation is per thread) and processed on the
+ * thread pool when it is up (see threads.rs); a stroke whose processing
+ * panicked on a pool thread reports Internal.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn run_batch(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    record_floats: usize,
+    process: impl Fn(&[f32]) -> EngineResult<Vec<f32>> + Sync,
+) -> EngineBuffer {
+    run_export(|| {
+        let strokes = read_batch(batch_ptr, batch_len)?;
+        let values: Vec<Vec<f32>> = strokes.iter().map(|stroke| batch_stroke_values(stroke)).collect();
+        let inputs: Vec<EngineResult<Cow<[f32]>>> =
+            values.iter().map(|values| read_points(values.as_ptr(), values.len())).collect();
+        let results: Vec<EngineResult<Vec<f32>>> = map_indexed(inputs.len(), |i| match &inputs[i] {
+            Ok(points) => process(points),
+            Err(error) => Err(error.clone()),
+        })
+        .into_iter()
+        .enumerate()
+        .map(|(i, result)| {
+            result.unwrap_or_else(|| {
+                Err(EngineError::new(CreoVexError::Internal, format!("stroke {i} panicked on a pool thread")))
+            })
+        })
+        .collect();
+        serialize_batch(&results, record_floats)
+    })
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/**
+ * Smallest supported point stride (x, y, pressure)
+ */
+#[cfg(feature = "std")]
+pub(crate) const MIN_STRIDE: usize = 3;
+
+/**
+ * Largest supported point stride
+ * 
+ * Room for tiltX, tiltY, barrel rotation, and two spare channels after
+ * x, y, pressure.
+ */
+#[cfg(feature = "std")]
+pub(crate) const MAX_STRIDE: usize = 8;
+
+/**
+ * Validate a caller-provided point stride (3-8 floats per point)
+ */
+#[cfg(feature = "std")]
+pub(crate) fn require_stride(stride: usize) -> EngineResult<usize> {
+    if !(MIN_STRIDE..=MAX_STRIDE).contains(&stride) {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("stride must be {MIN_STRIDE}-{MAX_STRIDE} (got {stride})"),
+        ));
+    }
+    Ok(stride)
+}
+
+/**
+ * Serialize points of any stride to a buffer JS can read
  * 
  * Output format:
  * - 4 bytes: point count (u32)
- * - N * 12 bytes: f32 triplets [x0, y0, p0, x1, y1, p1, ...]
+ * - 4 bytes: stride, floats per point (u32)
+ * - N * stride * 4 bytes: f32 point data
+ * 
+ * Used by the stride-aware exports; the 3-float exports keep the
+ * serialize_points header so existing callers are unaffected.
  */
-fn serialize_points(points: &[f32]) -> *mut u8 {
-    let point_count = (points.len() / 3) as u32;
-    let total_bytes = 4 + (points.len() * 4);
-    
-    // Allocate buffer
-    let mut buffer = Vec::<u8>::with_capacity(total_bytes);
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_strided_points(points: &[f32], stride: usize) -> EngineResult<EngineBuffer> {
+    let mut buffer = output_buffer(8 + (points.len() * 4))?;
+    buffer.extend_from_slice(&((points.len() / stride) as u32).to_le_bytes());
+    buffer.extend_from_slice(&(stride as u32).to_le_bytes());
+    for &value in points {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    Ok(hand_off(buffer))
+}
+
+/**
+ * Serialize a record count plus f32 payload to a buffer JS can read
+ * 
+ * Output format:
+ * - 4 bytes: record count (u32)
+ * - values.len() * 4 bytes: f32 payload, little-endian
+ * 
+ * Shared by every export that returns float data; the meaning of a
+ * record (point, vertex, segment) is documented at each call site.
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_records(count: u32, values: &[f32]) -> EngineResult<EngineBuffer> {
+    let mut buffer = output_buffer(4 + (values.len() * 4))?;
     
-    // Write point count
-    buffer.extend_from_slice(&point_count.to_le_bytes());
+    // Write record count
+    buffer.extend_from_slice(&count.to_le_bytes());
     
-    // Write points (x, y, pressure triplets)
-    for &value in points {
+    // Write payload
+    for &value in values {
         buffer.extend_from_slice(&value.to_le_bytes());
     }
     
-    // Return pointer (JS must free this)
-    let ptr = buffer.as_mut_ptr();
-    std::mem::forget(buffer);
-    ptr
+    Ok(hand_off(buffer))
+}
+
+/**
+ * Write the serialize_records layout into caller-provided memory
+ * 
+ * Returns the number of bytes written. When the buffer is too small,
+ * fails with BufferTooSmall and, if at least 4 bytes are available,
+ * stores the required byte count as a u32 at the start of the buffer.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn write_records_into(out: &mut [u8], count: u32, values: &[f32]) -> EngineResult<usize> {
+    let total_bytes = 4 + (values.len() * 4);
+    require_capacity(out, total_bytes)?;
+
+    out[..4].copy_from_slice(&count.to_le_bytes());
+    for (bytes, value) in out[4..total_bytes].chunks_exact_mut(4).zip(values) {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    Ok(total_bytes)
+}
+
+/**
+ * Check that caller-provided memory holds `total_bytes`
+ * 
+ * On failure stores the required byte count as a u32 at the start of the
+ * buffer (if at least 4 bytes are available, saturating at u32::MAX), as
+ * write_records_into documents.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn require_capacity(out: &mut [u8], total_bytes: usize) -> EngineResult<()> {
+    if out.len() < total_bytes {
+        if out.len() >= 4 {
+            out[..4].copy_from_slice(&u32::try_from(total_bytes).unwrap_or(u32::MAX).to_le_bytes());
+        }
+        return Err(EngineError::new(
+            CreoVexError::BufferTooSmall,
+            format!("output needs {total_bytes} bytes, buffer holds {}", out.len()),
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * Serialize a list of u32 indices to a buffer JS can read
+ * 
+ * Output format:
+ * - 4 bytes: index count (u32)
+ * - N * 4 bytes: u32 indices, little-endian
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_indices(indices: &[u32]) -> EngineResult<EngineBuffer> {
+    let mut buffer = output_buffer(4 + (indices.len() * 4))?;
+    buffer.extend_from_slice(&(indices.len() as u32).to_le_bytes());
+    for &index in indices {
+        buffer.extend_from_slice(&index.to_le_bytes());
+    }
+    Ok(hand_off(buffer))
+}
+
+/**
+ * Serialize a row-major grid of f32 values to a buffer JS can read
+ * 
+ * Output format:
+ * - 4 bytes: width (u32, values per row)
+ * - 4 bytes: height (u32, row count)
+ * - width * height * 4 bytes: f32 values, top row first
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_grid(width: u32, height: u32, values: &[f32]) -> EngineResult<EngineBuffer> {
+    let mut buffer = output_buffer(8 + (values.len() * 4))?;
+    buffer.extend_from_slice(&width.to_le_bytes());
+    buffer.extend_from_slice(&height.to_le_bytes());
+    for &value in values {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    Ok(hand_off(buffer))
+}
+
+/**
+ * Serialize a polygon and its area to a buffer JS can read
+ * 
+ * Output format:
+ * - 4 bytes: vertex count N (u32)
+ * - 4 bytes: f32 area
+ * - N * 8 bytes: f32 [x, y] per vertex
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_polygon(vertices: &[[f32; 2]], area: f32) -> EngineResult<EngineBuffer> {
+    let mut buffer = output_buffer(8 + (vertices.len() * 8))?;
+    buffer.extend_from_slice(&(vertices.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(&area.to_le_bytes());
+    for vertex in vertices {
+        buffer.extend_from_slice(&vertex[0].to_le_bytes());
+        buffer.extend_from_slice(&vertex[1].to_le_bytes());
+    }
+    Ok(hand_off(buffer))
+}
+
+/**
+ * Serialize an indexed triangle mesh to a buffer JS can read
+ * 
+ * Output format:
+ * - 4 bytes: vertex count V (u32)
+ * - 4 bytes: index count I (u32, a multiple of 3)
+ * - V * floats_per_vertex * 4 bytes: f32 vertex data, [x, y, ...] per
+ *   vertex (the extra floats are documented at each call site)
+ * - I * 4 bytes: u32 vertex indices, three per triangle
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn serialize_mesh(mesh: &TessellationResult) -> EngineResult<EngineBuffer> {
+    Ok(hand_off(mesh.mesh_bytes()?))
+}
+
+/**
+ * Empty 4-byte aligned buffer for a `total_bytes` result
+ * 
+ * The capacity is bucket_capacity(total_bytes), drawn from the buffer
+ * pool when a recycled buffer of that bucket is waiting.
+ */
+#[cfg(feature = "std")]
+pub(crate) fn output_buffer(total_bytes: usize) -> EngineResult<AlignedBuffer> {
+    take_buffer(total_bytes).map_err(|_| {
+        EngineError::new(
+            CreoVexError::AllocationFailed,
+            format!("could not allocate {total_bytes} byte output buffer"),
+        )
+    })
+}
+
+/**
+ * Hand ownership of a filled buffer to JS
+ * 
+ * The allocation is recorded with its capacity, so free_buffer and
+ * recycle_buffer rebuild exactly this Vec whatever size JS passes.
+ * 
+ * With allocation tracking on, the buffer is recorded with the caller's
+ * source location as its origin (the serialize_* helpers pass their own
+ * caller through).
+ */
+#[cfg(feature = "std")]
+#[track_caller]
+pub(crate) fn hand_off(buffer: AlignedBuffer) -> EngineBuffer {
+    let byte_len = buffer.len();
+    let buffer = hand_out(buffer);
+    if tracking_enabled() {
+        track_allocation(buffer, byte_len, Origin::Output(Location::caller()));
+    }
+    EngineBuffer {
+        ptr: buffer,
+        byte_len,
+        error: 0,
+    }
 }
 
 /**
  * Memory management: Allocate buffer for JS to write into
  * 
+ * The buffer is at least 4-byte aligned, so JS can create a Float32Array
+ * or Uint32Array directly over it (alloc_aligned gives more). It comes
+ * from the pool when one of its size bucket is waiting; release it with
+ * free_buffer or recycle_buffer(ptr, size).
+ * 
+ * Returns null when the allocation fails; last_error_code() then reports
+ * CreoVexError::AllocationFailed.
+ * 
  * Authority: ffi_contract.md (Memory Ownership Rules)
  */
-#[wasm_bindgen]
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn alloc(size: usize) -> *mut u8 {
-    let mut buffer = Vec::<u8>::with_capacity(size);
-    let ptr = buffer.as_mut_ptr();
-    std::mem::forget(buffer);
+    let Ok(buffer) = take_buffer(size) else {
+        record_error(EngineError::new(
+            CreoVexError::AllocationFailed,
+            format!("could not allocate {size} bytes"),
+        ));
+        return std::ptr::null_mut();
+    };
+    let ptr = hand_out(buffer);
+    if tracking_enabled() {
+        track_allocation(ptr, size, Origin::Alloc);
+    }
     ptr
 }
 
 /**
- * Memory management: Free buffer allocated by Wasm
+ * Memory management: Allocate buffer for JS to write into, aligned to
+ * `align` bytes
+ * 
+ * `align` must be a power of two up to 65536; values below 4 give alloc's
+ * 4-byte alignment. Release it with free_buffer or recycle_buffer like an
+ * alloc buffer (the alignment is recorded with it; buffers aligned to
+ * more than 4 bytes are freed rather than pooled).
+ * 
+ * Returns null on failure; last_error_code() then reports
+ * CreoVexError::InvalidParameter for a bad `align` or AllocationFailed.
  * 
  * Authority: ffi_contract.md (Memory Ownership Rules)
  */
-#[wasm_bindgen]
-pub fn free_buffer(ptr: *mut u8, size: usize) {
-    if !ptr.is_null() && size > 0 {
-        unsafe {
-            let _ = Vec::from_raw_parts(ptr, size, size);
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn alloc_aligned(size: usize, align: usize) -> *mut u8 {
+    let buffer = match take_aligned_buffer(size, align) {
+        Ok(buffer) => buffer,
+        Err(error) => {
+            record_error(error);
+            return std::ptr::null_mut();
         }
+    };
+    let ptr = hand_out(buffer);
+    if tracking_enabled() {
+        track_allocation(ptr, size, Origin::Alloc);
     }
+    ptr
 }
 
-// TODO: Implement tessellate_stroke (quad strip extrusion)
-// TODO: Implement simplify_stroke (Ramer-Douglas-Peucker)
-// TODO: Implement apply_pressure (variable width based on pressure curve)
-// TODO: Implement fit_curve (cubic Bezier fitting)
-// TODO: Implement camera matrix computation
-// TODO: Implement cache management
+/**
+ * Memory management: Free buffer allocated by Wasm
+ * 
+ * The engine records the capacity of every buffer it hands out, so only
+ * `ptr` decides what is freed. Pass the EngineBuffer `byte_len` (or the
+ * alloc size) as `size` anyway: allocation tracking checks it; 0 skips
+ * the check. Results that will be replaced by similar ones (e.g. per
+ * frame) are better returned with recycle_buffer.
+ * 
+ * A pointer the engine does not own (freed already, or never an engine
+ * buffer) is left alone and reported as CreoVexError::InvalidFree
+ * (last_error_code()); debug builds also assert, unless allocation
+ * tracking is on. With set_allocation_tracking(true), a non-zero `size`
+ * that does not match the buffer is reported the same way.
+ * 
+ * Free a buffer on the thread that allocated it. The ownership records
+ * are thread-local, so a buffer passed to another thread (e.g. a
+ * worker) is not recognised there: it is handled like any pointer the
+ * engine does not own, and stays allocated.
+ * 
+ * Authority: ffi_contract.md (Memory Ownership Rules)
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn free_buffer(ptr: *mut u8, size: usize) {
+    if tracking_enabled() && !track_release(ptr, size) {
+        return;
+    }
+    drop(reclaim(ptr));
+}
@@ -0,0 +1,259 @@
+/*!
+ * Stroke morphing
+ *
+ * Animation in-betweens: a stroke drawn on one key frame blends into the
+ * stroke drawn on the next. Both are resampled to the same point count
+ * by relative arc length, so each point of A is paired with the point of
+ * B the same fraction of the way along it, whatever their lengths. The
+ * "repeat and average" assist pairs several attempts at one line the
+ * same way.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_non_negative, run_export, CreoVexError, EngineError, EngineResult};
+use crate::resample::{resample_by_count, MAX_RESAMPLED_POINTS};
+use crate::validate::validate_floats;
+use crate::{batch_stroke_values, read_batch, read_points, serialize_points, serialize_strokes, EngineBuffer};
+
+/**
+ * Blend stroke A into stroke B
+ *
+ * Input format: two [x, y, p, ...] buffers, A then B
+ * Output format: [x, y, p] points (see serialize_points)
+ *
+ * Both strokes are resampled to the larger of their point counts (at
+ * least 2), evenly by arc length along each: point i lies i / (n - 1) of
+ * the way along A and along B. Position and pressure are then blended,
+ * `a + (b - a) * t`, so `t` = 0 reproduces A's shape and 1 B's. A stroke
+ * with no length (a dot) contributes its point at every position.
+ *
+ * With `match_direction`, B is walked from its last point instead when
+ * that pairs the points closer together (smaller summed squared
+ * distance), so a stroke redrawn in the opposite direction does not
+ * flip through itself on the way.
+ *
+ * Errors: the read_points errors for either stroke; NonFiniteValue/
+ * InvalidParameter for a `t` that is NaN or outside [0, 1].
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn morph_strokes(
+    a_ptr: *const f32,
+    a_len: usize,
+    b_ptr: *const f32,
+    b_len: usize,
+    t: f32,
+    match_direction: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let a = &read_points(a_ptr, a_len)?;
+        let b = &read_points(b_ptr, b_len)?;
+        let t = require_non_negative("t", t)?;
+        if t > 1.0 {
+            return Err(EngineError::new(CreoVexError::InvalidParameter, format!("t must be in [0, 1] (got {t})")));
+        }
+
+        let pair = MorphPair::new(a, b, match_direction);
+        serialize_points(&pair.blend(t))
+    })
+}
+
+/**
+ * In-betweens from stroke A to stroke B
+ *
+ * Input format: two [x, y, p, ...] buffers, A then B
+ *
+ * Output format: the batch container (see read_batch) holding `count`
+ * strokes, without a status array
+ *
+ * Stroke k (from 1) is morph_strokes at `t = k / (count + 1)`, so the
+ * key frames themselves are not repeated and the in-betweens are evenly
+ * spaced between them. `count` = 0 gives an empty container.
+ *
+ * Errors: the read_points errors for either stroke; InvalidParameter
+ * when the strokes would hold more than MAX_RESAMPLED_POINTS points in
+ * total.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn morph_sequence(
+    a_ptr: *const f32,
+    a_len: usize,
+    b_ptr: *const f32,
+    b_len: usize,
+    count: u32,
+    match_direction: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let a = &read_points(a_ptr, a_len)?;
+        let b = &read_points(b_ptr, b_len)?;
+
+        let pair = MorphPair::new(a, b, match_direction);
+        let total_points = (pair.a.len() / 3).saturating_mul(count as usize);
+        if total_points > MAX_RESAMPLED_POINTS {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("{count} in-betweens would produce {total_points} points (max {MAX_RESAMPLED_POINTS})"),
+            ));
+        }
+
+        let frames: Vec<Vec<f32>> =
+            (1..=count).map(|k| pair.blend((k as f64 / (count as f64 + 1.0)) as f32)).collect();
+        serialize_strokes(&frames)
+    })
+}
+
+/**
+ * Replace repeated attempts at the same stroke with their mean path
+ *
+ * Input format: the batch container (see read_batch)
+ * Output format: [x, y, p] points (see serialize_points)
+ *
+ * Every stroke is resampled as in morph_strokes, to the largest point
+ * count in the batch, and reversed if it was drawn the other way from
+ * the first stroke (see `match_direction`). Point i of the result is the
+ * mean position and pressure of point i of every stroke. Strokes with no
+ * points are skipped.
+ *
+ * With `reject_outlier` and more than three strokes, the stroke farthest
+ * from the median path (summed squared distance to the per-point median
+ * x/y) is left out of the mean, so one slip does not drag the result.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container;
+ * TooFewPoints when no stroke has points.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn average_strokes(batch_ptr: *const u8, batch_len: usize, reject_outlier: bool) -> EngineBuffer {
+    run_export(|| {
+        let strokes: Vec<Vec<f32>> = read_batch(batch_ptr, batch_len)?
+            .into_iter()
+            .map(batch_stroke_values)
+            .filter(|values| !values.is_empty())
+            .map(|values| Ok(validate_floats(&values, 3)?.into_owned()))
+            .collect::<EngineResult<_>>()?;
+        if strokes.is_empty() {
+            return Err(EngineError::new(CreoVexError::TooFewPoints, "batch has no strokes with points"));
+        }
+
+        let count = strokes.iter().map(|values| values.len() / 3).max().unwrap_or(0).max(2);
+        let mut samples: Vec<Vec<f32>> = strokes.iter().map(|values| resample_by_count(values, 3, count)).collect();
+        // The per-point mean and median index every sample at the same points
+        if samples.iter().any(|sample| sample.len() != count * 3) {
+            return Err(EngineError::new(CreoVexError::Internal, "resampled strokes differ in point count"));
+        }
+        if let Some((first, rest)) = samples.split_first_mut() {
+            for sample in rest {
+                match_direction_to(first, sample);
+            }
+        }
+
+        if reject_outlier && samples.len() > 3 {
+            let median = median_path(&samples);
+            let farthest = (0..samples.len())
+                .max_by(|&i, &j| pairing_cost(&median, &samples[i]).total_cmp(&pairing_cost(&median, &samples[j])))
+                .unwrap_or(0);
+            samples.remove(farthest);
+        }
+
+        let scale = 1.0 / samples.len() as f64;
+        let mean: Vec<f32> = (0..count * 3)
+            .map(|k| (samples.iter().map(|sample| sample[k] as f64).sum::<f64>() * scale) as f32)
+            .collect();
+        serialize_points(&mean)
+    })
+}
+
+/**
+ * Per-point median x/y (and pressure) of equally resampled strokes
+ */
+fn median_path(samples: &[Vec<f32>]) -> Vec<f32> {
+    let mut column = Vec::with_capacity(samples.len());
+    (0..samples[0].len())
+        .map(|k| {
+            column.clear();
+            column.extend(samples.iter().map(|sample| sample[k]));
+            column.sort_by(f32::total_cmp);
+            let middle = column.len() / 2;
+            if column.len() % 2 == 0 { (column[middle - 1] + column[middle]) / 2.0 } else { column[middle] }
+        })
+        .collect()
+}
+
+/**
+ * The two strokes resampled to corresponding points
+ */
+struct MorphPair {
+    a: Vec<f32>,
+    b: Vec<f32>,
+}
+
+impl MorphPair {
+    fn new(a: &[f32], b: &[f32], match_direction: bool) -> Self {
+        let count = (a.len() / 3).max(b.len() / 3).max(2);
+        let a = resample_by_count(a, 3, count);
+        let mut b = resample_by_count(b, 3, count);
+
+        if match_direction {
+            match_direction_to(&a, &mut b);
+        }
+        MorphPair { a, b }
+    }
+
+    fn blend(&self, t: f32) -> Vec<f32> {
+        self.a.iter().zip(&self.b).map(|(&a, &b)| a + (b - a) * t).collect()
+    }
+}
+
+/**
+ * Reverse resampled `stroke` when that pairs its points closer to
+ * `reference`'s (same count)
+ */
+fn match_direction_to(reference: &[f32], stroke: &mut Vec<f32>) {
+    let reversed: Vec<f32> = stroke.chunks_exact(3).rev().flatten().copied().collect();
+    if pairing_cost(reference, &reversed) < pairing_cost(reference, stroke) {
+        *stroke = reversed;
+    }
+}
+
+/**
+ * Summed squared x/y distance between paired points
+ */
+fn pairing_cost(a: &[f32], b: &[f32]) -> f64 {
+    a.chunks_exact(3)
+        .zip(b.chunks_exact(3))
+        .map(|(p, q)| {
+            let (dx, dy) = ((q[0] - p[0]) as f64, (q[1] - p[1]) as f64);
+            dx * dx + dy * dy
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{pack_batch, sine_stroke, take_points};
+
+    #[test]
+    fn average_strokes_survives_an_overflowing_stroke() {
+        let mut strokes: Vec<Vec<f32>> = (0..4).map(|k| sine_stroke(12, 2.0, 3.0 + k as f32, 5.0)).collect();
+        // x differences overflow f32, so this stroke's arc length is infinite
+        strokes.push(vec![-3.0e38, 0.0, 0.5, 3.0e38, 0.0, 0.5, -3.0e38, 1.0, 0.5]);
+
+        for reject_outlier in [false, true] {
+            let batch = pack_batch(&strokes);
+            let mean = take_points(average_strokes(batch.as_ptr(), batch.len(), reject_outlier));
+            assert_eq!(mean.len(), 12 * 3);
+        }
+    }
+
+    #[test]
+    fn morph_strokes_survives_an_overflowing_stroke() {
+        let a = sine_stroke(20, 2.0, 3.0, 5.0);
+        let b = [-3.0e38, 0.0, 0.5, 3.0e38, 0.0, 0.5];
+        let blend = take_points(morph_strokes(a.as_ptr(), a.len(), b.as_ptr(), b.len(), 0.0, true));
+        assert_eq!(blend.len(), 20 * 3);
+    }
+}
@@ -0,0 +1,263 @@
+/*!
+ * Stroke outlines (filled polygon of the painted area)
+ *
+ * The same half-widths tessellate_stroke extrudes, traced as one closed
+ * boundary per connected piece: left side forward, round end cap, right
+ * side backward, round start cap. Used for hit regions, SVG export, and
+ * boolean operations, where a triangle strip is no use.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Tessellation)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_non_negative, run_export};
+use crate::tessellate::{direction, distinct_points, CapStyle, MITER_LIMIT};
+use crate::{read_points, serialize_packed, EngineBuffer};
+
+/**
+ * Largest angle between consecutive vertices of a round cap or join
+ */
+pub(crate) const OUTLINE_ROUND_STEP_RAD: f32 = std::f32::consts::PI / 12.0;
+
+/**
+ * Outline a stroke as closed polygons (round caps)
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Each point has a half-width of `base_width * pressure`, as in
+ * tessellate_stroke.
+ *
+ * Output format:
+ * - 4 bytes: sub-path count S (u32)
+ * - S * 4 bytes: vertex count of each sub-path (u32)
+ * - f32 [x, y] pairs of every sub-path, concatenated in order
+ *
+ * Sub-paths are implicitly closed (the first vertex is not repeated).
+ * Ends get round caps. At each turn the outer side gets a round join and
+ * the inner side the miter point, clamped to MITER_LIMIT half-widths; a
+ * 180° reversal is capped round. Fill with the nonzero rule: the inner
+ * side of a very tight turn, and any reversal, wind over parts of the
+ * outline twice.
+ *
+ * A point with zero (or negative) pressure pinches the outline to that
+ * point, and the stroke splits into one sub-path per piece between such
+ * points; pieces with no width at all are dropped. A single distinct
+ * point gives a circle. Consecutive duplicate points are skipped.
+ *
+ * Equivalent to stroke_outline_ex with round caps at both ends.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a NaN or negative base width.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_outline(points_ptr: *const f32, points_len: usize, base_width: f32) -> EngineBuffer {
+    stroke_outline_ex(points_ptr, points_len, base_width, CapStyle::Round, CapStyle::Round)
+}
+
+/**
+ * Outline a stroke as closed polygons with chosen caps
+ *
+ * Input and output format as stroke_outline. `start_cap` closes the
+ * first point and `end_cap` the last: butt cuts straight across at the
+ * point, square one half-width past it, round with a semicircle. Ends
+ * with zero width (including those where the outline splits) collapse
+ * to their point whatever the style.
+ *
+ * A single distinct point gives a circle if either cap is round, else an
+ * axis-aligned square if either is square; with two butt caps it has no
+ * area and yields no sub-path.
+ *
+ * Errors: as stroke_outline.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_outline_ex(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    start_cap: CapStyle,
+    end_cap: CapStyle,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", base_width)?;
+
+        serialize_packed(&outline_polygons(points, base_width, [start_cap, end_cap]), 2)
+    })
+}
+
+/**
+ * Closed [x, y, ...] polygons of the stroke, one per piece of nonzero width
+ *
+ * `caps` are the start and end cap styles.
+ */
+pub(crate) fn outline_polygons(points: &[f32], base_width: f32, caps: [CapStyle; 2]) -> Vec<Vec<f32>> {
+    let centerline = distinct_points(points);
+    let width = |point: &[f32; 3]| base_width * point[2].max(0.0);
+
+    if centerline.len() == 1 {
+        let [x, y, _] = centerline[0];
+        let radius = width(&centerline[0]);
+        if radius == 0.0 {
+            return Vec::new();
+        }
+        if caps.contains(&CapStyle::Round) {
+            let steps = (std::f32::consts::TAU / OUTLINE_ROUND_STEP_RAD).ceil() as usize;
+            let circle = arc([x, y], (1.0, 0.0), std::f32::consts::TAU, radius);
+            return vec![circle[..steps].iter().flatten().copied().collect()];
+        }
+        if caps.contains(&CapStyle::Square) {
+            let (x0, y0, x1, y1) = (x - radius, y - radius, x + radius, y + radius);
+            return vec![vec![x0, y0, x1, y0, x1, y1, x0, y1]];
+        }
+        return Vec::new();
+    }
+
+    // Pieces run between zero-width points, which end one piece and start the next
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for i in 1..centerline.len() {
+        if width(&centerline[i]) == 0.0 {
+            pieces.push(&centerline[start..=i]);
+            start = i;
+        }
+    }
+    if start < centerline.len() - 1 {
+        pieces.push(&centerline[start..]);
+    }
+
+    pieces
+        .into_iter()
+        .filter(|piece| piece.iter().any(|point| width(point) > 0.0))
+        .map(|piece| piece_polygon(piece, base_width, caps))
+        .collect()
+}
+
+/**
+ * Boundary of one piece (at least two distinct points)
+ */
+fn piece_polygon(centerline: &[[f32; 3]], base_width: f32, caps: [CapStyle; 2]) -> Vec<f32> {
+    let count = centerline.len();
+    let normals: Vec<(f32, f32)> = centerline
+        .windows(2)
+        .map(|pair| {
+            let (dx, dy) = direction(pair[0], pair[1]);
+            (-dy, dx)
+        })
+        .collect();
+    let half_width = |i: usize| base_width * centerline[i][2].max(0.0);
+    let center = |i: usize| [centerline[i][0], centerline[i][1]];
+
+    let mut vertices: Vec<[f32; 2]> = Vec::with_capacity(count * 4);
+    for i in 0..count {
+        vertices.extend(side_vertices(centerline, &normals, i, 1.0, base_width));
+    }
+
+    let last = normals[count - 2];
+    vertices.extend(cap_vertices(caps[1], center(count - 1), last, half_width(count - 1)));
+
+    for i in (0..count).rev() {
+        let mut side = side_vertices(centerline, &normals, i, -1.0, base_width);
+        side.reverse();
+        vertices.extend(side);
+    }
+
+    let first = (-normals[0].0, -normals[0].1);
+    vertices.extend(cap_vertices(caps[0], center(0), first, half_width(0)));
+
+    // Zero-width ends and straight joins repeat vertices
+    vertices.dedup();
+    if vertices.len() > 1 && vertices.first() == vertices.last() {
+        vertices.pop();
+    }
+    vertices.into_iter().flatten().collect()
+}
+
+/**
+ * Vertices between the two side vertices `center + half_width * from`
+ * and `center - half_width * from` at an end
+ *
+ * Caps turn clockwise (in x-right, y-up terms) from `from`, which puts
+ * them outside the stroke at both ends.
+ */
+fn cap_vertices(cap: CapStyle, center: [f32; 2], from: (f32, f32), half_width: f32) -> Vec<[f32; 2]> {
+    match cap {
+        CapStyle::Butt => Vec::new(),
+        CapStyle::Round => {
+            let arc = arc(center, from, -std::f32::consts::PI, half_width);
+            arc[1..arc.len() - 1].to_vec()
+        }
+        CapStyle::Square => {
+            // Outward is `from` turned a quarter clockwise
+            let out = (from.1, -from.0);
+            let corner = |side: f32| {
+                [
+                    center[0] + (side * from.0 + out.0) * half_width,
+                    center[1] + (side * from.1 + out.1) * half_width,
+                ]
+            };
+            vec![corner(1.0), corner(-1.0)]
+        }
+    }
+}
+
+/**
+ * Offset vertices of centerline point `i` on one side (`sign` 1 for
+ * left, -1 for right), in stroke order
+ */
+fn side_vertices(
+    centerline: &[[f32; 3]],
+    normals: &[(f32, f32)],
+    i: usize,
+    sign: f32,
+    base_width: f32,
+) -> Vec<[f32; 2]> {
+    let [x, y, pressure] = centerline[i];
+    let offset = base_width * pressure.max(0.0);
+    let at = |n: (f32, f32), scale: f32| [x + sign * n.0 * offset * scale, y + sign * n.1 * offset * scale];
+
+    if i == 0 {
+        return vec![at(normals[0], 1.0)];
+    }
+    if i == centerline.len() - 1 {
+        return vec![at(normals[i - 1], 1.0)];
+    }
+
+    let (n0, n1) = (normals[i - 1], normals[i]);
+    let (mx, my) = (n0.0 + n1.0, n0.1 + n1.1);
+    let m_len = (mx * mx + my * my).sqrt();
+    if m_len < 1e-6 {
+        // Full reversal: the left side caps it round, the right side bevels
+        return if sign > 0.0 {
+            arc([x, y], n0, -std::f32::consts::PI, offset)
+        } else {
+            vec![at(n0, 1.0), at(n1, 1.0)]
+        };
+    }
+
+    // Normals turn by the same signed angle as the directions
+    let turn = (n0.0 * n1.1 - n0.1 * n1.0).atan2(n0.0 * n1.0 + n0.1 * n1.1);
+    if sign * turn < 0.0 {
+        arc([x, y], (sign * n0.0, sign * n0.1), turn, offset)
+    } else {
+        let (mx, my) = (mx / m_len, my / m_len);
+        let cos_half = mx * n0.0 + my * n0.1;
+        vec![at((mx, my), (1.0 / cos_half).min(MITER_LIMIT))]
+    }
+}
+
+/**
+ * Points from `center + radius * from` rotating by `angle`, both ends
+ * included, at most OUTLINE_ROUND_STEP_RAD apart
+ */
+fn arc(center: [f32; 2], from: (f32, f32), angle: f32, radius: f32) -> Vec<[f32; 2]> {
+    let steps = ((angle.abs() / OUTLINE_ROUND_STEP_RAD).ceil() as usize).max(1);
+    (0..=steps)
+        .map(|j| {
+            let (sin, cos) = (angle * j as f32 / steps as f32).sin_cos();
+            let (ux, uy) = (from.0 * cos - from.1 * sin, from.0 * sin + from.1 * cos);
+            [center[0] + ux * radius, center[1] + uy * radius]
+        })
+        .collect()
+}
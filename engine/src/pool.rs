@@ -0,0 +1,532 @@
+/*!
+ * Engine buffer ownership and reuse
+ *
+ * Every buffer handed to JS (alloc, alloc_aligned, and every
+ * EngineBuffer result) is recorded with its true capacity and alignment
+ * until it comes back, so free_buffer and recycle_buffer release exactly
+ * the allocation that was made, from the pointer alone, and refuse
+ * pointers the engine does not own.
+ *
+ * Engine buffers are AlignedBuffers rather than Vec<u8>, whose 1-byte
+ * alignment would not let JS view f32 results or alloc buffers as a
+ * Float32Array in place.
+ *
+ * A fast stroke calls the smoothing and tessellation exports every frame,
+ * and each call used to allocate its result and leave the free to JS;
+ * the churn fragments wasm memory. Buffers have size-bucketed
+ * capacities, so a result JS is done with can go back to the pool with
+ * recycle_buffer and serve a later result of the same bucket without
+ * touching the allocator. Scratch space an export uses while it runs
+ * is still ordinary heap memory; only the buffers that cross to JS are
+ * pooled.
+ *
+ * Ownership records and the pool are per thread: a buffer goes back
+ * (free_buffer or recycle_buffer) on the thread that made it.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Memory Ownership Rules)
+ */
+
+use std::alloc::{self as allocator, Layout};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{record_error, CreoVexError, EngineError, EngineResult};
+use crate::tracking::{track_release, tracking_enabled};
+
+/**
+ * Smallest bucket; smaller buffers are rounded up to it
+ */
+const MIN_BUCKET_BYTES: usize = 64;
+
+/**
+ * Largest bucketed (and poolable) buffer; larger ones are sized exactly
+ */
+const MAX_BUCKET_BYTES: usize = 1 << 20;
+
+/**
+ * Bytes the pool may hold unless set_pool_capacity says otherwise
+ */
+const DEFAULT_POOL_CAPACITY: usize = 4 << 20;
+
+/**
+ * Alignment of alloc buffers and EngineBuffer results (f32 and u32
+ * typed-array views); also the least alloc_aligned gives
+ */
+pub(crate) const DEFAULT_ALIGN: usize = 4;
+
+/**
+ * Largest alignment alloc_aligned accepts (one wasm page)
+ */
+const MAX_ALIGN: usize = 64 << 10;
+
+/**
+ * Growable byte buffer over an allocation of a chosen alignment
+ *
+ * The Vec<u8> subset the serializers need (extend_from_slice and slice
+ * access). It always owns an allocation, of at least one byte, and
+ * grows by reallocating with the same alignment.
+ */
+pub(crate) struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+impl AlignedBuffer {
+    /**
+     * Empty buffer of `capacity` bytes aligned to `align` (a power of
+     * two); None when the allocation fails
+     */
+    fn try_with_capacity(capacity: usize, align: usize) -> Option<Self> {
+        let layout = Layout::from_size_align(capacity.max(1), align).ok()?;
+        let ptr = NonNull::new(unsafe { allocator::alloc(layout) })?;
+        Some(Self { ptr, len: 0, layout })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.layout.size()
+    }
+
+    pub(crate) fn extend_from_slice(&mut self, bytes: &[u8]) {
+        let len = self.len.checked_add(bytes.len()).expect("buffer length overflow");
+        if len > self.capacity() {
+            self.grow(len.max(self.capacity().saturating_mul(2)));
+        }
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.as_ptr().add(self.len), bytes.len()) };
+        self.len = len;
+    }
+
+    /**
+     * Reallocate to `capacity` bytes, keeping the alignment (aborts when
+     * out of memory, as Vec does)
+     */
+    fn grow(&mut self, capacity: usize) {
+        let layout = Layout::from_size_align(capacity, self.layout.align()).expect("buffer length overflow");
+        let ptr = unsafe { allocator::realloc(self.ptr.as_ptr(), self.layout, capacity) };
+        self.ptr = NonNull::new(ptr).unwrap_or_else(|| allocator::handle_alloc_error(layout));
+        self.layout = layout;
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { allocator::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+/**
+ * Capacity to allocate for an engine buffer of `size` bytes
+ *
+ * Sizes up to MAX_BUCKET_BYTES round up to a multiple of an eighth of the
+ * next power of two: four buckets per doubling, e.g. 80, 96, 112, and 128
+ * bytes for 65-128, so at most 25% slack. Anything up to MIN_BUCKET_BYTES
+ * gets that size (zero-size buffers too, so each is a real, distinct
+ * allocation); larger sizes are exact.
+ */
+pub(crate) fn bucket_capacity(size: usize) -> usize {
+    if size > MAX_BUCKET_BYTES {
+        return size;
+    }
+    if size <= MIN_BUCKET_BYTES {
+        return MIN_BUCKET_BYTES;
+    }
+    let step = size.next_power_of_two() / 8;
+    size.div_ceil(step) * step
+}
+
+/**
+ * Counters reported by pool_stats()
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PoolStats {
+    /** Buffers waiting in the pool */
+    pub pooled_buffers: usize,
+    /** Capacity of the pooled buffers, in bytes */
+    pub pooled_bytes: usize,
+    /** Most bytes the pool will hold (set_pool_capacity) */
+    pub capacity_bytes: usize,
+    /** Buffers served from the pool */
+    pub hits: usize,
+    /** Bucketed buffers that had to be allocated */
+    pub misses: usize,
+    /** Buffers recycle_buffer kept for reuse */
+    pub recycled: usize,
+    /** Buffers recycle_buffer freed because the pool was full or they are too large */
+    pub released: usize,
+}
+
+struct Pool {
+    /**
+     * Empty DEFAULT_ALIGN buffers by capacity (emptied buckets stay until
+     * a trim, so reuse never allocates)
+     */
+    buckets: BTreeMap<usize, Vec<AlignedBuffer>>,
+    stats: PoolStats,
+}
+
+impl Pool {
+    /**
+     * Drop pooled buffers, largest first, until at most `limit` bytes stay
+     */
+    fn trim_to(&mut self, limit: usize) -> usize {
+        let before = self.stats.pooled_bytes;
+        for (&capacity, buffers) in self.buckets.iter_mut().rev() {
+            while self.stats.pooled_bytes > limit && buffers.pop().is_some() {
+                self.stats.pooled_buffers -= 1;
+                self.stats.pooled_bytes -= capacity;
+            }
+        }
+        self.buckets.retain(|_, buffers| !buffers.is_empty());
+        before - self.stats.pooled_bytes
+    }
+}
+
+thread_local! {
+    /** Layout of every buffer JS currently owns, by address */
+    static OWNED: RefCell<BTreeMap<usize, Layout>> = const { RefCell::new(BTreeMap::new()) };
+    static POOL: RefCell<Pool> = RefCell::new(Pool {
+        buckets: BTreeMap::new(),
+        stats: PoolStats { capacity_bytes: DEFAULT_POOL_CAPACITY, ..PoolStats::default() },
+    });
+}
+
+/**
+ * Empty DEFAULT_ALIGN buffer with capacity bucket_capacity(`size`), from
+ * the pool when one is waiting
+ */
+pub(crate) fn take_buffer(size: usize) -> EngineResult<AlignedBuffer> {
+    let capacity = bucket_capacity(size);
+    if capacity <= MAX_BUCKET_BYTES {
+        let pooled = POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            let buffer = pool.buckets.get_mut(&capacity).and_then(Vec::pop);
+            if buffer.is_some() {
+                pool.stats.hits += 1;
+                pool.stats.pooled_buffers -= 1;
+                pool.stats.pooled_bytes -= capacity;
+            } else {
+                pool.stats.misses += 1;
+            }
+            buffer
+        });
+        if let Some(buffer) = pooled {
+            return Ok(buffer);
+        }
+    }
+
+    allocate(size, capacity, DEFAULT_ALIGN)
+}
+
+/**
+ * Empty buffer with capacity bucket_capacity(`size`) aligned to `align`
+ *
+ * Alignments up to DEFAULT_ALIGN come from take_buffer (and the pool);
+ * larger ones are always freshly allocated.
+ *
+ * Errors: InvalidParameter unless `align` is a power of two of at most
+ * MAX_ALIGN; AllocationFailed.
+ */
+pub(crate) fn take_aligned_buffer(size: usize, align: usize) -> EngineResult<AlignedBuffer> {
+    if !align.is_power_of_two() || align > MAX_ALIGN {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("align must be a power of two between 1 and {MAX_ALIGN}, got {align}"),
+        ));
+    }
+    if align <= DEFAULT_ALIGN {
+        return take_buffer(size);
+    }
+    allocate(size, bucket_capacity(size), align)
+}
+
+fn allocate(size: usize, capacity: usize, align: usize) -> EngineResult<AlignedBuffer> {
+    AlignedBuffer::try_with_capacity(capacity, align).ok_or_else(|| {
+        EngineError::new(CreoVexError::AllocationFailed, format!("could not allocate {size} byte buffer"))
+    })
+}
+
+/**
+ * Pass ownership of a buffer's allocation to JS, recording its layout
+ */
+pub(crate) fn hand_out(buffer: AlignedBuffer) -> *mut u8 {
+    let buffer = std::mem::ManuallyDrop::new(buffer);
+    let ptr = buffer.ptr.as_ptr();
+    OWNED.with(|owned| owned.borrow_mut().insert(ptr as usize, buffer.layout));
+    ptr
+}
+
+/**
+ * Whether `ptr` is a buffer JS currently owns
+ */
+pub(crate) fn is_owned(ptr: *mut u8) -> bool {
+    OWNED.with(|owned| owned.borrow().contains_key(&(ptr as usize)))
+}
+
+/**
+ * Take back a buffer from JS: the original allocation, emptied
+ *
+ * A null pointer gives None. So does a pointer the engine does not own
+ * (freed already, or never an engine buffer), which records InvalidFree
+ * and, in debug builds without allocation tracking, fails an assertion:
+ * freeing it would corrupt the allocator.
+ */
+pub(crate) fn reclaim(ptr: *mut u8) -> Option<AlignedBuffer> {
+    let address = ptr as usize;
+    let ptr = NonNull::new(ptr)?;
+    let Some(layout) = OWNED.with(|owned| owned.borrow_mut().remove(&address)) else {
+        debug_assert!(tracking_enabled(), "{address:#x} is not a live engine buffer (double free?)");
+        record_error(EngineError::new(
+            CreoVexError::InvalidFree,
+            format!("{address:#x} is not a live engine buffer (already freed, or not from the engine)"),
+        ));
+        return None;
+    };
+    // Length 0: the bytes were written by JS or a serializer; only the layout matters from here
+    Some(AlignedBuffer { ptr, len: 0, layout })
+}
+
+/**
+ * Return an engine buffer to the pool instead of freeing it
+ *
+ * Arguments and checks as free_buffer, and the buffer must not be used
+ * afterwards. The buffer is kept for a later alloc or result of the same
+ * size bucket, unless that would take the pool past its capacity or the
+ * buffer is larger than the pool keeps (over 1 MiB) or was allocated
+ * with more than the default 4-byte alignment; then it is freed. A null
+ * pointer is a no-op.
+ *
+ * Call it on the thread that allocated the buffer: the pool, like the
+ * ownership records, is thread-local, so another thread treats the
+ * buffer as one the engine does not own and leaves it allocated.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn recycle_buffer(ptr: *mut u8, size: usize) {
+    if tracking_enabled() && !track_release(ptr, size) {
+        return;
+    }
+    let Some(buffer) = reclaim(ptr) else {
+        return;
+    };
+    let capacity = buffer.capacity();
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if capacity > MAX_BUCKET_BYTES
+            || capacity != bucket_capacity(capacity)
+            || buffer.layout.align() != DEFAULT_ALIGN
+            || pool.stats.pooled_bytes + capacity > pool.stats.capacity_bytes
+        {
+            pool.stats.released += 1;
+            return;
+        }
+        pool.buckets.entry(capacity).or_default().push(buffer);
+        pool.stats.recycled += 1;
+        pool.stats.pooled_buffers += 1;
+        pool.stats.pooled_bytes += capacity;
+    });
+}
+
+/**
+ * Free every pooled buffer; returns the bytes released
+ *
+ * The memory goes back to the allocator for reuse (wasm memory itself
+ * never shrinks).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn trim_pool() -> usize {
+    POOL.with(|pool| pool.borrow_mut().trim_to(0))
+}
+
+/**
+ * Set the most bytes the pool may hold (default 4 MiB; 0 disables
+ * pooling); pooled buffers beyond it are freed, largest first
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn set_pool_capacity(bytes: usize) {
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        pool.stats.capacity_bytes = bytes;
+        pool.trim_to(bytes);
+    });
+}
+
+/**
+ * Current pool contents and counters
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn pool_stats() -> PoolStats {
+    POOL.with(|pool| pool.borrow().stats)
+}
+
+// These go through the allocator directly; check them under Miri with
+//   cargo +nightly miri test --lib pool::tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smooth::smooth_stroke_v2;
+    use crate::tessellate::tessellate_stroke;
+    use crate::tracking::set_allocation_tracking;
+    use crate::{alloc, alloc_aligned, free_buffer, last_error_code, serialize_points};
+
+    const STROKE: [f32; 9] = [0.0, 0.0, 1.0, 10.0, 0.0, 1.0, 20.0, 5.0, 0.5];
+
+    /**
+     * Fill all `size` bytes at `ptr` and read back the ends (Miri checks
+     * the whole range is in bounds)
+     */
+    fn write_and_check(ptr: *mut u8, size: usize, value: u8) {
+        assert!(!ptr.is_null());
+        assert!((ptr as usize).is_multiple_of(DEFAULT_ALIGN));
+        unsafe {
+            std::ptr::write_bytes(ptr, value, size);
+            if size > 0 {
+                assert_eq!((*ptr, *ptr.add(size - 1)), (value, value));
+            }
+        }
+    }
+
+    /**
+     * Every byte of a result, read as JS would before freeing it
+     */
+    fn read_result(buffer: &crate::EngineBuffer) -> u32 {
+        assert_eq!(buffer.error_code(), 0);
+        let bytes = unsafe { std::slice::from_raw_parts(buffer.ptr(), buffer.byte_len()) };
+        bytes.iter().map(|&byte| byte as u32).sum()
+    }
+
+    #[test]
+    fn written_buffers_free_with_any_size() {
+        for (i, size) in [0, 1, 3, 64, 65, 300, 4096].into_iter().enumerate() {
+            // The size passed back is only checked under tracking: right, 0, or wrong
+            for passed in [size, 0, size + 12345] {
+                let ptr = alloc(size);
+                write_and_check(ptr, size, i as u8);
+                free_buffer(ptr, passed);
+            }
+        }
+
+        let ptr = alloc_aligned(100, 64);
+        assert!((ptr as usize).is_multiple_of(64));
+        write_and_check(ptr, 100, 9);
+        free_buffer(ptr, 100);
+
+        // A recycled buffer serves the next alloc of its bucket, written in full again
+        let ptr = alloc(300);
+        write_and_check(ptr, 300, 1);
+        recycle_buffer(ptr, 1);
+        let reused = alloc(290);
+        assert_eq!(reused, ptr);
+        write_and_check(reused, 290, 2);
+        free_buffer(reused, 290);
+        assert_eq!(trim_pool(), 0);
+    }
+
+    #[test]
+    fn results_free_from_the_pointer_alone() {
+        for passed in [None, Some(0), Some(12345)] {
+            let results = [
+                smooth_stroke_v2(STROKE.as_ptr(), STROKE.len()),
+                tessellate_stroke(STROKE.as_ptr(), STROKE.len(), 2.0),
+                serialize_points(&STROKE).unwrap(),
+            ];
+            for result in results {
+                read_result(&result);
+                free_buffer(result.ptr(), passed.unwrap_or(result.byte_len()));
+            }
+        }
+
+        let result = tessellate_stroke(STROKE.as_ptr(), STROKE.len(), 2.0);
+        read_result(&result);
+        recycle_buffer(result.ptr(), result.byte_len());
+        let reused = tessellate_stroke(STROKE.as_ptr(), STROKE.len(), 2.0);
+        assert_eq!(reused.ptr(), result.ptr());
+        read_result(&reused);
+        free_buffer(reused.ptr(), reused.byte_len());
+    }
+
+    #[test]
+    fn foreign_pointers_are_left_alone_under_tracking() {
+        set_allocation_tracking(true);
+        let ptr = alloc(10);
+        free_buffer(ptr, 10);
+        free_buffer(ptr, 10);
+        assert_eq!(last_error_code(), CreoVexError::InvalidFree as u32);
+        let mut local = 5u8;
+        recycle_buffer(&mut local, 1);
+        assert_eq!(last_error_code(), CreoVexError::InvalidFree as u32);
+        assert_eq!(local, 5);
+        set_allocation_tracking(false);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not a live engine buffer")]
+    fn double_frees_assert_in_debug_builds() {
+        let ptr = alloc(10);
+        free_buffer(ptr, 10);
+        free_buffer(ptr, 10);
+    }
+
+    #[test]
+    fn addresses_meet_their_alignment() {
+        for size in [0, 1, 3, 5, 63, 65, 100, 1000, 5000, MAX_BUCKET_BYTES + 1] {
+            let ptr = alloc(size);
+            write_and_check(ptr, size, 1);
+            free_buffer(ptr, size);
+            for align in [1, 2, 4, 8, 16, 64, 4096, 65536] {
+                let ptr = alloc_aligned(size, align);
+                assert!((ptr as usize).is_multiple_of(align.max(DEFAULT_ALIGN)), "{size} bytes aligned to {align}");
+                write_and_check(ptr, size, 2);
+                if align <= DEFAULT_ALIGN {
+                    recycle_buffer(ptr, size);
+                } else {
+                    free_buffer(ptr, size);
+                }
+            }
+        }
+        for align in [0, 3, 48, 1 << 17] {
+            assert!(alloc_aligned(10, align).is_null());
+            assert_eq!(last_error_code(), CreoVexError::InvalidParameter as u32, "align {align}");
+        }
+
+        // Results (pooled or not) are f32/u32 views from their first byte
+        for _ in 0..3 {
+            let results = [
+                smooth_stroke_v2(STROKE.as_ptr(), STROKE.len()),
+                tessellate_stroke(STROKE.as_ptr(), STROKE.len(), 2.0),
+                serialize_points(&STROKE).unwrap(),
+            ];
+            for result in results {
+                assert!((result.ptr() as usize).is_multiple_of(DEFAULT_ALIGN));
+                let words = unsafe { std::slice::from_raw_parts(result.ptr() as *const u32, result.byte_len() / 4) };
+                assert_eq!(words.len() * 4, result.byte_len());
+                recycle_buffer(result.ptr(), result.byte_len());
+            }
+        }
+        trim_pool();
+    }
+}
@@ -0,0 +1,234 @@
+/*!
+ * Predicted stroke tails
+ *
+ * Between a stylus sample and its frame on screen the rendered stroke
+ * trails the pen by 20-30 ms. predict_points extrapolates the recent
+ * trajectory past the last sample so the renderer can draw a short
+ * preview ahead of the stroke. The preview is recomputed on every
+ * pointermove and never becomes part of the stroke: the next real
+ * samples replace it.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Input Stabilization)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_finite, require_non_negative, run_export, CreoVexError, EngineError};
+use crate::smooth::TIMED_STRIDE;
+use crate::{read_strided_points, serialize_records, EngineBuffer};
+
+/**
+ * Most recent samples the trajectory fit uses
+ */
+pub(crate) const PREDICT_FIT_POINTS: usize = 6;
+
+/**
+ * Samples older than this (ms before the last one) are left out of the
+ * fit, as long as two remain: they belong to an earlier motion
+ */
+pub(crate) const PREDICT_FIT_WINDOW_MS: f64 = 50.0;
+
+/**
+ * Longest horizon in ms; further out a fit to 50 ms of input is a guess
+ */
+pub(crate) const MAX_PREDICT_HORIZON_MS: f32 = 100.0;
+
+/**
+ * Most predicted points per call
+ */
+pub(crate) const MAX_PREDICTED_POINTS: u32 = 32;
+
+/**
+ * Heading change across the fit window (degrees) at which prediction
+ * stops: the confidence reaches 0 and every predicted point sits on the
+ * last sample
+ */
+pub(crate) const PREDICT_MAX_TURN_DEGREES: f64 = 60.0;
+
+/**
+ * Predict where the stroke goes next, for a preview ahead of the pen
+ *
+ * Input format: [x0, y0, p0, t0, x1, y1, p1, t1, ...] (t in milliseconds,
+ * as smooth_stroke_timed reads it); only the last PREDICT_FIT_POINTS
+ * samples are used, so passing the whole stroke is fine.
+ *
+ * Output format:
+ * - 4 bytes: point count (u32)
+ * - N * 16 bytes: f32 quads [x, y, pressure, confidence]
+ *
+ * Position follows a least-squares quadratic in time over the recent
+ * samples (linear with fewer than three), shifted to start at the last
+ * sample, at `count` evenly spaced times up to `horizon_ms` (clamped to
+ * MAX_PREDICT_HORIZON_MS; `count` to MAX_PREDICTED_POINTS) after it.
+ * Pressure continues from the last sample along a linear fit's slope,
+ * clamped to [0, 1].
+ *
+ * Conservative on turns: the heading change across the fit window scales
+ * both the horizon and the confidence down, to 0 at
+ * PREDICT_MAX_TURN_DEGREES, and a decelerating fit stops where its speed
+ * would reach zero instead of turning back. Confidence (0-1) also falls
+ * to half along the horizon, for the renderer to fade the preview.
+ *
+ * The result is empty for a zero horizon or count, and when the samples
+ * give no velocity (a single sample, or no elapsed time). Timestamps are
+ * repaired as in smooth_stroke_timed.
+ *
+ * Errors: the read_points errors (length a multiple of 4),
+ * NonFiniteValue/InvalidParameter for a NaN, infinite, or negative
+ * horizon, and NonFiniteValue for a NaN or infinite timestamp.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn predict_points(points_ptr: *const f32, points_len: usize, horizon_ms: f32, count: u32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_strided_points(points_ptr, points_len, TIMED_STRIDE)?;
+        let horizon_ms = require_non_negative("horizon_ms", require_finite("horizon_ms", horizon_ms)?)?;
+        let horizon_ms = horizon_ms.min(MAX_PREDICT_HORIZON_MS) as f64;
+        let count = count.min(MAX_PREDICTED_POINTS) as usize;
+
+        let total = points.len() / TIMED_STRIDE;
+        let first = total.saturating_sub(PREDICT_FIT_POINTS);
+        let mut samples: Vec<[f64; 4]> = Vec::with_capacity(total - first);
+        let mut latest = f64::NEG_INFINITY;
+        for (i, point) in points.chunks_exact(TIMED_STRIDE).enumerate().skip(first) {
+            if !point[3].is_finite() {
+                return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("timestamp {i} is not finite")));
+            }
+            latest = latest.max(point[3] as f64);
+            samples.push([point[0] as f64, point[1] as f64, point[2] as f64, latest]);
+        }
+
+        let mut predicted = Vec::with_capacity(count * 4);
+        let fit = if horizon_ms > 0.0 && count > 0 { Trajectory::fit(&samples) } else { None };
+        if let Some(fit) = fit {
+            let turn = (fit.turn_degrees() / PREDICT_MAX_TURN_DEGREES).min(1.0);
+            let reach = fit.stop_ms().map_or(horizon_ms, |stop| stop.min(horizon_ms)) * (1.0 - turn);
+            for k in 1..=count {
+                let along = k as f64 / count as f64;
+                let [x, y, pressure] = fit.at(reach * along);
+                let confidence = (1.0 - turn) * (1.0 - 0.5 * along);
+                predicted.extend_from_slice(&[x as f32, y as f32, pressure as f32, confidence as f32]);
+            }
+        }
+
+        serialize_records((predicted.len() / 4) as u32, &predicted)
+    })
+}
+
+/**
+ * Fitted motion over the recent samples, as polynomials in u: time
+ * since the last sample divided by the window span
+ */
+struct Trajectory {
+    span_ms: f64,
+    /** Position coefficients [constant, linear, quadratic] per axis */
+    x: [f64; 3],
+    y: [f64; 3],
+    /** Pressure coefficients [constant, linear] */
+    pressure: [f64; 2],
+}
+
+impl Trajectory {
+    /**
+     * Least-squares fit to monotonic [x, y, pressure, t] samples; None
+     * without two distinct timestamps (after dropping ones older than
+     * PREDICT_FIT_WINDOW_MS)
+     */
+    fn fit(samples: &[[f64; 4]]) -> Option<Self> {
+        let last = *samples.last()?;
+        let recent = samples.iter().position(|s| last[3] - s[3] <= PREDICT_FIT_WINDOW_MS)?;
+        let samples = &samples[recent.min(samples.len().saturating_sub(2))..];
+        let span_ms = last[3] - samples[0][3];
+        if span_ms <= 0.0 {
+            return None;
+        }
+
+        // Power sums of u (in [-1, 0]) and moments of each channel
+        let mut powers = [0.0; 5];
+        let mut moments = [[0.0; 3]; 3];
+        for sample in samples {
+            let u = (sample[3] - last[3]) / span_ms;
+            let mut power = 1.0;
+            for (k, sum) in powers.iter_mut().enumerate() {
+                *sum += power;
+                if k < 3 {
+                    for (moment, value) in moments.iter_mut().zip(sample) {
+                        moment[k] += power * value;
+                    }
+                }
+                power *= u;
+            }
+        }
+
+        let [s0, s1, s2, s3, s4] = powers;
+        let linear = |m: [f64; 3]| {
+            let slope = (s0 * m[1] - s1 * m[0]) / (s0 * s2 - s1 * s1);
+            [(m[0] - slope * s1) / s0, slope, 0.0]
+        };
+        // Cramer's rule on the normal equations; near-singular (three
+        // samples at two timestamps, say) falls back to a line
+        let det = s0 * (s2 * s4 - s3 * s3) - s1 * (s1 * s4 - s3 * s2) + s2 * (s1 * s3 - s2 * s2);
+        let quadratic = |m: [f64; 3]| {
+            if samples.len() < 3 || det.abs() <= 1e-9 * s0 * s0 * s0 {
+                return linear(m);
+            }
+            let c0 = m[0] * (s2 * s4 - s3 * s3) - s1 * (m[1] * s4 - s3 * m[2]) + s2 * (m[1] * s3 - s2 * m[2]);
+            let c1 = s0 * (m[1] * s4 - s3 * m[2]) - m[0] * (s1 * s4 - s3 * s2) + s2 * (s1 * m[2] - m[1] * s2);
+            let c2 = s0 * (s2 * m[2] - m[1] * s3) - s1 * (s1 * m[2] - m[1] * s2) + m[0] * (s1 * s3 - s2 * s2);
+            [c0 / det, c1 / det, c2 / det]
+        };
+
+        // Anchored at the last sample, so the preview starts where the stroke ends
+        let [_, x1, x2] = quadratic(moments[0]);
+        let [_, y1, y2] = quadratic(moments[1]);
+        let [_, pressure, _] = linear(moments[2]);
+        Some(Trajectory {
+            span_ms,
+            x: [last[0], x1, x2],
+            y: [last[1], y1, y2],
+            pressure: [last[2], pressure],
+        })
+    }
+
+    /**
+     * Velocity (per unit u) at `u`
+     */
+    fn velocity(&self, u: f64) -> [f64; 2] {
+        [self.x[1] + 2.0 * self.x[2] * u, self.y[1] + 2.0 * self.y[2] * u]
+    }
+
+    /**
+     * Heading change between the start and the end of the window, in
+     * degrees (0 when either end is at rest)
+     */
+    fn turn_degrees(&self) -> f64 {
+        let [ax, ay] = self.velocity(-1.0);
+        let [bx, by] = self.velocity(0.0);
+        if (ax == 0.0 && ay == 0.0) || (bx == 0.0 && by == 0.0) {
+            return 0.0;
+        }
+        (ax * by - ay * bx).abs().atan2(ax * bx + ay * by).to_degrees()
+    }
+
+    /**
+     * Time (ms after the last sample) at which a decelerating fit stops
+     * moving along its current heading, if it does
+     */
+    fn stop_ms(&self) -> Option<f64> {
+        let [vx, vy] = self.velocity(0.0);
+        let braking = vx * self.x[2] + vy * self.y[2];
+        (braking < 0.0).then(|| -(vx * vx + vy * vy) / (2.0 * braking) * self.span_ms)
+    }
+
+    /**
+     * [x, y, pressure] `ms` after the last sample
+     */
+    fn at(&self, ms: f64) -> [f64; 3] {
+        let u = ms / self.span_ms;
+        let x = self.x[0] + (self.x[1] + self.x[2] * u) * u;
+        let y = self.y[0] + (self.y[1] + self.y[2] * u) * u;
+        let pressure = (self.pressure[0] + self.pressure[1] * u).clamp(0.0, 1.0);
+        [x, y, pressure]
+    }
+}
@@ -0,0 +1,655 @@
+/*!
+ * Pressure response curves
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Pressure Curve, exponent 0.7 default)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::{cumulative_lengths, segment_length};
+use crate::error::{
+    require_finite, require_non_negative, require_positive, run_export, CreoVexError, EngineError, EngineResult,
+};
+use crate::smooth::TIMED_STRIDE;
+use crate::{read_points, read_strided_points, serialize_points, serialize_records, EngineBuffer};
+
+/**
+ * Maximum number of samples accepted for a lookup-table curve
+ */
+pub(crate) const MAX_LUT_SAMPLES: usize = 256;
+
+/**
+ * Pressure curve families selectable per brush
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PressureCurve {
+    /** p^gamma (gamma < 1 lifts light pressure, > 1 steepens it) */
+    Gamma = 0,
+    /** Cubic ease-in: p³ */
+    EaseIn = 1,
+    /** Cubic ease-out: 1 - (1 - p)³ */
+    EaseOut = 2,
+    /** Cubic ease-in-out (smooth S-curve) */
+    EaseInOut = 3,
+    /** Caller-provided samples, linearly interpolated over [0, 1] */
+    Lut = 4,
+}
+
+/**
+ * Shape of a start/end taper ramp
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaperProfile {
+    /** Width grows in proportion to distance */
+    Linear = 0,
+    /** Smoothstep 3t² - 2t³: flat at the tip and where the taper meets the body */
+    Smoothstep = 1,
+}
+
+impl TaperProfile {
+    fn evaluate(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            TaperProfile::Linear => t,
+            TaperProfile::Smoothstep => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/**
+ * How normalize_pressure stretches a stroke's pressure range
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PressureNormalization {
+    /** Lowest pressure to 0, highest to 1 */
+    MinMax = 0,
+    /** 5th percentile to 0, 95th to 1 (a few spikes do not set the range) */
+    Percentile = 1,
+    /** No range: map through a caller-provided lookup table */
+    Lut = 2,
+}
+
+/**
+ * Percentiles normalize_pressure's Percentile mode maps to 0 and 1
+ */
+pub(crate) const NORMALIZE_PERCENTILES: (f64, f64) = (0.05, 0.95);
+
+/**
+ * Filter shapes for smooth_pressure
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PressureSmoothing {
+    /** Every point in the window counts equally */
+    MovingAverage = 0,
+    /** Gaussian weights with the window spanning ±3σ */
+    Gaussian = 1,
+}
+
+/**
+ * Resolved pressure curve, ready to evaluate
+ *
+ * Built once per call from the FFI descriptor so the per-point loop only
+ * does arithmetic.
+ */
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum CurveDescriptor<'a> {
+    Gamma(f32),
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Lut(&'a [f32]),
+}
+
+impl<'a> CurveDescriptor<'a> {
+    /**
+     * Resolve an FFI curve descriptor
+     *
+     * `gamma` is only read for `PressureCurve::Gamma` and must be positive.
+     * The LUT is only read for `PressureCurve::Lut` and must hold 1-256
+     * finite samples.
+     */
+    pub(crate) fn from_ffi(
+        curve: PressureCurve,
+        gamma: f32,
+        lut_ptr: *const f32,
+        lut_len: usize,
+    ) -> EngineResult<Self> {
+        match curve {
+            PressureCurve::Gamma => {
+                let gamma = require_positive("gamma", require_finite("gamma", gamma)?)?;
+                Ok(CurveDescriptor::Gamma(gamma))
+            }
+            PressureCurve::EaseIn => Ok(CurveDescriptor::EaseIn),
+            PressureCurve::EaseOut => Ok(CurveDescriptor::EaseOut),
+            PressureCurve::EaseInOut => Ok(CurveDescriptor::EaseInOut),
+            PressureCurve::Lut => {
+                if lut_ptr.is_null() {
+                    return Err(EngineError::new(CreoVexError::NullInput, "lut_ptr is null"));
+                }
+                if lut_len == 0 || lut_len > MAX_LUT_SAMPLES {
+                    return Err(EngineError::new(
+                        CreoVexError::InvalidLength,
+                        format!("lut_len must be 1-{MAX_LUT_SAMPLES} (got {lut_len})"),
+                    ));
+                }
+                let lut: &'a [f32] = unsafe { std::slice::from_raw_parts(lut_ptr, lut_len) };
+                match lut.iter().position(|v| !v.is_finite()) {
+                    Some(index) => Err(EngineError::new(
+                        CreoVexError::NonFiniteValue,
+                        format!("lut sample {index} is not finite"),
+                    )),
+                    None => Ok(CurveDescriptor::Lut(lut)),
+                }
+            }
+        }
+    }
+
+    /**
+     * Map a pressure value through the curve
+     *
+     * Input is clamped to [0, 1] first, and so is the result.
+     */
+    pub(crate) fn evaluate(&self, pressure: f32) -> f32 {
+        let p = if pressure.is_nan() { 0.0 } else { pressure.clamp(0.0, 1.0) };
+
+        let mapped = match *self {
+            CurveDescriptor::Gamma(gamma) => p.powf(gamma),
+            CurveDescriptor::EaseIn => p * p * p,
+            CurveDescriptor::EaseOut => {
+                let inv = 1.0 - p;
+                1.0 - inv * inv * inv
+            }
+            CurveDescriptor::EaseInOut => {
+                if p < 0.5 {
+                    4.0 * p * p * p
+                } else {
+                    let inv = -2.0 * p + 2.0;
+                    1.0 - inv * inv * inv / 2.0
+                }
+            }
+            CurveDescriptor::Lut(lut) => sample_lut(lut, p),
+        };
+
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+/**
+ * Linear interpolation into evenly spaced samples over [0, 1]
+ */
+fn sample_lut(lut: &[f32], p: f32) -> f32 {
+    if lut.len() == 1 {
+        return lut[0];
+    }
+
+    let position = p * (lut.len() - 1) as f32;
+    let index = (position as usize).min(lut.len() - 2);
+    let t = position - index as f32;
+    lut[index] + (lut[index + 1] - lut[index]) * t
+}
+
+/**
+ * Apply a pressure response curve to a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, pressure remapped
+ *
+ * Only the pressure channel changes; x/y are copied bit-for-bit. Pressure
+ * is clamped to [0, 1] before and after the curve.
+ *
+ * `gamma` is used by `PressureCurve::Gamma` (0.7 is the house default);
+ * `lut_ptr`/`lut_len` describe up to 256 samples for `PressureCurve::Lut`
+ * and may be null/0 for every other curve.
+ *
+ * Errors: the read_points errors, plus NullInput/InvalidLength/
+ * NonFiniteValue/InvalidParameter for an unusable curve descriptor.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn apply_pressure(
+    points_ptr: *const f32,
+    points_len: usize,
+    curve: PressureCurve,
+    gamma: f32,
+    lut_ptr: *const f32,
+    lut_len: usize,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let descriptor = CurveDescriptor::from_ffi(curve, gamma, lut_ptr, lut_len)?;
+
+        let mut remapped = points.to_vec();
+        for point in remapped.chunks_exact_mut(3) {
+            point[2] = descriptor.evaluate(point[2]);
+        }
+
+        serialize_points(&remapped)
+    })
+}
+
+/**
+ * Taper the start and end of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, pressure multiplied by the taper
+ *
+ * Pressure is multiplied by a ramp from 0 at the first point to 1 at arc
+ * length `taper_in_length`, and by the mirrored ramp over the last
+ * `taper_out_length`; `profile` shapes both ramps. When the two lengths
+ * add up to more than the stroke, both are shortened in proportion so
+ * they meet at full pressure instead of overlapping. A zero length
+ * disables that end's taper, and a stroke that never moves (no length)
+ * is returned unchanged.
+ *
+ * Only the pressure channel changes and it is not clamped, so the result
+ * can go straight into apply_pressure or tessellate_stroke.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a NaN, infinite, or negative taper length.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn apply_taper(
+    points_ptr: *const f32,
+    points_len: usize,
+    taper_in_length: f32,
+    taper_out_length: f32,
+    profile: TaperProfile,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let taper_in = require_finite("taper_in_length", taper_in_length)?;
+        let taper_in = require_non_negative("taper_in_length", taper_in)?;
+        let taper_out = require_finite("taper_out_length", taper_out_length)?;
+        let taper_out = require_non_negative("taper_out_length", taper_out)?;
+
+        let mut tapered = points.to_vec();
+        taper_pressure(&mut tapered, 3, taper_in as f64, taper_out as f64, profile);
+        serialize_points(&tapered)
+    })
+}
+
+/**
+ * Multiply the pressure channel of `points` by the start/end tapers (see
+ * apply_taper)
+ */
+pub(crate) fn taper_pressure(points: &mut [f32], stride: usize, taper_in: f64, taper_out: f64, profile: TaperProfile) {
+    let table = cumulative_lengths(points, stride);
+    let total = table[table.len() - 1];
+    if total == 0.0 {
+        return;
+    }
+
+    let overlap = (total / (taper_in + taper_out)).min(1.0);
+    let (taper_in, taper_out) = (taper_in * overlap, taper_out * overlap);
+    for (point, &walked) in points.chunks_exact_mut(stride).zip(&table) {
+        let mut factor = 1.0;
+        if taper_in > 0.0 {
+            factor *= profile.evaluate(walked / taper_in);
+        }
+        if taper_out > 0.0 {
+            factor *= profile.evaluate((total - walked) / taper_out);
+        }
+        point[2] = (point[2] as f64 * factor) as f32;
+    }
+}
+
+/**
+ * Smooth the pressure channel of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, pressure smoothed
+ *
+ * Each pressure becomes a weighted mean of the pressures around it;
+ * x/y are copied bit-for-bit, so the result can go straight into
+ * smooth_stroke_v2. `window` is the full width of the neighborhood,
+ * centered on the point: a count of points, or with
+ * `arc_length_window` a distance in canvas units along the stroke.
+ * `mode` chooses equal weights or a Gaussian whose ±3σ spans the window.
+ *
+ * Near the ends the window shrinks so it stays centered, ending at the
+ * first or last point, instead of being padded or cut off on one side
+ * (a Gaussian keeps its σ and only loses its tails there);
+ * the endpoints keep their own pressure and a tapered end is not lifted
+ * by the body of the stroke. A window of 0 (or under one point) changes
+ * nothing.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a NaN, infinite, or negative window.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_pressure(
+    points_ptr: *const f32,
+    points_len: usize,
+    window: f32,
+    mode: PressureSmoothing,
+    arc_length_window: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let window = require_non_negative("window", require_finite("window", window)?)?;
+
+        // Position of each point in window units
+        let positions: Vec<f64> = if arc_length_window {
+            cumulative_lengths(points, 3)
+        } else {
+            (0..points.len() / 3).map(|i| i as f64).collect()
+        };
+        let pressures: Vec<f64> = points.chunks_exact(3).map(|point| point[2] as f64).collect();
+
+        let mut smoothed = points.to_vec();
+        let means = window_means(&positions, &pressures, window as f64, mode);
+        for (point, pressure) in smoothed.chunks_exact_mut(3).zip(means) {
+            point[2] = pressure as f32;
+        }
+        serialize_points(&smoothed)
+    })
+}
+
+/**
+ * Centered, end-shrunk window mean of `values` at each of the ascending
+ * `positions` (see smooth_pressure)
+ */
+fn window_means(positions: &[f64], values: &[f64], window: f64, mode: PressureSmoothing) -> Vec<f64> {
+    let (first, last) = (positions[0], positions[positions.len() - 1]);
+    let mut prefix = Vec::with_capacity(values.len() + 1);
+    prefix.push(0.0);
+    for &value in values {
+        prefix.push(prefix[prefix.len() - 1] + value);
+    }
+
+    positions
+        .iter()
+        .enumerate()
+        .map(|(i, &center)| {
+            let radius = (window / 2.0).min(center - first).min(last - center);
+            if radius <= 0.0 {
+                return values[i];
+            }
+            let lo = positions[..i].partition_point(|&position| position < center - radius);
+            let hi = i + 1 + positions[i + 1..].partition_point(|&position| position <= center + radius);
+            match mode {
+                PressureSmoothing::MovingAverage => (prefix[hi] - prefix[lo]) / (hi - lo) as f64,
+                PressureSmoothing::Gaussian => {
+                    // σ stays that of the full window; only the reach shrinks
+                    let sigma = window / 6.0;
+                    let (mut sum, mut weights) = (0.0, 0.0);
+                    for (&position, &value) in positions[lo..hi].iter().zip(&values[lo..hi]) {
+                        let d = (position - center) / sigma;
+                        let weight = (-0.5 * d * d).exp();
+                        sum += weight * value;
+                        weights += weight;
+                    }
+                    sum / weights
+                }
+            }
+        })
+        .collect()
+}
+
+/**
+ * Stretch a stroke's pressure to the full [0, 1] range
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, pressure remapped
+ *
+ * For MinMax and Percentile, each pressure becomes
+ * `(p - low) / (high - low)` clamped to [0, 1]. The range is measured on
+ * this stroke (min/max, or the NORMALIZE_PERCENTILES percentiles with
+ * linear interpolation between samples), or with `use_given_range` it is
+ * `range_low`/`range_high` as passed, so every stroke of a document can
+ * share one range. A range of zero width (every pressure equal) maps
+ * every point to 1: a stroke is at its own full pressure rather than
+ * NaN.
+ *
+ * Lut maps each pressure through `lut_ptr`/`lut_len` exactly as
+ * apply_pressure's PressureCurve::Lut does; the range arguments are not
+ * read. The LUT arguments may be null/0 for the other modes.
+ *
+ * Only the pressure channel changes; x/y are copied bit-for-bit. NaN
+ * pressure is ignored when measuring and maps to 0.
+ *
+ * Errors: the read_points errors; NonFiniteValue/InvalidParameter for a
+ * given range that is not finite or has `range_high` below `range_low`;
+ * the apply_pressure LUT errors in Lut mode.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn normalize_pressure(
+    points_ptr: *const f32,
+    points_len: usize,
+    mode: PressureNormalization,
+    use_given_range: bool,
+    range_low: f32,
+    range_high: f32,
+    lut_ptr: *const f32,
+    lut_len: usize,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+
+        let mut remapped = points.to_vec();
+        if mode == PressureNormalization::Lut {
+            let descriptor = CurveDescriptor::from_ffi(PressureCurve::Lut, 0.0, lut_ptr, lut_len)?;
+            for point in remapped.chunks_exact_mut(3) {
+                point[2] = descriptor.evaluate(point[2]);
+            }
+            return serialize_points(&remapped);
+        }
+
+        let (low, high) = if use_given_range {
+            let low = require_finite("range_low", range_low)? as f64;
+            let high = require_finite("range_high", range_high)? as f64;
+            if high < low {
+                return Err(EngineError::new(
+                    CreoVexError::InvalidParameter,
+                    format!("range_high {high} is below range_low {low}"),
+                ));
+            }
+            (low, high)
+        } else {
+            pressure_range(points, mode)
+        };
+
+        for point in remapped.chunks_exact_mut(3) {
+            point[2] = if point[2].is_nan() {
+                0.0
+            } else if high > low {
+                ((point[2] as f64 - low) / (high - low)).clamp(0.0, 1.0) as f32
+            } else {
+                1.0
+            };
+        }
+        serialize_points(&remapped)
+    })
+}
+
+/**
+ * The (low, high) pressures of a stroke for a MinMax or Percentile
+ * normalization; (0, 0) when every pressure is NaN
+ */
+fn pressure_range(points: &[f32], mode: PressureNormalization) -> (f64, f64) {
+    let mut sorted: Vec<f64> = points.chunks_exact(3).map(|point| point[2] as f64).filter(|p| !p.is_nan()).collect();
+    if sorted.is_empty() {
+        return (0.0, 0.0);
+    }
+    sorted.sort_by(f64::total_cmp);
+
+    let at = |fraction: f64| {
+        let rank = fraction * (sorted.len() - 1) as f64;
+        let index = (rank.floor() as usize).min(sorted.len() - 1);
+        let next = (index + 1).min(sorted.len() - 1);
+        sorted[index] + (sorted[next] - sorted[index]) * (rank - index as f64)
+    };
+    match mode {
+        PressureNormalization::Percentile => (at(NORMALIZE_PERCENTILES.0), at(NORMALIZE_PERCENTILES.1)),
+        _ => (sorted[0], sorted[sorted.len() - 1]),
+    }
+}
+
+/**
+ * Give pressure-less input (mouse, touch) a pressure from its speed
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, ...], or with `timed`
+ * [x0, y0, p0, t0, x1, y1, p1, t1, ...] (t in milliseconds, as
+ * smooth_stroke_timed reads it)
+ *
+ * Output format: the input layout with the pressure channel replaced
+ * (see serialize_points, or 16-byte [x, y, pressure, t_ms] records when
+ * `timed`)
+ *
+ * Speed is distance over elapsed time with timestamps, or else distance
+ * per point (input events arrive at a steady rate, so spacing tracks
+ * speed). A median of three neighboring segment speeds removes one-off
+ * jumps, and each point takes the mean speed of its two segments. Speeds are
+ * measured against the stroke's median speed, so the result does not
+ * depend on zoom or device rate: at speed `v` and median `m`,
+ * `p = max_p - (max_p - min_p) * (v / m) / (1 + v / m)`, giving `max_p`
+ * when still, the middle of the range at the median speed, and nearing
+ * `min_p` for fast flicks.
+ *
+ * The pressures are then smoothed by an exponential moving average run
+ * forwards and backwards (no lag), where `responsiveness` is the weight
+ * of each new point: 1 follows speed changes exactly, smaller values
+ * react more slowly. Finally the first and last points are extrapolated
+ * linearly from their two neighbors (clamped to [min_p, max_p]), so
+ * stroke ends continue the taper instead of dropping to zero. A single
+ * point gets the middle of the range.
+ *
+ * Timestamps are repaired as in smooth_stroke_timed; a segment with no
+ * elapsed time takes the speed of the nearest segment with some, and a
+ * stroke with no elapsed time at all falls back to point spacing.
+ *
+ * Errors: the read_points errors (length a multiple of 4 when `timed`);
+ * NonFiniteValue/InvalidParameter for a NaN, infinite, or negative
+ * `min_p`/`max_p`, `min_p` above `max_p`, or a `responsiveness` outside
+ * (0, 1]; NonFiniteValue for a NaN or infinite timestamp.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn synthesize_pressure_from_velocity(
+    points_ptr: *const f32,
+    points_len: usize,
+    timed: bool,
+    min_p: f32,
+    max_p: f32,
+    responsiveness: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let stride = if timed { TIMED_STRIDE } else { 3 };
+        let points = &read_strided_points(points_ptr, points_len, stride)?;
+        let min_p = require_non_negative("min_p", require_finite("min_p", min_p)?)?;
+        let max_p = require_non_negative("max_p", require_finite("max_p", max_p)?)?;
+        if min_p > max_p {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("min_p {min_p} is above max_p {max_p}"),
+            ));
+        }
+        let responsiveness = require_positive("responsiveness", require_finite("responsiveness", responsiveness)?)?;
+        if responsiveness > 1.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("responsiveness must be in (0, 1] (got {responsiveness})"),
+            ));
+        }
+
+        let mut synthesized = points.to_vec();
+        if timed {
+            let mut latest = f32::NEG_INFINITY;
+            for (i, point) in synthesized.chunks_exact_mut(stride).enumerate() {
+                if !point[3].is_finite() {
+                    return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("timestamp {i} is not finite")));
+                }
+                latest = latest.max(point[3]);
+                point[3] = latest;
+            }
+        }
+
+        let pressures = speed_pressures(&synthesized, stride, min_p as f64, max_p as f64, responsiveness as f64);
+        for (point, pressure) in synthesized.chunks_exact_mut(stride).zip(pressures) {
+            point[2] = pressure as f32;
+        }
+        if timed {
+            serialize_records((synthesized.len() / stride) as u32, &synthesized)
+        } else {
+            serialize_points(&synthesized)
+        }
+    })
+}
+
+/**
+ * Per-point pressure from speed (see synthesize_pressure_from_velocity);
+ * timestamps, if `stride` is TIMED_STRIDE, must already be monotonic
+ */
+fn speed_pressures(points: &[f32], stride: usize, min_p: f64, max_p: f64, responsiveness: f64) -> Vec<f64> {
+    let count = points.len() / stride;
+    if count == 1 {
+        return vec![(min_p + max_p) / 2.0];
+    }
+
+    // Segment speeds; segments without elapsed time borrow the nearest timed one
+    let segments: Vec<(f64, f64)> = points
+        .chunks_exact(stride)
+        .zip(points.chunks_exact(stride).skip(1))
+        .map(|(a, b)| {
+            let elapsed = if stride == TIMED_STRIDE { (b[3] - a[3]) as f64 } else { 1.0 };
+            (segment_length(a, b), elapsed)
+        })
+        .collect();
+    let timed: Vec<usize> = (0..segments.len()).filter(|&i| segments[i].1 > 0.0).collect();
+    let speeds: Vec<f64> = (0..segments.len())
+        .map(|i| {
+            let source = match timed.binary_search(&i) {
+                _ if timed.is_empty() => return segments[i].0,
+                Ok(_) => i,
+                Err(at) if at == timed.len() => timed[at - 1],
+                Err(0) => timed[0],
+                Err(at) => if i - timed[at - 1] <= timed[at] - i { timed[at - 1] } else { timed[at] },
+            };
+            segments[source].0 / segments[source].1
+        })
+        .collect();
+
+    // A median of three segments drops one-off jumps, then each point takes its segments' mean
+    let last = speeds.len() - 1;
+    let despiked: Vec<f64> = (0..speeds.len())
+        .map(|i| {
+            let mut window = [speeds[i.saturating_sub(1)], speeds[i], speeds[(i + 1).min(last)]];
+            window.sort_by(f64::total_cmp);
+            window[1]
+        })
+        .collect();
+    let at_points: Vec<f64> =
+        (0..count).map(|i| (despiked[i.saturating_sub(1)] + despiked[i.min(last)]) / 2.0).collect();
+
+    let mut sorted = at_points.clone();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[count / 2];
+    let mut pressures: Vec<f64> = at_points
+        .iter()
+        .map(|&speed| {
+            let ratio = if median > 0.0 { speed / median } else if speed > 0.0 { f64::INFINITY } else { 0.0 };
+            let thinning = if ratio.is_infinite() { 1.0 } else { ratio / (1.0 + ratio) };
+            max_p - (max_p - min_p) * thinning
+        })
+        .collect();
+
+    // Zero-phase smoothing: forward pass, then backward over its result
+    for i in 1..count {
+        pressures[i] = pressures[i - 1] + (pressures[i] - pressures[i - 1]) * responsiveness;
+    }
+    for i in (0..count - 1).rev() {
+        pressures[i] = pressures[i + 1] + (pressures[i] - pressures[i + 1]) * responsiveness;
+    }
+
+    if count >= 3 {
+        let extrapolate = |near: f64, far: f64| (2.0 * near - far).clamp(min_p, max_p);
+        pressures[0] = extrapolate(pressures[1], pressures[2]);
+        pressures[count - 1] = extrapolate(pressures[count - 2], pressures[count - 3]);
+    }
+    pressures
+}
@@ -0,0 +1,739 @@
+/*!
+ * Compact stroke storage
+ *
+ * Documents store strokes as f32 triplets, 12 bytes per point. Inside
+ * known bounds (the stroke's or the whole layer's), 16-bit positions and
+ * an 8-bit pressure keep every point within half a quantization step in
+ * 5 bytes. Consecutive points are close together and move smoothly, so
+ * storing how far each quantized value strays from a linear prediction
+ * as short varints shrinks that again.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Data Formats)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::bounds::Bounds;
+use crate::error::{run_export, CreoVexError, EngineError, EngineResult};
+use crate::validate::{validate_floats, with_float_validation, FloatValidation};
+use crate::{
+    batch_stroke_values, hand_off, output_buffer, read_batch, read_points, read_u32, serialize_points,
+    serialize_strokes, EngineBuffer,
+};
+
+/**
+ * Bytes per quantized point: u16 x, u16 y, u8 pressure
+ */
+const QUANTIZED_POINT_BYTES: usize = 5;
+
+/**
+ * Bytes of the bounds header ([min_x, min_y, max_x, max_y] as f32)
+ */
+const QUANTIZED_BOUNDS_BYTES: usize = 16;
+
+/**
+ * First bytes of every compress_stroke blob
+ */
+const COMPRESSED_MAGIC: [u8; 4] = *b"CVXS";
+
+/**
+ * compress_stroke format version; decompress_stroke rejects others
+ */
+const COMPRESSED_VERSION: u8 = 1;
+
+/**
+ * Bytes before the varint payload: magic, version, point count, bounds
+ */
+const COMPRESSED_HEADER_BYTES: usize = 4 + 1 + 4 + QUANTIZED_BOUNDS_BYTES;
+
+/**
+ * Longest varint a zigzagged prediction error needs, in nibbles of three
+ * value bits (18 bits: the error lies within twice the u16 range either
+ * way)
+ */
+const MAX_VARINT_NIBBLES: usize = 6;
+
+/**
+ * Quantize a stroke to 16-bit positions and 8-bit pressure
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...], every point
+ * inside the given bounds (e.g. stroke_bounds, or the layer's box so
+ * strokes share one header)
+ *
+ * Output format (little-endian):
+ * - 16 bytes: f32 [min_x, min_y, max_x, max_y], the dequantization
+ *   parameters
+ * - 4 bytes: point count N (u32)
+ * - N * 5 bytes: u16 x, u16 y, u8 pressure per point
+ *
+ * x maps `min_x..=max_x` onto 0..=65535 (likewise y) and pressure
+ * 0..=1 onto 0..=255, rounding to the nearest step, so dequantize_stroke
+ * returns every value within half a step: (max_x - min_x) / 131070 for x,
+ * 1/510 for pressure (plus f32 rounding at the coordinates' magnitude).
+ * Pressure outside [0, 1] is clamped.
+ *
+ * Errors: the read_points errors; NonFiniteValue for a non-finite bound;
+ * InvalidParameter for bounds with zero or negative width or height, or
+ * a point outside them.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn quantize_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let grid = QuantizationGrid::new([min_x, min_y, max_x, max_y])?;
+
+        let mut records = Vec::with_capacity(points.len() / 3 * QUANTIZED_POINT_BYTES);
+        grid.quantize_into(points, &mut records).map_err(|index| outside_error(&grid, None, index))?;
+        serialize_quantized(&grid, None, &records)
+    })
+}
+
+/**
+ * Quantize every stroke of a batch against one set of bounds (a layer)
+ *
+ * Input format: the batch container (see read_batch)
+ *
+ * Output format (little-endian):
+ * - 16 bytes: f32 [min_x, min_y, max_x, max_y]
+ * - 4 bytes: stroke count S (u32)
+ * - S * 4 bytes: point count of each stroke (u32)
+ * - the 5-byte quantize_stroke points of every stroke, concatenated
+ *
+ * That is the batch container with the bounds in front and 5-byte
+ * records; dequantize_strokes_batch turns it back into a batch
+ * container. Strokes with no points are kept as empty strokes.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container; the
+ * quantize_stroke bounds errors; InvalidParameter when any point of any
+ * stroke is outside the bounds (the message names the stroke).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn quantize_strokes_batch(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+    validation: Option<FloatValidation>,
+) -> EngineBuffer {
+    with_float_validation(validation, || {
+        run_export(|| {
+            let strokes = read_batch(batch_ptr, batch_len)?;
+            let grid = QuantizationGrid::new([min_x, min_y, max_x, max_y])?;
+
+            let mut counts = Vec::with_capacity(strokes.len());
+            let mut records = Vec::new();
+            for (stroke_index, stroke) in strokes.iter().enumerate() {
+                let values = batch_stroke_values(stroke);
+                let values = validate_floats(&values, 3)?;
+                grid.quantize_into(&values, &mut records)
+                    .map_err(|index| outside_error(&grid, Some(stroke_index), index))?;
+                counts.push((values.len() / 3) as u32);
+            }
+            serialize_quantized(&grid, Some(&counts), &records)
+        })
+    })
+}
+
+/**
+ * Expand a quantize_stroke buffer back to f32 points
+ *
+ * Input format: the quantize_stroke output, `quantized_len` bytes
+ * Output format: [x, y, p] points (see serialize_points)
+ *
+ * Each value is the center of its step: `min_x + q * (max_x - min_x) /
+ * 65535` for x (likewise y) and `q / 255` for pressure.
+ *
+ * Errors: NullInput for a null pointer; InvalidLength when the length
+ * does not match the header; NonFiniteValue/InvalidParameter for header
+ * bounds quantize_stroke would refuse.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn dequantize_stroke(quantized_ptr: *const u8, quantized_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let bytes = read_quantized(quantized_ptr, quantized_len)?;
+        let grid = read_grid(bytes)?;
+        let count = read_u32(bytes, QUANTIZED_BOUNDS_BYTES).unwrap_or(0) as usize;
+        let records = &bytes[QUANTIZED_BOUNDS_BYTES + 4..];
+        require_record_bytes(records.len(), count, quantized_len)?;
+
+        serialize_points(&grid.dequantize(records))
+    })
+}
+
+/**
+ * Expand a quantize_strokes_batch buffer back to a batch container
+ *
+ * Input format: the quantize_strokes_batch output, `quantized_len` bytes
+ * Output format: the batch container (see read_batch), without a status
+ * array
+ *
+ * Values are reconstructed as in dequantize_stroke.
+ *
+ * Errors: as dequantize_stroke, with InvalidLength also for stroke
+ * counts that do not fit the buffer.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn dequantize_strokes_batch(quantized_ptr: *const u8, quantized_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let bytes = read_quantized(quantized_ptr, quantized_len)?;
+        let grid = read_grid(bytes)?;
+        let stroke_count = read_u32(bytes, QUANTIZED_BOUNDS_BYTES).unwrap_or(0) as usize;
+        let header_len = stroke_count
+            .checked_mul(4)
+            .and_then(|n| n.checked_add(QUANTIZED_BOUNDS_BYTES + 4))
+            .filter(|&n| n <= bytes.len())
+            .ok_or_else(|| {
+                EngineError::new(
+                    CreoVexError::InvalidLength,
+                    format!("{stroke_count} stroke counts do not fit in {quantized_len} bytes"),
+                )
+            })?;
+
+        let counts: Vec<usize> = (0..stroke_count)
+            .map(|i| read_u32(bytes, QUANTIZED_BOUNDS_BYTES + 4 + i * 4).unwrap_or(0) as usize)
+            .collect();
+        let total = counts.iter().try_fold(0usize, |sum, &count| sum.checked_add(count)).unwrap_or(usize::MAX);
+        let records = &bytes[header_len..];
+        require_record_bytes(records.len(), total, quantized_len)?;
+
+        let mut strokes = Vec::with_capacity(stroke_count);
+        let mut offset = 0;
+        for count in counts {
+            let end = offset + count * QUANTIZED_POINT_BYTES;
+            strokes.push(grid.dequantize(&records[offset..end]));
+            offset = end;
+        }
+        serialize_strokes(&strokes)
+    })
+}
+
+/**
+ * Compress a stroke to quantized zigzag-varint deltas
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format (little-endian, self-describing):
+ * - 4 bytes: magic "CVXS"
+ * - 1 byte: format version (1)
+ * - 4 bytes: point count N (u32)
+ * - 16 bytes: f32 [min_x, min_y, max_x, max_y], the stroke's own x/y
+ *   range (a zero-width axis is widened to the next f32)
+ * - N x residuals, then N y residuals, then N pressure residuals: each
+ *   the quantize_stroke value minus its prediction, the previous value
+ *   plus the previous change (0 for the first point, the first value for
+ *   the second), zigzag-mapped
+ * - residuals are varints of 4-bit nibbles, low nibble of each byte
+ *   first: three value bits, least significant group first, and a
+ *   continuation bit (0x8); the payload ends on a byte, padding an odd
+ *   last nibble with 0
+ *
+ * Values are quantized exactly as quantize_stroke does, so the round
+ * trip is within half a step. On smooth strokes most residuals are
+ * within ±3 steps and take one nibble: 1000-point sine strokes come out
+ * 2.5-3.3x smaller than quantize_stroke's 5 bytes per point. Residuals of
+ * 4-31 steps take a byte and up to 255 steps a byte and a half, so even
+ * erratic input costs no more than quantize_stroke's 2 bytes per
+ * coordinate.
+ *
+ * Errors: the read_points errors; NonFiniteValue for a NaN or infinite
+ * x/y (the bounds would be undefined).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn compress_stroke(points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+
+        let compressed = compress_points(points)?;
+        let mut output = output_buffer(compressed.len())?;
+        output.extend_from_slice(&compressed);
+        Ok(hand_off(output))
+    })
+}
+
+/**
+ * Restore a compress_stroke blob to f32 points
+ *
+ * Input format: a compress_stroke blob, exactly `compressed_len` bytes
+ * Output format: [x, y, p] points (see serialize_points)
+ *
+ * Values are reconstructed as in dequantize_stroke. The blob is checked
+ * completely before anything is returned: every varint must be complete
+ * and in range, and the payload must end exactly after the last one
+ * (with a zero padding nibble if that ends mid-byte).
+ *
+ * Errors: NullInput for a null pointer; InvalidParameter for a wrong
+ * magic; UnsupportedVersion for another format version; InvalidLength for a truncated blob,
+ * trailing bytes or padding, a point count the payload cannot hold, or a
+ * corrupted residual (overlong varint, or a value leaving the
+ * quantization range);
+ * NonFiniteValue/InvalidParameter for header bounds quantize_stroke
+ * would refuse.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn decompress_stroke(compressed_ptr: *const u8, compressed_len: usize) -> EngineBuffer {
+    run_export(|| {
+        if compressed_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "compressed_ptr is null"));
+        }
+        let bytes: &[u8] = unsafe { std::slice::from_raw_parts(compressed_ptr, compressed_len) };
+
+        serialize_points(&decompress_points(bytes)?)
+    })
+}
+
+/**
+ * The compress_stroke blob of [x, y, p] points (at least one)
+ */
+pub(crate) fn compress_points(points: &[f32]) -> EngineResult<Vec<u8>> {
+    if let Some(index) = points.chunks_exact(3).position(|point| !(point[0].is_finite() && point[1].is_finite())) {
+        return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("point {index} has a non-finite position")));
+    }
+
+    let mut bounds = Bounds::of_stroke(points, 0.0).unwrap_or(Bounds::EMPTY).to_array();
+    for axis in 0..2 {
+        if bounds[axis + 2] == bounds[axis] {
+            bounds[axis + 2] = bounds[axis].next_up();
+        }
+    }
+    let grid = QuantizationGrid::new(bounds)?;
+
+    let count = points.len() / 3;
+    let mut buffer = Vec::with_capacity(COMPRESSED_HEADER_BYTES + count * 3 * 2);
+    buffer.extend_from_slice(&COMPRESSED_MAGIC);
+    buffer.push(COMPRESSED_VERSION);
+    buffer.extend_from_slice(&(count as u32).to_le_bytes());
+    for bound in grid.bounds {
+        buffer.extend_from_slice(&bound.to_le_bytes());
+    }
+
+    let quantized: Vec<[u16; 3]> = points
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(index, point)| grid.quantize_point(point).ok_or_else(|| outside_error(&grid, None, index)))
+        .collect::<EngineResult<_>>()?;
+    let mut nibbles = NibbleWriter { buffer: &mut buffer, odd: false };
+    for channel in 0..3 {
+        let mut predictor = Predictor::default();
+        for values in &quantized {
+            let value = values[channel] as i32;
+            nibbles.write_varint(zigzag(value - predictor.next()));
+            predictor.advance(value);
+        }
+    }
+    Ok(buffer)
+}
+
+/**
+ * Decode a complete compress_stroke blob (see decompress_stroke for the
+ * checks and errors)
+ */
+pub(crate) fn decompress_points(bytes: &[u8]) -> EngineResult<Vec<f32>> {
+    let malformed = |detail: String| {
+        EngineError::new(CreoVexError::InvalidLength, format!("malformed compressed stroke: {detail}"))
+    };
+
+    if bytes.len() < COMPRESSED_HEADER_BYTES {
+        let len = bytes.len();
+        return Err(malformed(format!("{len} bytes is shorter than the {COMPRESSED_HEADER_BYTES} byte header")));
+    }
+    if bytes[..4] != COMPRESSED_MAGIC {
+        return Err(EngineError::new(CreoVexError::InvalidParameter, "not a compressed stroke (wrong magic)"));
+    }
+    if bytes[4] != COMPRESSED_VERSION {
+        return Err(EngineError::new(
+            CreoVexError::UnsupportedVersion,
+            format!("unsupported compressed stroke version {} (expected {COMPRESSED_VERSION})", bytes[4]),
+        ));
+    }
+    let count = read_u32(bytes, 5).unwrap_or(0) as usize;
+    let grid = read_grid(&bytes[9..])?;
+    let payload = &bytes[COMPRESSED_HEADER_BYTES..];
+    // Every residual takes at least one nibble
+    if count == 0 || count.checked_mul(3).is_none_or(|residuals| residuals > payload.len() * 2) {
+        return Err(malformed(format!("{count} points do not fit in {} payload bytes", payload.len())));
+    }
+
+    let limits = [u16::MAX as i32, u16::MAX as i32, u8::MAX as i32];
+    let mut quantized = vec![[0u16; 3]; count];
+    let mut offset = 0;
+    for (channel, &limit) in limits.iter().enumerate() {
+        let mut predictor = Predictor::default();
+        for (index, values) in quantized.iter_mut().enumerate() {
+            let (residual, used) = read_varint(payload, offset)
+                .ok_or_else(|| malformed(format!("channel {channel} residual {index} is truncated or overlong")))?;
+            offset += used;
+            let value = predictor.next() + unzigzag(residual);
+            if !(0..=limit).contains(&value) {
+                return Err(malformed(format!("channel {channel} value {index} leaves the range 0-{limit}")));
+            }
+            values[channel] = value as u16;
+            predictor.advance(value);
+        }
+    }
+    if offset.div_ceil(2) != payload.len() {
+        return Err(malformed(format!("{} bytes after the last residual", payload.len() - offset.div_ceil(2))));
+    }
+    if offset % 2 == 1 && payload[payload.len() - 1] >> 4 != 0 {
+        return Err(malformed("nonzero padding after the last residual".into()));
+    }
+
+    Ok(quantized.iter().flat_map(|&values| grid.dequantize_point(values)).collect())
+}
+
+/**
+ * Linear prediction of one quantized channel: each value is expected to
+ * change by as much as the previous one did
+ *
+ * The first value is predicted as 0 and the second as the first, so a
+ * stroke's start costs no more than a plain delta would.
+ */
+#[derive(Default)]
+struct Predictor {
+    previous: Option<i32>,
+    velocity: i32,
+}
+
+impl Predictor {
+    fn next(&self) -> i32 {
+        self.previous.map_or(0, |previous| previous + self.velocity)
+    }
+
+    /**
+     * Record the actual value (in the channel's range, so the velocity
+     * stays within it too)
+     */
+    fn advance(&mut self, value: i32) {
+        if let Some(previous) = self.previous {
+            self.velocity = value - previous;
+        }
+        self.previous = Some(value);
+    }
+}
+
+fn zigzag(delta: i32) -> u32 {
+    ((delta << 1) ^ (delta >> 31)) as u32
+}
+
+fn unzigzag(value: u32) -> i32 {
+    (value >> 1) as i32 ^ -((value & 1) as i32)
+}
+
+/**
+ * Appends nibbles to a byte buffer, low nibble of each byte first
+ */
+struct NibbleWriter<'a> {
+    buffer: &'a mut Vec<u8>,
+    odd: bool,
+}
+
+impl NibbleWriter<'_> {
+    fn push(&mut self, nibble: u8) {
+        match self.buffer.last_mut() {
+            Some(last) if self.odd => *last |= nibble << 4,
+            _ => self.buffer.push(nibble),
+        }
+        self.odd = !self.odd;
+    }
+
+    fn write_varint(&mut self, mut value: u32) {
+        while value >= 0x8 {
+            self.push((value as u8 & 0x7) | 0x8);
+            value >>= 3;
+        }
+        self.push(value as u8);
+    }
+}
+
+/**
+ * Decode one varint of at most MAX_VARINT_NIBBLES starting `offset`
+ * nibbles into `bytes`; returns the value and the nibbles used, or None
+ * when truncated or overlong
+ */
+fn read_varint(bytes: &[u8], offset: usize) -> Option<(u32, usize)> {
+    let mut value = 0u32;
+    for i in 0..MAX_VARINT_NIBBLES {
+        let at = offset + i;
+        let nibble = bytes.get(at / 2).map(|&byte| if at.is_multiple_of(2) { byte & 0xF } else { byte >> 4 })?;
+        value |= ((nibble & 0x7) as u32) << (3 * i);
+        if nibble & 0x8 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/**
+ * Validated bounds and the step sizes derived from them
+ */
+struct QuantizationGrid {
+    bounds: [f32; 4],
+    origin: [f64; 2],
+    extent: [f64; 2],
+}
+
+impl QuantizationGrid {
+    fn new(bounds: [f32; 4]) -> EngineResult<Self> {
+        if let Some(bound) = bounds.iter().find(|bound| !bound.is_finite()) {
+            return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("bound {bound} is not finite")));
+        }
+        let [min_x, min_y, max_x, max_y] = bounds.map(|bound| bound as f64);
+        let extent = [max_x - min_x, max_y - min_y];
+        if extent[0] <= 0.0 || extent[1] <= 0.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("bounds must have positive width and height (got {} x {})", extent[0], extent[1]),
+            ));
+        }
+        Ok(QuantizationGrid { bounds, origin: [min_x, min_y], extent })
+    }
+
+    /**
+     * [x, y, pressure] steps of one point, or None outside the bounds
+     */
+    fn quantize_point(&self, point: &[f32]) -> Option<[u16; 3]> {
+        let mut steps = [0u16; 3];
+        for axis in 0..2 {
+            let t = (point[axis] as f64 - self.origin[axis]) / self.extent[axis];
+            if !(0.0..=1.0).contains(&t) {
+                return None;
+            }
+            steps[axis] = (t * u16::MAX as f64).round() as u16;
+        }
+        steps[2] = (point[2].clamp(0.0, 1.0) * u8::MAX as f32).round() as u16;
+        Some(steps)
+    }
+
+    fn dequantize_point(&self, steps: [u16; 3]) -> [f32; 3] {
+        let axis = |i: usize| (self.origin[i] + steps[i] as f64 * self.extent[i] / u16::MAX as f64) as f32;
+        [axis(0), axis(1), steps[2] as f32 / u8::MAX as f32]
+    }
+
+    /**
+     * Append the 5-byte record of every point; Err(point index) for the
+     * first point outside the bounds
+     */
+    fn quantize_into(&self, points: &[f32], records: &mut Vec<u8>) -> Result<(), usize> {
+        for (index, point) in points.chunks_exact(3).enumerate() {
+            let [x, y, pressure] = self.quantize_point(point).ok_or(index)?;
+            records.extend_from_slice(&x.to_le_bytes());
+            records.extend_from_slice(&y.to_le_bytes());
+            records.push(pressure as u8);
+        }
+        Ok(())
+    }
+
+    fn dequantize(&self, records: &[u8]) -> Vec<f32> {
+        let mut points = Vec::with_capacity(records.len() / QUANTIZED_POINT_BYTES * 3);
+        for record in records.chunks_exact(QUANTIZED_POINT_BYTES) {
+            let x = u16::from_le_bytes([record[0], record[1]]);
+            let y = u16::from_le_bytes([record[2], record[3]]);
+            points.extend(self.dequantize_point([x, y, record[4] as u16]));
+        }
+        points
+    }
+}
+
+fn outside_error(grid: &QuantizationGrid, stroke: Option<usize>, index: usize) -> EngineError {
+    let [min_x, min_y, max_x, max_y] = grid.bounds;
+    let point = match stroke {
+        Some(stroke) => format!("stroke {stroke} point {index}"),
+        None => format!("point {index}"),
+    };
+    EngineError::new(
+        CreoVexError::InvalidParameter,
+        format!("{point} is outside the bounds ({min_x}, {min_y})-({max_x}, {max_y})"),
+    )
+}
+
+/**
+ * Bounds header, then the count header (`counts` for a batch, else the
+ * point count of `records`), then the records
+ */
+fn serialize_quantized(grid: &QuantizationGrid, counts: Option<&[u32]>, records: &[u8]) -> EngineResult<EngineBuffer> {
+    let count_bytes = counts.map_or(4, |counts| 4 + counts.len() * 4);
+    let mut buffer = output_buffer(QUANTIZED_BOUNDS_BYTES + count_bytes + records.len())?;
+    for bound in grid.bounds {
+        buffer.extend_from_slice(&bound.to_le_bytes());
+    }
+    match counts {
+        Some(counts) => {
+            buffer.extend_from_slice(&(counts.len() as u32).to_le_bytes());
+            for &count in counts {
+                buffer.extend_from_slice(&count.to_le_bytes());
+            }
+        }
+        None => buffer.extend_from_slice(&((records.len() / QUANTIZED_POINT_BYTES) as u32).to_le_bytes()),
+    }
+    buffer.extend_from_slice(records);
+    Ok(hand_off(buffer))
+}
+
+fn read_quantized<'a>(quantized_ptr: *const u8, quantized_len: usize) -> EngineResult<&'a [u8]> {
+    if quantized_ptr.is_null() {
+        return Err(EngineError::new(CreoVexError::NullInput, "quantized_ptr is null"));
+    }
+    if quantized_len < QUANTIZED_BOUNDS_BYTES + 4 {
+        return Err(EngineError::new(
+            CreoVexError::InvalidLength,
+            format!("quantized_len {quantized_len} is shorter than the {} byte header", QUANTIZED_BOUNDS_BYTES + 4),
+        ));
+    }
+    Ok(unsafe { std::slice::from_raw_parts(quantized_ptr, quantized_len) })
+}
+
+fn read_grid(bytes: &[u8]) -> EngineResult<QuantizationGrid> {
+    let bounds: [f32; 4] = std::array::from_fn(|i| {
+        let field = &bytes[i * 4..i * 4 + 4];
+        f32::from_le_bytes([field[0], field[1], field[2], field[3]])
+    });
+    QuantizationGrid::new(bounds)
+}
+
+fn require_record_bytes(record_bytes: usize, count: usize, quantized_len: usize) -> EngineResult<()> {
+    if count.checked_mul(QUANTIZED_POINT_BYTES) != Some(record_bytes) {
+        return Err(EngineError::new(
+            CreoVexError::InvalidLength,
+            format!("quantized_len {quantized_len} does not match the header's {count} points"),
+        ));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{error_of, sine_stroke, take_bytes, take_points, Noise};
+
+    fn compress(points: &[f32]) -> Vec<u8> {
+        take_bytes(compress_stroke(points.as_ptr(), points.len()))
+    }
+
+    fn decompress_error(blob: &[u8]) -> u32 {
+        error_of(decompress_stroke(blob.as_ptr(), blob.len()))
+    }
+
+    /**
+     * Random walks mixing slow drift, jitter, and jumps across the canvas
+     */
+    fn random_stroke(noise: &mut Noise) -> Vec<f32> {
+        let count = 1 + ((noise.next() + 1.0) * 150.0) as usize;
+        let scale = [0.0, 0.01, 1.0, 50.0, 4000.0][((noise.next() + 1.0) * 2.5) as usize];
+        let (mut x, mut y) = (noise.next() * 1.0e4, noise.next() * 1.0e4);
+        let mut points = Vec::with_capacity(count * 3);
+        for _ in 0..count {
+            x += noise.next() * scale;
+            y += noise.next() * scale;
+            points.extend_from_slice(&[x, y, noise.next() * 0.75 + 0.5]);
+        }
+        points
+    }
+
+    #[test]
+    fn smooth_strokes_shrink_well_beyond_quantization() {
+        let mut stroke = sine_stroke(1000, 1.0, 100.0, 50.0);
+        for (index, point) in stroke.chunks_exact_mut(3).enumerate() {
+            point[2] = 0.5 + 0.4 * (index as f32 / 150.0).sin();
+        }
+        let quantized = take_bytes(quantize_stroke(stroke.as_ptr(), stroke.len(), 0.0, -100.0, 1000.0, 100.0));
+        let compressed = compress(&stroke);
+        assert!(
+            compressed.len() * 5 <= quantized.len() * 2,
+            "{} compressed bytes against {} quantized",
+            compressed.len(),
+            quantized.len()
+        );
+
+        let dense = sine_stroke(1000, 0.3, 20.0, 30.0);
+        assert!(compress(&dense).len() * 3 <= quantized.len());
+    }
+
+    #[test]
+    fn random_strokes_round_trip_like_quantize_stroke() {
+        let mut noise = Noise::new(68);
+        for _ in 0..300 {
+            let stroke = random_stroke(&mut noise);
+            let blob = compress(&stroke);
+
+            // The codec is lossless on the quantized values: decoding gives
+            // exactly what quantizing to the header's bounds gives
+            let bounds: Vec<f32> = blob[9..25].chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+            let quantized = take_bytes(quantize_stroke(stroke.as_ptr(), stroke.len(), bounds[0], bounds[1], bounds[2], bounds[3]));
+            let expected = take_points(dequantize_stroke(quantized.as_ptr(), quantized.len()));
+            let restored = take_points(decompress_stroke(blob.as_ptr(), blob.len()));
+            assert_eq!(restored, expected);
+
+            let step = [(bounds[2] - bounds[0]) as f64 / 131070.0, (bounds[3] - bounds[1]) as f64 / 131070.0];
+            for (original, restored) in stroke.chunks_exact(3).zip(restored.chunks_exact(3)) {
+                for axis in 0..2 {
+                    let slack = step[axis] + original[axis].abs() as f64 * f32::EPSILON as f64;
+                    assert!((original[axis] as f64 - restored[axis] as f64).abs() <= slack);
+                }
+                assert!((original[2].clamp(0.0, 1.0) - restored[2]).abs() <= 1.0 / 510.0 + f32::EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_and_corrupted_blobs_fail_cleanly() {
+        let invalid_length = CreoVexError::InvalidLength as u32;
+        let blob = compress(&sine_stroke(40, 2.0, 30.0, 20.0));
+        for len in 0..blob.len() {
+            assert_eq!(decompress_error(&blob[..len]), invalid_length, "prefix of {len} bytes");
+        }
+        let mut trailing = blob.clone();
+        trailing.push(0);
+        assert_eq!(decompress_error(&trailing), invalid_length);
+
+        // Flipped payload bits either fail or decode to some in-range stroke
+        for bit in COMPRESSED_HEADER_BYTES * 8..blob.len() * 8 {
+            let mut corrupted = blob.clone();
+            corrupted[bit / 8] ^= 1 << (bit % 8);
+            let result = decompress_stroke(corrupted.as_ptr(), corrupted.len());
+            if result.error_code() == 0 {
+                take_bytes(result);
+            } else {
+                assert_eq!(error_of(result), invalid_length, "bit {bit}");
+            }
+        }
+
+        let mut magic = blob.clone();
+        magic[0] = b'X';
+        assert_eq!(decompress_error(&magic), CreoVexError::InvalidParameter as u32);
+        let mut version = blob.clone();
+        version[4] = COMPRESSED_VERSION + 1;
+        assert_eq!(decompress_error(&version), CreoVexError::UnsupportedVersion as u32);
+        let mut count = blob.clone();
+        count[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decompress_error(&count), invalid_length);
+        let mut overlong = blob.clone();
+        overlong[COMPRESSED_HEADER_BYTES..].fill(0xFF);
+        assert_eq!(decompress_error(&overlong), invalid_length);
+
+        // One point at the origin: three one-nibble residuals and a padding nibble
+        let single = compress(&[0.0, 0.0, 0.0]);
+        assert_eq!(&single[COMPRESSED_HEADER_BYTES..], &[0x00, 0x00]);
+        let mut padded = single.clone();
+        padded[COMPRESSED_HEADER_BYTES + 1] = 0x10;
+        assert_eq!(decompress_error(&padded), invalid_length);
+        // A first x residual of -1 leaves the quantization range
+        let mut below = single.clone();
+        below[COMPRESSED_HEADER_BYTES] = 0x01;
+        assert_eq!(decompress_error(&below), invalid_length);
+        assert_eq!(error_of(decompress_stroke(std::ptr::null(), 0)), CreoVexError::NullInput as u32);
+    }
+}
@@ -0,0 +1,498 @@
+/*!
+ * Geometric stroke queries
+ *
+ * Cursor-facing lookups for the edit and selection tools: projection onto
+ * the centerline, hit testing against the painted area, and lasso
+ * containment.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Editing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{
+    require_finite, require_non_negative, run_export, run_status, CreoVexError, EngineError, EngineResult,
+};
+use crate::bounds::Bounds;
+use crate::validate::{with_float_validation, FloatValidation};
+use crate::{batch_stroke_values, read_batch, read_points, serialize_indices, serialize_records, EngineBuffer};
+
+/**
+ * Which strokes a lasso selects
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LassoMode {
+    /** Every point inside and no segment leaving the polygon */
+    Contained = 0,
+    /** Any point inside or any segment crossing the polygon's edge */
+    Touching = 1,
+}
+
+/**
+ * Size of one projection record in 4-byte fields
+ */
+const PROJECTION_FIELDS: usize = 6;
+
+/**
+ * Closest point on a stroke's centerline to a query point
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Projection {
+    /** Index of the segment's start point */
+    pub(crate) segment: usize,
+    /** Position within the segment, 0 at its start and 1 at its end */
+    pub(crate) t: f32,
+    /** Projected [x, y, p], pressure interpolated like position */
+    pub(crate) point: [f32; 3],
+    /** Squared distance from the query to the projected point */
+    pub(crate) distance_sq: f32,
+}
+
+impl Projection {
+    /**
+     * Record layout shared by the projection exports; the segment index is
+     * stored as raw u32 bits
+     */
+    fn to_record(self) -> [f32; PROJECTION_FIELDS] {
+        let [x, y, p] = self.point;
+        [f32::from_bits(self.segment as u32), self.t, x, y, p, self.distance_sq]
+    }
+}
+
+/**
+ * Find the closest point on a stroke to a query point
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: record count (u32, always 1)
+ * - 24 bytes: segment index (u32), then f32 t, x, y, pressure,
+ *   squared distance
+ *
+ * The query is projected onto every segment (clamped to the segment), not
+ * just compared against the vertices; the segment with the smallest
+ * distance wins, the earliest one on ties. A zero-length segment projects
+ * to its start point with t = 0, and a single-point stroke reports
+ * segment 0 at that point.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue for a NaN or
+ * infinite query coordinate.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn nearest_point_on_stroke(points_ptr: *const f32, points_len: usize, qx: f32, qy: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let qx = require_finite("qx", qx)?;
+        let qy = require_finite("qy", qy)?;
+
+        serialize_records(1, &project_onto_stroke(points, 3, qx, qy).to_record())
+    })
+}
+
+/**
+ * Find the closest point on a stroke for many query points
+ *
+ * Input format: stroke points as in nearest_point_on_stroke, plus
+ * `queries_ptr` holding [qx0, qy0, qx1, qy1, ...] with `queries_len`
+ * counting f32 values
+ *
+ * Output format: one nearest_point_on_stroke record per query, in query
+ * order, after a u32 record count.
+ *
+ * Errors: the read_points errors, NullInput/InvalidLength for a missing
+ * or odd-length query buffer, and NonFiniteValue when any query
+ * coordinate is NaN or infinite.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn nearest_points_on_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    queries_ptr: *const f32,
+    queries_len: usize,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let queries = read_xy_pairs("queries", queries_ptr, queries_len)?;
+
+        let mut records = Vec::with_capacity(queries.len() / 2 * PROJECTION_FIELDS);
+        for query in queries.chunks_exact(2) {
+            let qx = require_finite("qx", query[0])?;
+            let qy = require_finite("qy", query[1])?;
+            records.extend_from_slice(&project_onto_stroke(points, 3, qx, qy).to_record());
+        }
+        serialize_records((queries.len() / 2) as u32, &records)
+    })
+}
+
+/**
+ * Test whether a canvas point lies within the painted area of a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Returns 1 for a hit, 0 for a miss, or a negated CreoVexError
+ * discriminant on failure.
+ *
+ * The painted area is a chain of tapered capsules: point i has radius
+ * `base_width * p_i` (negative pressure counts as zero, matching
+ * tessellate_stroke's half-width), and each segment sweeps a circle
+ * whose radius varies linearly between its endpoints. The end circles
+ * form round caps, and a single-point stroke is one disc. Points on the
+ * edge count as hits.
+ *
+ * Errors: the read_points errors, NonFiniteValue for a NaN or infinite
+ * query coordinate, and NonFiniteValue/InvalidParameter for a NaN or
+ * negative width.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn hit_test_stroke(points_ptr: *const f32, points_len: usize, qx: f32, qy: f32, base_width: f32) -> i32 {
+    run_status(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let qx = require_finite("qx", qx)?;
+        let qy = require_finite("qy", qy)?;
+        let base_width = require_non_negative("base_width", base_width)?;
+
+        Ok(usize::from(edge_distance(points, 3, qx, qy, base_width) <= 0.0))
+    })
+}
+
+/**
+ * Find the topmost stroke of a batch under a canvas point
+ *
+ * Input format: the batch container (see read_batch), strokes in paint
+ * order (the last stroke is drawn on top)
+ *
+ * Output format:
+ * - 4 bytes: hit count (u32, 0 or 1)
+ * - 4 bytes (if count is 1): index of the topmost stroke hit (u32)
+ *
+ * Each stroke is tested as in hit_test_stroke. Strokes that are invalid
+ * on their own (empty, or not whole triplets) are treated as misses.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container, plus the
+ * query and width errors of hit_test_stroke.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn hit_test_strokes_batch(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    qx: f32,
+    qy: f32,
+    base_width: f32,
+    validation: Option<FloatValidation>,
+) -> EngineBuffer {
+    with_float_validation(validation, || {
+        run_export(|| {
+            let strokes = read_batch(batch_ptr, batch_len)?;
+            let qx = require_finite("qx", qx)?;
+            let qy = require_finite("qy", qy)?;
+            let base_width = require_non_negative("base_width", base_width)?;
+
+            let topmost = strokes.iter().rposition(|stroke| {
+                let values = batch_stroke_values(stroke);
+                read_points(values.as_ptr(), values.len())
+                    .is_ok_and(|values| edge_distance(&values, 3, qx, qy, base_width) <= 0.0)
+            });
+            let hits: Vec<u32> = topmost.map(|index| index as u32).into_iter().collect();
+            serialize_indices(&hits)
+        })
+    })
+}
+
+/**
+ * Test whether a lasso polygon selects a stroke
+ *
+ * Input format: stroke as [x, y, p, ...]; polygon as [x0, y0, x1, y1, ...]
+ * with `poly_len` counting f32 values
+ *
+ * Returns 1 when selected, 0 when not, or a negated CreoVexError
+ * discriminant on failure.
+ *
+ * The polygon is closed implicitly (last vertex back to the first) and
+ * inside means inside under the even-odd rule, so self-intersecting
+ * lassos select their odd-winding lobes. `LassoMode::Contained` needs
+ * every stroke point inside and no stroke segment crossing or touching a
+ * polygon edge; `LassoMode::Touching` needs any point inside or any
+ * crossing. Only the centerline is tested; stroke width is ignored.
+ *
+ * Errors: the read_points errors, NullInput/InvalidLength for a missing
+ * or odd-length polygon, TooFewPoints for fewer than 3 vertices, and
+ * NonFiniteValue for a NaN or infinite vertex.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_in_polygon(
+    points_ptr: *const f32,
+    points_len: usize,
+    poly_ptr: *const f32,
+    poly_len: usize,
+    mode: LassoMode,
+) -> i32 {
+    run_status(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let polygon = read_polygon(poly_ptr, poly_len)?;
+
+        Ok(usize::from(lasso_selects(points, polygon, mode)))
+    })
+}
+
+/**
+ * Find the strokes of a batch selected by a lasso polygon
+ *
+ * Input format: the batch container (see read_batch), plus the polygon
+ * as in stroke_in_polygon
+ *
+ * Output format:
+ * - 4 bytes: selected count (u32)
+ * - N * 4 bytes: indices of the selected strokes (u32), ascending
+ *
+ * Each stroke is tested as in stroke_in_polygon; strokes that are invalid
+ * on their own are never selected.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container, plus the
+ * polygon errors of stroke_in_polygon.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn strokes_in_polygon_batch(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    poly_ptr: *const f32,
+    poly_len: usize,
+    mode: LassoMode,
+    validation: Option<FloatValidation>,
+) -> EngineBuffer {
+    with_float_validation(validation, || {
+        run_export(|| {
+            let strokes = read_batch(batch_ptr, batch_len)?;
+            let polygon = read_polygon(poly_ptr, poly_len)?;
+
+            let selected: Vec<u32> = strokes
+                .iter()
+                .enumerate()
+                .filter(|(_, stroke)| {
+                    let values = batch_stroke_values(stroke);
+                    read_points(values.as_ptr(), values.len()).is_ok_and(|values| lasso_selects(&values, polygon, mode))
+                })
+                .map(|(index, _)| index as u32)
+                .collect();
+            serialize_indices(&selected)
+        })
+    })
+}
+
+/**
+ * Signed distance from (qx, qy) to the edge of the painted stroke
+ * (negative inside; see hit_test_stroke for the shape)
+ *
+ * Points are `stride` floats wide; x/y/pressure are the first three.
+ */
+pub(crate) fn edge_distance(points: &[f32], stride: usize, qx: f32, qy: f32, base_width: f32) -> f32 {
+    let radius = |point: &[f32]| base_width * point[2].max(0.0);
+    let first = &points[..stride];
+    let mut best = distance_sq(first[0], first[1], qx, qy).sqrt() - radius(first);
+
+    for (a, b) in points.chunks_exact(stride).zip(points.chunks_exact(stride).skip(1)) {
+        best = best.min(tapered_capsule_distance(a, b, radius(a), radius(b), qx, qy));
+    }
+    best
+}
+
+/**
+ * Signed distance to the union of circles swept from (a, ra) to (b, rb)
+ *
+ * |q - c(t)| - r(t) is convex in t, so its minimum over [0, 1] is the
+ * stationary point clamped to the segment. When one end circle contains
+ * the other the sweep is just the larger circle.
+ */
+pub(crate) fn tapered_capsule_distance(a: &[f32], b: &[f32], ra: f32, rb: f32, qx: f32, qy: f32) -> f32 {
+    let end_a = distance_sq(a[0], a[1], qx, qy).sqrt() - ra;
+    let end_b = distance_sq(b[0], b[1], qx, qy).sqrt() - rb;
+
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    let length = (dx * dx + dy * dy).sqrt();
+    let slope = (rb - ra) / length;
+    if length == 0.0 || slope.abs() >= 1.0 {
+        return end_a.min(end_b);
+    }
+
+    // Foot of the perpendicular, then shift along the segment by the taper
+    let t0 = ((qx - a[0]) * dx + (qy - a[1]) * dy) / (length * length);
+    let perpendicular = ((qx - a[0]) * dy - (qy - a[1]) * dx).abs() / length;
+    let t = (t0 + slope * perpendicular / (length * (1.0 - slope * slope).sqrt())).clamp(0.0, 1.0);
+
+    let cx = a[0] + dx * t;
+    let cy = a[1] + dy * t;
+    let swept = distance_sq(cx, cy, qx, qy).sqrt() - (ra + (rb - ra) * t);
+    swept.min(end_a).min(end_b)
+}
+
+/**
+ * Project (qx, qy) onto the stroke (see nearest_point_on_stroke)
+ *
+ * Points are `stride` floats wide; x/y/pressure are the first three.
+ */
+pub(crate) fn project_onto_stroke(points: &[f32], stride: usize, qx: f32, qy: f32) -> Projection {
+    let first = &points[..stride];
+    let mut best = Projection {
+        segment: 0,
+        t: 0.0,
+        point: [first[0], first[1], first[2]],
+        distance_sq: distance_sq(first[0], first[1], qx, qy),
+    };
+
+    for (segment, (a, b)) in points.chunks_exact(stride).zip(points.chunks_exact(stride).skip(1)).enumerate() {
+        let t = project_onto_segment(a, b, qx, qy);
+        let point: [f32; 3] = std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t);
+        let candidate = distance_sq(point[0], point[1], qx, qy);
+        if candidate < best.distance_sq {
+            best = Projection { segment, t, point, distance_sq: candidate };
+        }
+    }
+
+    best
+}
+
+/**
+ * Parameter (0-1) of the point on segment a-b closest to (qx, qy); 0 for
+ * a zero-length segment
+ */
+pub(crate) fn project_onto_segment(a: &[f32], b: &[f32], qx: f32, qy: f32) -> f32 {
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    let length_sq = dx * dx + dy * dy;
+    if length_sq == 0.0 {
+        return 0.0;
+    }
+    (((qx - a[0]) * dx + (qy - a[1]) * dy) / length_sq).clamp(0.0, 1.0)
+}
+
+/**
+ * Lasso test over [x, y, p] points (see stroke_in_polygon)
+ */
+pub(crate) fn lasso_selects(points: &[f32], polygon: &[f32], mode: LassoMode) -> bool {
+    let vertex = |i: usize| [polygon[i * 2] as f64, polygon[i * 2 + 1] as f64];
+    let vertices = polygon.len() / 2;
+
+    // A stroke outside the polygon's box is a miss in either mode
+    let (mut min, mut max) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+    for i in 0..vertices {
+        let v = vertex(i);
+        for axis in 0..2 {
+            min[axis] = min[axis].min(v[axis]);
+            max[axis] = max[axis].max(v[axis]);
+        }
+    }
+    if let Some(stroke) = Bounds::of_stroke(points, 0.0) {
+        if (stroke.max_x as f64) < min[0]
+            || (stroke.min_x as f64) > max[0]
+            || (stroke.max_y as f64) < min[1]
+            || (stroke.min_y as f64) > max[1]
+        {
+            return false;
+        }
+    }
+
+    let position = |p: &[f32]| [p[0] as f64, p[1] as f64];
+    let inside = |p: &[f32]| point_in_polygon(position(p), polygon);
+    let crosses_edge = |(a, b): (&[f32], &[f32])| {
+        let (a, b) = (position(a), position(b));
+        (0..vertices).any(|i| segments_touch(a, b, vertex(i), vertex((i + 1) % vertices)))
+    };
+    let mut segments = points.chunks_exact(3).zip(points.chunks_exact(3).skip(1));
+
+    match mode {
+        LassoMode::Contained => points.chunks_exact(3).all(inside) && !segments.any(crosses_edge),
+        LassoMode::Touching => points.chunks_exact(3).any(inside) || segments.any(crosses_edge),
+    }
+}
+
+/**
+ * Even-odd (crossing number) test against the implicitly closed polygon
+ */
+fn point_in_polygon(p: [f64; 2], polygon: &[f32]) -> bool {
+    let vertices = polygon.len() / 2;
+    let mut inside = false;
+    let mut j = vertices - 1;
+    for i in 0..vertices {
+        let (xi, yi) = (polygon[i * 2] as f64, polygon[i * 2 + 1] as f64);
+        let (xj, yj) = (polygon[j * 2] as f64, polygon[j * 2 + 1] as f64);
+        if (yi > p[1]) != (yj > p[1]) && p[0] < xj + (p[1] - yj) * (xi - xj) / (yi - yj) {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/**
+ * Whether segments p-q and a-b share any point (touching counts)
+ */
+fn segments_touch(p: [f64; 2], q: [f64; 2], a: [f64; 2], b: [f64; 2]) -> bool {
+    let orient = |o: [f64; 2], s: [f64; 2], t: [f64; 2]| (s[0] - o[0]) * (t[1] - o[1]) - (s[1] - o[1]) * (t[0] - o[0]);
+    let within = |o: [f64; 2], s: [f64; 2], t: [f64; 2]| {
+        t[0] >= o[0].min(s[0]) && t[0] <= o[0].max(s[0]) && t[1] >= o[1].min(s[1]) && t[1] <= o[1].max(s[1])
+    };
+
+    let d1 = orient(a, b, p);
+    let d2 = orient(a, b, q);
+    let d3 = orient(p, q, a);
+    let d4 = orient(p, q, b);
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    (d1 == 0.0 && within(a, b, p))
+        || (d2 == 0.0 && within(a, b, q))
+        || (d3 == 0.0 && within(p, q, a))
+        || (d4 == 0.0 && within(p, q, b))
+}
+
+/**
+ * Borrow an [x0, y0, x1, y1, ...] buffer; `name` prefixes the argument
+ * names in error messages
+ */
+fn read_xy_pairs<'a>(name: &str, values_ptr: *const f32, values_len: usize) -> EngineResult<&'a [f32]> {
+    if values_ptr.is_null() {
+        return Err(EngineError::new(CreoVexError::NullInput, format!("{name}_ptr is null")));
+    }
+    if !values_len.is_multiple_of(2) {
+        return Err(EngineError::new(
+            CreoVexError::InvalidLength,
+            format!("{name}_len {values_len} is not a multiple of 2 (x, y)"),
+        ));
+    }
+    Ok(unsafe { std::slice::from_raw_parts(values_ptr, values_len) })
+}
+
+/**
+ * Borrow a lasso polygon: at least 3 finite vertices
+ */
+fn read_polygon<'a>(poly_ptr: *const f32, poly_len: usize) -> EngineResult<&'a [f32]> {
+    let polygon = read_xy_pairs("poly", poly_ptr, poly_len)?;
+    if polygon.len() < 6 {
+        return Err(EngineError::new(
+            CreoVexError::TooFewPoints,
+            format!("polygon needs at least 3 vertices (got {})", polygon.len() / 2),
+        ));
+    }
+    if let Some(index) = polygon.iter().position(|v| !v.is_finite()) {
+        return Err(EngineError::new(
+            CreoVexError::NonFiniteValue,
+            format!("polygon vertex {} is not finite", index / 2),
+        ));
+    }
+    Ok(polygon)
+}
+
+fn distance_sq(ax: f32, ay: f32, bx: f32, by: f32) -> f32 {
+    let dx = bx - ax;
+    let dy = by - ay;
+    dx * dx + dy * dy
+}
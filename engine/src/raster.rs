@@ -0,0 +1,291 @@
+/*!
+ * Stroke rasterization
+ *
+ * Smudge, selection masks, fill boundaries, and magnetic snapping work
+ * on pixels, not geometry. The painted shape is the same tapered capsule
+ * chain that hit_test_stroke tests against, so a mask pixel is covered
+ * exactly where a click at its center would hit.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Rendering)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{
+    require_finite, require_non_negative, require_positive, run_export, run_status, CreoVexError, EngineError,
+};
+use crate::query::tapered_capsule_distance;
+use crate::{read_points, require_capacity, serialize_grid, EngineBuffer};
+
+/**
+ * Side, in cells, of the square tiles stroke_sdf culls segments for
+ */
+pub(crate) const SDF_TILE_CELLS: usize = 8;
+
+/**
+ * Rasterize a stroke into a caller-provided 8-bit coverage mask
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format: `w * h` bytes at `out_ptr`, row-major from the top row,
+ * one byte of coverage (0-255) per pixel; no header. Allocate it with
+ * alloc(w * h) and reuse it across calls.
+ *
+ * Pixel (i, j) covers canvas x from `x0 + i / scale` to
+ * `x0 + (i + 1) / scale`, and likewise y from `y0`: `scale` is pixels per
+ * canvas unit. The painted shape is hit_test_stroke's (circles of radius
+ * `base_width * pressure` swept along each segment, so caps and joins
+ * are round). Coverage falls from 255 to 0 across one pixel centered on
+ * the edge, measured at the pixel center, and each segment only touches
+ * the pixels near its own bounds, clipped to the mask.
+ *
+ * The mask is cleared first; overlapping segments combine with max, so
+ * joins and self-overlaps are never brighter than a single pass.
+ *
+ * Returns the number of bytes written (`w * h`), or a negative
+ * CreoVexError discriminant on failure. When the buffer is too small the
+ * result is -BufferTooSmall and, if `out_capacity_bytes` >= 4, the
+ * required byte count is stored as a u32 at `out_ptr`.
+ *
+ * Errors: the read_points errors; NullInput for a null `out_ptr`;
+ * NonFiniteValue/InvalidParameter for a NaN, infinite, or negative base
+ * width, a non-finite origin, or a scale that is not positive and
+ * finite; InvalidParameter when `w * h` overflows; BufferTooSmall.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn rasterize_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    x0: f32,
+    y0: f32,
+    w: u32,
+    h: u32,
+    scale: f32,
+    out_ptr: *mut u8,
+    out_capacity_bytes: usize,
+) -> i32 {
+    run_status(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", require_finite("base_width", base_width)?)?;
+        let (x0, y0) = (require_finite("x0", x0)?, require_finite("y0", y0)?);
+        let scale = require_positive("scale", require_finite("scale", scale)?)?;
+        if out_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "out_ptr is null"));
+        }
+        let total_bytes = (w as usize)
+            .checked_mul(h as usize)
+            .ok_or_else(|| EngineError::new(CreoVexError::InvalidParameter, format!("{w}x{h} mask is too large")))?;
+        let out = unsafe { std::slice::from_raw_parts_mut(out_ptr, out_capacity_bytes) };
+        require_capacity(out, total_bytes)?;
+
+        let mask = &mut out[..total_bytes];
+        mask.fill(0);
+        let grid = PixelGrid { x0, y0, w: w as usize, h: h as usize, scale };
+        let radius = |point: &[f32]| base_width * point[2].max(0.0);
+
+        let first = &points[..3];
+        if points.len() == 3 {
+            let r = radius(first);
+            grid.cover(mask, [first[0] - r, first[1] - r, first[0] + r, first[1] + r], |qx, qy| {
+                tapered_capsule_distance(first, first, r, r, qx, qy)
+            });
+        }
+        for (a, b) in points.chunks_exact(3).zip(points.chunks_exact(3).skip(1)) {
+            let (ra, rb) = (radius(a), radius(b));
+            let bounds = [
+                (a[0] - ra).min(b[0] - rb),
+                (a[1] - ra).min(b[1] - rb),
+                (a[0] + ra).max(b[0] + rb),
+                (a[1] + ra).max(b[1] + rb),
+            ];
+            grid.cover(mask, bounds, |qx, qy| tapered_capsule_distance(a, b, ra, rb, qx, qy));
+        }
+        Ok(total_bytes)
+    })
+}
+
+/**
+ * Signed distance field of a stroke's painted area
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format (see serialize_grid):
+ * - 4 bytes: width `w` (u32)
+ * - 4 bytes: height `h` (u32)
+ * - w * h * 4 bytes: f32 distances, row-major from the top row
+ *
+ * Cell (i, j) samples the canvas point `(x0 + (i + 0.5) * cell_size,
+ * y0 + (j + 0.5) * cell_size)`. Its value is the signed distance, in
+ * canvas units, to the outline of hit_test_stroke's shape: negative
+ * inside the painted area, 0 on the edge, positive outside.
+ *
+ * Distances are exact per tapered capsule. The grid is split into tiles
+ * of SDF_TILE_CELLS cells; each tile keeps only the segments that can be
+ * nearest to some cell in it (by a bound from the tile's center), sorted
+ * by how close they can get, and each cell stops at the first segment
+ * that cannot beat its best so far. A long stroke therefore does not
+ * cost every cell every segment.
+ *
+ * Errors: the read_points errors; NonFiniteValue/InvalidParameter for a
+ * NaN, infinite, or negative base width, a non-finite origin, or a cell
+ * size that is not positive and finite; InvalidParameter when `w * h`
+ * overflows; AllocationFailed when the grid does not fit in memory.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn stroke_sdf(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    x0: f32,
+    y0: f32,
+    w: u32,
+    h: u32,
+    cell_size: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", require_finite("base_width", base_width)?)?;
+        let (x0, y0) = (require_finite("x0", x0)?, require_finite("y0", y0)?);
+        let cell_size = require_positive("cell_size", require_finite("cell_size", cell_size)?)?;
+        let (width, height) = (w as usize, h as usize);
+        let total = width
+            .checked_mul(height)
+            .ok_or_else(|| EngineError::new(CreoVexError::InvalidParameter, format!("{w}x{h} grid is too large")))?;
+
+        let radius = |point: &[f32]| base_width * point[2].max(0.0);
+        let mut segments: Vec<(&[f32], &[f32])> = points.chunks_exact(3).zip(points.chunks_exact(3).skip(1)).collect();
+        if segments.is_empty() {
+            segments.push((&points[..3], &points[..3]));
+        }
+        // [min_x, min_y, max_x, max_y, largest radius] of each capsule
+        let boxes: Vec<[f32; 5]> = segments
+            .iter()
+            .map(|&(a, b)| {
+                let (ra, rb) = (radius(a), radius(b));
+                [
+                    (a[0] - ra).min(b[0] - rb),
+                    (a[1] - ra).min(b[1] - rb),
+                    (a[0] + ra).max(b[0] + rb),
+                    (a[1] + ra).max(b[1] + rb),
+                    ra.max(rb),
+                ]
+            })
+            .collect();
+        let distance = |s: usize, qx: f32, qy: f32| {
+            let (a, b) = segments[s];
+            tapered_capsule_distance(a, b, radius(a), radius(b), qx, qy)
+        };
+        let center = |index: usize, origin: f32| origin + (index as f32 + 0.5) * cell_size;
+
+        let mut field = vec![0.0f32; total];
+        let mut candidates = Vec::new();
+        for row0 in (0..height).step_by(SDF_TILE_CELLS) {
+            let row1 = (row0 + SDF_TILE_CELLS).min(height);
+            for col0 in (0..width).step_by(SDF_TILE_CELLS) {
+                let col1 = (col0 + SDF_TILE_CELLS).min(width);
+                let tile = [center(col0, x0), center(row0, y0), center(col1 - 1, x0), center(row1 - 1, y0)];
+
+                // Every cell is within half a diagonal of the tile center, and distances are 1-Lipschitz
+                let (mid_x, mid_y) = ((tile[0] + tile[2]) * 0.5, (tile[1] + tile[3]) * 0.5);
+                let half_diagonal = (tile[2] - tile[0]).hypot(tile[3] - tile[1]) * 0.5;
+                let bound = (0..segments.len()).map(|s| distance(s, mid_x, mid_y)).fold(f32::INFINITY, f32::min)
+                    + half_diagonal;
+
+                // Nearest-looking segments first, so each cell can stop once no other can be closer
+                candidates.clear();
+                candidates.extend(
+                    (0..segments.len())
+                        .map(|s| (lower_bound(&boxes[s], &tile), s))
+                        .filter(|&(lowest, _)| lowest <= bound),
+                );
+                candidates.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+                for row in row0..row1 {
+                    let qy = center(row, y0);
+                    for col in col0..col1 {
+                        let qx = center(col, x0);
+                        let mut best = f32::INFINITY;
+                        for &(lowest, s) in &candidates {
+                            if lowest > best {
+                                break;
+                            }
+                            best = best.min(distance(s, qx, qy));
+                        }
+                        field[row * width + col] = best;
+                    }
+                }
+            }
+        }
+        serialize_grid(w, h, &field)
+    })
+}
+
+/**
+ * Lowest signed distance a capsule with `bounds` ([min_x, min_y, max_x,
+ * max_y, largest radius]) can have at any point of `tile`
+ *
+ * Outside its box the distance is at least the gap to the box; inside,
+ * it is at least minus the largest radius.
+ */
+fn lower_bound(bounds: &[f32; 5], tile: &[f32; 4]) -> f32 {
+    let gap_x = (bounds[0] - tile[2]).max(tile[0] - bounds[2]).max(0.0);
+    let gap_y = (bounds[1] - tile[3]).max(tile[1] - bounds[3]).max(0.0);
+    if gap_x == 0.0 && gap_y == 0.0 {
+        -bounds[4]
+    } else {
+        gap_x.hypot(gap_y)
+    }
+}
+
+/**
+ * Mapping between mask pixels and canvas coordinates
+ */
+struct PixelGrid {
+    x0: f32,
+    y0: f32,
+    w: usize,
+    h: usize,
+    /** Pixels per canvas unit */
+    scale: f32,
+}
+
+impl PixelGrid {
+    /**
+     * Pixel index range [first, end) that may be within a pixel of the
+     * canvas range [min, max] along one axis
+     */
+    fn span(&self, origin: f32, min: f32, max: f32, size: usize) -> (usize, usize) {
+        let first = ((min - origin) * self.scale - 1.0).floor().max(0.0);
+        let end = ((max - origin) * self.scale + 1.0).ceil().min(size as f32);
+        if first.is_nan() || end.is_nan() || end <= first {
+            return (0, 0);
+        }
+        (first as usize, end as usize)
+    }
+
+    /**
+     * Max-accumulate coverage from `distance` (signed, canvas units) over
+     * the pixels near `bounds` ([min_x, min_y, max_x, max_y])
+     */
+    fn cover(&self, mask: &mut [u8], bounds: [f32; 4], distance: impl Fn(f32, f32) -> f32) {
+        let (i0, i1) = self.span(self.x0, bounds[0], bounds[2], self.w);
+        let (j0, j1) = self.span(self.y0, bounds[1], bounds[3], self.h);
+        for j in j0..j1 {
+            let qy = self.y0 + (j as f32 + 0.5) / self.scale;
+            let row = &mut mask[j * self.w..(j + 1) * self.w];
+            for (i, pixel) in row.iter_mut().enumerate().take(i1).skip(i0) {
+                // Max accumulation: a full pixel cannot change
+                if *pixel == u8::MAX {
+                    continue;
+                }
+                let qx = self.x0 + (i as f32 + 0.5) / self.scale;
+                let coverage = (0.5 - distance(qx, qy) * self.scale).clamp(0.0, 1.0);
+                *pixel = (*pixel).max((coverage * 255.0).round() as u8);
+            }
+        }
+    }
+}
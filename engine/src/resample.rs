@@ -0,0 +1,296 @@
+/*!
+ * Arc-length resampling
+ *
+ * Stamp-based brushes need evenly spaced input regardless of how fast
+ * the pointer moved.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+use alloc::format;
+use alloc::vec::Vec;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::{polyline_length, segment_length};
+use crate::error::{require_finite, require_positive, CreoVexError, EngineError, EngineResult};
+#[cfg(feature = "std")]
+use crate::error::{run_export, run_vec};
+#[cfg(feature = "std")]
+use crate::validate::{with_float_validation, FloatValidation};
+#[cfg(feature = "std")]
+use crate::{read_points, read_strided_points, require_stride, serialize_points, serialize_strided_points, EngineBuffer};
+
+/**
+ * Largest point count a resampling call may produce
+ *
+ * Guards against a tiny spacing turning one stroke into an unbounded loop
+ * and allocation.
+ */
+pub(crate) const MAX_RESAMPLED_POINTS: usize = 1 << 22;
+
+/**
+ * Resample stroke at a fixed arc-length spacing
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, evenly spaced
+ *
+ * Walks the polyline from the first point and emits a point every
+ * `spacing` canvas units of arc length, with pressure linearly
+ * interpolated between the two source points bracketing it. The final
+ * source point is always emitted, so the last interval may be shorter than
+ * `spacing`. Zero-length segments (duplicate points) are skipped.
+ *
+ * Errors: the read_points errors, NonFiniteValue/InvalidParameter for a
+ * non-finite or non-positive spacing, and InvalidParameter when the
+ * spacing would produce more than MAX_RESAMPLED_POINTS points.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn resample_stroke(points_ptr: *const f32, points_len: usize, spacing: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        serialize_points(&resample_points(points, spacing)?)
+    })
+}
+
+/**
+ * resample_stroke from and to a Float32Array
+ *
+ * The default for pointer-up processing (see smooth_stroke_js); both
+ * forms run resample_points.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: as resample_stroke. A failure returns an empty array and sets
+ * last_error_code() / last_error_message().
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn resample_stroke_js(points: &[f32], spacing: f32, validation: Option<FloatValidation>) -> Vec<f32> {
+    with_float_validation(validation, || {
+        run_vec(|| resample_points(&read_points(points.as_ptr(), points.len())?, spacing))
+    })
+}
+
+/**
+ * Resample a stroke to exactly `count` points
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, `count` points
+ *
+ * The points sit at equal arc-length intervals, walked as in
+ * resample_stroke, with the first and last exactly the original
+ * endpoints and pressure interpolated. One point returns the start point,
+ * and a stroke of zero length (every point the same) returns `count`
+ * copies of it. A stroke whose length is not finite (NaN coordinates
+ * under FloatValidation::Passthrough, or coordinates so far apart the
+ * length overflows) still returns `count` points: source vertices at
+ * equal index steps, repeated as needed.
+ *
+ * Errors: the read_points errors, and InvalidParameter for a count of 0
+ * or above MAX_RESAMPLED_POINTS.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn resample_to_count(points_ptr: *const f32, points_len: usize, count: u32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        serialize_points(&resample_count_points(points, 3, count)?)
+    })
+}
+
+/**
+ * resample_to_count with extra per-point channels
+ *
+ * Input format: `stride` floats per point, [x, y, pressure, extra...]
+ * Output format: as smooth_stroke_n (point count, stride, points)
+ *
+ * Every channel after x/y is interpolated like pressure.
+ *
+ * Errors: InvalidParameter for a stride outside 3-8, then the
+ * read_points errors with `points_len` a multiple of `stride`, then the
+ * count errors of resample_to_count.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn resample_to_count_n(points_ptr: *const f32, points_len: usize, count: u32, stride: usize) -> EngineBuffer {
+    run_export(|| {
+        let stride = require_stride(stride)?;
+        let points = &read_strided_points(points_ptr, points_len, stride)?;
+        serialize_strided_points(&resample_count_points(points, stride, count)?, stride)
+    })
+}
+
+/**
+ * resample_to_count on validated points (shared with
+ * Stroke::resample_to_count)
+ */
+pub(crate) fn resample_count_points(points: &[f32], stride: usize, count: u32) -> EngineResult<Vec<f32>> {
+    let count = count as usize;
+    if count == 0 || count > MAX_RESAMPLED_POINTS {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("count must be 1-{MAX_RESAMPLED_POINTS} (got {count})"),
+        ));
+    }
+    Ok(resample_by_count(points, stride, count))
+}
+
+/**
+ * resample_stroke on validated points (shared with resample_stroke_js)
+ */
+pub(crate) fn resample_points(points: &[f32], spacing: f32) -> EngineResult<Vec<f32>> {
+    let spacing = require_positive("spacing", require_finite("spacing", spacing)?)?;
+
+    let total = polyline_length(points, 3);
+    let estimate = total / spacing as f64 + 2.0;
+    if estimate > MAX_RESAMPLED_POINTS as f64 {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("spacing {spacing} would produce about {estimate:.0} points (max {MAX_RESAMPLED_POINTS})"),
+        ));
+    }
+
+    Ok(resample_by_arc_length(points, 3, spacing as f64, total))
+}
+
+/**
+ * Emit points at k * spacing for every k with k * spacing < total, then
+ * the final source point
+ *
+ * Points are `stride` floats wide; every channel after x/y is
+ * interpolated like pressure.
+ */
+pub(crate) fn resample_by_arc_length(points: &[f32], stride: usize, spacing: f64, total: f64) -> Vec<f32> {
+    let interior = (total / spacing) as usize + 1;
+    walk_arc_length(points, stride, total, interior, |k| k as f64 * spacing)
+}
+
+/**
+ * Exactly `count` points at equal arc-length intervals, first and last
+ * exactly the source endpoints
+ *
+ * One point is the first source point; a stroke of zero length gives
+ * `count` copies of it. A length that is not finite (NaN coordinates, or
+ * segments overflowing f64) has no intervals to walk, so the points are
+ * source vertices picked at equal index steps instead, repeating
+ * vertices when `count` exceeds them. Points are `stride` floats wide,
+ * interpolated as in resample_by_arc_length.
+ */
+pub(crate) fn resample_by_count(points: &[f32], stride: usize, count: usize) -> Vec<f32> {
+    let total = polyline_length(points, stride);
+    if count == 1 || total == 0.0 {
+        return points[..stride].repeat(count);
+    }
+    if !total.is_finite() {
+        let last = points.len() / stride - 1;
+        let mut result = Vec::with_capacity(count * stride);
+        for k in 0..count {
+            // Integer steps, so the endpoints are exact and indices increase
+            let i = (k as u64 * last as u64 / (count - 1) as u64) as usize;
+            result.extend_from_slice(&points[i * stride..(i + 1) * stride]);
+        }
+        return result;
+    }
+
+    let result = walk_arc_length(points, stride, total, count - 2, |k| total * k as f64 / (count - 1) as f64);
+    debug_assert_eq!(result.len(), count * stride);
+    result
+}
+
+/**
+ * The first source point, a point at `target(k)` for k = 1, 2, ... up to
+ * `interior` while the target is short of `total`, then the final source
+ * point
+ *
+ * `total` is polyline_length(points, stride); targets must increase.
+ */
+fn walk_arc_length(
+    points: &[f32],
+    stride: usize,
+    total: f64,
+    interior: usize,
+    target: impl Fn(usize) -> f64,
+) -> Vec<f32> {
+    let mut result = Vec::with_capacity((interior + 2) * stride);
+    result.extend_from_slice(&points[..stride]);
+
+    let mut next = 1usize;
+    let mut walked = 0.0f64;
+    for (a, b) in points.chunks_exact(stride).zip(points.chunks_exact(stride).skip(1)) {
+        let length = segment_length(a, b);
+        if length == 0.0 {
+            continue;
+        }
+
+        // Targets are recomputed from k rather than accumulated to avoid drift
+        while next <= interior {
+            let target = target(next);
+            if target > walked + length || target >= total {
+                break;
+            }
+            let t = ((target - walked) / length) as f32;
+            result.extend(a.iter().zip(b).map(|(&a, &b)| a + (b - a) * t));
+            next += 1;
+        }
+        walked += length;
+    }
+
+    let last = points.len() - stride;
+    if points.len() > stride {
+        result.extend_from_slice(&points[last..]);
+    }
+    result
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_support::{sine_stroke, take_points};
+
+    #[test]
+    fn resample_to_count_hits_the_count_and_endpoints() {
+        let points = sine_stroke(40, 3.0, 10.0, 8.0);
+        for count in [1, 2, 3, 17, 40, 500] {
+            let result = take_points(resample_to_count(points.as_ptr(), points.len(), count));
+            assert_eq!(result.len(), count as usize * 3);
+            assert_eq!(result[..3], points[..3]);
+            if count > 1 {
+                assert_eq!(result[result.len() - 3..], points[points.len() - 3..]);
+            }
+        }
+    }
+
+    #[test]
+    fn resample_to_count_survives_an_overflowing_length() {
+        // x differences overflow f32, so the arc length is infinite
+        let points = [-3.0e38, 0.0, 0.2, 3.0e38, 0.0, 0.4, -3.0e38, 1.0, 0.6, 0.0, 0.0, 0.8];
+        assert!(polyline_length(&points, 3).is_infinite());
+
+        for count in [2, 3, 4, 9, 64] {
+            let result = take_points(resample_to_count(points.as_ptr(), points.len(), count));
+            assert_eq!(result.len(), count as usize * 3, "count {count}");
+            assert_eq!(result[..3], points[..3]);
+            assert_eq!(result[result.len() - 3..], points[points.len() - 3..]);
+            // Every point is a source vertex, in order
+            let mut previous = 0;
+            for point in result.chunks_exact(3) {
+                let index = points.chunks_exact(3).position(|source| source == point).unwrap();
+                assert!(index >= previous);
+                previous = index;
+            }
+        }
+    }
+
+    #[test]
+    fn resample_to_count_survives_nan_coordinates() {
+        let points = [0.0, 0.0, 1.0, f32::NAN, 1.0, 1.0, 5.0, 2.0, 1.0, 7.0, 0.0, 1.0];
+        let result = resample_by_count(&points, 3, 10);
+        assert_eq!(result.len(), 30);
+        assert_eq!(result[27..], points[9..]);
+    }
+}
@@ -0,0 +1,65 @@
+/*!
+ * Seeded random numbers
+ *
+ * Brush jitter, scatter, and roughening must look the same every time a
+ * stroke is replayed (undo/redo, document reload), so they never use a
+ * system RNG. Each call builds a generator from the caller's seed and
+ * draws a fixed number of values per item.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+/**
+ * Seeded splitmix64 generator
+ *
+ * Pure integer arithmetic, so a seed produces the same sequence on every
+ * platform and build.
+ */
+pub(crate) struct SeededRng(u64);
+
+impl SeededRng {
+    pub(crate) fn new(seed: u32) -> Self {
+        SeededRng(seed as u64)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /**
+     * Uniform value in [0, 1)
+     */
+    pub(crate) fn next_unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /**
+     * Uniform value in [-1, 1)
+     */
+    pub(crate) fn next_signed(&mut self) -> f32 {
+        (self.next_unit() * 2.0 - 1.0) as f32
+    }
+}
+
+/**
+ * Smooth 1D value noise in [-1, 1), continuous in `x`
+ *
+ * Seeded random values at the integers, blended with a smoothstep, so
+ * the value at any `x` depends only on `seed` and `x`, never on how many
+ * samples were taken before it.
+ */
+pub(crate) fn value_noise(seed: u32, x: f64) -> f64 {
+    let lattice = |cell: f64| {
+        let mixed = (cell as i64 as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93) ^ seed as u64;
+        SeededRng(mixed).next_unit() * 2.0 - 1.0
+    };
+    let cell = x.floor();
+    let t = x - cell;
+    let (a, b) = (lattice(cell), lattice(cell + 1.0));
+    a + (b - a) * t * t * (3.0 - 2.0 * t)
+}
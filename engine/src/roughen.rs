@@ -0,0 +1,81 @@
+/*!
+ * Stroke roughening
+ *
+ * Textured brushes (pencil, chalk) add controlled wobble after
+ * smoothing. The wobble is seeded noise along the arc length, so it is
+ * part of the stroke's data: replaying the same stroke and seed always
+ * draws the same line.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::cumulative_lengths;
+use crate::error::{require_finite, require_non_negative, require_positive, run_export};
+use crate::rng::value_noise;
+use crate::{read_points, serialize_points, EngineBuffer};
+
+/**
+ * Displace points sideways by seeded noise (pencil texture)
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, same point count
+ *
+ * Each point moves along its normal (perpendicular to the direction from
+ * the previous point to the next) by `amplitude` canvas units times a
+ * smooth value noise in [-1, 1) sampled at its arc length times
+ * `frequency` (noise cycles per canvas unit). Sampling by distance rather
+ * than by point index means the wobble follows the same path however
+ * densely the stroke is sampled, and the same `seed` always gives the
+ * same output.
+ *
+ * The first and last points stay fixed, easing into the full amplitude
+ * over one noise cycle (1 / `frequency`) at each end so the displacement
+ * has no jump. Pressure is unchanged, and points where the stroke has no
+ * direction (repeated points) are not moved.
+ *
+ * Errors: the read_points errors; NonFiniteValue/InvalidParameter for a
+ * negative or infinite `amplitude` or a non-positive or infinite
+ * `frequency`.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn roughen_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    amplitude: f32,
+    frequency: f32,
+    seed: u32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let amplitude = require_non_negative("amplitude", require_finite("amplitude", amplitude)?)? as f64;
+        let frequency = require_positive("frequency", require_finite("frequency", frequency)?)? as f64;
+
+        let table = cumulative_lengths(points, 3);
+        let total = table[table.len() - 1];
+        let count = table.len();
+        let mut roughened = points.to_vec();
+        if amplitude == 0.0 {
+            return serialize_points(&roughened);
+        }
+
+        for i in 1..count.saturating_sub(1) {
+            let (before, after) = (&points[(i - 1) * 3..i * 3], &points[(i + 1) * 3..(i + 2) * 3]);
+            let (dx, dy) = ((after[0] - before[0]) as f64, (after[1] - before[1]) as f64);
+            let length = dx.hypot(dy);
+            if length == 0.0 {
+                continue;
+            }
+
+            let s = table[i];
+            let ease = (s.min(total - s) * frequency).clamp(0.0, 1.0);
+            let offset = amplitude * ease * ease * (3.0 - 2.0 * ease) * value_noise(seed, s * frequency);
+            roughened[i * 3] = (points[i * 3] as f64 - dy / length * offset) as f32;
+            roughened[i * 3 + 1] = (points[i * 3 + 1] as f64 + dx / length * offset) as f32;
+        }
+        serialize_points(&roughened)
+    })
+}
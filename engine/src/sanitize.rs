@@ -0,0 +1,128 @@
+/*!
+ * Input sanitization
+ *
+ * Pointer event streams repeat points (coalesced events) and jitter in
+ * place. Those zero-length segments survive Chaikin as stacked vertices
+ * with no direction, which the outline and tessellation stages cannot
+ * offset. Sanitizing first leaves every segment with a length.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Input Stabilization)
+ */
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::segment_length;
+use crate::error::{CreoVexError, EngineError, EngineResult};
+#[cfg(feature = "std")]
+use crate::error::{require_finite, require_non_negative, run_export};
+#[cfg(feature = "std")]
+use crate::{borrow_strided_points, serialize_points, EngineBuffer};
+
+/**
+ * Merge distance smooth_stroke_ex uses when asked to sanitize, in canvas
+ * units
+ *
+ * Far below a visible pixel at any useful zoom, and far above f32
+ * rounding at canvas coordinates.
+ */
+pub(crate) const SMOOTH_SANITIZE_DISTANCE: f32 = 0.01;
+
+/**
+ * What sanitize_stroke does with a point that has a NaN or infinite value
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    /** Fail with NonFiniteValue */
+    Reject = 0,
+    /** Drop the point */
+    Skip = 1,
+}
+
+/**
+ * Remove duplicate, near-duplicate, and non-finite points
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: [x, y, p] points (see serialize_points)
+ *
+ * Walking the stroke, a point closer than `min_distance` to the last
+ * point kept (or at exactly the same position, whatever `min_distance`)
+ * is merged into it: the kept point takes the larger pressure. Distance
+ * is measured from the kept point, so slow drift still advances once it
+ * adds up to `min_distance`.
+ *
+ * The first and last finite points always survive. When the last one
+ * would merge, it replaces the kept point it merged into instead (unless
+ * that is the first point, which then stays with the last added after
+ * it, if they are at different positions). A stroke whose points all
+ * coincide comes back as one point.
+ *
+ * `non_finite` chooses between failing on a point with a NaN or infinite
+ * x, y, or pressure and dropping it; it takes the place of the
+ * engine-wide FloatValidation mode, which this export ignores.
+ *
+ * Errors: the read_points errors; NonFiniteValue/InvalidParameter for a
+ * negative or non-finite `min_distance`; NonFiniteValue for a non-finite
+ * point under NonFinitePolicy::Reject; TooFewPoints when
+ * NonFinitePolicy::Skip leaves no points.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn sanitize_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    min_distance: f32,
+    non_finite: NonFinitePolicy,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = borrow_strided_points(points_ptr, points_len, 3)?;
+        let min_distance = require_non_negative("min_distance", require_finite("min_distance", min_distance)?)?;
+
+        serialize_points(&sanitize_points(points, min_distance, non_finite)?)
+    })
+}
+
+/**
+ * sanitize_stroke's points, for exports that sanitize internally
+ */
+pub(crate) fn sanitize_points(
+    points: &[f32],
+    min_distance: f32,
+    non_finite: NonFinitePolicy,
+) -> EngineResult<Vec<f32>> {
+    let mut finite = Vec::with_capacity(points.len());
+    for (i, point) in points.chunks_exact(3).enumerate() {
+        if point.iter().all(|value| value.is_finite()) {
+            finite.push(point);
+        } else if non_finite == NonFinitePolicy::Reject {
+            return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("point {i} has a non-finite value")));
+        }
+    }
+    let Some((&first, rest)) = finite.split_first() else {
+        return Err(EngineError::new(CreoVexError::TooFewPoints, "stroke has no finite points"));
+    };
+
+    let merges = |a: &[f32], b: &[f32]| a[..2] == b[..2] || segment_length(a, b) < min_distance as f64;
+    let mut kept: Vec<[f32; 3]> = vec![[first[0], first[1], first[2]]];
+    for (i, &point) in rest.iter().enumerate() {
+        let last = kept.len() - 1;
+        let is_end = i == rest.len() - 1;
+        if !merges(&kept[last], point) {
+            kept.push([point[0], point[1], point[2]]);
+        } else if is_end && last > 0 {
+            // The end point wins over the kept point it merges into
+            kept[last] = [point[0], point[1], point[2].max(kept[last][2])];
+        } else if is_end && kept[0][..2] != point[..2] {
+            kept.push([point[0], point[1], point[2]]);
+        } else {
+            kept[last][2] = kept[last][2].max(point[2]);
+        }
+    }
+    Ok(kept.into_iter().flatten().collect())
+}
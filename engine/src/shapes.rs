@@ -0,0 +1,500 @@
+/*!
+ * Shape recognition
+ *
+ * Hold-to-snap: a finished scribble is fitted against a few clean
+ * primitives and, when one fits well enough, redrawn as that primitive.
+ * Fitting runs on an arc-length resampling of the stroke so dense, slow
+ * parts of the scribble do not outweigh fast ones.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::polyline_length;
+use crate::error::{require_finite, require_positive, run_export, CreoVexError, EngineError};
+use crate::resample::resample_by_arc_length;
+use crate::{read_points, read_strided_points, serialize_points, serialize_records, EngineBuffer};
+
+/**
+ * Primitive a stroke was recognized as
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeKind {
+    /** Nothing to fit (all points coincide) */
+    Unknown = 0,
+    /** Parameters: x0, y0, x1, y1 */
+    Line = 1,
+    /** Parameters: cx, cy, radius */
+    Circle = 2,
+    /** Parameters: cx, cy, rx, ry, rotation (radians) */
+    Ellipse = 3,
+    /** Parameters: cx, cy, half width, half height, rotation (radians) */
+    Rectangle = 4,
+    /** Parameters: x0, y0, x1, y1, x2, y2 */
+    Triangle = 5,
+}
+
+impl ShapeKind {
+    fn from_bits(bits: u32) -> Option<Self> {
+        Some(match bits {
+            0 => ShapeKind::Unknown,
+            1 => ShapeKind::Line,
+            2 => ShapeKind::Circle,
+            3 => ShapeKind::Ellipse,
+            4 => ShapeKind::Rectangle,
+            5 => ShapeKind::Triangle,
+            _ => return None,
+        })
+    }
+}
+
+/**
+ * Size of one shape record in 4-byte fields
+ *
+ * [kind (u32 bits), confident (u32 bits, 0/1), score, pressure, 6 parameters]
+ */
+pub(crate) const SHAPE_RECORD_FLOATS: usize = 10;
+
+/**
+ * Points the stroke is resampled to before fitting
+ */
+pub(crate) const SHAPE_SAMPLE_COUNT: usize = 64;
+
+/**
+ * Largest end-to-start gap, as a fraction of stroke length, that still
+ * counts as a closed stroke
+ *
+ * A full circle drawn with a small overshoot or gap is closed; a 270
+ * degree arc (gap about 0.3 of its length) is not.
+ */
+pub(crate) const SHAPE_CLOSED_GAP_FRACTION: f64 = 0.2;
+
+/**
+ * Error multiplier applied to the ellipse fit before comparing it with
+ * the circle, so a round scribble is not reported as a barely oval ellipse
+ */
+pub(crate) const SHAPE_ELLIPSE_BIAS: f64 = 1.2;
+
+/**
+ * Segment count range for curved shapes in shape_to_stroke
+ */
+pub(crate) const SHAPE_MIN_SEGMENTS: u32 = 8;
+pub(crate) const SHAPE_MAX_SEGMENTS: u32 = 1024;
+
+type Vec2 = [f64; 2];
+
+struct Fit {
+    kind: ShapeKind,
+    params: [f64; 6],
+    /** RMS distance of the samples to the shape's outline */
+    error: f64,
+}
+
+/**
+ * Fit a stroke against line, circle, ellipse, rectangle, and triangle
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format: one shape record (see serialize_records):
+ * - kind: ShapeKind discriminant (u32 bits)
+ * - confident: 1 when the fit is within `tolerance`, else 0 (u32 bits)
+ * - score: 1 - RMS error / bounding-box diagonal, clamped to 0-1
+ * - pressure: average input pressure
+ * - 6 parameters as documented at ShapeKind, unused ones 0
+ *
+ * Open strokes (end farther than SHAPE_CLOSED_GAP_FRACTION of the length
+ * from the start) are only fitted as lines, closed ones only as the
+ * closed shapes, so an open arc never snaps to a full circle. The best
+ * fit is always reported; `tolerance` is the largest RMS error, as a
+ * fraction of the bounding-box diagonal, that is still confident (0.05
+ * is a reasonable default). Callers should decline to snap when
+ * confident is 0.
+ *
+ * Errors: the read_points errors, TooFewPoints for a single point, and
+ * NonFiniteValue/InvalidParameter for a NaN or non-positive tolerance.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn recognize_shape(points_ptr: *const f32, points_len: usize, tolerance: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let tolerance = require_positive("tolerance", tolerance)? as f64;
+        if points.len() < 6 {
+            return Err(EngineError::new(
+                CreoVexError::TooFewPoints,
+                "shape recognition needs at least 2 points",
+            ));
+        }
+
+        let pressure = points.chunks_exact(3).map(|p| p[2] as f64).sum::<f64>() / (points.len() / 3) as f64;
+        let total = polyline_length(points, 3);
+        let mut record = [0.0f32; SHAPE_RECORD_FLOATS];
+        record[0] = f32::from_bits(ShapeKind::Unknown as u32);
+        record[1] = f32::from_bits(0);
+        record[3] = pressure as f32;
+        if total == 0.0 {
+            return serialize_records(1, &record);
+        }
+
+        let samples: Vec<Vec2> = resample_by_arc_length(points, 3, total / (SHAPE_SAMPLE_COUNT - 1) as f64, total)
+            .chunks_exact(3)
+            .map(|p| [p[0] as f64, p[1] as f64])
+            .collect();
+        let diagonal = diagonal(&samples);
+        let (first, last) = (samples[0], samples[samples.len() - 1]);
+        let closed = distance(first, last) <= SHAPE_CLOSED_GAP_FRACTION * total;
+
+        let best = if closed {
+            let circle = fit_circle(&samples);
+            let ellipse = fit_ellipse(&samples);
+            let hull = convex_hull(&samples);
+            [circle, ellipse, fit_rectangle(&samples, &hull), fit_triangle(&samples, &hull)]
+                .into_iter()
+                .flatten()
+                .min_by(|a, b| biased_error(a).total_cmp(&biased_error(b)))
+        } else {
+            Some(fit_line(&samples))
+        };
+
+        if let Some(fit) = best {
+            let relative = fit.error / diagonal;
+            record[0] = f32::from_bits(fit.kind as u32);
+            record[1] = f32::from_bits((relative <= tolerance) as u32);
+            record[2] = (1.0 - relative).clamp(0.0, 1.0) as f32;
+            for (field, value) in record[4..].iter_mut().zip(fit.params) {
+                *field = value as f32;
+            }
+        }
+        serialize_records(1, &record)
+    })
+}
+
+/**
+ * Emit the idealized shape of a recognize_shape record as a stroke
+ *
+ * Input format: the SHAPE_RECORD_FLOATS values of one record, as
+ * returned by recognize_shape (or built by the caller)
+ * Output format: [x, y, p] points (see serialize_points), all with the
+ * record's pressure
+ *
+ * Lines emit their two endpoints; rectangles and triangles their corners
+ * with the first repeated at the end. Circles and ellipses emit
+ * `segments` + 1 points around the outline (first repeated), `segments`
+ * clamped to SHAPE_MIN_SEGMENTS-SHAPE_MAX_SEGMENTS.
+ *
+ * Errors: NullInput, InvalidLength when `shape_len` is not
+ * SHAPE_RECORD_FLOATS, InvalidParameter for an unknown kind or
+ * ShapeKind::Unknown, and NonFiniteValue for a NaN or infinite parameter.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn shape_to_stroke(shape_ptr: *const f32, shape_len: usize, segments: u32) -> EngineBuffer {
+    run_export(|| {
+        let record = &read_strided_points(shape_ptr, shape_len, SHAPE_RECORD_FLOATS)?;
+        if record.len() != SHAPE_RECORD_FLOATS {
+            return Err(EngineError::new(
+                CreoVexError::InvalidLength,
+                format!("shape_len must be {SHAPE_RECORD_FLOATS} (got {shape_len})"),
+            ));
+        }
+        let kind = match ShapeKind::from_bits(record[0].to_bits()) {
+            Some(ShapeKind::Unknown) | None => {
+                return Err(EngineError::new(CreoVexError::InvalidParameter, "shape record has no shape"));
+            }
+            Some(kind) => kind,
+        };
+        let pressure = require_finite("pressure", record[3])?;
+        let mut params = [0.0f64; 6];
+        for (param, &value) in params.iter_mut().zip(&record[4..]) {
+            *param = require_finite("shape parameter", value)? as f64;
+        }
+
+        let outline = match kind {
+            ShapeKind::Line => vec![[params[0], params[1]], [params[2], params[3]]],
+            ShapeKind::Circle => ellipse_outline([params[0], params[1]], params[2], params[2], 0.0, segments),
+            ShapeKind::Ellipse => ellipse_outline([params[0], params[1]], params[2], params[3], params[4], segments),
+            ShapeKind::Rectangle => {
+                let corners = rectangle_corners([params[0], params[1]], params[2], params[3], params[4]);
+                closed_polygon(&corners)
+            }
+            ShapeKind::Triangle => {
+                closed_polygon(&[[params[0], params[1]], [params[2], params[3]], [params[4], params[5]]])
+            }
+            ShapeKind::Unknown => unreachable!("rejected above"),
+        };
+
+        let points: Vec<f32> = outline.iter().flat_map(|&[x, y]| [x as f32, y as f32, pressure]).collect();
+        serialize_points(&points)
+    })
+}
+
+/**
+ * Error used to rank candidates (see SHAPE_ELLIPSE_BIAS)
+ */
+fn biased_error(fit: &Fit) -> f64 {
+    match fit.kind {
+        ShapeKind::Ellipse => fit.error * SHAPE_ELLIPSE_BIAS,
+        _ => fit.error,
+    }
+}
+
+/**
+ * Segment from the first to the last sample
+ */
+fn fit_line(samples: &[Vec2]) -> Fit {
+    let (a, b) = (samples[0], samples[samples.len() - 1]);
+    Fit {
+        kind: ShapeKind::Line,
+        params: [a[0], a[1], b[0], b[1], 0.0, 0.0],
+        error: rms(samples, |p| segment_distance(p, a, b)),
+    }
+}
+
+/**
+ * Algebraic (Kasa) least-squares circle, solved on mean-centered samples
+ */
+fn fit_circle(samples: &[Vec2]) -> Option<Fit> {
+    let mean = mean(samples);
+    let (mut sxx, mut sxy, mut syy, mut sxz, mut syz, mut sz) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+    for &p in samples {
+        let (x, y) = (p[0] - mean[0], p[1] - mean[1]);
+        let z = x * x + y * y;
+        sxx += x * x;
+        sxy += x * y;
+        syy += y * y;
+        sxz += x * z;
+        syz += y * z;
+        sz += z;
+    }
+
+    // x^2 + y^2 + D x + E y + F = 0 with sum x = sum y = 0; det is 0 for collinear samples
+    let det = sxx * syy - sxy * sxy;
+    if det <= f64::EPSILON * sxx * syy {
+        return None;
+    }
+    let d = -(sxz * syy - syz * sxy) / det;
+    let e = -(syz * sxx - sxz * sxy) / det;
+    let f = -sz / samples.len() as f64;
+    let radius_sq = (d * d + e * e) / 4.0 - f;
+    if radius_sq <= 0.0 {
+        return None;
+    }
+
+    let center = [mean[0] - d / 2.0, mean[1] - e / 2.0];
+    let radius = radius_sq.sqrt();
+    Some(Fit {
+        kind: ShapeKind::Circle,
+        params: [center[0], center[1], radius, 0.0, 0.0, 0.0],
+        error: rms(samples, |p| (distance(p, center) - radius).abs()),
+    })
+}
+
+/**
+ * Ellipse aligned with the samples' principal axes, spanning their extent
+ * along each axis
+ */
+fn fit_ellipse(samples: &[Vec2]) -> Option<Fit> {
+    let mean = mean(samples);
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    for &p in samples {
+        let (x, y) = (p[0] - mean[0], p[1] - mean[1]);
+        sxx += x * x;
+        sxy += x * y;
+        syy += y * y;
+    }
+    let rotation = 0.5 * (2.0 * sxy).atan2(sxx - syy);
+    let (center, rx, ry) = oriented_extent(samples, rotation);
+    if rx <= 0.0 || ry <= 0.0 {
+        return None;
+    }
+
+    let (sin, cos) = rotation.sin_cos();
+    let error = rms(samples, |p| {
+        let (x, y) = (p[0] - center[0], p[1] - center[1]);
+        let (u, v) = (x * cos + y * sin, -x * sin + y * cos);
+        let rho = ((u / rx).powi(2) + (v / ry).powi(2)).sqrt();
+        // Distance along the ray from the center to the outline
+        if rho == 0.0 {
+            rx.min(ry)
+        } else {
+            (u.hypot(v) * (1.0 - 1.0 / rho)).abs()
+        }
+    });
+    Some(Fit { kind: ShapeKind::Ellipse, params: [center[0], center[1], rx, ry, rotation, 0.0], error })
+}
+
+/**
+ * Minimum-area bounding rectangle, which is aligned with a hull edge
+ */
+fn fit_rectangle(samples: &[Vec2], hull: &[Vec2]) -> Option<Fit> {
+    if hull.len() < 3 {
+        return None;
+    }
+    let (center, half_w, half_h, rotation) = (0..hull.len())
+        .map(|i| {
+            let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+            let rotation = (b[1] - a[1]).atan2(b[0] - a[0]);
+            let (center, half_w, half_h) = oriented_extent(hull, rotation);
+            (center, half_w, half_h, rotation)
+        })
+        .min_by(|a, b| (a.1 * a.2).total_cmp(&(b.1 * b.2)))?;
+    if half_w <= 0.0 || half_h <= 0.0 {
+        return None;
+    }
+    // A rectangle looks the same turned half way round
+    let rotation = rotation - std::f64::consts::PI * (rotation / std::f64::consts::PI).round();
+
+    let (sin, cos) = rotation.sin_cos();
+    let error = rms(samples, |p| {
+        let (x, y) = (p[0] - center[0], p[1] - center[1]);
+        let (u, v) = ((x * cos + y * sin).abs(), (-x * sin + y * cos).abs());
+        if u <= half_w && v <= half_h {
+            (half_w - u).min(half_h - v)
+        } else {
+            (u - half_w).max(0.0).hypot((v - half_h).max(0.0))
+        }
+    });
+    Some(Fit { kind: ShapeKind::Rectangle, params: [center[0], center[1], half_w, half_h, rotation, 0.0], error })
+}
+
+/**
+ * Largest-area triangle on the convex hull
+ */
+fn fit_triangle(samples: &[Vec2], hull: &[Vec2]) -> Option<Fit> {
+    let n = hull.len();
+    let mut best = (0.0f64, [0usize; 3]);
+    for i in 0..n {
+        for j in i + 1..n {
+            for k in j + 1..n {
+                let area = cross(hull[i], hull[j], hull[k]).abs();
+                if area > best.0 {
+                    best = (area, [i, j, k]);
+                }
+            }
+        }
+    }
+    if best.0 == 0.0 {
+        return None;
+    }
+
+    let [a, b, c] = best.1.map(|i| hull[i]);
+    let error = rms(samples, |p| {
+        segment_distance(p, a, b).min(segment_distance(p, b, c)).min(segment_distance(p, c, a))
+    });
+    Some(Fit { kind: ShapeKind::Triangle, params: [a[0], a[1], b[0], b[1], c[0], c[1]], error })
+}
+
+/**
+ * Center and half extents of `points` in the frame rotated by `rotation`
+ */
+fn oriented_extent(points: &[Vec2], rotation: f64) -> (Vec2, f64, f64) {
+    let (sin, cos) = rotation.sin_cos();
+    let (mut u_min, mut v_min) = (f64::INFINITY, f64::INFINITY);
+    let (mut u_max, mut v_max) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &[x, y] in points {
+        let (u, v) = (x * cos + y * sin, -x * sin + y * cos);
+        u_min = u_min.min(u);
+        u_max = u_max.max(u);
+        v_min = v_min.min(v);
+        v_max = v_max.max(v);
+    }
+    let (u, v) = ((u_min + u_max) / 2.0, (v_min + v_max) / 2.0);
+    ([u * cos - v * sin, u * sin + v * cos], (u_max - u_min) / 2.0, (v_max - v_min) / 2.0)
+}
+
+/**
+ * Convex hull in counter-clockwise order (monotone chain), collinear
+ * points dropped
+ */
+fn convex_hull(points: &[Vec2]) -> Vec<Vec2> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    // Lower chain left to right, then upper chain right to left
+    let mut hull: Vec<Vec2> = Vec::with_capacity(sorted.len() + 1);
+    let extend_chain = |hull: &mut Vec<Vec2>, chain: &mut dyn Iterator<Item = &Vec2>| {
+        let start = hull.len();
+        for &p in chain {
+            while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The chain's last point starts the other chain
+        hull.pop();
+    };
+    extend_chain(&mut hull, &mut sorted.iter());
+    extend_chain(&mut hull, &mut sorted.iter().rev());
+    hull
+}
+
+fn ellipse_outline(center: Vec2, rx: f64, ry: f64, rotation: f64, segments: u32) -> Vec<Vec2> {
+    let segments = segments.clamp(SHAPE_MIN_SEGMENTS, SHAPE_MAX_SEGMENTS);
+    let (sin, cos) = rotation.sin_cos();
+    let step = std::f64::consts::TAU / segments as f64;
+    (0..=segments)
+        .map(|i| {
+            // Index `segments` reuses angle 0 so the outline closes exactly
+            let (s, c) = (step * (i % segments) as f64).sin_cos();
+            let (u, v) = (rx * c, ry * s);
+            [center[0] + u * cos - v * sin, center[1] + u * sin + v * cos]
+        })
+        .collect()
+}
+
+fn rectangle_corners(center: Vec2, half_w: f64, half_h: f64, rotation: f64) -> [Vec2; 4] {
+    let (sin, cos) = rotation.sin_cos();
+    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(su, sv)| {
+        let (u, v) = (su * half_w, sv * half_h);
+        [center[0] + u * cos - v * sin, center[1] + u * sin + v * cos]
+    })
+}
+
+fn closed_polygon(corners: &[Vec2]) -> Vec<Vec2> {
+    corners.iter().chain(&corners[..1]).copied().collect()
+}
+
+fn rms(samples: &[Vec2], distance_to_shape: impl Fn(Vec2) -> f64) -> f64 {
+    (samples.iter().map(|&p| distance_to_shape(p).powi(2)).sum::<f64>() / samples.len() as f64).sqrt()
+}
+
+fn mean(points: &[Vec2]) -> Vec2 {
+    let n = points.len() as f64;
+    let (x, y) = points.iter().fold((0.0, 0.0), |(x, y), p| (x + p[0], y + p[1]));
+    [x / n, y / n]
+}
+
+fn diagonal(points: &[Vec2]) -> f64 {
+    let (min, max) = points.iter().fold(([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]), |(min, max), p| {
+        ([min[0].min(p[0]), min[1].min(p[1])], [max[0].max(p[0]), max[1].max(p[1])])
+    });
+    distance(min, max)
+}
+
+fn segment_distance(p: Vec2, a: Vec2, b: Vec2) -> f64 {
+    let d = [b[0] - a[0], b[1] - a[1]];
+    let length_sq = d[0] * d[0] + d[1] * d[1];
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((p[0] - a[0]) * d[0] + (p[1] - a[1]) * d[1]) / length_sq).clamp(0.0, 1.0)
+    };
+    distance(p, [a[0] + d[0] * t, a[1] + d[1] * t])
+}
+
+fn distance(a: Vec2, b: Vec2) -> f64 {
+    (b[0] - a[0]).hypot(b[1] - a[1])
+}
+
+/**
+ * Twice the signed area of triangle o-a-b (positive when counter-clockwise)
+ */
+fn cross(o: Vec2, a: Vec2, b: Vec2) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
@@ -0,0 +1,188 @@
+/*!
+ * wasm SIMD kernels
+ *
+ * Built only with `-C target-feature=+simd128` (see the README): Chaikin
+ * subdivision and affine transforms of [x, y, pressure] strokes then run
+ * four points per instruction. Every other build, and every other stride,
+ * uses the scalar loops in smooth.rs and transform.rs, which stay the
+ * reference: each lane performs the scalar expression's operations in
+ * the same order (wasm SIMD has no fused multiply-add), so both paths
+ * give bitwise identical results.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+#[cfg(target_feature = "simd128")]
+use alloc::vec::Vec;
+#[cfg(target_feature = "simd128")]
+use core::arch::wasm32::*;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/**
+ * Whether this build runs the SIMD kernels (compiled with simd128)
+ *
+ * For the JS loader to report which engine build it got; a build without
+ * it gives the same results, only slower.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn simd_available() -> bool {
+    cfg!(target_feature = "simd128")
+}
+
+/**
+ * SIMD chaikin_cuts for stride 3: four segments (12 cut points) per step
+ */
+#[cfg(target_feature = "simd128")]
+pub(crate) fn chaikin_cuts_xyz(points: &[f32], cut: f32, out: &mut Vec<f32>) {
+    let segments = (points.len() / 3).saturating_sub(1);
+    let blocks = segments / 4;
+    let (cut_v, keep_v) = (f32x4_splat(cut), f32x4_splat(1.0 - cut));
+    out.reserve(segments * 6);
+
+    for block in 0..blocks {
+        // a: the first points of four segments, b: their second points (one point later)
+        let base = block * 12;
+        let [a0, a1, a2] = [0, 4, 8].map(|offset| load(points, base + offset));
+        let [b0, b1, b2] = [3, 7, 11].map(|offset| load(points, base + offset));
+        let near = |a, b| f32x4_add(f32x4_mul(keep_v, a), f32x4_mul(cut_v, b));
+        let far = |a, b| f32x4_add(f32x4_mul(cut_v, a), f32x4_mul(keep_v, b));
+        let (p0, p1, p2) = (near(a0, b0), near(a1, b1), near(a2, b2));
+        let (q0, q1, q2) = (far(a0, b0), far(a1, b1), far(a2, b2));
+
+        // Interleave to p(segment 0), q(segment 0), p(segment 1), ...
+        let output = [
+            i32x4_shuffle::<0, 1, 2, 4>(p0, q0),
+            i32x4_shuffle::<0, 1, 2, 4>(i32x4_shuffle::<1, 2, 7, 0>(q0, p0), p1),
+            i32x4_shuffle::<0, 1, 4, 5>(i32x4_shuffle::<1, 7, 0, 0>(p1, q0), q1),
+            i32x4_shuffle::<0, 1, 2, 6>(i32x4_shuffle::<2, 3, 4, 0>(p1, p2), q1),
+            i32x4_shuffle::<0, 1, 5, 6>(i32x4_shuffle::<3, 4, 0, 0>(q1, q2), p2),
+            i32x4_shuffle::<3, 5, 6, 7>(p2, q2),
+        ];
+        append(out, &output);
+    }
+
+    crate::smooth::chaikin_cuts(&points[blocks * 12..], 3, cut, out);
+}
+
+/**
+ * SIMD Affine::apply for stride 3: four points per step
+ *
+ * Every lane is transformed where it sits: an x lane as
+ * m00 x + m01 y + m02 with its y brought alongside by one shuffle, a y
+ * lane as m11 y + m10 x + m12 (the same sum), and pressure lanes take
+ * p * pressure_scale through a lane mask.
+ */
+#[cfg(target_feature = "simd128")]
+pub(crate) fn affine_xyz(points: &mut [f32], m: [f32; 6], pressure_scale: f32) {
+    let [m00, m01, m02, m10, m11, m12] = m;
+    let scale = f32x4_splat(pressure_scale);
+    let pressure = |lanes: [bool; 4]| {
+        let [a, b, c, d] = lanes.map(|lane| if lane { -1 } else { 0 });
+        i32x4(a, b, c, d)
+    };
+    // Per input vector, by lane: [x y p x], [y p x y], [p x y p]
+    let own = [f32x4(m00, m11, 0.0, m00), f32x4(m11, 0.0, m00, m11), f32x4(0.0, m00, m11, 0.0)];
+    let partner = [f32x4(m01, m10, 0.0, m01), f32x4(m10, 0.0, m01, m10), f32x4(0.0, m01, m10, 0.0)];
+    let offset = [f32x4(m02, m12, 0.0, m02), f32x4(m12, 0.0, m02, m12), f32x4(0.0, m02, m12, 0.0)];
+    let pressure_lanes = [
+        pressure([false, false, true, false]),
+        pressure([false, true, false, false]),
+        pressure([true, false, false, true]),
+    ];
+    let blocks = points.len() / 12;
+
+    for chunk in points[..blocks * 12].chunks_exact_mut(12) {
+        let ptr = chunk.as_mut_ptr() as *mut v128;
+        let [v0, v1, v2] = [0, 1, 2].map(|i| unsafe { v128_load(ptr.add(i)) });
+        // The other coordinate of each x/y lane's point
+        let partners = [
+            i32x4_shuffle::<1, 0, 0, 4>(v0, v1),
+            i32x4_shuffle::<7, 0, 3, 2>(v1, v0),
+            i32x4_shuffle::<0, 2, 1, 0>(v2, v2),
+        ];
+        for (i, (v, w)) in [v0, v1, v2].into_iter().zip(partners).enumerate() {
+            let moved = f32x4_add(f32x4_add(f32x4_mul(own[i], v), f32x4_mul(partner[i], w)), offset[i]);
+            let result = v128_bitselect(f32x4_mul(v, scale), moved, pressure_lanes[i]);
+            unsafe { v128_store(ptr.add(i), result) };
+        }
+    }
+
+    crate::transform::affine_scalar(&mut points[blocks * 12..], 3, m, pressure_scale);
+}
+
+/**
+ * Four floats from `values[offset..offset + 4]` (unaligned)
+ */
+#[cfg(target_feature = "simd128")]
+fn load(values: &[f32], offset: usize) -> v128 {
+    assert!(offset + 4 <= values.len());
+    unsafe { v128_load(values.as_ptr().add(offset) as *const v128) }
+}
+
+/**
+ * Push the lanes of `vectors` onto `out`, which has room for them
+ */
+#[cfg(target_feature = "simd128")]
+fn append(out: &mut Vec<f32>, vectors: &[v128]) {
+    assert!(out.capacity() - out.len() >= vectors.len() * 4);
+    let len = out.len();
+    for (i, &vector) in vectors.iter().enumerate() {
+        unsafe { v128_store(out.as_mut_ptr().add(len + i * 4) as *mut v128, vector) };
+    }
+    unsafe { out.set_len(len + vectors.len() * 4) };
+}
+
+/*
+ * Run with the scalar loops as the oracle, on a wasm runtime:
+ * RUSTFLAGS="-C target-feature=+simd128" cargo test --target wasm32-wasip1 simd
+ */
+#[cfg(all(test, target_feature = "simd128"))]
+mod tests {
+    use super::*;
+    use crate::test_support::Noise;
+
+    /**
+     * `count` points of mixed magnitudes, signs, and subnormals
+     */
+    fn stroke(noise: &mut Noise, count: usize) -> Vec<f32> {
+        let scales = [1.0, 1.0e-3, 1.0e6, 1.0e-40, 3.0e38];
+        (0..count * 3).map(|i| noise.next() * scales[i % scales.len()]).collect()
+    }
+
+    fn bits(values: &[f32]) -> Vec<u32> {
+        values.iter().map(|value| value.to_bits()).collect()
+    }
+
+    #[test]
+    fn chaikin_cuts_match_the_scalar_path() {
+        let mut noise = Noise::new(87);
+        // 0-40 points covers empty input, partial blocks, and the scalar tail
+        for count in 0..=40 {
+            let points = stroke(&mut noise, count);
+            for cut in [0.25, 0.1, 0.5, noise.next().abs()] {
+                let (mut simd, mut scalar) = (Vec::new(), Vec::new());
+                chaikin_cuts_xyz(&points, cut, &mut simd);
+                crate::smooth::chaikin_cuts(&points, 3, cut, &mut scalar);
+                assert_eq!(bits(&simd), bits(&scalar), "{count} points, cut {cut}");
+            }
+        }
+    }
+
+    #[test]
+    fn affine_transforms_match_the_scalar_path() {
+        let mut noise = Noise::new(87);
+        for count in 0..=40 {
+            let points = stroke(&mut noise, count);
+            let m: [f32; 6] = std::array::from_fn(|_| noise.next() * 100.0);
+            let pressure_scale = noise.next() + 1.0;
+            let (mut simd, mut scalar) = (points.clone(), points);
+            affine_xyz(&mut simd, m, pressure_scale);
+            crate::transform::affine_scalar(&mut scalar, 3, m, pressure_scale);
+            assert_eq!(bits(&simd), bits(&scalar), "{count} points, matrix {m:?}");
+        }
+    }
+}
@@ -0,0 +1,773 @@
+/*!
+ * Stroke simplification (Ramer-Douglas-Peucker, Visvalingam-Whyatt)
+ *
+ * RDP keeps every point that deviates from the chord, spikes included;
+ * Visvalingam-Whyatt drops points by the area they add, which reads
+ * smoother on decorative strokes. Both can also rank points so a stroke
+ * fits a fixed point budget.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Simplification, 0.3px tolerance)
+ */
+
+use alloc::collections::BinaryHeap;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::corners::find_corners;
+use crate::error::{require_finite, require_non_negative, CreoVexError, EngineError, EngineResult};
+#[cfg(feature = "std")]
+use crate::error::{require_positive, run_export, run_status, run_vec};
+use crate::float::Float;
+#[cfg(feature = "std")]
+use crate::validate::{with_float_validation, FloatValidation};
+#[cfg(feature = "std")]
+use crate::{read_points, serialize_points, serialize_points_f64, serialize_strokes, EngineBuffer};
+
+/**
+ * Most levels one LOD pyramid may have
+ */
+pub(crate) const MAX_LOD_LEVELS: usize = 16;
+
+/**
+ * On-screen deviation, in pixels, a recommended LOD level may introduce
+ *
+ * Matches the simplification tolerance of the architecture document.
+ */
+pub(crate) const LOD_SCREEN_TOLERANCE_PX: f32 = 0.3;
+
+/**
+ * Strokes smaller than this on screen, in pixels, get the coarsest level
+ */
+pub(crate) const LOD_MIN_SCREEN_EXTENT_PX: f32 = 1.0;
+
+/**
+ * Simplify stroke using the Ramer-Douglas-Peucker algorithm
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, reduced point set
+ *
+ * `epsilon` is the maximum allowed deviation in canvas units. Surviving
+ * points keep their original pressure (no re-interpolation), and both
+ * endpoints are always preserved. A degenerate stroke (all points
+ * identical) collapses to its two endpoints.
+ *
+ * Points that detect_corners reports for `corner_angle_deg` are kept as
+ * anchors: each span between them is simplified on its own, so corners
+ * of architectural sketches are never cut. 0 disables corner
+ * preservation, and so does NaN: that is what JS passes when the
+ * argument is left out, so callers of the three-argument form from
+ * before corner preservation keep their results.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a NaN or negative epsilon and InvalidParameter for a negative
+ * corner angle.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn simplify_stroke(points_ptr: *const f32, points_len: usize, epsilon: f32, corner_angle_deg: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        serialize_points(&simplify_points(points, epsilon, corner_angle_deg)?)
+    })
+}
+
+/**
+ * simplify_stroke from and to a Float32Array
+ *
+ * The default for pointer-up processing (see smooth_stroke_js); both
+ * forms run simplify_points.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: as simplify_stroke. A failure returns an empty array and sets
+ * last_error_code() / last_error_message().
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn simplify_stroke_js(
+    points: &[f32],
+    epsilon: f32,
+    corner_angle_deg: f32,
+    validation: Option<FloatValidation>,
+) -> Vec<f32> {
+    with_float_validation(validation, || {
+        run_vec(|| simplify_points(&read_points(points.as_ptr(), points.len())?, epsilon, corner_angle_deg))
+    })
+}
+
+/**
+ * simplify_stroke on validated points (shared with simplify_stroke_js)
+ */
+pub(crate) fn simplify_points(points: &[f32], epsilon: f32, corner_angle_deg: f32) -> EngineResult<Vec<f32>> {
+    let epsilon = require_non_negative("epsilon", epsilon)?;
+    let corner_angle_deg = corner_angle_or_off(corner_angle_deg)?;
+
+    // Nothing to remove with fewer than 3 points
+    if points.len() < 9 {
+        return Ok(points.to_vec());
+    }
+
+    let anchors = find_corners(points, 3, corner_angle_deg.to_radians());
+    Ok(simplify_spans(points, &anchors, |span| rdp_simplify(span, epsilon)))
+}
+
+/**
+ * A validated corner_angle_deg, with NaN (an omitted JS argument) as 0
+ */
+fn corner_angle_or_off<F: Float>(corner_angle_deg: F) -> EngineResult<F> {
+    if corner_angle_deg.is_nan() {
+        return Ok(F::ZERO);
+    }
+    require_non_negative("corner_angle_deg", corner_angle_deg)
+}
+
+/**
+ * Simplify stroke with Ramer-Douglas-Peucker in f64 precision
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, ...] as f64 values
+ * Output format: see serialize_points_f64
+ *
+ * simplify_stroke computed in f64 (see smooth_stroke_f64 for when that
+ * matters); `epsilon` and `corner_angle_deg` mean the same.
+ *
+ * Errors: as simplify_stroke.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn simplify_stroke_f64(
+    points_ptr: *const f64,
+    points_len: usize,
+    epsilon: f64,
+    corner_angle_deg: f64,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let epsilon = require_non_negative("epsilon", epsilon)?;
+        let corner_angle_deg = corner_angle_or_off(corner_angle_deg)?;
+
+        if points.len() < 9 {
+            return serialize_points_f64(points);
+        }
+
+        let anchors = find_corners(points, 3, corner_angle_deg.to_radians());
+        serialize_points_f64(&simplify_spans(points, &anchors, |span| rdp_simplify(span, epsilon)))
+    })
+}
+
+/**
+ * Simplify stroke with RDP, never removing caller-chosen points
+ *
+ * Input format:
+ * - points: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * - anchors: u32 point indices, any order (duplicates and the endpoints
+ *   are allowed and change nothing)
+ *
+ * Output format: same structure as the points, reduced point set
+ *
+ * Like simplify_stroke with corner preservation, but the anchors are
+ * given (e.g. the node editor's user-placed control points): every
+ * anchor survives, and each span between consecutive anchors is
+ * simplified independently with `epsilon`. An empty anchor list
+ * (`anchors_len` 0, pointer unused) is plain simplify_stroke.
+ *
+ * Errors: the read_points errors; NullInput for a null `anchors_ptr`
+ * with a non-zero `anchors_len`; InvalidParameter for an anchor index
+ * past the last point; NonFiniteValue/InvalidParameter for a NaN or
+ * negative epsilon.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn simplify_between_anchors(
+    points_ptr: *const f32,
+    points_len: usize,
+    anchors_ptr: *const u32,
+    anchors_len: usize,
+    epsilon: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let epsilon = require_non_negative("epsilon", epsilon)?;
+        if anchors_len > 0 && anchors_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "anchors_ptr is null"));
+        }
+        let given: &[u32] =
+            if anchors_len == 0 { &[] } else { unsafe { core::slice::from_raw_parts(anchors_ptr, anchors_len) } };
+
+        let count = points.len() / 3;
+        if let Some(&index) = given.iter().find(|&&index| index as usize >= count) {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("anchor {index} is past the last point ({})", count - 1),
+            ));
+        }
+        let mut anchors: Vec<usize> =
+            given.iter().map(|&index| index as usize).filter(|&index| index > 0 && index < count - 1).collect();
+        anchors.sort_unstable();
+        anchors.dedup();
+
+        let simplified = simplify_spans(points, &anchors, |span| rdp_simplify(span, epsilon));
+        serialize_points(&simplified)
+    })
+}
+
+/**
+ * Simplification algorithm for simplify_stroke_to_count
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimplifyMethod {
+    /** Ramer-Douglas-Peucker (simplify_stroke) */
+    Rdp = 0,
+    /** Visvalingam-Whyatt (simplify_stroke_vw) */
+    VisvalingamWhyatt = 1,
+}
+
+/**
+ * Simplify stroke using the Visvalingam-Whyatt algorithm
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, reduced point set
+ *
+ * Repeatedly removes the interior point whose triangle with its two
+ * current neighbors has the smallest area, re-measuring the neighbors
+ * after each removal, until every remaining triangle is larger than
+ * `min_triangle_area` (canvas units squared; 0 removes only repeated
+ * points and points on a straight line). A removal never gives a later
+ * one a smaller area (areas are taken as at least the last one removed),
+ * so raising `min_triangle_area` only ever removes more.
+ * Surviving points keep their pressure and both endpoints are kept; a
+ * degenerate stroke collapses to its two endpoints. `corner_angle_deg`
+ * keeps detected corners as in simplify_stroke.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a NaN or negative min_triangle_area and InvalidParameter for a
+ * negative corner angle (NaN disables corners, as in simplify_stroke).
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn simplify_stroke_vw(
+    points_ptr: *const f32,
+    points_len: usize,
+    min_triangle_area: f32,
+    corner_angle_deg: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let min_triangle_area = require_non_negative("min_triangle_area", min_triangle_area)? as f64;
+        let corner_angle_deg = corner_angle_or_off(corner_angle_deg)?;
+
+        let anchors = find_corners(points, 3, corner_angle_deg.to_radians());
+        let simplified = simplify_spans(points, &anchors, |span| {
+            let ranks = point_ranks(span, SimplifyMethod::VisvalingamWhyatt);
+            kept_points(span, |i| ranks[i] > min_triangle_area)
+        });
+        serialize_points(&simplified)
+    })
+}
+
+/**
+ * Simplify stroke down to a point budget
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, at most `max_points` points
+ *
+ * Keeps the `max_points` most significant points under `method`, in
+ * their original order: for Visvalingam-Whyatt, points are removed
+ * smallest area first until the budget is met; for RDP, the chord with
+ * the farthest point is split first until the budget is used (so the
+ * result matches simplify_stroke at the tolerance where it has that many
+ * points). The endpoints always count toward the budget, pressure travels
+ * with the surviving points, and a stroke already within the budget is
+ * returned unchanged.
+ *
+ * Errors: the read_points errors, plus InvalidParameter for a
+ * `max_points` below 2.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn simplify_stroke_to_count(
+    points_ptr: *const f32,
+    points_len: usize,
+    max_points: u32,
+    method: SimplifyMethod,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        if max_points < 2 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("max_points must be at least 2 to keep both endpoints (got {max_points})"),
+            ));
+        }
+        let count = points.len() / 3;
+        if count <= max_points as usize {
+            return serialize_points(points);
+        }
+
+        // Most significant first; ties keep the earlier point
+        let ranks = point_ranks(points, method);
+        let mut order: Vec<usize> = (0..count).collect();
+        order.sort_by(|&a, &b| ranks[b].total_cmp(&ranks[a]).then(a.cmp(&b)));
+        let mut keep = vec![false; count];
+        for &index in &order[..max_points as usize] {
+            keep[index] = true;
+        }
+        serialize_points(&kept_points(points, |i| keep[i]))
+    })
+}
+
+/**
+ * Build a level-of-detail pyramid of a stroke
+ *
+ * Input format:
+ * - points: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * - levels: [epsilon0, epsilon1, ...] in canvas units, non-decreasing
+ *
+ * Output format: the batch container (see read_batch) holding one
+ * stroke per level, in level order, without a status array; the
+ * per-stroke point counts give each level's size
+ *
+ * Level i is simplify_stroke with epsilon levels[i], always run on the
+ * full stroke (not the previous level), so each level is within its own
+ * epsilon of the original. Surviving points keep their pressure and the
+ * endpoints are kept at every level, so widths stay close at any zoom.
+ * Pick a level with recommend_lod_level.
+ *
+ * Errors: the read_points errors; NullInput or InvalidLength (0 or more
+ * than MAX_LOD_LEVELS entries) for the levels buffer; NonFiniteValue or
+ * InvalidParameter for an epsilon that is NaN, infinite, negative, or
+ * smaller than the one before it.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_lod(
+    points_ptr: *const f32,
+    points_len: usize,
+    levels_ptr: *const f32,
+    levels_len: usize,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let levels = read_lod_levels(levels_ptr, levels_len)?;
+
+        let pyramid: Vec<Vec<f32>> = levels
+            .iter()
+            .map(|&epsilon| if points.len() < 9 { points.to_vec() } else { rdp_simplify(points, epsilon) })
+            .collect();
+        serialize_strokes(&pyramid)
+    })
+}
+
+/**
+ * Recommend which generate_lod level to draw at a zoom factor
+ *
+ * `levels` are the epsilons the pyramid was built with; the bounds are
+ * the stroke's canvas-space box (e.g. from stroke_bounds) and `zoom` is
+ * screen pixels per canvas unit.
+ *
+ * Returns the coarsest level whose epsilon stays within
+ * LOD_SCREEN_TOLERANCE_PX on screen, 0 when none does (the finest level
+ * available), or the last level when the whole stroke is smaller than
+ * LOD_MIN_SCREEN_EXTENT_PX on screen. On failure returns the negated
+ * CreoVexError discriminant.
+ *
+ * Errors: the generate_lod levels errors, NonFiniteValue/InvalidParameter
+ * for a zoom that is not positive and finite, NonFiniteValue for a
+ * non-finite bound, and InvalidParameter when a max bound is below its
+ * min.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn recommend_lod_level(
+    levels_ptr: *const f32,
+    levels_len: usize,
+    zoom: f32,
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+) -> i32 {
+    run_status(|| {
+        let levels = read_lod_levels(levels_ptr, levels_len)?;
+        let zoom = require_positive("zoom", require_finite("zoom", zoom)?)?;
+        let (min_x, min_y) = (require_finite("min_x", min_x)?, require_finite("min_y", min_y)?);
+        let (max_x, max_y) = (require_finite("max_x", max_x)?, require_finite("max_y", max_y)?);
+        if max_x < min_x || max_y < min_y {
+            return Err(EngineError::new(CreoVexError::InvalidParameter, "bounds max is below min"));
+        }
+
+        let extent = (max_x - min_x).max(max_y - min_y);
+        if extent * zoom < LOD_MIN_SCREEN_EXTENT_PX {
+            return Ok(levels.len() - 1);
+        }
+        let tolerance = LOD_SCREEN_TOLERANCE_PX / zoom;
+        Ok(levels.iter().rposition(|&epsilon| epsilon <= tolerance).unwrap_or(0))
+    })
+}
+
+/**
+ * Validate a buffer of LOD epsilons (1-MAX_LOD_LEVELS, non-decreasing)
+ */
+fn read_lod_levels<'a>(levels_ptr: *const f32, levels_len: usize) -> EngineResult<&'a [f32]> {
+    if levels_ptr.is_null() {
+        return Err(EngineError::new(CreoVexError::NullInput, "levels_ptr is null"));
+    }
+    if levels_len == 0 || levels_len > MAX_LOD_LEVELS {
+        return Err(EngineError::new(
+            CreoVexError::InvalidLength,
+            format!("levels_len must be 1-{MAX_LOD_LEVELS} (got {levels_len})"),
+        ));
+    }
+    let levels: &'a [f32] = unsafe { core::slice::from_raw_parts(levels_ptr, levels_len) };
+
+    let mut previous = 0.0f32;
+    for (index, &epsilon) in levels.iter().enumerate() {
+        let epsilon = require_non_negative("level epsilon", require_finite("level epsilon", epsilon)?)?;
+        if epsilon < previous {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("level {index} epsilon {epsilon} is below the previous level's {previous}"),
+            ));
+        }
+        previous = epsilon;
+    }
+    Ok(levels)
+}
+
+/**
+ * Simplify each span between anchors (ascending interior point indices)
+ * on its own; spans share their anchor, which `simplify` must keep as an
+ * endpoint
+ */
+fn simplify_spans<F: Float>(points: &[F], anchors: &[usize], simplify: impl Fn(&[F]) -> Vec<F>) -> Vec<F> {
+    if anchors.is_empty() {
+        return simplify(points);
+    }
+
+    let mut result = Vec::with_capacity(points.len());
+    let mut start = 0;
+    for &end in anchors.iter().chain(core::iter::once(&(points.len() / 3 - 1))) {
+        let span = simplify(&points[start * 3..(end + 1) * 3]);
+        let skip = if start == 0 { 0 } else { 3 };
+        result.extend_from_slice(&span[skip..]);
+        start = end;
+    }
+    result
+}
+
+/**
+ * Ramer-Douglas-Peucker over [x, y, pressure] triplets
+ *
+ * Uses an explicit work stack instead of recursion so very long strokes
+ * cannot overflow the (small) wasm stack. Distances are measured against
+ * the segment rather than the infinite line, so strokes that double back
+ * past their chord endpoints are not collapsed.
+ */
+fn rdp_simplify<F: Float>(points: &[F], epsilon: F) -> Vec<F> {
+    let count = points.len() / 3;
+    let mut keep = vec![false; count];
+    keep[0] = true;
+    keep[count - 1] = true;
+
+    let epsilon_sq = epsilon * epsilon;
+    let mut stack = vec![(0usize, count - 1)];
+
+    while let Some((first, last)) = stack.pop() {
+        if last <= first + 1 {
+            continue;
+        }
+
+        let (ax, ay) = (points[first * 3], points[first * 3 + 1]);
+        let (bx, by) = (points[last * 3], points[last * 3 + 1]);
+
+        // Find the interior point farthest from the current chord
+        let mut max_dist_sq = -F::ONE;
+        let mut max_index = first;
+        for i in (first + 1)..last {
+            let d = segment_distance_sq(points[i * 3], points[i * 3 + 1], ax, ay, bx, by);
+            if d > max_dist_sq {
+                max_dist_sq = d;
+                max_index = i;
+            }
+        }
+
+        if max_dist_sq > epsilon_sq {
+            keep[max_index] = true;
+            stack.push((first, max_index));
+            stack.push((max_index, last));
+        }
+    }
+
+    let mut result = Vec::with_capacity(points.len());
+    for (i, point) in points.chunks_exact(3).enumerate() {
+        if keep[i] {
+            result.extend_from_slice(point);
+        }
+    }
+    result
+}
+
+/**
+ * Significance of every point under `method`: the tolerance (RDP
+ * distance, or VW area) up to which the point survives
+ *
+ * Ranks are nested: a point survives every tolerance below its rank and
+ * none above it, so thresholding them and keeping the top N both give
+ * a consistent simplification. The endpoints rank infinity.
+ */
+fn point_ranks(points: &[f32], method: SimplifyMethod) -> Vec<f64> {
+    let count = points.len() / 3;
+    let mut ranks = vec![f64::INFINITY; count];
+    if count < 3 {
+        return ranks;
+    }
+    match method {
+        SimplifyMethod::Rdp => rdp_ranks(points, &mut ranks),
+        SimplifyMethod::VisvalingamWhyatt => vw_ranks(points, &mut ranks),
+    }
+    ranks
+}
+
+/**
+ * RDP ranks: chords are split farthest point first, and a point's rank
+ * is its distance capped by the rank of the split that made its chord
+ *
+ * Non-finite distances (NaN coordinates let through by
+ * FloatValidation::Passthrough, or distances overflowing f32) never
+ * split a chord: a chord with only those left stops splitting, and its
+ * interior points rank lowest (negative infinity).
+ */
+fn rdp_ranks(points: &[f32], ranks: &mut [f64]) {
+    let farthest = |first: usize, last: usize| {
+        let (ax, ay) = (points[first * 3], points[first * 3 + 1]);
+        let (bx, by) = (points[last * 3], points[last * 3 + 1]);
+        let mut best: Option<(f32, usize)> = None;
+        for i in (first + 1)..last {
+            let d = segment_distance_sq(points[i * 3], points[i * 3 + 1], ax, ay, bx, by);
+            if d.is_finite() && best.is_none_or(|(max, _)| d > max) {
+                best = Some((d, i));
+            }
+        }
+        best.map(|(d, i)| (d.sqrt() as f64, i))
+    };
+
+    // Chords waiting to be split, with the rank capping their points
+    let mut pending = vec![(0, ranks.len() - 1, f64::INFINITY)];
+    let mut chords = BinaryHeap::new();
+    loop {
+        for (first, last, cap) in pending.drain(..) {
+            if last <= first + 1 {
+                continue;
+            }
+            match farthest(first, last) {
+                Some((distance, split)) => chords.push(Ranked { rank: distance.min(cap), index: split, first, last }),
+                None => ranks[first + 1..last].fill(f64::NEG_INFINITY),
+            }
+        }
+        let Some(Ranked { rank, index, first, last }) = chords.pop() else {
+            break;
+        };
+        ranks[index] = rank;
+        pending.extend([(first, index, rank), (index, last, rank)]);
+    }
+}
+
+/**
+ * VW ranks: each point's effective area when it is removed, smallest
+ * first, at least the area of the removal before it
+ */
+fn vw_ranks(points: &[f32], ranks: &mut [f64]) {
+    let count = ranks.len();
+    let mut prev: Vec<usize> = (0..count).map(|i| i.wrapping_sub(1)).collect();
+    let mut next: Vec<usize> = (1..=count).collect();
+    let area = |a: usize, b: usize, c: usize| {
+        let (ax, ay) = (points[a * 3] as f64, points[a * 3 + 1] as f64);
+        let (bx, by) = (points[b * 3] as f64, points[b * 3 + 1] as f64);
+        let (cx, cy) = (points[c * 3] as f64, points[c * 3 + 1] as f64);
+        ((bx - ax) * (cy - ay) - (by - ay) * (cx - ax)).abs() / 2.0
+    };
+
+    // Min-heap of current areas; entries go stale when a neighbor is removed
+    let mut current: Vec<f64> =
+        (0..count).map(|i| if i == 0 || i == count - 1 { f64::INFINITY } else { area(i - 1, i, i + 1) }).collect();
+    let mut heap: BinaryHeap<Ranked> =
+        (1..count - 1).map(|i| Ranked { rank: -current[i], index: i, first: 0, last: 0 }).collect();
+    let mut floor = 0.0f64;
+    while let Some(Ranked { rank, index, .. }) = heap.pop() {
+        if ranks[index].is_finite() || -rank != current[index] {
+            continue;
+        }
+        floor = floor.max(-rank);
+        ranks[index] = floor;
+
+        let (p, n) = (prev[index], next[index]);
+        next[p] = n;
+        prev[n] = p;
+        for neighbor in [p, n] {
+            if neighbor != 0 && neighbor != count - 1 {
+                current[neighbor] = area(prev[neighbor], neighbor, next[neighbor]);
+                heap.push(Ranked { rank: -current[neighbor], index: neighbor, first: 0, last: 0 });
+            }
+        }
+    }
+}
+
+/**
+ * Heap entry ordered by `rank` (ties: lower index first); `first`/`last`
+ * carry the chord for RDP
+ */
+struct Ranked {
+    rank: f64,
+    index: usize,
+    first: usize,
+    last: usize,
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank.total_cmp(&other.rank).then(other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+/**
+ * The points whose index satisfies `keep`
+ */
+fn kept_points(points: &[f32], keep: impl Fn(usize) -> bool) -> Vec<f32> {
+    points.chunks_exact(3).enumerate().filter(|&(i, _)| keep(i)).flat_map(|(_, point)| point.iter().copied()).collect()
+}
+
+/**
+ * Squared distance from (px, py) to the segment (ax, ay)-(bx, by)
+ *
+ * Zero-length segments fall back to point distance.
+ */
+fn segment_distance_sq<F: Float>(px: F, py: F, ax: F, ay: F, bx: F, by: F) -> F {
+    let dx = bx - ax;
+    let dy = by - ay;
+    let len_sq = dx * dx + dy * dy;
+
+    let (cx, cy) = if len_sq > F::ZERO {
+        let t = (((px - ax) * dx + (py - ay) * dy) / len_sq).clamp(F::ZERO, F::ONE);
+        (ax + t * dx, ay + t * dy)
+    } else {
+        (ax, ay)
+    };
+
+    let ex = px - cx;
+    let ey = py - cy;
+    ex * ex + ey * ey
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_support::{error_of, sine_stroke, take_points, take_records_f64};
+
+    fn to_count(points: &[f32], max_points: u32, method: SimplifyMethod) -> Vec<f32> {
+        take_points(simplify_stroke_to_count(points.as_ptr(), points.len(), max_points, method))
+    }
+
+    #[test]
+    fn to_count_keeps_the_most_significant_points() {
+        let points = [0.0, 0.0, 1.0, 1.0, 0.1, 1.0, 2.0, 5.0, 1.0, 3.0, 0.2, 1.0, 4.0, 0.0, 1.0];
+        for method in [SimplifyMethod::Rdp, SimplifyMethod::VisvalingamWhyatt] {
+            assert_eq!(to_count(&points, 2, method), [0.0, 0.0, 1.0, 4.0, 0.0, 1.0]);
+            assert_eq!(to_count(&points, 3, method), [0.0, 0.0, 1.0, 2.0, 5.0, 1.0, 4.0, 0.0, 1.0]);
+            assert_eq!(to_count(&points, 5, method), points);
+        }
+    }
+
+    #[test]
+    fn rdp_to_count_terminates_on_nan_points() {
+        // Passthrough lets the NaN through; it used to make rdp_ranks loop forever
+        crate::set_float_validation(crate::FloatValidation::Passthrough);
+        let points = [0.0, 0.0, 1.0, f32::NAN, 1.0, 1.0, 5.0, 2.0, 1.0, 7.0, 0.0, 1.0];
+        assert_eq!(to_count(&points, 2, SimplifyMethod::Rdp), [0.0, 0.0, 1.0, 7.0, 0.0, 1.0]);
+        assert_eq!(to_count(&points, 3, SimplifyMethod::Rdp), [0.0, 0.0, 1.0, 5.0, 2.0, 1.0, 7.0, 0.0, 1.0]);
+        assert_eq!(to_count(&points, 3, SimplifyMethod::VisvalingamWhyatt).len(), 9);
+
+        let endpoint_nan = [f32::NAN, 0.0, 1.0, 1.0, 1.0, 1.0, 5.0, 2.0, 1.0, 7.0, 0.0, 1.0];
+        assert_eq!(to_count(&endpoint_nan, 3, SimplifyMethod::Rdp).len(), 9);
+    }
+
+    #[test]
+    fn rdp_ranks_terminates_on_overflowing_distances() {
+        let points = [-3.0e38, 0.0, 1.0, 0.0, 3.0e38, 1.0, 1.0, -3.0e38, 1.0, 2.0, 1.0, 1.0, 3.0e38, 0.0, 1.0];
+        let ranks = point_ranks(&points, SimplifyMethod::Rdp);
+        assert_eq!(ranks.len(), 5);
+        assert!(ranks[1..4].iter().all(|&rank| !rank.is_nan()));
+        assert_eq!(to_count(&points, 3, SimplifyMethod::Rdp).len(), 9);
+    }
+
+    #[test]
+    fn omitted_corner_angle_disables_corners() {
+        // An L whose corner lies within epsilon of the chord
+        let points: Vec<f32> = (0..=20)
+            .map(|i| [i as f32 * 2.0, 0.0])
+            .chain((1..=20).map(|i| [40.0, i as f32 * 2.0]))
+            .flat_map(|[x, y]| [x, y, 1.0])
+            .collect();
+        let simplify = |angle: f32| take_points(simplify_stroke(points.as_ptr(), points.len(), 30.0, angle));
+        let simplify_vw = |angle: f32| take_points(simplify_stroke_vw(points.as_ptr(), points.len(), 1000.0, angle));
+        assert_eq!(simplify(f32::NAN), simplify(0.0));
+        assert_eq!(simplify_vw(f32::NAN), simplify_vw(0.0));
+        assert_eq!(simplify(0.0).len(), 6);
+        assert_eq!(simplify(120.0), [0.0, 0.0, 1.0, 40.0, 0.0, 1.0, 40.0, 40.0, 1.0]);
+
+        let negative = simplify_stroke(points.as_ptr(), points.len(), 4.0, -1.0);
+        assert!(crate::test_support::fails_with(negative, CreoVexError::InvalidParameter));
+    }
+
+    #[test]
+    fn rdp_ranks_match_simplify_stroke() {
+        let points = sine_stroke(200, 1.0, 20.0, 9.0);
+        let ranks = point_ranks(&points, SimplifyMethod::Rdp);
+        for epsilon in [0.1f32, 0.5, 2.0, 8.0] {
+            let kept = kept_points(&points, |i| ranks[i] > epsilon as f64);
+            assert_eq!(kept, rdp_simplify(&points, epsilon), "epsilon {epsilon}");
+        }
+    }
+
+    #[test]
+    fn f64_simplification_keeps_the_same_points_ten_million_units_out() {
+        const FAR: f64 = 1.0e7;
+        let near: Vec<f64> = (0..50)
+            .flat_map(|i| {
+                let t = i as f64 * 0.1;
+                [t * 3.1, (t * 1.7).sin() * 2.3, 0.5]
+            })
+            .collect();
+        let far: Vec<f64> = near.chunks_exact(3).flat_map(|p| [p[0] + FAR, p[1] + FAR, p[2]]).collect();
+        let kept = take_records_f64(simplify_stroke_f64(near.as_ptr(), near.len(), 0.05, 0.0), 3);
+        let kept_far = take_records_f64(simplify_stroke_f64(far.as_ptr(), far.len(), 0.05, 0.0), 3);
+        assert!(kept.len() < near.len() && kept.len() >= 6);
+
+        // Kept points are input points, so the same ones come back moved out
+        let index = |points: &[f64], point: &[f64]| points.chunks_exact(3).position(|p| p == point).unwrap();
+        let indices: Vec<usize> = kept.chunks_exact(3).map(|point| index(&near, point)).collect();
+        assert_eq!(kept_far.chunks_exact(3).map(|point| index(&far, point)).collect::<Vec<_>>(), indices);
+        let result = simplify_stroke_f64(far.as_ptr(), far.len(), -1.0, 0.0);
+        assert_eq!(error_of(result), crate::CreoVexError::InvalidParameter as u32);
+    }
+}
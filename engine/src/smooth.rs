@@ -0,0 +1,1330 @@
+/*!
+ * Stroke smoothing (Chaikin subdivision)
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md
+ */
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::corners::find_corners;
+use crate::error::{require_non_negative, CreoVexError, EngineError, EngineResult};
+#[cfg(feature = "std")]
+use crate::error::{require_positive, run_export, run_status, run_vec};
+use crate::float::{math, Float};
+use crate::sanitize::{sanitize_points, NonFinitePolicy, SMOOTH_SANITIZE_DISTANCE};
+use crate::validate::{float_validation, FloatValidation};
+#[cfg(feature = "std")]
+use crate::validate::with_float_validation;
+#[cfg(feature = "std")]
+use crate::{
+    read_points, read_strided_points, require_stride, run_batch, serialize_points, serialize_points_f64,
+    serialize_records, serialize_strided_points, write_records_into, EngineBuffer,
+};
+
+/**
+ * Smooth stroke using Chaikin subdivision (legacy pointer-only contract)
+ * 
+ * DEPRECATED: kept for one release so existing callers keep working.
+ * Use smooth_stroke_v2, which also returns the byte length to pass to
+ * free_buffer. Output is identical; the buffer size is
+ * 4 + point_count * 12 bytes.
+ */
+#[cfg(feature = "std")]
+#[deprecated(note = "use smooth_stroke_v2")]
+pub fn smooth_stroke(points_ptr: *const f32, points_len: usize) -> *mut u8 {
+    smooth_stroke_v2(points_ptr, points_len).ptr()
+}
+
+/**
+ * The JS export of smooth_stroke, under the same name
+ * 
+ * A separate function so the deprecation warns Rust callers without
+ * tripping over the wasm-bindgen glue that calls it.
+ */
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = smooth_stroke)]
+pub fn smooth_stroke_legacy(points_ptr: *const f32, points_len: usize) -> *mut u8 {
+    #[allow(deprecated)]
+    smooth_stroke(points_ptr, points_len)
+}
+
+/**
+ * Smooth stroke using Chaikin subdivision algorithm
+ * 
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, smoothed
+ * 
+ * Algorithm: 2 iterations of Chaikin subdivision for noticeable smoothing
+ * Performance: O(n) where n = point count
+ * 
+ * Pressure is preserved and smoothed alongside position data.
+ * 
+ * Returns the serialized buffer together with its exact byte length.
+ * Errors: see read_points (NullInput, TooFewPoints, InvalidLength).
+ * 
+ * Equivalent to smooth_stroke_ex with DEFAULT_ITERATIONS, tension 1,
+ * corner preservation disabled, strength 1, and no sanitizing.
+ * See smooth_stroke_adaptive for angle-driven subdivision,
+ * smooth_stroke_chunked for long strokes, smooth_stroke_overlap for
+ * live strokes, and apply_pressure for per-brush pressure curves.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_v2(points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+    smooth_stroke_ex(points_ptr, points_len, DEFAULT_ITERATIONS, 1.0, 0.0, 1.0, false)
+}
+
+/**
+ * smooth_stroke_v2 from and to a Float32Array
+ * 
+ * The default for pointer-up processing: wasm-bindgen copies the points
+ * in and the result out, so there is no buffer to free and no view that
+ * goes stale when memory grows. The pointer form stays for the live
+ * path; both run smooth_points, so their results are identical.
+ * 
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ * 
+ * Errors: as smooth_stroke_v2. A failure returns an empty array and sets
+ * last_error_code() / last_error_message().
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_js(points: &[f32], validation: Option<FloatValidation>) -> Vec<f32> {
+    with_float_validation(validation, || {
+        run_vec(|| {
+            let points = read_points(points.as_ptr(), points.len())?;
+            smooth_points(&points, DEFAULT_ITERATIONS, 1.0, 0.0, 1.0, false)
+        })
+    })
+}
+
+/**
+ * Smooth stroke using Chaikin subdivision in f64 precision
+ * 
+ * Input format: [x0, y0, p0, x1, y1, p1, ...] as f64 values (e.g. a
+ * Float64Array), `points_len` counting f64 values
+ * Output format: see serialize_points_f64 (point count, element size 8,
+ * f64 triplets)
+ * 
+ * The smooth_stroke_v2 algorithm computed in f64, for strokes far from
+ * the origin of an infinite canvas: beyond 2^24 (about 1.7 * 10^7) canvas
+ * units an f32 cannot even hold fractional coordinates, and long before
+ * that smoothed points snap to a visible grid.
+ * 
+ * Errors: see read_points (NullInput, TooFewPoints, InvalidLength).
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_f64(points_ptr: *const f64, points_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        serialize_points_f64(&chaikin_uniform(points, 3, DEFAULT_ITERATIONS as usize, 0.25))
+    })
+}
+
+/**
+ * Smooth stroke into a caller-provided buffer (no engine allocation kept)
+ * 
+ * Same input and output layout as smooth_stroke_v2, but the result is
+ * written to `out_ptr`, typically a buffer obtained once from alloc and
+ * reused every frame. Size it with smooth_output_size(in_len, 2).
+ * 
+ * Returns the number of bytes written, or a negative CreoVexError
+ * discriminant on failure. When the buffer is too small the result is
+ * -BufferTooSmall and, if `out_capacity_bytes` >= 4, the required byte
+ * count is stored as a u32 at `out_ptr` so the caller can grow it.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_into(in_ptr: *const f32, in_len: usize, out_ptr: *mut u8, out_capacity_bytes: usize) -> i32 {
+    run_status(|| {
+        let points = &read_points(in_ptr, in_len)?;
+        if out_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "out_ptr is null"));
+        }
+        let out = unsafe { core::slice::from_raw_parts_mut(out_ptr, out_capacity_bytes) };
+
+        let smoothed = chaikin_uniform(points, 3, DEFAULT_ITERATIONS as usize, 0.25);
+        write_records_into(out, (smoothed.len() / 3) as u32, &smoothed)
+    })
+}
+
+/**
+ * Worst-case serialized size in bytes of a Chaikin-smoothed stroke
+ * 
+ * `points_len` counts f32 values (triplets) as for smooth_stroke_v2;
+ * `iterations` is clamped to 0-6 like smooth_stroke_ex. Each pass
+ * doubles the point count of strokes with two or more points, so the
+ * result is exact for smooth_stroke_v2 / smooth_stroke_into (2 passes).
+ * Sizes too large for usize saturate to usize::MAX, which no buffer
+ * can satisfy.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_output_size(points_len: usize, iterations: u32) -> usize {
+    let count = points_len / 3;
+    let smoothed = if count < 2 { Some(count) } else { count.checked_mul(1 << iterations.min(MAX_ITERATIONS)) };
+    smoothed
+        .and_then(|smoothed| smoothed.checked_mul(12))
+        .and_then(|bytes| bytes.checked_add(4))
+        .unwrap_or(usize::MAX)
+}
+
+/**
+ * Smooth many strokes in one call
+ * 
+ * Input and output use the batch container documented at read_batch
+ * (lib.rs); each stroke is smoothed exactly like smooth_stroke_v2 and
+ * gets its own status entry.
+ * 
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ * 
+ * Errors: NullInput/InvalidLength for a malformed container only;
+ * per-stroke failures are reported in the status array.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_strokes_batch(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    validation: Option<FloatValidation>,
+) -> EngineBuffer {
+    with_float_validation(validation, || {
+        run_batch(batch_ptr, batch_len, 3, |points| {
+            Ok(chaikin_uniform(points, 3, DEFAULT_ITERATIONS as usize, 0.25))
+        })
+    })
+}
+
+/**
+ * Default Chaikin pass count (visible smoothing without oversmoothing)
+ */
+pub(crate) const DEFAULT_ITERATIONS: u32 = 2;
+
+/**
+ * Upper bound on Chaikin passes (each pass doubles the point count)
+ */
+pub(crate) const MAX_ITERATIONS: u32 = 6;
+
+/**
+ * Smooth stroke using Chaikin subdivision with explicit parameters
+ * 
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, smoothed
+ * 
+ * `iterations` is clamped to 0-6; 0 returns an exact copy. `tension`
+ * (0-1) scales the corner cut: 1 is classic Chaikin (points at 1/4 and
+ * 3/4 of each segment), smaller values cut closer to the original
+ * vertices, and 0 keeps the original points unchanged.
+ * 
+ * Points that detect_corners reports for `corner_angle_deg` are kept as
+ * fixed anchors that are never cut, so deliberate corners stay sharp.
+ * 0 disables corner preservation.
+ * 
+ * `strength` (0-1) is the smoothing slider: each output point is blended
+ * from the input polyline toward its smoothed position. The input is
+ * evaluated where the point came from (Chaikin tracks every output
+ * point's position along the original points), so 0 gives the input
+ * path at the smoothed point count, 1 the full smoothing, and the
+ * geometry moves continuously in between. Pressure blends the same way.
+ * 
+ * With `sanitize`, the input first goes through sanitize_stroke with
+ * SMOOTH_SANITIZE_DISTANCE and NonFinitePolicy::Skip, so duplicate
+ * pointer events do not become stacked output points.
+ * 
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a tension or strength outside [0, 1] or a NaN/negative corner
+ * angle; with `sanitize`, TooFewPoints when no point is finite.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_ex(
+    points_ptr: *const f32,
+    points_len: usize,
+    iterations: u32,
+    tension: f32,
+    corner_angle_deg: f32,
+    strength: f32,
+    sanitize: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        // Validate input (must be triplets: x, y, pressure)
+        let points = &read_points(points_ptr, points_len)?;
+        let smoothed = smooth_points(points, iterations, tension, corner_angle_deg, strength, sanitize)?;
+
+        // Serialize result
+        serialize_points(&smoothed)
+    })
+}
+
+/**
+ * smooth_stroke_ex on validated points (shared with smooth_stroke_js)
+ */
+pub(crate) fn smooth_points(
+    points: &[f32],
+    iterations: u32,
+    tension: f32,
+    corner_angle_deg: f32,
+    strength: f32,
+    sanitize: bool,
+) -> EngineResult<Vec<f32>> {
+    let sanitized;
+    let points = if sanitize {
+        sanitized = sanitize_points(points, SMOOTH_SANITIZE_DISTANCE, NonFinitePolicy::Skip)?;
+        &sanitized[..]
+    } else {
+        points
+    };
+    let tension = require_non_negative("tension", tension)?;
+    if tension > 1.0 {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("tension must be in [0, 1] (got {tension})"),
+        ));
+    }
+    let corner_angle_deg = require_non_negative("corner_angle_deg", corner_angle_deg)?;
+    let strength = require_non_negative("strength", strength)?;
+    if strength > 1.0 {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("strength must be in [0, 1] (got {strength})"),
+        ));
+    }
+
+    // Need at least 2 points (6 floats) to smooth
+    if points.len() < 6 || iterations == 0 || tension == 0.0 {
+        // Return copy of original points
+        return Ok(points.to_vec());
+    }
+
+    let iterations = iterations.min(MAX_ITERATIONS) as usize;
+    let anchors = find_corners(points, 3, corner_angle_deg.to_radians());
+    if strength == 1.0 {
+        Ok(chaikin_subdivide(points, 3, iterations, 0.25 * tension, &anchors))
+    } else {
+        // Carry each point's index as a fourth channel: Chaikin interpolates it
+        // into the output point's parameter along the input
+        let indexed: Vec<f32> =
+            points.chunks_exact(3).enumerate().flat_map(|(i, p)| [p[0], p[1], p[2], i as f32]).collect();
+        let subdivided = chaikin_subdivide(&indexed, 4, iterations, 0.25 * tension, &anchors);
+        Ok(blend_from_source(points, &subdivided, strength))
+    }
+}
+
+/**
+ * [x, y, p] points `strength` of the way from the input polyline to the
+ * smoothed points, whose fourth channel is their parameter along the
+ * input (point index, fractional between points)
+ */
+fn blend_from_source(points: &[f32], indexed: &[f32], strength: f32) -> Vec<f32> {
+    let last_segment = points.len() / 3 - 2;
+    let mut blended = Vec::with_capacity(indexed.len() / 4 * 3);
+    for point in indexed.chunks_exact(4) {
+        let segment = (point[3].max(0.0) as usize).min(last_segment);
+        let t = (point[3] - segment as f32).clamp(0.0, 1.0);
+        let (a, b) = (&points[segment * 3..segment * 3 + 3], &points[segment * 3 + 3..segment * 3 + 6]);
+        for c in 0..3 {
+            let source = a[c] + (b[c] - a[c]) * t;
+            blended.push(source + (point[c] - source) * strength);
+        }
+    }
+    blended
+}
+
+/**
+ * Smooth stroke with extra per-point channels (tilt, rotation, ...)
+ * 
+ * Input format: `stride` floats per point, [x, y, pressure, extra...]
+ * 
+ * Output format:
+ * - 4 bytes: point count (u32)
+ * - 4 bytes: stride (u32)
+ * - N * stride * 4 bytes: f32 points, same channel layout as the input
+ * 
+ * Same smoothing as smooth_stroke_v2 (DEFAULT_ITERATIONS classic Chaikin
+ * passes); every channel after x/y is interpolated exactly like pressure.
+ * `stride` is 3-8; stride 3 gives smooth_stroke_v2's points with the
+ * wider header.
+ * 
+ * Errors: InvalidParameter for a stride outside 3-8, then the
+ * read_points errors with `points_len` a multiple of `stride`.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_n(points_ptr: *const f32, points_len: usize, stride: usize) -> EngineBuffer {
+    run_export(|| {
+        let stride = require_stride(stride)?;
+        let points = &read_strided_points(points_ptr, points_len, stride)?;
+
+        let smoothed = chaikin_uniform(points, stride, DEFAULT_ITERATIONS as usize, 0.25);
+        serialize_strided_points(&smoothed, stride)
+    })
+}
+
+/**
+ * Smooth a closed stroke (loop) using cyclic Chaikin subdivision
+ * 
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, smoothed, with first point == last point
+ * 
+ * The points are treated as a cycle: the last segment wraps back to the
+ * first point and no endpoint is pinned, so the seam is as smooth as the
+ * rest of the loop. An input that already repeats its first point at the
+ * end is accepted; the duplicate is dropped before smoothing. The output
+ * stays an open list whose last point repeats the first, ready for the
+ * renderer to close. `iterations` and `tension` work as in
+ * smooth_stroke_ex.
+ * 
+ * Errors: the read_points errors, TooFewPoints when the loop has fewer
+ * than 3 points, and NonFiniteValue/InvalidParameter for a tension outside
+ * [0, 1].
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_closed(points_ptr: *const f32, points_len: usize, iterations: u32, tension: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let tension = require_non_negative("tension", tension)?;
+        if tension > 1.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("tension must be in [0, 1] (got {tension})"),
+            ));
+        }
+
+        // Drop an explicit closing point
+        let count = points.len() / 3;
+        let cycle = if count > 1 && points[..3] == points[points.len() - 3..] {
+            &points[..points.len() - 3]
+        } else {
+            points
+        };
+        if cycle.len() < 9 {
+            return Err(EngineError::new(
+                CreoVexError::TooFewPoints,
+                format!("a closed stroke needs at least 3 points (got {})", cycle.len() / 3),
+            ));
+        }
+
+        let iterations = iterations.min(MAX_ITERATIONS) as usize;
+        let mut smoothed = if tension == 0.0 {
+            cycle.to_vec()
+        } else {
+            chaikin_closed_passes(cycle, 3, iterations, 0.25 * tension)
+        };
+        smoothed.extend_from_within(..3);
+        serialize_points(&smoothed)
+    })
+}
+
+/**
+ * Cyclic Chaikin passes: every segment, including last -> first, is cut
+ * and no point is pinned (output is the open cycle, without repeat)
+ */
+fn chaikin_closed_passes(points: &[f32], stride: usize, iterations: usize, cut: f32) -> Vec<f32> {
+    let keep = 1.0 - cut;
+    let mut current = points.to_vec();
+
+    for _ in 0..iterations {
+        let count = current.len() / stride;
+        let mut next = Vec::with_capacity(current.len() * 2);
+
+        for i in 0..count {
+            let a = &current[i * stride..(i + 1) * stride];
+            let j = (i + 1) % count;
+            let b = &current[j * stride..(j + 1) * stride];
+            for c in 0..stride {
+                next.push(keep * a[c] + cut * b[c]);
+            }
+            for c in 0..stride {
+                next.push(cut * a[c] + keep * b[c]);
+            }
+        }
+
+        current = next;
+    }
+
+    current
+}
+
+/**
+ * Chaikin subdivision that never cuts the anchor points
+ * 
+ * The stroke is split at each anchor (ascending interior point indices)
+ * and every piece is subdivided on its own; pieces keep their endpoints,
+ * so anchors appear unchanged in the output.
+ */
+fn chaikin_subdivide(points: &[f32], stride: usize, iterations: usize, cut: f32, anchors: &[usize]) -> Vec<f32> {
+    if anchors.is_empty() {
+        return chaikin_uniform(points, stride, iterations, cut);
+    }
+
+    let mut result = Vec::with_capacity(points.len() << iterations);
+    let mut start = 0;
+    for &end in anchors.iter().chain(core::iter::once(&(points.len() / stride - 1))) {
+        let piece = chaikin_uniform(&points[start * stride..(end + 1) * stride], stride, iterations, cut);
+        // Consecutive pieces share the anchor point
+        let skip = if start == 0 { 0 } else { stride };
+        result.extend_from_slice(&piece[skip..]);
+        start = end;
+    }
+    result
+}
+
+/**
+ * Chaikin subdivision algorithm with pressure preservation
+ * 
+ * Each iteration:
+ * - For each line segment, create 2 new points at `cut` and `1 - cut`
+ *   (classic Chaikin: cut = 1/4), where `segment_cut` picks the ratio
+ *   for each segment
+ * - Interpolate pressure and every extra channel at the same ratios
+ * - Remove original intermediate points (keep endpoints)
+ * 
+ * This creates a corner-cutting effect that smooths the curve while
+ * maintaining natural pressure transitions. Points are `stride` floats
+ * wide; every channel after x/y is interpolated like pressure.
+ * 
+ * Strokes with one ratio for every segment go through chaikin_uniform,
+ * which computes the same points with the SIMD kernel when available.
+ */
+pub(crate) fn chaikin_passes<F: Float>(
+    points: &[F],
+    stride: usize,
+    iterations: usize,
+    segment_cut: impl Fn(&[F], &[F]) -> F,
+) -> Vec<F> {
+    chaikin_iterate(points, stride, iterations, |current, next| {
+        for (a, b) in current.chunks_exact(stride).zip(current.chunks_exact(stride).skip(1)) {
+            push_cuts(a, b, segment_cut(a, b), next);
+        }
+    })
+}
+
+/**
+ * chaikin_passes with the same `cut` for every segment
+ */
+pub(crate) fn chaikin_uniform<F: Float>(points: &[F], stride: usize, iterations: usize, cut: F) -> Vec<F> {
+    chaikin_iterate(points, stride, iterations, |current, next| F::chaikin_cuts(current, stride, cut, next))
+}
+
+/**
+ * Run `iterations` Chaikin passes; `cut_segments` appends the cut points
+ * of every segment of the current polyline
+ */
+fn chaikin_iterate<F: Float>(
+    points: &[F],
+    stride: usize,
+    iterations: usize,
+    cut_segments: impl Fn(&[F], &mut Vec<F>),
+) -> Vec<F> {
+    let mut current = points.to_vec();
+    
+    for _ in 0..iterations {
+        if current.len() < stride * 2 {
+            break;
+        }
+        
+        let mut next = Vec::with_capacity(current.len() * 2);
+        
+        // Always keep first point (all channels)
+        next.extend_from_slice(&current[..stride]);
+        
+        cut_segments(&current, &mut next);
+        
+        // Always keep last point (all channels)
+        next.extend_from_slice(&current[current.len() - stride..]);
+        
+        current = next;
+    }
+    
+    current
+}
+
+/**
+ * Scalar Chaikin kernel: both cut points of every segment at ratio `cut`
+ * (the reference for the SIMD kernel)
+ */
+pub(crate) fn chaikin_cuts<F: Float>(points: &[F], stride: usize, cut: F, out: &mut Vec<F>) {
+    for (a, b) in points.chunks_exact(stride).zip(points.chunks_exact(stride).skip(1)) {
+        push_cuts(a, b, cut, out);
+    }
+}
+
+/**
+ * The points at `cut` and `1 - cut` along the segment from `a` to `b`
+ * (position, pressure, extras)
+ */
+fn push_cuts<F: Float>(a: &[F], b: &[F], cut: F, out: &mut Vec<F>) {
+    let keep = F::ONE - cut;
+    for (&a, &b) in a.iter().zip(b) {
+        out.push(keep * a + cut * b);
+    }
+    for (&a, &b) in a.iter().zip(b) {
+        out.push(cut * a + keep * b);
+    }
+}
+
+/**
+ * Floats per point in the timed layout [x, y, pressure, t_ms]
+ */
+pub(crate) const TIMED_STRIDE: usize = 4;
+
+/**
+ * Smooth a timestamped stroke with speed-dependent Chaikin subdivision
+ * 
+ * Input format: [x0, y0, p0, t0, x1, y1, p1, t1, ...] (t in milliseconds)
+ * 
+ * Output format:
+ * - 4 bytes: point count (u32)
+ * - N * 16 bytes: f32 quads [x, y, pressure, t_ms]
+ * 
+ * Each segment is cut with tension `speed / reference_speed` (clamped to
+ * 0-1, speed in canvas units per ms): fast flicks get full Chaikin
+ * smoothing, slow deliberate segments are left nearly untouched.
+ * `iterations` is clamped to 0-6. Pressure and time are interpolated at
+ * the same ratios as position, so the time channel stays monotonic.
+ * 
+ * Non-monotonic timestamps are repaired by clamping each one to its
+ * predecessor; segments with no elapsed time count as fast.
+ * 
+ * Errors: NullInput/TooFewPoints/InvalidLength for a bad buffer (length
+ * must be a multiple of 4), NonFiniteValue/InvalidParameter for a
+ * non-positive reference speed, and NonFiniteValue for a NaN or infinite
+ * timestamp.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_timed(
+    points_ptr: *const f32,
+    points_len: usize,
+    iterations: u32,
+    reference_speed: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_strided_points(points_ptr, points_len, TIMED_STRIDE)?;
+        let reference_speed = require_positive("reference_speed", reference_speed)?;
+
+        let mut repaired = points.to_vec();
+        let mut latest = f32::NEG_INFINITY;
+        for (i, point) in repaired.chunks_exact_mut(TIMED_STRIDE).enumerate() {
+            if !point[3].is_finite() {
+                return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("timestamp {i} is not finite")));
+            }
+            latest = latest.max(point[3]);
+            point[3] = latest;
+        }
+
+        let iterations = iterations.min(MAX_ITERATIONS) as usize;
+        let smoothed = chaikin_passes(&repaired, TIMED_STRIDE, iterations, |a, b| timed_cut(a, b, reference_speed));
+        serialize_records((smoothed.len() / TIMED_STRIDE) as u32, &smoothed)
+    })
+}
+
+/**
+ * Per-segment cut ratio for timed smoothing: classic 1/4 scaled by
+ * speed / reference_speed (up to 1)
+ */
+fn timed_cut(a: &[f32], b: &[f32], reference_speed: f32) -> f32 {
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    let dt = b[3] - a[3];
+    let distance = (dx * dx + dy * dy).sqrt();
+    let tension = if dt > 0.0 { (distance / dt / reference_speed).min(1.0) } else { 1.0 };
+    0.25 * tension
+}
+
+/**
+ * Upper bound on adaptive passes (each pass at most doubles the point count)
+ */
+const MAX_ADAPTIVE_ITERATIONS: u32 = 8;
+
+/**
+ * Smooth stroke with angle-adaptive Chaikin subdivision
+ * 
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, smoothed
+ * 
+ * Each pass cuts only the corners whose turn angle exceeds
+ * `angle_threshold_deg` (0° = straight, 180° = full reversal), so
+ * nearly-straight runs keep their original points while zigzags receive
+ * up to `max_iterations` passes. Pressure is interpolated at exactly the
+ * same ratios as position.
+ * 
+ * The output never holds more than `max_points` points: when a pass would
+ * exceed the budget, only the sharpest corners that fit are cut and
+ * subdivision stops.
+ * 
+ * Errors: the read_points errors, NonFiniteValue for a NaN threshold,
+ * and InvalidParameter for a budget smaller than the input point count
+ * (smoothing never removes points).
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_adaptive(
+    points_ptr: *const f32,
+    points_len: usize,
+    angle_threshold_deg: f32,
+    max_iterations: u32,
+    max_points: usize,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        if angle_threshold_deg.is_nan() {
+            return Err(EngineError::new(CreoVexError::NonFiniteValue, "angle_threshold_deg is NaN"));
+        }
+        if max_points < points.len() / 3 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("max_points {max_points} is below the input point count {}", points.len() / 3),
+            ));
+        }
+
+        let iterations = max_iterations.min(MAX_ADAPTIVE_ITERATIONS) as usize;
+        let smoothed = adaptive_chaikin(points, 3, angle_threshold_deg.to_radians(), iterations, max_points);
+        serialize_points(&smoothed)
+    })
+}
+
+/**
+ * Adaptive corner cutting
+ * 
+ * A sharp vertex v(i) is replaced by the two Chaikin points adjacent to
+ * it: 3/4 along the incoming segment and 1/4 along the outgoing one.
+ * Endpoints are never cut. Cutting every interior vertex reproduces the
+ * classic Chaikin pass with pinned endpoints. All `stride` channels are
+ * interpolated.
+ */
+fn adaptive_chaikin(
+    points: &[f32],
+    stride: usize,
+    threshold_rad: f32,
+    iterations: usize,
+    max_points: usize,
+) -> Vec<f32> {
+    let mut current = points.to_vec();
+
+    for _ in 0..iterations {
+        let count = current.len() / stride;
+        if count < 3 {
+            break;
+        }
+
+        // Collect sharp interior vertices with their turn angles
+        let mut sharp: Vec<(usize, f32)> = (1..count - 1)
+            .filter_map(|i| {
+                let angle = turn_angle(&current, stride, i);
+                (angle > threshold_rad).then_some((i, angle))
+            })
+            .collect();
+
+        if sharp.is_empty() {
+            break;
+        }
+
+        // Each cut adds one point; keep the sharpest corners that fit
+        let available = max_points - count;
+        if available == 0 {
+            break;
+        }
+        let budget_hit = sharp.len() > available;
+        if budget_hit {
+            sharp.sort_by(|a, b| b.1.total_cmp(&a.1));
+            sharp.truncate(available);
+        }
+
+        let mut cut = vec![false; count];
+        for &(i, _) in &sharp {
+            cut[i] = true;
+        }
+
+        let mut next = Vec::with_capacity((count + sharp.len()) * stride);
+        for i in 0..count {
+            let v = &current[i * stride..(i + 1) * stride];
+            if !cut[i] {
+                next.extend_from_slice(v);
+                continue;
+            }
+
+            let prev = &current[(i - 1) * stride..i * stride];
+            let following = &current[(i + 1) * stride..(i + 2) * stride];
+            for c in 0..stride {
+                next.push(0.25 * prev[c] + 0.75 * v[c]);
+            }
+            for c in 0..stride {
+                next.push(0.75 * v[c] + 0.25 * following[c]);
+            }
+        }
+
+        current = next;
+        if budget_hit {
+            break;
+        }
+    }
+
+    current
+}
+
+/**
+ * Turn angle (radians) at interior vertex i
+ * 
+ * Zero-length neighbor segments have no direction and report 0 so they
+ * are never treated as corners.
+ */
+fn turn_angle(points: &[f32], stride: usize, i: usize) -> f32 {
+    let (prev, v, next) = ((i - 1) * stride, i * stride, (i + 1) * stride);
+    let ax = points[v] - points[prev];
+    let ay = points[v + 1] - points[prev + 1];
+    let bx = points[next] - points[v];
+    let by = points[next + 1] - points[v + 1];
+
+    let len_a = (ax * ax + ay * ay).sqrt();
+    let len_b = (bx * bx + by * by).sqrt();
+    if len_a == 0.0 || len_b == 0.0 {
+        return 0.0;
+    }
+
+    let cos = ((ax * bx + ay * by) / (len_a * len_b)).clamp(-1.0, 1.0);
+    math::acosf(cos)
+}
+
+/**
+ * Chaikin passes applied by the chunked smoother (matches smooth_stroke)
+ */
+const CHUNK_LEVELS: usize = 2;
+
+/**
+ * Floats per level in the chunk context: state, then x, y, pressure
+ */
+const CHUNK_LEVEL_FLOATS: usize = 4;
+
+/**
+ * Total chunk context size in floats
+ */
+const CHUNK_CONTEXT_FLOATS: usize = CHUNK_LEVELS * CHUNK_LEVEL_FLOATS;
+
+/**
+ * Size (in f32 values) of the context buffer used by smooth_stroke_chunked
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn chunk_context_len() -> usize {
+    CHUNK_CONTEXT_FLOATS
+}
+
+/**
+ * Smooth one chunk of a long stroke
+ * 
+ * Input format: [x0, y0, p0, x1, y1, p1, ...] for this chunk only
+ * Context format: [state, x, y, p] per Chaikin level (chunk_context_len()
+ * floats). Pass a null/empty context for the first chunk of a stroke.
+ * 
+ * Output format:
+ * - 4 bytes: point count (u32)
+ * - N * 12 bytes: f32 triplets for the newly finalized points
+ * - chunk_context_len() * 4 bytes: updated context (f32)
+ * 
+ * The caller copies the trailing context into its context buffer and
+ * passes it with the next chunk. Set `is_final` on the last chunk (it may
+ * be empty) to flush the pinned end point. Concatenating every chunk's
+ * points reproduces smooth_stroke on the whole stroke exactly, because
+ * each Chaikin level runs as a streaming filter whose only state is the
+ * previous point at that level (the carried "last smoothed points").
+ * 
+ * Errors: NullInput/InvalidLength for a bad chunk, InvalidLength for a
+ * context of the wrong size, InvalidParameter for a corrupted context
+ * (including NaN or infinite carried points, unless under
+ * FloatValidation::Passthrough).
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_chunked(
+    points_ptr: *const f32,
+    points_len: usize,
+    context_ptr: *const f32,
+    context_len: usize,
+    is_final: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        // An empty chunk is allowed (typically the final flush)
+        let points: &[f32] = if points_len == 0 { &[] } else { &read_points(points_ptr, points_len)? };
+
+        let mut stream = if context_ptr.is_null() || context_len == 0 {
+            ChaikinStream::new()
+        } else {
+            if context_len != CHUNK_CONTEXT_FLOATS {
+                return Err(EngineError::new(
+                    CreoVexError::InvalidLength,
+                    format!("context_len must be {CHUNK_CONTEXT_FLOATS} (got {context_len})"),
+                ));
+            }
+            let context = unsafe { core::slice::from_raw_parts(context_ptr, context_len) };
+            ChaikinStream::from_context(context).ok_or_else(|| {
+                EngineError::new(CreoVexError::InvalidParameter, "chunk context is corrupted")
+            })?
+        };
+
+        let mut output = Vec::with_capacity(points.len() * 4 + 12);
+        for point in points.chunks_exact(3) {
+            stream.push([point[0], point[1], point[2]], &mut output);
+        }
+        if is_final {
+            stream.finish(&mut output);
+        }
+
+        let point_count = (output.len() / 3) as u32;
+        output.extend_from_slice(&stream.to_context());
+        serialize_records(point_count, &output)
+    })
+}
+
+/**
+ * Raw points each smooth_stroke_overlap chunk smooths again with the next
+ * one: with two Chaikin levels an output point depends on at most three
+ * consecutive raw points, so two of them rebuild every point after the
+ * last final one
+ */
+const OVERLAP_RAW_POINTS: usize = 2;
+
+/**
+ * Output points at the end of a chunk that the next chunk replaces: the
+ * ones that depend on the pinned end point
+ */
+const OVERLAP_OUTPUT_POINTS: usize = (1 << CHUNK_LEVELS) - 1;
+
+/**
+ * Chunk context size in floats: raw points seen (0, 1, or 2), then the
+ * last OVERLAP_RAW_POINTS raw points
+ */
+const OVERLAP_CONTEXT_FLOATS: usize = 1 + OVERLAP_RAW_POINTS * 3;
+
+/**
+ * Size (in f32 values) of the context buffer used by smooth_stroke_overlap
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn overlap_context_len() -> usize {
+    OVERLAP_CONTEXT_FLOATS
+}
+
+/**
+ * Output points at the end of each smooth_stroke_overlap chunk that are
+ * not final yet (the next chunk's output starts with their replacement)
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn chunk_overlap_points() -> usize {
+    OVERLAP_OUTPUT_POINTS
+}
+
+/**
+ * Smooth one chunk of a live stroke, with a provisional tail
+ * 
+ * Input format: [x0, y0, p0, x1, y1, p1, ...] for this chunk only
+ * Context format: raw points seen (capped at 2), then the last two raw
+ * points (overlap_context_len() floats). Pass a null/empty context for
+ * the first chunk of a stroke.
+ * 
+ * Output format:
+ * - 4 bytes: point count (u32)
+ * - N * 12 bytes: f32 triplets
+ * - overlap_context_len() * 4 bytes: updated context (f32)
+ * 
+ * Every chunk smooths the context's raw points again together with its
+ * own, as a stroke ending at its last point, so its output ends with the
+ * pinned end point. The last chunk_overlap_points() output points depend
+ * on that end: the caller draws them but does not treat them as final,
+ * and replaces them with the next chunk's output (drop
+ * min(chunk_overlap_points(), points held), then append). The buffer
+ * after the last chunk is smooth_stroke on the whole stroke, bitwise,
+ * however the stroke was split, so clients chunking differently agree.
+ * 
+ * Errors: NullInput/InvalidLength for a bad chunk, InvalidLength for a
+ * context of the wrong size, InvalidParameter for a corrupted context
+ * (including NaN or infinite carried points, unless under
+ * FloatValidation::Passthrough).
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_overlap(
+    points_ptr: *const f32,
+    points_len: usize,
+    context_ptr: *const f32,
+    context_len: usize,
+) -> EngineBuffer {
+    run_export(|| {
+        // An empty chunk is allowed (it resends the provisional tail)
+        let points: &[f32] = if points_len == 0 { &[] } else { &read_points(points_ptr, points_len)? };
+
+        let (seen, carried): (usize, &[f32]) = if context_ptr.is_null() || context_len == 0 {
+            (0, &[])
+        } else {
+            if context_len != OVERLAP_CONTEXT_FLOATS {
+                return Err(EngineError::new(
+                    CreoVexError::InvalidLength,
+                    format!("context_len must be {OVERLAP_CONTEXT_FLOATS} (got {context_len})"),
+                ));
+            }
+            let context = unsafe { core::slice::from_raw_parts(context_ptr, context_len) };
+            let seen = match context[0] {
+                0.0 => 0,
+                1.0 => 1,
+                2.0 => 2,
+                _ => return Err(EngineError::new(CreoVexError::InvalidParameter, "chunk context is corrupted")),
+            };
+            let carried = &context[1..1 + seen * 3];
+            if !carried_values_valid(carried) {
+                return Err(EngineError::new(CreoVexError::InvalidParameter, "chunk context carries non-finite points"));
+            }
+            (seen, carried)
+        };
+
+        let window: Vec<f32> = carried.iter().chain(points).copied().collect();
+        let mut smoothed = Vec::with_capacity(window.len() * 4 + 12);
+        let mut stream = ChaikinStream::new();
+        for point in window.chunks_exact(3) {
+            stream.push([point[0], point[1], point[2]], &mut smoothed);
+        }
+        stream.finish(&mut smoothed);
+
+        // With the full overlap the window starts OVERLAP_RAW_POINTS raw
+        // points before the previous chunk's end, 1 << CHUNK_LEVELS output
+        // points each; a shorter one is the whole stroke so far
+        let skip = if seen == OVERLAP_RAW_POINTS {
+            (OVERLAP_RAW_POINTS << CHUNK_LEVELS) - OVERLAP_OUTPUT_POINTS
+        } else {
+            0
+        };
+        let mut output = smoothed.split_off(skip * 3);
+
+        let kept = (window.len() / 3).min(OVERLAP_RAW_POINTS);
+        let mut context = [0.0; OVERLAP_CONTEXT_FLOATS];
+        context[0] = kept as f32;
+        context[1..1 + kept * 3].copy_from_slice(&window[window.len() - kept * 3..]);
+
+        let point_count = (output.len() / 3) as u32;
+        output.extend_from_slice(&context);
+        serialize_records(point_count, &output)
+    })
+}
+
+/**
+ * One Chaikin pass as a streaming filter
+ * 
+ * `seen` counts input points (saturating at 2): the first point is
+ * emitted as the pinned start, every later point emits the 1/4 and 3/4
+ * cuts of the segment it closes, and finishing emits the pinned end.
+ */
+#[derive(Clone, Copy, Default)]
+pub(crate) struct ChaikinLevel {
+    seen: u8,
+    prev: [f32; 3],
+}
+
+impl ChaikinLevel {
+    /**
+     * Feed one point, returning the points emitted downstream
+     */
+    fn push(&mut self, b: [f32; 3]) -> ([[f32; 3]; 2], usize) {
+        let emitted = if self.seen == 0 {
+            ([b, [0.0; 3]], 1)
+        } else {
+            let a = self.prev;
+            // Same expressions as chaikin_passes so results match bitwise
+            let q = [
+                0.75 * a[0] + 0.25 * b[0],
+                0.75 * a[1] + 0.25 * b[1],
+                0.75 * a[2] + 0.25 * b[2],
+            ];
+            let r = [
+                0.25 * a[0] + 0.75 * b[0],
+                0.25 * a[1] + 0.75 * b[1],
+                0.25 * a[2] + 0.75 * b[2],
+            ];
+            ([q, r], 2)
+        };
+        self.prev = b;
+        self.seen = (self.seen + 1).min(2);
+        emitted
+    }
+}
+
+/**
+ * Whether point values carried in a chunk context can be trusted
+ *
+ * Every chunk's points are checked before they are carried, so a NaN or
+ * infinite value in a context is corruption, unless
+ * FloatValidation::Passthrough let it through.
+ */
+fn carried_values_valid(values: &[f32]) -> bool {
+    float_validation() == FloatValidation::Passthrough || values.iter().all(|value| value.is_finite())
+}
+
+/**
+ * Cascade of Chaikin levels fed one raw point at a time
+ */
+struct ChaikinStream {
+    levels: [ChaikinLevel; CHUNK_LEVELS],
+}
+
+impl ChaikinStream {
+    fn new() -> Self {
+        ChaikinStream {
+            levels: [ChaikinLevel::default(); CHUNK_LEVELS],
+        }
+    }
+
+    fn from_context(context: &[f32]) -> Option<Self> {
+        let mut stream = ChaikinStream::new();
+        for (level, values) in stream.levels.iter_mut().zip(context.chunks_exact(CHUNK_LEVEL_FLOATS)) {
+            level.seen = match values[0] {
+                0.0 => 0,
+                1.0 => 1,
+                2.0 => 2,
+                _ => return None,
+            };
+            if !carried_values_valid(&values[1..]) {
+                return None;
+            }
+            level.prev = [values[1], values[2], values[3]];
+        }
+        Some(stream)
+    }
+
+    fn to_context(&self) -> [f32; CHUNK_CONTEXT_FLOATS] {
+        let mut context = [0.0; CHUNK_CONTEXT_FLOATS];
+        for (level, values) in self.levels.iter().zip(context.chunks_exact_mut(CHUNK_LEVEL_FLOATS)) {
+            values[0] = level.seen as f32;
+            values[1..].copy_from_slice(&level.prev);
+        }
+        context
+    }
+
+    fn push(&mut self, point: [f32; 3], output: &mut Vec<f32>) {
+        feed_levels(&mut self.levels, point, output);
+    }
+
+    fn finish(&mut self, output: &mut Vec<f32>) {
+        finish_levels(&mut self.levels, output);
+    }
+}
+
+/**
+ * Flush pinned end points level by level
+ */
+pub(crate) fn finish_levels(levels: &mut [ChaikinLevel], output: &mut Vec<f32>) {
+    for depth in 0..levels.len() {
+        let level = levels[depth];
+        if level.seen >= 2 {
+            feed_levels(&mut levels[depth + 1..], level.prev, output);
+        }
+        // Reset so a stray second flush cannot duplicate the end point
+        levels[depth].seen = 0;
+    }
+}
+
+/**
+ * Push a point through a cascade of levels, collecting the final output
+ */
+pub(crate) fn feed_levels(levels: &mut [ChaikinLevel], point: [f32; 3], output: &mut Vec<f32>) {
+    match levels.split_first_mut() {
+        None => output.extend_from_slice(&point),
+        Some((level, rest)) => {
+            let (emitted, count) = level.push(point);
+            for &p in &emitted[..count] {
+                feed_levels(rest, p, output);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_support::{error_of, take_bytes, take_points, take_records_f64};
+
+    /**
+     * Smoothed points and updated context of one smooth_stroke_chunked call
+     */
+    fn chunk(points: &[f32], context: &[f32], is_final: bool) -> (Vec<f32>, Vec<f32>) {
+        let result = smooth_stroke_chunked(points.as_ptr(), points.len(), context.as_ptr(), context.len(), is_final);
+        let bytes = take_bytes(result);
+        let values: Vec<f32> = bytes[4..].chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+        let count = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        assert_eq!(values.len(), count * 3 + chunk_context_len());
+        let (smoothed, context) = values.split_at(count * 3);
+        (smoothed.to_vec(), context.to_vec())
+    }
+
+    fn scribble(count: usize) -> Vec<f32> {
+        (0..count)
+            .flat_map(|i| {
+                let t = i as f32 * 0.01;
+                [t * 30.0, (t * 3.0).sin() * 50.0, 0.5 + 0.5 * t.cos()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunks_join_into_the_whole_stroke() {
+        for count in [1, 2, 3, 5000] {
+            let stroke = scribble(count);
+            let whole = take_points(smooth_stroke_v2(stroke.as_ptr(), stroke.len()));
+
+            let mut joined = Vec::with_capacity(whole.len());
+            let mut context = Vec::new();
+            for points in stroke.chunks(500 * 3) {
+                let (smoothed, next) = chunk(points, &context, false);
+                joined.extend_from_slice(&smoothed);
+                context = next;
+            }
+            // The final chunk may be empty: it only flushes the end point
+            let (tail, _) = chunk(&[], &context, true);
+            joined.extend_from_slice(&tail);
+
+            assert_eq!(joined.len(), whole.len(), "{count} points");
+            let gap = joined.iter().zip(&whole).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+            assert!(gap <= 1e-4, "{count} points: chunked output is {gap} off");
+        }
+    }
+
+    #[test]
+    fn output_size_saturates_instead_of_overflowing() {
+        assert_eq!(smooth_output_size(30, 2), 4 + 40 * 12);
+        assert_eq!(smooth_output_size(3, 6), 16);
+        assert_eq!(smooth_output_size(usize::MAX - 2, 6), usize::MAX);
+        assert_eq!(smooth_output_size(usize::MAX / 12 * 3, 0), usize::MAX);
+    }
+
+    #[test]
+    fn bad_contexts_are_rejected() {
+        let points = scribble(10);
+        let (_, context) = chunk(&points, &[], false);
+        let short = &context[..context.len() - 1];
+        let result = smooth_stroke_chunked(points.as_ptr(), points.len(), short.as_ptr(), short.len(), true);
+        assert_eq!(error_of(result), CreoVexError::InvalidLength as u32);
+
+        let corrupted = vec![f32::NAN; chunk_context_len()];
+        let result = smooth_stroke_chunked(points.as_ptr(), points.len(), corrupted.as_ptr(), corrupted.len(), true);
+        assert_eq!(error_of(result), CreoVexError::InvalidParameter as u32);
+
+        // A valid state with a NaN carried point
+        let mut carried_nan = context.clone();
+        carried_nan[1] = f32::NAN;
+        let result = smooth_stroke_chunked(points.as_ptr(), points.len(), carried_nan.as_ptr(), carried_nan.len(), true);
+        assert_eq!(error_of(result), CreoVexError::InvalidParameter as u32);
+
+        // The same for smooth_stroke_overlap's carried raw points
+        let bytes = take_bytes(smooth_stroke_overlap(points.as_ptr(), points.len(), std::ptr::null(), 0));
+        let count = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let context = &bytes[4 + count * 12..];
+        let mut carried_nan: Vec<f32> = context.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+        assert_eq!((carried_nan.len(), carried_nan[0]), (overlap_context_len(), 2.0));
+        carried_nan[4] = f32::NAN;
+        let result = smooth_stroke_overlap(points.as_ptr(), points.len(), carried_nan.as_ptr(), carried_nan.len());
+        assert_eq!(error_of(result), CreoVexError::InvalidParameter as u32);
+    }
+
+    const FAR: f64 = 1.0e7;
+
+    /**
+     * A wavy stroke near the origin and the same stroke FAR units out on
+     * both axes
+     */
+    fn near_and_far() -> (Vec<f64>, Vec<f64>) {
+        let near: Vec<f64> = (0..50)
+            .flat_map(|i| {
+                let t = i as f64 * 0.1;
+                [t * 3.1, (t * 1.7).sin() * 2.3, 0.5]
+            })
+            .collect();
+        let far = near.chunks_exact(3).flat_map(|p| [p[0] + FAR, p[1] + FAR, p[2]]).collect();
+        (near, far)
+    }
+
+    #[test]
+    fn f64_smoothing_stays_precise_ten_million_units_out() {
+        let (near, far) = near_and_far();
+        let reference = take_records_f64(smooth_stroke_f64(near.as_ptr(), near.len()), 3);
+        let smoothed = take_records_f64(smooth_stroke_f64(far.as_ptr(), far.len()), 3);
+        assert_eq!(smoothed.len(), reference.len());
+        let error = |p: [f64; 2], q: &[f64]| (p[0] - FAR - q[0]).abs().max((p[1] - FAR - q[1]).abs());
+        let worst = smoothed.chunks_exact(3).zip(reference.chunks_exact(3)).map(|(p, q)| error([p[0], p[1]], q)).fold(0.0, f64::max);
+        assert!(worst < 0.01, "{worst} units off");
+
+        // The f32 pipeline on the same stroke is off by most of a unit
+        let far32: Vec<f32> = far.iter().map(|&value| value as f32).collect();
+        let smoothed32 = take_points(smooth_stroke_v2(far32.as_ptr(), far32.len()));
+        let worst32 = smoothed32.chunks_exact(3).zip(reference.chunks_exact(3)).map(|(p, q)| error([p[0] as f64, p[1] as f64], q)).fold(0.0, f64::max);
+        assert!(worst32 > 0.1, "{worst32} units off");
+
+        // Near the origin both run the same algorithm
+        let near32: Vec<f32> = near.iter().map(|&value| value as f32).collect();
+        let smoothed32 = take_points(smooth_stroke_v2(near32.as_ptr(), near32.len()));
+        assert!(smoothed32.iter().zip(&reference).all(|(&a, &b)| (a as f64 - b).abs() < 1e-5));
+        assert_eq!(error_of(smooth_stroke_f64(far.as_ptr(), 4)), CreoVexError::InvalidLength as u32);
+    }
+
+    /**
+     * The buffer a caller holds after feeding `points` to
+     * smooth_stroke_overlap `chunk_points` at a time, checking each chunk
+     * only replaces the provisional tail
+     */
+    fn overlap_run(points: &[f32], chunk_points: usize) -> Vec<f32> {
+        let (mut held, mut context) = (Vec::new(), Vec::new());
+        for chunk in points.chunks(chunk_points * 3) {
+            let bytes = take_bytes(smooth_stroke_overlap(chunk.as_ptr(), chunk.len(), context.as_ptr(), context.len()));
+            let values: Vec<f32> = bytes[4..].chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+            let count = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+            assert_eq!(values.len(), count * 3 + overlap_context_len());
+
+            let kept = held.len() - chunk_overlap_points().min(held.len() / 3) * 3;
+            held.truncate(kept);
+            held.extend_from_slice(&values[..count * 3]);
+            context = values[count * 3..].to_vec();
+        }
+        held
+    }
+
+    #[test]
+    fn overlap_chunking_is_bitwise_independent_of_chunk_size() {
+        for (k, count) in [2, 3, 5, 17, 64, 65, 200, 1001].into_iter().enumerate() {
+            let step = 0.13 + k as f32 * 0.07;
+            let points: Vec<f32> = (0..count)
+                .flat_map(|i| {
+                    let t = i as f32 * step;
+                    [t * 5.0 + (t * 7.0).cos(), (t * 2.0).sin() * 9.0, 0.3 + (t * 0.1).cos() * 0.2]
+                })
+                .collect();
+            let whole = take_points(smooth_stroke_v2(points.as_ptr(), points.len()));
+            for chunk_points in [1, 2, 3, 7, 64, count] {
+                let held = overlap_run(&points, chunk_points);
+                let bits = |values: &[f32]| values.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+                assert_eq!(bits(&held), bits(&whole), "{count} points, {chunk_points} per chunk");
+
+                // Everything before the provisional tail of a partial run is final
+                let partial = overlap_run(&points[..(count / 2).max(1) * 3], chunk_points);
+                let settled = partial.len() - chunk_overlap_points().min(partial.len() / 3) * 3;
+                assert_eq!(bits(&partial[..settled]), bits(&whole[..settled]), "{count} points, {chunk_points} per chunk, cut in half");
+            }
+        }
+
+        let stroke = [1.0f32, 2.0, 0.5, 3.0, 4.0, 0.5];
+        let short = [0.0f32; 7];
+        let overlap = |points: usize, context: &[f32], context_len: usize| {
+            error_of(smooth_stroke_overlap(stroke.as_ptr(), points, context.as_ptr(), context_len))
+        };
+        assert_eq!(overlap(6, &short, 6), CreoVexError::InvalidLength as u32);
+        let corrupted = [3.0f32, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        assert_eq!(overlap(6, &corrupted, 7), CreoVexError::InvalidParameter as u32);
+        assert_eq!(overlap(5, &[], 0), CreoVexError::InvalidLength as u32);
+    }
+}
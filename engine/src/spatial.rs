@@ -0,0 +1,381 @@
+/*!
+ * Persistent spatial index over stroke bounds
+ *
+ * cull_strokes and the batch hit tests look at every stroke; with large
+ * documents the index answers the same questions from the strokes near
+ * the query only. JS keeps one SpatialIndex per layer and updates it as
+ * strokes are added, moved, and erased.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Culling)
+ */
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::bounds::Bounds;
+use crate::error::{record_error, require_finite, run_status, CreoVexError, EngineError, EngineResult};
+
+/**
+ * Items a node holds before it splits into quadrants
+ */
+const NODE_CAPACITY: usize = 8;
+
+/**
+ * Deepest level below the root that items are pushed down to
+ */
+const MAX_DEPTH: u32 = 20;
+
+/**
+ * Smallest half-size of a new root, in canvas units (a power of two)
+ *
+ * Keeps the first box around a point item from being degenerate.
+ */
+const MIN_ROOT_HALF: f64 = 64.0;
+
+/**
+ * Child slot value for a node that has not split
+ */
+const NO_CHILD: u32 = u32::MAX;
+
+/**
+ * One quadtree cell: items stored here plus four child cells (NO_CHILD
+ * until the cell splits)
+ */
+#[derive(Clone, Debug)]
+struct Node {
+    items: Vec<(u32, Bounds)>,
+    children: [u32; 4],
+}
+
+impl Node {
+    fn new() -> Self {
+        Node { items: Vec::new(), children: [NO_CHILD; 4] }
+    }
+}
+
+/**
+ * Square cell geometry; children are (-x -y), (+x -y), (-x +y), (+x +y)
+ */
+#[derive(Clone, Copy, Debug)]
+struct Cell {
+    cx: f64,
+    cy: f64,
+    half: f64,
+}
+
+impl Cell {
+    fn child(self, quadrant: usize) -> Cell {
+        let half = self.half * 0.5;
+        let cx = if quadrant & 1 == 0 { self.cx - half } else { self.cx + half };
+        let cy = if quadrant & 2 == 0 { self.cy - half } else { self.cy + half };
+        Cell { cx, cy, half }
+    }
+
+    fn quadrant(self, x: f64, y: f64) -> usize {
+        usize::from(x >= self.cx) | (usize::from(y >= self.cy) << 1)
+    }
+
+    /**
+     * Whether the item can live in this cell: center inside the cell,
+     * extent within the loose margin (the loose cell is twice the size)
+     */
+    fn fits(self, item: &Footprint) -> bool {
+        (item.cx - self.cx).abs() <= self.half && (item.cy - self.cy).abs() <= self.half && item.extent <= self.half
+    }
+
+    /**
+     * Whether the loose cell (half-size doubled) overlaps `query`
+     */
+    fn loose_intersects(self, query: Bounds) -> bool {
+        let loose = 2.0 * self.half;
+        query.min_x as f64 <= self.cx + loose
+            && query.max_x as f64 >= self.cx - loose
+            && query.min_y as f64 <= self.cy + loose
+            && query.max_y as f64 >= self.cy - loose
+    }
+}
+
+/**
+ * Item center and largest half-extent, which decide the cell it lives in
+ */
+struct Footprint {
+    cx: f64,
+    cy: f64,
+    extent: f64,
+}
+
+impl Footprint {
+    fn of(bounds: Bounds) -> Self {
+        let (min_x, min_y, max_x, max_y) =
+            (bounds.min_x as f64, bounds.min_y as f64, bounds.max_x as f64, bounds.max_y as f64);
+        Footprint {
+            cx: (min_x + max_x) * 0.5,
+            cy: (min_y + max_y) * 0.5,
+            extent: ((max_x - min_x) * 0.5).max((max_y - min_y) * 0.5),
+        }
+    }
+}
+
+/**
+ * Loose quadtree of stroke bounding boxes, keyed by stroke id
+ *
+ * Each box is stored in a cell that holds its center and whose loose
+ * bounds (the cell grown by half its size on every side) contain the
+ * whole box, so boxes never straddle cells and a query only visits cells
+ * whose loose bounds overlap it. A cell splits into quadrants once it
+ * holds more than NODE_CAPACITY boxes, and boxes small enough for a
+ * quadrant move down into it. The root starts around
+ * the first box and doubles toward any box that falls outside it, so
+ * there is no fixed world size.
+ *
+ * Boxes are in canvas units, e.g. from stroke_bounds. Queries return ids
+ * in ascending order; a box overlapping the query or touching its edge
+ * counts as a hit.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct SpatialIndex {
+    nodes: Vec<Node>,
+    root: u32,
+    bounds: Cell,
+    /** Node holding each id */
+    locations: BTreeMap<u32, u32>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SpatialIndex {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> SpatialIndex {
+        SpatialIndex {
+            nodes: Vec::new(),
+            root: 0,
+            bounds: Cell { cx: 0.0, cy: 0.0, half: MIN_ROOT_HALF },
+            locations: BTreeMap::new(),
+        }
+    }
+
+    /**
+     * Number of ids in the index
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /**
+     * Whether the index holds no ids
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /**
+     * Add a stroke's bounding box
+     *
+     * An id that is already present is moved to the new box (as update).
+     * Returns the number of ids in the index, or a negative CreoVexError
+     * discriminant when the box is invalid (the index is unchanged then).
+     *
+     * Errors: NonFiniteValue for a NaN or infinite bound; InvalidParameter
+     * for a max below its min.
+     */
+    pub fn insert(&mut self, id: u32, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> i32 {
+        run_status(|| {
+            let bounds = read_rect(min_x, min_y, max_x, max_y)?;
+            self.remove(id);
+            self.insert_bounds(id, bounds);
+            Ok(self.locations.len())
+        })
+    }
+
+    /**
+     * Move a stroke to a new bounding box
+     *
+     * Same as insert: an id that is not present yet is added.
+     *
+     * Errors: as insert.
+     */
+    pub fn update(&mut self, id: u32, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> i32 {
+        self.insert(id, min_x, min_y, max_x, max_y)
+    }
+
+    /**
+     * Remove a stroke; returns whether it was present (a missing id is a
+     * no-op)
+     */
+    pub fn remove(&mut self, id: u32) -> bool {
+        let Some(node) = self.locations.remove(&id) else {
+            return false;
+        };
+        let items = &mut self.nodes[node as usize].items;
+        if let Some(position) = items.iter().position(|&(item, _)| item == id) {
+            items.swap_remove(position);
+        }
+        if self.locations.is_empty() {
+            self.clear();
+        }
+        true
+    }
+
+    /**
+     * Ids whose boxes overlap the rect, ascending
+     *
+     * An invalid rect (NaN/infinite, or max below min) returns no ids and
+     * records the error for last_error_code() / last_error_message().
+     */
+    pub fn query_rect(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Vec<u32> {
+        match read_rect(min_x, min_y, max_x, max_y) {
+            Ok(query) => self.query(query),
+            Err(error) => {
+                record_error(error);
+                Vec::new()
+            }
+        }
+    }
+
+    /**
+     * Ids whose boxes contain the point (edges included), ascending
+     *
+     * A NaN or infinite coordinate returns no ids and records the error.
+     */
+    pub fn query_point(&self, x: f32, y: f32) -> Vec<u32> {
+        self.query_rect(x, y, x, y)
+    }
+
+    /**
+     * Remove every id and release the index's memory
+     */
+    pub fn clear(&mut self) {
+        self.nodes = Vec::new();
+        self.locations.clear();
+        self.root = 0;
+    }
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        SpatialIndex::new()
+    }
+}
+
+impl SpatialIndex {
+    fn insert_bounds(&mut self, id: u32, bounds: Bounds) {
+        let item = Footprint::of(bounds);
+        if self.nodes.is_empty() {
+            // Power-of-two size on a grid of its own size: splitting and growing stay exact in f64
+            let half = item.extent.max(MIN_ROOT_HALF).log2().ceil().exp2();
+            self.nodes.push(Node::new());
+            self.root = 0;
+            self.bounds = Cell { cx: (item.cx / half).round() * half, cy: (item.cy / half).round() * half, half };
+        }
+        while !self.bounds.fits(&item) {
+            self.grow_toward(item.cx, item.cy);
+        }
+
+        // Descend while a child can take the item; split full leaves on the way
+        let (mut node, mut cell) = (self.root, self.bounds);
+        for _ in 0..MAX_DEPTH {
+            let quadrant = cell.quadrant(item.cx, item.cy);
+            let child = cell.child(quadrant);
+            if !child.fits(&item) {
+                break;
+            }
+            if self.nodes[node as usize].children[quadrant] == NO_CHILD {
+                if self.nodes[node as usize].items.len() < NODE_CAPACITY {
+                    break;
+                }
+                self.split(node, cell);
+            }
+            node = self.nodes[node as usize].children[quadrant];
+            cell = child;
+        }
+        self.nodes[node as usize].items.push((id, bounds));
+        self.locations.insert(id, node);
+    }
+
+    /**
+     * Double the root toward (x, y); the old root becomes one quadrant
+     */
+    fn grow_toward(&mut self, x: f64, y: f64) {
+        let old = self.bounds;
+        let cx = if x >= old.cx { old.cx + old.half } else { old.cx - old.half };
+        let cy = if y >= old.cy { old.cy + old.half } else { old.cy - old.half };
+        let grown = Cell { cx, cy, half: old.half * 2.0 };
+
+        let old_quadrant = grown.quadrant(old.cx, old.cy);
+        let mut root = Node::new();
+        root.children = [0, 1, 2, 3].map(|quadrant| {
+            if quadrant == old_quadrant {
+                self.root
+            } else {
+                self.nodes.push(Node::new());
+                self.nodes.len() as u32 - 1
+            }
+        });
+        self.nodes.push(root);
+        self.root = self.nodes.len() as u32 - 1;
+        self.bounds = grown;
+    }
+
+    /**
+     * Give a leaf its four children and push down the items that fit one
+     */
+    fn split(&mut self, node: u32, cell: Cell) {
+        let first = self.nodes.len() as u32;
+        self.nodes.extend((0..4).map(|_| Node::new()));
+        self.nodes[node as usize].children = [first, first + 1, first + 2, first + 3];
+
+        let items = std::mem::take(&mut self.nodes[node as usize].items);
+        for (id, bounds) in items {
+            let item = Footprint::of(bounds);
+            let quadrant = cell.quadrant(item.cx, item.cy);
+            let target = if cell.child(quadrant).fits(&item) { first + quadrant as u32 } else { node };
+            self.nodes[target as usize].items.push((id, bounds));
+            self.locations.insert(id, target);
+        }
+    }
+
+    fn query(&self, query: Bounds) -> Vec<u32> {
+        let mut hits = Vec::new();
+        if self.nodes.is_empty() {
+            return hits;
+        }
+        let mut stack = vec![(self.root, self.bounds)];
+        while let Some((node, cell)) = stack.pop() {
+            if !cell.loose_intersects(query) {
+                continue;
+            }
+            let node = &self.nodes[node as usize];
+            hits.extend(node.items.iter().filter(|(_, bounds)| bounds.intersects(query)).map(|&(id, _)| id));
+            for (quadrant, &child) in node.children.iter().enumerate() {
+                if child != NO_CHILD {
+                    stack.push((child, cell.child(quadrant)));
+                }
+            }
+        }
+        hits.sort_unstable();
+        hits
+    }
+}
+
+/**
+ * Validate a box given as min/max corners
+ */
+fn read_rect(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> EngineResult<Bounds> {
+    let bounds = Bounds {
+        min_x: require_finite("min_x", min_x)?,
+        min_y: require_finite("min_y", min_y)?,
+        max_x: require_finite("max_x", max_x)?,
+        max_y: require_finite("max_y", max_y)?,
+    };
+    if bounds.max_x < bounds.min_x || bounds.max_y < bounds.min_y {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("rect max is below its min ({:?})", bounds.to_array()),
+        ));
+    }
+    Ok(bounds)
+}
@@ -0,0 +1,322 @@
+/*!
+ * Spline stroke interpolation
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Smoothing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_non_negative, run_export, CreoVexError, EngineError, EngineResult};
+use crate::{read_points, serialize_points, EngineBuffer, MAX_STRIDE};
+
+/**
+ * Upper bound on samples per segment (output grows linearly with it)
+ */
+pub(crate) const MAX_SAMPLES_PER_SEGMENT: u32 = 256;
+
+/**
+ * Point of up to MAX_STRIDE channels; lanes past the stride stay zero
+ */
+type Point = [f32; MAX_STRIDE];
+
+/**
+ * Interpolate stroke with a Catmull-Rom spline
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, densified
+ *
+ * The curve passes through every input point. Each segment between two
+ * consecutive points contributes `samples_per_segment` points (1-256),
+ * the last of which is the segment's end point itself, so the output
+ * holds `1 + segments * samples_per_segment` points.
+ *
+ * `alpha` selects the knot parameterization: 0 uniform, 0.5 centripetal
+ * (no loops or cusps on tight turns; recommended), 1 chordal. Knot spacing
+ * uses x/y only; pressure is interpolated with the same basis and clamped
+ * to be non-negative. The end segments use mirrored phantom points, and a
+ * two-point input yields an evenly spaced straight line.
+ *
+ * Consecutive duplicate points are skipped; a stroke with a single
+ * distinct point returns that point.
+ *
+ * Errors: the read_points errors, plus InvalidParameter for
+ * samples_per_segment outside 1-256 and NonFiniteValue/InvalidParameter
+ * for an alpha outside [0, 1].
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn interpolate_stroke_catmull_rom(
+    points_ptr: *const f32,
+    points_len: usize,
+    samples_per_segment: u32,
+    alpha: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let samples = require_samples(samples_per_segment)?;
+        let alpha = require_non_negative("alpha", alpha)?;
+        if alpha > 1.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("alpha must be in [0, 1] (got {alpha})"),
+            ));
+        }
+
+        let control = distinct_points(points, 3);
+        let interpolated = catmull_rom(&control, 3, samples, alpha);
+        serialize_points(&interpolated)
+    })
+}
+
+/**
+ * Smooth stroke with a clamped uniform cubic B-spline
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...] (control points)
+ * Output format: same structure, sampled curve
+ *
+ * The input points are treated as control points of a cubic B-spline
+ * with a clamped uniform knot vector, so the curve starts exactly at the
+ * first point and ends exactly at the last. Each of the `n - 3` knot
+ * spans is sampled `samples_per_segment` times (1-256), giving
+ * `1 + (n - 3) * samples_per_segment` points. Strokes with fewer than four
+ * points drop to the highest degree they support (a single Bézier span).
+ *
+ * Pressure uses the same basis functions. By the convex hull property
+ * every sample lies within the hull of the (at most four) control points
+ * of its span, so it never strays further from the control polygon than
+ * those points are from each other, and pressure stays within their range.
+ *
+ * Errors: the read_points errors, plus InvalidParameter for
+ * samples_per_segment outside 1-256.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_stroke_bspline(points_ptr: *const f32, points_len: usize, samples_per_segment: u32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let samples = require_samples(samples_per_segment)?;
+
+        let control: Vec<Point> = points.chunks_exact(3).map(to_point).collect();
+        let sampled = clamped_bspline(&control, 3, samples);
+        serialize_points(&sampled)
+    })
+}
+
+/**
+ * Sample a clamped uniform B-spline of degree min(3, n - 1)
+ */
+fn clamped_bspline(control: &[Point], stride: usize, samples: usize) -> Vec<f32> {
+    let count = control.len();
+    let degree = (count - 1).min(3);
+    let spans = count - degree;
+
+    let mut result = Vec::with_capacity((1 + spans * samples) * stride);
+    result.extend_from_slice(&control[0][..stride]);
+    if degree == 0 {
+        return result;
+    }
+
+    // Clamped knots: degree + 1 zeros, 1..spans - 1, degree + 1 copies of spans
+    let knots: Vec<f32> = (0..count + degree + 1)
+        .map(|i| i.saturating_sub(degree).min(spans) as f32)
+        .collect();
+
+    for span in 0..spans {
+        for j in 1..=samples {
+            if span + 1 == spans && j == samples {
+                // End exactly on the last control point
+                result.extend_from_slice(&control[count - 1][..stride]);
+                break;
+            }
+            let u = span as f32 + j as f32 / samples as f32;
+            result.extend_from_slice(&de_boor(control, &knots, degree, span + degree, u)[..stride]);
+        }
+    }
+
+    result
+}
+
+/**
+ * Evaluate the spline at `u` inside knot interval `index` (de Boor)
+ */
+fn de_boor(control: &[Point], knots: &[f32], degree: usize, index: usize, u: f32) -> Point {
+    let mut d = [[0.0f32; MAX_STRIDE]; 4];
+    d[..=degree].copy_from_slice(&control[index - degree..=index]);
+
+    for r in 1..=degree {
+        for i in (r..=degree).rev() {
+            let left = knots[index - degree + i];
+            let right = knots[index + 1 + i - r];
+            let t = (u - left) / (right - left);
+            d[i] = lerp(d[i - 1], d[i], t);
+        }
+    }
+
+    d[degree]
+}
+
+/**
+ * Validate a per-segment sample count
+ */
+pub(crate) fn require_samples(samples_per_segment: u32) -> EngineResult<usize> {
+    if samples_per_segment == 0 || samples_per_segment > MAX_SAMPLES_PER_SEGMENT {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("samples_per_segment must be 1-{MAX_SAMPLES_PER_SEGMENT} (got {samples_per_segment})"),
+        ));
+    }
+    Ok(samples_per_segment as usize)
+}
+
+/**
+ * Sample a Catmull-Rom spline through `control` (Barry-Goldman form)
+ */
+fn catmull_rom(control: &[Point], stride: usize, samples: usize, alpha: f32) -> Vec<f32> {
+    let count = control.len();
+    let mut result = Vec::with_capacity((1 + count.saturating_sub(1) * samples) * stride);
+    result.extend_from_slice(&control[0][..stride]);
+
+    if count == 2 {
+        for j in 1..=samples {
+            let t = j as f32 / samples as f32;
+            result.extend_from_slice(&lerp(control[0], control[1], t)[..stride]);
+        }
+        return result;
+    }
+
+    for i in 0..count.saturating_sub(1) {
+        let p1 = control[i];
+        let p2 = control[i + 1];
+        // Mirror phantom points beyond the ends
+        let p0 = if i > 0 { control[i - 1] } else { lerp(p2, p1, 2.0) };
+        let p3 = if i + 2 < count { control[i + 2] } else { lerp(p1, p2, 2.0) };
+
+        let t1 = knot_interval(p0, p1, alpha);
+        let t2 = t1 + knot_interval(p1, p2, alpha);
+        let t3 = t2 + knot_interval(p2, p3, alpha);
+
+        for j in 1..samples {
+            let t = t1 + (t2 - t1) * (j as f32 / samples as f32);
+            let a1 = blend(p0, p1, 0.0, t1, t);
+            let a2 = blend(p1, p2, t1, t2, t);
+            let a3 = blend(p2, p3, t2, t3, t);
+            let b1 = blend(a1, a2, 0.0, t2, t);
+            let b2 = blend(a2, a3, t1, t3, t);
+            let mut point = blend(b1, b2, t1, t2, t);
+            point[2] = point[2].max(0.0);
+            result.extend_from_slice(&point[..stride]);
+        }
+        // End exactly on the input point
+        result.extend_from_slice(&p2[..stride]);
+    }
+
+    result
+}
+
+/**
+ * Knot spacing |b - a|^alpha over x/y (points are distinct)
+ */
+fn knot_interval(a: Point, b: Point, alpha: f32) -> f32 {
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    (dx * dx + dy * dy).powf(alpha * 0.5)
+}
+
+/**
+ * Linear blend of `a` at knot `ta` and `b` at knot `tb`, evaluated at `t`
+ */
+fn blend(a: Point, b: Point, ta: f32, tb: f32, t: f32) -> Point {
+    lerp(a, b, (t - ta) / (tb - ta))
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t)
+}
+
+/**
+ * Widen a `stride`-float point to a zero-padded Point
+ */
+fn to_point(values: &[f32]) -> Point {
+    let mut point = [0.0; MAX_STRIDE];
+    point[..values.len()].copy_from_slice(values);
+    point
+}
+
+/**
+ * Collect points, dropping consecutive duplicates
+ *
+ * Separations that underflow to zero in f32 count as duplicates too, so
+ * every knot interval is positive.
+ */
+fn distinct_points(points: &[f32], stride: usize) -> Vec<Point> {
+    let mut result: Vec<Point> = Vec::with_capacity(points.len() / stride);
+    for point in points.chunks_exact(stride) {
+        let point = to_point(point);
+        match result.last() {
+            Some(last) if knot_interval(*last, point, 1.0) == 0.0 => {}
+            _ => result.push(point),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{error_of, take_points, Noise};
+
+    fn bspline(points: &[f32], samples: u32) -> Vec<f32> {
+        take_points(smooth_stroke_bspline(points.as_ptr(), points.len(), samples))
+    }
+
+    fn segment_distance(p: [f32; 2], a: &[f32], b: &[f32]) -> f32 {
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        let length = dx * dx + dy * dy;
+        let t = if length > 0.0 { (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length).clamp(0.0, 1.0) } else { 0.0 };
+        (p[0] - a[0] - t * dx).hypot(p[1] - a[1] - t * dy)
+    }
+
+    #[test]
+    fn samples_stay_in_their_control_hull() {
+        let mut noise = Noise::new(16);
+        let control: Vec<f32> = (0..30).flat_map(|i| [i as f32 * 5.0, noise.next() * 20.0, (i % 5) as f32 / 4.0]).collect();
+        let samples = 10;
+        let curve = bspline(&control, samples as u32);
+        assert_eq!(curve.len() / 3, 1 + 27 * samples);
+        assert_eq!(curve[..3], control[..3]);
+        assert_eq!(curve[curve.len() - 3..], control[control.len() - 3..]);
+
+        // Sample k of span j lies in the box (which holds the hull) of control points j..j + 3
+        for (index, point) in curve.chunks_exact(3).enumerate().skip(1) {
+            let span = (index - 1) / samples;
+            let hull = &control[span * 3..(span + 4) * 3];
+            for (channel, value) in point.iter().enumerate() {
+                let values = hull.iter().skip(channel).step_by(3);
+                let (low, high) = values.fold((f32::MAX, f32::MIN), |(low, high), &v| (low.min(v), high.max(v)));
+                assert!((low - 1e-4..=high + 1e-4).contains(value), "sample {index} channel {channel} = {value} outside {low}..{high}");
+            }
+
+            // The documented deviation bound: no further from the span's
+            // polygon than its points are from each other
+            let corners: Vec<&[f32]> = hull.chunks_exact(3).collect();
+            let deviation = corners.windows(2).map(|w| segment_distance([point[0], point[1]], w[0], w[1])).fold(f32::MAX, f32::min);
+            let spread = corners.iter().flat_map(|a| corners.iter().map(|b| (a[0] - b[0]).hypot(a[1] - b[1]))).fold(0.0, f32::max);
+            assert!(deviation <= spread, "sample {index} is {deviation} from its polygon (spread {spread})");
+        }
+    }
+
+    #[test]
+    fn straight_control_polygons_give_straight_curves() {
+        let line: Vec<f32> = (0..6).flat_map(|i| [i as f32, 2.0 * i as f32, 1.0]).collect();
+        for point in bspline(&line, 4).chunks_exact(3) {
+            assert!((point[1] - 2.0 * point[0]).abs() < 1e-4);
+        }
+        // Fewer than four points drop to a lower degree, still ending on the last one
+        for count in 1..4 {
+            let curve = bspline(&line[..count * 3], 4);
+            assert_eq!(curve[curve.len() - 3..], line[(count - 1) * 3..count * 3]);
+        }
+        let result = smooth_stroke_bspline(line.as_ptr(), line.len(), MAX_SAMPLES_PER_SEGMENT + 1);
+        assert_eq!(error_of(result), CreoVexError::InvalidParameter as u32);
+    }
+}
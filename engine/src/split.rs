@@ -0,0 +1,195 @@
+/*!
+ * Stroke splitting
+ *
+ * Cuts a stroke into pieces at given positions, for the scissors tool
+ * and for animation: by distance along the stroke, or by segment and
+ * parameter for the node editor. Both locate every cut as a segment
+ * index plus parameter, so adjacent pieces share the exact cut point.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Editing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::{cumulative_lengths, polyline_length};
+use crate::error::{run_export, CreoVexError, EngineError, EngineResult};
+use crate::erase::ERASE_MIN_FRAGMENT_LENGTH;
+use crate::{read_points, serialize_strokes, EngineBuffer};
+
+/**
+ * Shortest piece kept with `drop_short`, in canvas units (the eraser's
+ * fragment threshold, so scissors and eraser leave the same slivers)
+ */
+pub(crate) const SPLIT_MIN_PIECE_LENGTH: f64 = ERASE_MIN_FRAGMENT_LENGTH;
+
+/**
+ * A cut position: segment index and parameter in [0, 1]
+ */
+type Cut = (usize, f32);
+
+/**
+ * Split a stroke at distances along it
+ *
+ * Input format: stroke [x, y, p, ...]; cuts are f32 arc-length distances
+ * from the first point (as point_at_length measures them), in any order
+ *
+ * Output format: the batch container (see read_batch) holding the pieces
+ * in stroke order, without a status array
+ *
+ * Each cut point is interpolated exactly, position and pressure alike,
+ * and ends one piece and starts the next. Cuts at or before the start and
+ * at or beyond the total length are ignored, and equal cuts collapse, so
+ * no cut returns the stroke whole. With `drop_short`, pieces shorter than
+ * SPLIT_MIN_PIECE_LENGTH are dropped.
+ *
+ * Errors: the read_points errors, NullInput for a null cut buffer with a
+ * nonzero cuts_len, and NonFiniteValue for a NaN or infinite cut.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn split_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    cuts_ptr: *const f32,
+    cuts_len: usize,
+    drop_short: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let distances = read_cuts(cuts_ptr, cuts_len)?;
+
+        let table = cumulative_lengths(points, 3);
+        let total = table[table.len() - 1];
+        let cuts = distances
+            .iter()
+            .filter(|&&s| s > 0.0 && (s as f64) < total)
+            .map(|&s| {
+                // First point strictly beyond s, as in interpolate_at_length
+                let end = table.partition_point(|&walked| walked <= s as f64);
+                let t = ((s as f64 - table[end - 1]) / (table[end] - table[end - 1])) as f32;
+                (end - 1, t)
+            })
+            .collect();
+        serialize_strokes(&split_polyline(points, 3, cuts, drop_short))
+    })
+}
+
+/**
+ * Split a stroke at segment parameters
+ *
+ * Input format: stroke [x, y, p, ...]; cuts are f32 pairs
+ * [segment0, t0, segment1, t1, ...] in any order, where segment i runs
+ * from point i to point i + 1 and t (0-1) is the fraction along it
+ *
+ * Output format: as split_stroke
+ *
+ * The cut point is point[segment] + t * (point[segment + 1] -
+ * point[segment]) in every channel, and t 1 is the next point itself.
+ * Cuts at or before the first point and at or beyond the last (t 1 on
+ * the last segment, or a later segment) are ignored and equal cuts
+ * collapse, as in split_stroke.
+ *
+ * Errors: the read_points errors, NullInput for a null cut buffer with a
+ * nonzero cuts_len, InvalidLength for an odd cuts_len, NonFiniteValue for
+ * a NaN or infinite value, and InvalidParameter for a segment that is not
+ * a whole number >= 0 or a t outside [0, 1].
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn split_stroke_at_params(
+    points_ptr: *const f32,
+    points_len: usize,
+    cuts_ptr: *const f32,
+    cuts_len: usize,
+    drop_short: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let params = read_cuts(cuts_ptr, cuts_len)?;
+        if params.len() % 2 != 0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidLength,
+                format!("cuts_len must be a multiple of 2 (got {cuts_len})"),
+            ));
+        }
+
+        let mut cuts = Vec::with_capacity(params.len() / 2);
+        for (index, pair) in params.chunks_exact(2).enumerate() {
+            let (segment, t) = (pair[0], pair[1]);
+            if segment < 0.0 || segment.fract() != 0.0 || !(0.0..=1.0).contains(&t) {
+                return Err(EngineError::new(
+                    CreoVexError::InvalidParameter,
+                    format!("cut {index} needs a whole segment >= 0 and t in [0, 1] (got {segment}, {t})"),
+                ));
+            }
+            cuts.push((segment as usize, t));
+        }
+        serialize_strokes(&split_polyline(points, 3, cuts, drop_short))
+    })
+}
+
+/**
+ * Validate a cut buffer (empty allowed, with any pointer)
+ */
+fn read_cuts<'a>(cuts_ptr: *const f32, cuts_len: usize) -> EngineResult<&'a [f32]> {
+    if cuts_len == 0 {
+        return Ok(&[]);
+    }
+    if cuts_ptr.is_null() {
+        return Err(EngineError::new(CreoVexError::NullInput, "cuts_ptr is null"));
+    }
+    let cuts: &[f32] = unsafe { std::slice::from_raw_parts(cuts_ptr, cuts_len) };
+    if let Some(index) = cuts.iter().position(|value| !value.is_finite()) {
+        return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("cut value {index} is not finite")));
+    }
+    Ok(cuts)
+}
+
+/**
+ * Pieces of `points` between `cuts`, in stroke order
+ *
+ * Points are `stride` floats wide; every channel after x/y is
+ * interpolated like pressure.
+ */
+pub(crate) fn split_polyline(points: &[f32], stride: usize, cuts: Vec<Cut>, drop_short: bool) -> Vec<Vec<f32>> {
+    let count = points.len() / stride;
+    // t 1 is the next segment's start, which is exactly its point; cuts
+    // on the first point or from the last one on have nothing to split
+    let mut cuts: Vec<Cut> = cuts
+        .into_iter()
+        .map(|(segment, t)| if t == 1.0 { (segment.saturating_add(1), 0.0) } else { (segment, t) })
+        .filter(|&(segment, t)| segment < count - 1 && (segment, t) != (0, 0.0))
+        .collect();
+    cuts.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    cuts.dedup();
+    if cuts.is_empty() {
+        return vec![points.to_vec()];
+    }
+
+    let point = |i: usize| &points[i * stride..(i + 1) * stride];
+    let mut pieces = Vec::with_capacity(cuts.len() + 1);
+    let mut piece = point(0).to_vec();
+    // First input point not yet in a piece
+    let mut next = 1;
+    for (segment, t) in cuts {
+        // Points before the cut; a cut at t 0 is its segment's start point itself
+        let before = if t > 0.0 { segment + 1 } else { segment };
+        for i in next..before {
+            piece.extend_from_slice(point(i));
+        }
+        next = segment + 1;
+
+        let cut: Vec<f32> = point(segment).iter().zip(point(segment + 1)).map(|(&a, &b)| a + (b - a) * t).collect();
+        piece.extend_from_slice(&cut);
+        pieces.push(std::mem::replace(&mut piece, cut));
+    }
+    for i in next..count {
+        piece.extend_from_slice(point(i));
+    }
+    pieces.push(piece);
+
+    if drop_short {
+        pieces.retain(|piece| polyline_length(piece, stride) >= SPLIT_MIN_PIECE_LENGTH);
+    }
+    pieces
+}
@@ -0,0 +1,245 @@
+/*!
+ * Stamp and particle placement for stamp-based brushes
+ *
+ * Textured brushes draw one stamp image every few canvas units along the
+ * stroke; spray brushes scatter particles around it. Placing them here
+ * keeps the arc-length walk next to the other distance-based features,
+ * and the seeded jitter (see rng) makes a replayed stroke paint exactly
+ * the same stamps.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::arclength::{cumulative_lengths, interpolate_at_length};
+use crate::error::{require_finite, require_non_negative, require_positive, run_export, CreoVexError, EngineError};
+use crate::rng::SeededRng;
+use crate::{read_points, serialize_records, EngineBuffer};
+
+/**
+ * Floats per stamp record: x, y, rotation, scale, opacity
+ */
+pub(crate) const STAMP_RECORD_FLOATS: usize = 5;
+
+/**
+ * Most stamps one call may produce
+ */
+pub(crate) const MAX_STAMPS: usize = 1 << 20;
+
+/**
+ * Lowest pressure used to stretch pressure-scaled spacing
+ *
+ * Spacing is divided by the pressure, so near-zero pressure would
+ * otherwise leave one stamp for the rest of the stroke.
+ */
+pub(crate) const STAMP_MIN_SPACING_PRESSURE: f32 = 0.1;
+
+/**
+ * Place brush stamps along a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: stamp count (u32)
+ * - count * 20 bytes: f32 x, y, rotation, scale, opacity per stamp
+ *
+ * The first stamp sits on the first point and each next one `spacing`
+ * canvas units further along the arc length; the last point only gets a
+ * stamp if the spacing lands on it. A stroke with no length gets one
+ * stamp. Position and pressure are interpolated at each stamp.
+ *
+ * With `pressure_spacing`, the step after a stamp is `spacing` divided by
+ * its pressure (at least STAMP_MIN_SPACING_PRESSURE), so stamps pack
+ * tighter where the stroke is wider; full pressure steps `spacing`.
+ *
+ * - rotation: direction of the segment under the stamp in radians
+ *   (atan2, 0 along +x; 0 for a stroke with no length), plus
+ *   `angle_jitter * u`
+ * - scale: pressure times `1 + scale_jitter * u`
+ * - opacity: pressure clamped to [0, 1]
+ *
+ * Each `u` is uniform in [-1, 1) from a generator seeded with `seed`;
+ * every stamp draws its scale value and then its angle value even when
+ * jitter is 0, so the same stroke and seed always give identical stamps.
+ *
+ * Errors: the read_points errors; NonFiniteValue/InvalidParameter for a
+ * spacing that is not positive and finite, a jitter that is negative or
+ * not finite, or a scale jitter above 1; InvalidParameter when the stroke
+ * would need more than MAX_STAMPS stamps.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn compute_stamps(
+    points_ptr: *const f32,
+    points_len: usize,
+    spacing: f32,
+    scale_jitter: f32,
+    angle_jitter: f32,
+    seed: u32,
+    pressure_spacing: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let spacing = require_positive("spacing", require_finite("spacing", spacing)?)?;
+        let scale_jitter = require_non_negative("scale_jitter", require_finite("scale_jitter", scale_jitter)?)?;
+        let angle_jitter = require_non_negative("angle_jitter", require_finite("angle_jitter", angle_jitter)?)?;
+        if scale_jitter > 1.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("scale_jitter must be at most 1 (got {scale_jitter})"),
+            ));
+        }
+
+        let table = cumulative_lengths(points, 3);
+        let total = table[table.len() - 1];
+        let shortest_step = if pressure_spacing {
+            spacing / max_pressure(points).max(STAMP_MIN_SPACING_PRESSURE)
+        } else {
+            spacing
+        };
+        // A NaN length (non-finite points let through) is refused too
+        if total.is_nan() || total / shortest_step as f64 >= MAX_STAMPS as f64 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("spacing {spacing} would place more than {MAX_STAMPS} stamps along length {total}"),
+            ));
+        }
+
+        let mut rng = SeededRng::new(seed);
+        let mut records = Vec::new();
+        let mut s = 0.0f64;
+        loop {
+            let point = interpolate_at_length(points, 3, &table, s);
+            let (x, y, pressure) = (point[0], point[1], point[2]);
+            let (scale_u, angle_u) = (rng.next_signed(), rng.next_signed());
+
+            let rotation = tangent_angle(points, &table, s) + angle_jitter * angle_u;
+            let scale = pressure * (1.0 + scale_jitter * scale_u);
+            records.extend_from_slice(&[x, y, rotation, scale, pressure.clamp(0.0, 1.0)]);
+
+            let step = if pressure_spacing { spacing / pressure.max(STAMP_MIN_SPACING_PRESSURE) } else { spacing };
+            s += step as f64;
+            // A NaN pressure makes the step NaN; stop rather than walk forever
+            if s > total || s.is_nan() {
+                break;
+            }
+        }
+
+        serialize_records((records.len() / STAMP_RECORD_FLOATS) as u32, &records)
+    })
+}
+
+fn max_pressure(points: &[f32]) -> f32 {
+    points.chunks_exact(3).map(|point| point[2]).fold(0.0, f32::max)
+}
+
+/**
+ * Direction of the segment containing distance `s`, in radians
+ *
+ * At a segment boundary the later segment counts; at the end, the last
+ * segment with length. 0 when the stroke has no length.
+ */
+fn tangent_angle(points: &[f32], table: &[f64], s: f64) -> f32 {
+    let count = table.len();
+    let mut end = table.partition_point(|&walked| walked <= s).clamp(1, count.max(2) - 1);
+    while end > 1 && table[end] == table[end - 1] {
+        end -= 1;
+    }
+    if count < 2 || table[end] == table[end - 1] {
+        return 0.0;
+    }
+    let (a, b) = (&points[(end - 1) * 3..end * 3], &points[end * 3..end * 3 + 3]);
+    (b[1] - a[1]).atan2(b[0] - a[0])
+}
+
+/**
+ * Scatter particles around a stroke (spray brushes)
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: compute_stamps records, in arc-length order
+ *
+ * The stroke gets `ceil(length * count_per_unit)` particles, one in each
+ * equal stretch of arc length at a random distance within it. Each is
+ * pushed off the path along the normal by up to `radius * pressure` on
+ * either side, with pressure interpolated at its distance.
+ *
+ * - rotation: uniform in [-π, π)
+ * - scale: pressure times `1 + size_jitter * u`, u uniform in [-1, 1)
+ * - opacity: pressure clamped to [0, 1]
+ *
+ * A stroke with no length sprays `ceil(count_per_unit)` particles around
+ * its point, each in a random direction.
+ *
+ * Every particle draws the same four values from the generator seeded
+ * with `seed` (distance, offset, size, rotation), so the same stroke and
+ * seed give byte-identical output.
+ *
+ * Errors: the read_points errors; NonFiniteValue/InvalidParameter for a
+ * count_per_unit that is not positive and finite, a radius or size
+ * jitter that is negative or not finite, or a size jitter above 1;
+ * InvalidParameter when the stroke would need more than MAX_STAMPS
+ * particles.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn compute_scatter(
+    points_ptr: *const f32,
+    points_len: usize,
+    count_per_unit: f32,
+    radius: f32,
+    size_jitter: f32,
+    seed: u32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let count_per_unit = require_positive("count_per_unit", require_finite("count_per_unit", count_per_unit)?)?;
+        let radius = require_non_negative("radius", require_finite("radius", radius)?)?;
+        let size_jitter = require_non_negative("size_jitter", require_finite("size_jitter", size_jitter)?)?;
+        if size_jitter > 1.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("size_jitter must be at most 1 (got {size_jitter})"),
+            ));
+        }
+
+        let table = cumulative_lengths(points, 3);
+        let total = table[table.len() - 1];
+        let density = count_per_unit as f64;
+        let count = if total == 0.0 { density.ceil() } else { (total * density).ceil() };
+        if count >= MAX_STAMPS as f64 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("count_per_unit {count_per_unit} would place more than {MAX_STAMPS} particles"),
+            ));
+        }
+
+        let mut rng = SeededRng::new(seed);
+        let mut records = Vec::with_capacity(count as usize * STAMP_RECORD_FLOATS);
+        for i in 0..count as usize {
+            let (along, offset) = (rng.next_unit(), rng.next_signed());
+            let (size_u, turn) = (rng.next_signed(), rng.next_signed());
+
+            let s = ((i as f64 + along) / density).min(total);
+            let point = interpolate_at_length(points, 3, &table, s);
+            let (x, y, pressure) = (point[0], point[1], point[2]);
+            let normal = if total == 0.0 {
+                // Reuse the distance draw as the direction
+                along as f32 * std::f32::consts::TAU
+            } else {
+                tangent_angle(points, &table, s) + std::f32::consts::FRAC_PI_2
+            };
+            let push = radius * pressure * offset;
+
+            records.extend_from_slice(&[
+                x + normal.cos() * push,
+                y + normal.sin() * push,
+                turn * std::f32::consts::PI,
+                pressure * (1.0 + size_jitter * size_u),
+                pressure.clamp(0.0, 1.0),
+            ]);
+        }
+
+        serialize_records((records.len() / STAMP_RECORD_FLOATS) as u32, &records)
+    })
+}
@@ -0,0 +1,871 @@
+/*!
+ * SVG path export and import
+ *
+ * Document export writes every stroke as an SVG path. Building the "d"
+ * attribute here keeps it small: coordinates are rounded once to the
+ * requested precision, later points are written relative to the rounded
+ * pen position (short numbers, no drift), and repeated command letters
+ * and optional separators are left out.
+ *
+ * Import goes the other way for referenced SVG artwork: any path data is
+ * parsed and its curves and arcs flattened into strokes.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Data Formats)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::bezier::fit_cubic_segments;
+use crate::error::{require_positive, run_export, CreoVexError, EngineError, EngineResult};
+use crate::resample::MAX_RESAMPLED_POINTS;
+use crate::validate::{with_float_validation, FloatValidation};
+use crate::{batch_stroke_values, hand_off, output_buffer, read_batch, read_points, serialize_strokes, EngineBuffer};
+
+/**
+ * Largest supported number of decimal places
+ */
+const SVG_MAX_PRECISION: u32 = 6;
+
+/**
+ * Largest |coordinate * 10^precision| a path may contain
+ *
+ * Integers up to 2^53 are exact in f64, and the difference of two stays
+ * far inside i64, so relative coordinates cannot overflow.
+ */
+const SVG_MAX_SCALED: f64 = (1u64 << 53) as f64;
+
+/**
+ * fit_curve tolerance for Bézier paths, in canvas units
+ */
+const SVG_CURVE_MAX_ERROR: f32 = 0.5;
+
+/**
+ * Most line segments one curve or arc is flattened into
+ */
+const MAX_FLATTEN_SEGMENTS: usize = 4096;
+
+/**
+ * Build the SVG path data of a stroke's centerline
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: byte length N (u32)
+ * - N bytes: UTF-8 (ASCII) "d" attribute value, e.g. "M10 20l5-3h2"
+ *
+ * The path starts with an absolute `M` at the first point. Without
+ * `use_beziers` every later point becomes a relative `l` (or `h`/`v`
+ * when one axis does not change), skipping points that round onto the
+ * previous one. With `use_beziers` the stroke is fitted as in fit_curve
+ * (0.5 canvas unit tolerance) and each segment becomes a relative `c`.
+ * A stroke that rounds to one position gives "M x y h0", which draws a
+ * dot with round caps. Pressure is not represented; style width on the
+ * path element.
+ *
+ * `precision` is the number of decimal places, clamped to 0-6. Numbers
+ * are written without trailing zeros, a 0 before the decimal point, or
+ * separators the SVG grammar does not need.
+ *
+ * Errors: the read_points errors; NonFiniteValue for a NaN or infinite
+ * position; InvalidParameter for a position (or, with `use_beziers`, a
+ * fitted control point) whose magnitude times 10^precision exceeds 2^53,
+ * e.g. beyond about 9e9 canvas units at precision 6.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stroke_to_svg_path(
+    points_ptr: *const f32,
+    points_len: usize,
+    precision: u32,
+    use_beziers: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+
+        let path = svg_path(points, precision, use_beziers)?;
+        let mut buffer = output_buffer(4 + path.len())?;
+        buffer.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(path.as_bytes());
+        Ok(hand_off(buffer))
+    })
+}
+
+/**
+ * Build the SVG path data of every stroke of a batch
+ *
+ * Input format: the batch container (see read_batch)
+ *
+ * Output format (little-endian):
+ * - 4 bytes: stroke count S (u32)
+ * - S * 4 bytes: byte length of each path string (u32)
+ * - the UTF-8 path strings, concatenated in order
+ * - S * 4 bytes: per-stroke status (u32): 0 = ok, otherwise the
+ *   CreoVexError discriminant; failed strokes have an empty string
+ *
+ * Each string is what stroke_to_svg_path returns for that stroke, so a
+ * whole document exports in one call. As in the other batch exports, one
+ * invalid stroke (including an empty one) does not fail the batch.
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn strokes_to_svg_paths_batch(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    precision: u32,
+    use_beziers: bool,
+    validation: Option<FloatValidation>,
+) -> EngineBuffer {
+    with_float_validation(validation, || {
+        run_export(|| {
+            let strokes = read_batch(batch_ptr, batch_len)?;
+            let results: Vec<EngineResult<String>> = strokes
+                .iter()
+                .map(|stroke| {
+                    let values = batch_stroke_values(stroke);
+                    svg_path(&read_points(values.as_ptr(), values.len())?, precision, use_beziers)
+                })
+                .collect();
+
+            let text_bytes: usize = results.iter().map(|result| result.as_ref().map_or(0, String::len)).sum();
+            let mut buffer = output_buffer(4 + results.len() * 8 + text_bytes)?;
+            buffer.extend_from_slice(&(results.len() as u32).to_le_bytes());
+            for result in &results {
+                let len = result.as_ref().map_or(0, String::len);
+                buffer.extend_from_slice(&(len as u32).to_le_bytes());
+            }
+            for path in results.iter().flatten() {
+                buffer.extend_from_slice(path.as_bytes());
+            }
+            for result in &results {
+                let status = result.as_ref().map_or_else(|error| error.code as u32, |_| 0);
+                buffer.extend_from_slice(&status.to_le_bytes());
+            }
+            Ok(hand_off(buffer))
+        })
+    })
+}
+
+/**
+ * Parse SVG path data into strokes
+ *
+ * Input format: `d_len` bytes of a path "d" attribute (ASCII/UTF-8)
+ *
+ * Output format: the batch container (see read_batch) holding one stroke
+ * per subpath, without a status array; every pressure is 1.0
+ *
+ * Supports the full path grammar: M/L/H/V/C/S/Q/T/A/Z in absolute and
+ * relative form, implicit command repetition (pairs after a moveto are
+ * lines), numbers with exponents ("1e-3") and without separators
+ * ("1-2.5.5"), and arc flags written without separators ("a1 1 0 01 5
+ * 5"). Curves and arcs are flattened to line segments that stay within
+ * `flatten_tolerance` canvas units of the true curve; an arc with a
+ * zero radius is a line, and radii too small to reach the end point are
+ * scaled up as the SVG spec requires.
+ *
+ * Each moveto starts a new stroke; Z adds the line back to the subpath
+ * start (when it is not already there) and ends the stroke. A moveto
+ * with nothing drawn from it produces no stroke, so empty or
+ * moveto-only data gives an empty container.
+ *
+ * Errors: NullInput for a null pointer with `d_len` > 0;
+ * NonFiniteValue/InvalidParameter for a NaN or non-positive tolerance;
+ * InvalidParameter for malformed data (the message describes the
+ * problem and last_error_index() gives the byte offset of the first bad
+ * token), or when the flattened path would exceed MAX_RESAMPLED_POINTS
+ * points.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn svg_path_to_stroke(d_ptr: *const u8, d_len: usize, flatten_tolerance: f32) -> EngineBuffer {
+    run_export(|| {
+        let data: &[u8] = if d_len == 0 {
+            &[]
+        } else if d_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "d_ptr is null"));
+        } else {
+            unsafe { std::slice::from_raw_parts(d_ptr, d_len) }
+        };
+        let tolerance = require_positive("flatten_tolerance", flatten_tolerance)? as f64;
+
+        let subpaths = PathParser::new(data, tolerance).parse()?;
+        let strokes: Vec<Vec<f32>> = subpaths
+            .iter()
+            .map(|subpath| subpath.iter().flat_map(|&[x, y]| [x as f32, y as f32, 1.0]).collect())
+            .collect();
+        serialize_strokes(&strokes)
+    })
+}
+
+fn svg_path(points: &[f32], precision: u32, use_beziers: bool) -> EngineResult<String> {
+    if let Some(index) = points.chunks_exact(3).position(|point| !(point[0].is_finite() && point[1].is_finite())) {
+        return Err(EngineError::new(CreoVexError::NonFiniteValue, format!("point {index} has a non-finite position")));
+    }
+
+    let mut writer = PathWriter::new(precision.min(SVG_MAX_PRECISION));
+    let start = writer.round(points[0], points[1])?;
+    writer.command('M', &[start[0], start[1]]);
+    let mut pen = start;
+    let mut drew = false;
+
+    if use_beziers {
+        for segment in fit_cubic_segments(points, SVG_CURVE_MAX_ERROR).chunks_exact(9) {
+            let c1 = writer.round(segment[2], segment[3])?;
+            let c2 = writer.round(segment[4], segment[5])?;
+            let end = writer.round(segment[6], segment[7])?;
+            if [c1, c2, end].iter().all(|&control| control == pen) {
+                continue;
+            }
+            let relative = |q: [i64; 2]| [q[0] - pen[0], q[1] - pen[1]];
+            let [c1, c2, delta] = [relative(c1), relative(c2), relative(end)];
+            writer.command('c', &[c1[0], c1[1], c2[0], c2[1], delta[0], delta[1]]);
+            pen = end;
+            drew = true;
+        }
+    } else {
+        for point in points.chunks_exact(3).skip(1) {
+            let q = writer.round(point[0], point[1])?;
+            match [q[0] - pen[0], q[1] - pen[1]] {
+                [0, 0] => continue,
+                [dx, 0] => writer.command('h', &[dx]),
+                [0, dy] => writer.command('v', &[dy]),
+                [dx, dy] => writer.command('l', &[dx, dy]),
+            }
+            pen = q;
+            drew = true;
+        }
+    }
+
+    if !drew {
+        writer.command('h', &[0]);
+    }
+    Ok(writer.path)
+}
+
+/**
+ * Compact path data in integer units of 10^-precision
+ */
+struct PathWriter {
+    path: String,
+    precision: usize,
+    scale: f64,
+    last_command: Option<char>,
+    /** Whether the last number written contains a decimal point */
+    last_has_point: bool,
+}
+
+impl PathWriter {
+    fn new(precision: u32) -> Self {
+        PathWriter {
+            path: String::new(),
+            precision: precision as usize,
+            scale: 10f64.powi(precision as i32),
+            last_command: None,
+            last_has_point: false,
+        }
+    }
+
+    /**
+     * Position in integer units; InvalidParameter beyond SVG_MAX_SCALED
+     */
+    fn round(&self, x: f32, y: f32) -> EngineResult<[i64; 2]> {
+        let scaled = [(x as f64 * self.scale).round(), (y as f64 * self.scale).round()];
+        if scaled.iter().any(|value| !value.is_finite() || value.abs() > SVG_MAX_SCALED) {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("position ({x}, {y}) is too large for precision {}", self.precision),
+            ));
+        }
+        Ok(scaled.map(|value| value as i64))
+    }
+
+    /**
+     * Append a command, leaving its letter out when it repeats the last one
+     */
+    fn command(&mut self, letter: char, numbers: &[i64]) {
+        let repeated = self.last_command == Some(letter);
+        if !repeated {
+            self.path.push(letter);
+            self.last_command = Some(letter);
+        }
+        for (i, &number) in numbers.iter().enumerate() {
+            self.number(number, i > 0 || repeated);
+        }
+    }
+
+    fn number(&mut self, value: i64, after_number: bool) {
+        let mut text = if value < 0 { String::from("-") } else { String::new() };
+        let magnitude = value.unsigned_abs();
+        let unit = 10u64.pow(self.precision as u32);
+        let (whole, fraction) = (magnitude / unit, magnitude % unit);
+        if whole > 0 || fraction == 0 {
+            text.push_str(&whole.to_string());
+        }
+        if fraction > 0 {
+            let digits = format!("{fraction:0width$}", width = self.precision);
+            text.push('.');
+            text.push_str(digits.trim_end_matches('0'));
+        }
+
+        // A sign, or a second decimal point, already ends the previous number
+        let has_point = fraction > 0;
+        let self_delimiting = text.starts_with('-') || (text.starts_with('.') && self.last_has_point);
+        if after_number && !self_delimiting {
+            self.path.push(' ');
+        }
+        self.path.push_str(&text);
+        self.last_has_point = has_point;
+    }
+}
+
+type Point = [f64; 2];
+
+/**
+ * Single pass over path data, flattening as it goes
+ */
+struct PathParser<'a> {
+    data: &'a [u8],
+    offset: usize,
+    tolerance: f64,
+    subpaths: Vec<Vec<Point>>,
+    current: Vec<Point>,
+    total_points: usize,
+    pen: Point,
+    subpath_start: Point,
+    /** Second control point of the last C/S or the control of the last Q/T, for S/T reflection */
+    last_control: Option<(u8, Point)>,
+}
+
+impl<'a> PathParser<'a> {
+    fn new(data: &'a [u8], tolerance: f64) -> Self {
+        PathParser {
+            data,
+            offset: 0,
+            tolerance,
+            subpaths: Vec::new(),
+            current: Vec::new(),
+            total_points: 0,
+            pen: [0.0, 0.0],
+            subpath_start: [0.0, 0.0],
+            last_control: None,
+        }
+    }
+
+    fn parse(mut self) -> EngineResult<Vec<Vec<Point>>> {
+        self.skip_whitespace();
+        let mut first = true;
+        while self.offset < self.data.len() {
+            let at = self.offset;
+            let letter = self.data[at];
+            if !b"MmLlHhVvCcSsQqTtAaZz".contains(&letter) {
+                return Err(self.error(at, "expected a path command"));
+            }
+            if first && !matches!(letter, b'M' | b'm') {
+                return Err(self.error(at, "path data must start with a moveto (M or m)"));
+            }
+            first = false;
+            self.offset += 1;
+            self.command(letter)?;
+            self.skip_whitespace();
+        }
+        self.finish_subpath();
+        Ok(self.subpaths)
+    }
+
+    /**
+     * One command letter and all of its (implicitly repeated) arguments
+     */
+    fn command(&mut self, letter: u8) -> EngineResult<()> {
+        let relative = letter.is_ascii_lowercase();
+        let kind = letter.to_ascii_uppercase();
+        if kind == b'Z' {
+            self.line_to(self.subpath_start)?;
+            self.finish_subpath();
+            self.pen = self.subpath_start;
+            self.last_control = None;
+            return Ok(());
+        }
+
+        let mut kind = kind;
+        loop {
+            let origin = if relative { self.pen } else { [0.0, 0.0] };
+            let point = |parser: &mut Self| -> EngineResult<Point> {
+                let x = parser.number()?;
+                let y = parser.number()?;
+                Ok([origin[0] + x, origin[1] + y])
+            };
+
+            let mut control = None;
+            match kind {
+                b'M' => {
+                    let target = point(self)?;
+                    self.finish_subpath();
+                    self.pen = target;
+                    self.subpath_start = target;
+                    // Further pairs are implicit linetos
+                    kind = b'L';
+                }
+                b'L' => {
+                    let target = point(self)?;
+                    self.line_to(target)?;
+                }
+                b'H' => {
+                    let x = self.number()? + if relative { self.pen[0] } else { 0.0 };
+                    self.line_to([x, self.pen[1]])?;
+                }
+                b'V' => {
+                    let y = self.number()? + if relative { self.pen[1] } else { 0.0 };
+                    self.line_to([self.pen[0], y])?;
+                }
+                b'C' | b'S' => {
+                    let c1 = if kind == b'C' { point(self)? } else { self.reflected_control(b'C') };
+                    let c2 = point(self)?;
+                    let end = point(self)?;
+                    self.cubic_to(c1, c2, end)?;
+                    control = Some((b'C', c2));
+                }
+                b'Q' | b'T' => {
+                    let c = if kind == b'Q' { point(self)? } else { self.reflected_control(b'Q') };
+                    let end = point(self)?;
+                    self.quadratic_to(c, end)?;
+                    control = Some((b'Q', c));
+                }
+                _ => {
+                    let radii = [self.number()?.abs(), self.number()?.abs()];
+                    let rotation = self.number()?;
+                    let large_arc = self.flag()?;
+                    let sweep = self.flag()?;
+                    let end = point(self)?;
+                    self.arc_to(radii, rotation, large_arc, sweep, end)?;
+                }
+            }
+            self.last_control = control;
+
+            self.skip_separator();
+            if !self.data.get(self.offset).is_some_and(|&byte| byte.is_ascii_digit() || b".+-".contains(&byte)) {
+                return Ok(());
+            }
+        }
+    }
+
+    /**
+     * The control point mirrored through the pen, or the pen itself when
+     * the previous command was not of the same family
+     */
+    fn reflected_control(&self, family: u8) -> Point {
+        match self.last_control {
+            Some((previous, control)) if previous == family => {
+                [2.0 * self.pen[0] - control[0], 2.0 * self.pen[1] - control[1]]
+            }
+            _ => self.pen,
+        }
+    }
+
+    fn line_to(&mut self, target: Point) -> EngineResult<()> {
+        if self.current.is_empty() {
+            self.push(self.pen)?;
+        }
+        if target != self.pen || self.current.len() == 1 {
+            self.push(target)?;
+        }
+        self.pen = target;
+        Ok(())
+    }
+
+    fn cubic_to(&mut self, c1: Point, c2: Point, end: Point) -> EngineResult<()> {
+        let p0 = self.pen;
+        // Wang's bound: n segments keep a cubic within the tolerance
+        let bend = |a: Point, b: Point, c: Point| (a[0] - 2.0 * b[0] + c[0]).hypot(a[1] - 2.0 * b[1] + c[1]);
+        let segments = self.segment_count((0.75 * bend(p0, c1, c2).max(bend(c1, c2, end)) / self.tolerance).sqrt());
+        for i in 1..=segments {
+            let t = i as f64 / segments as f64;
+            let u = 1.0 - t;
+            let (b0, b1, b2, b3) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            let point = [
+                b0 * p0[0] + b1 * c1[0] + b2 * c2[0] + b3 * end[0],
+                b0 * p0[1] + b1 * c1[1] + b2 * c2[1] + b3 * end[1],
+            ];
+            self.line_to(if i == segments { end } else { point })?;
+        }
+        Ok(())
+    }
+
+    fn quadratic_to(&mut self, control: Point, end: Point) -> EngineResult<()> {
+        // Degree elevation: the same curve as a cubic
+        let p0 = self.pen;
+        let c1 = [p0[0] + 2.0 / 3.0 * (control[0] - p0[0]), p0[1] + 2.0 / 3.0 * (control[1] - p0[1])];
+        let c2 = [end[0] + 2.0 / 3.0 * (control[0] - end[0]), end[1] + 2.0 / 3.0 * (control[1] - end[1])];
+        self.cubic_to(c1, c2, end)
+    }
+
+    /**
+     * Endpoint-to-center conversion per SVG 1.1 appendix F.6.5
+     */
+    fn arc_to(
+        &mut self,
+        radii: Point,
+        rotation_deg: f64,
+        large_arc: bool,
+        sweep: bool,
+        end: Point,
+    ) -> EngineResult<()> {
+        let start = self.pen;
+        if start == end {
+            return Ok(());
+        }
+        let [mut rx, mut ry] = radii;
+        if rx == 0.0 || ry == 0.0 {
+            return self.line_to(end);
+        }
+
+        let (sin, cos) = rotation_deg.to_radians().sin_cos();
+        let (hx, hy) = ((start[0] - end[0]) / 2.0, (start[1] - end[1]) / 2.0);
+        let (x1, y1) = (cos * hx + sin * hy, -sin * hx + cos * hy);
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1.0 {
+            rx *= lambda.sqrt();
+            ry *= lambda.sqrt();
+        }
+
+        let numerator = (rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1).max(0.0);
+        let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let mut factor = (numerator / denominator).sqrt();
+        if large_arc == sweep {
+            factor = -factor;
+        }
+        let (cx1, cy1) = (factor * rx * y1 / ry, -factor * ry * x1 / rx);
+        let center = [
+            cos * cx1 - sin * cy1 + (start[0] + end[0]) / 2.0,
+            sin * cx1 + cos * cy1 + (start[1] + end[1]) / 2.0,
+        ];
+
+        let angle = |ux: f64, uy: f64| uy.atan2(ux);
+        let theta = angle((x1 - cx1) / rx, (y1 - cy1) / ry);
+        let mut delta = angle((-x1 - cx1) / rx, (-y1 - cy1) / ry) - theta;
+        if sweep && delta < 0.0 {
+            delta += std::f64::consts::TAU;
+        } else if !sweep && delta > 0.0 {
+            delta -= std::f64::consts::TAU;
+        }
+
+        // Chord sagitta r(1 - cos(step / 2)) stays within the tolerance
+        let radius = rx.max(ry);
+        let step = 2.0 * (1.0 - (self.tolerance / radius).min(1.0)).acos();
+        let segments = self.segment_count(delta.abs() / step.max(f64::MIN_POSITIVE));
+        for i in 1..=segments {
+            let a = theta + delta * i as f64 / segments as f64;
+            let (x, y) = (rx * a.cos(), ry * a.sin());
+            let point = [cos * x - sin * y + center[0], sin * x + cos * y + center[1]];
+            self.line_to(if i == segments { end } else { point })?;
+        }
+        Ok(())
+    }
+
+    fn segment_count(&self, estimate: f64) -> usize {
+        if estimate.is_finite() {
+            (estimate.ceil() as usize).clamp(1, MAX_FLATTEN_SEGMENTS)
+        } else {
+            MAX_FLATTEN_SEGMENTS
+        }
+    }
+
+    fn push(&mut self, point: Point) -> EngineResult<()> {
+        self.total_points += 1;
+        if self.total_points > MAX_RESAMPLED_POINTS {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("flattened path exceeds {MAX_RESAMPLED_POINTS} points; raise flatten_tolerance"),
+            ));
+        }
+        self.current.push(point);
+        Ok(())
+    }
+
+    fn finish_subpath(&mut self) {
+        if !self.current.is_empty() {
+            self.subpaths.push(std::mem::take(&mut self.current));
+        }
+    }
+
+    fn number(&mut self) -> EngineResult<f64> {
+        self.skip_separator();
+        let start = self.offset;
+        let bytes = self.data;
+        let digits = |mut at: usize| {
+            while bytes.get(at).is_some_and(u8::is_ascii_digit) {
+                at += 1;
+            }
+            at
+        };
+
+        let mut at = start;
+        if bytes.get(at).is_some_and(|&byte| byte == b'+' || byte == b'-') {
+            at += 1;
+        }
+        let integer_end = digits(at);
+        let mut end = integer_end;
+        let mut has_digits = integer_end > at;
+        if bytes.get(end) == Some(&b'.') {
+            end = digits(end + 1);
+            has_digits |= end > integer_end + 1;
+        }
+        if !has_digits {
+            return Err(self.error(start, "expected a number"));
+        }
+        if bytes.get(end).is_some_and(|&byte| byte == b'e' || byte == b'E') {
+            let mut exponent = end + 1;
+            if bytes.get(exponent).is_some_and(|&byte| byte == b'+' || byte == b'-') {
+                exponent += 1;
+            }
+            let exponent_end = digits(exponent);
+            if exponent_end == exponent {
+                return Err(self.error(end, "exponent has no digits"));
+            }
+            end = exponent_end;
+        }
+
+        let text = std::str::from_utf8(&bytes[start..end]).unwrap_or_default();
+        let value: f64 = text.parse().map_err(|_| self.error(start, "expected a number"))?;
+        if !value.is_finite() {
+            return Err(self.error(start, "number is out of range"));
+        }
+        self.offset = end;
+        Ok(value)
+    }
+
+    /**
+     * An arc flag: a single 0 or 1, which needs no separator after it
+     */
+    fn flag(&mut self) -> EngineResult<bool> {
+        self.skip_separator();
+        match self.data.get(self.offset) {
+            Some(b'0') => {
+                self.offset += 1;
+                Ok(false)
+            }
+            Some(b'1') => {
+                self.offset += 1;
+                Ok(true)
+            }
+            _ => Err(self.error(self.offset, "expected an arc flag (0 or 1)")),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.data.get(self.offset).is_some_and(|&byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | 0x0C)) {
+            self.offset += 1;
+        }
+    }
+
+    /**
+     * Whitespace with at most one comma
+     */
+    fn skip_separator(&mut self) {
+        self.skip_whitespace();
+        if self.data.get(self.offset) == Some(&b',') {
+            self.offset += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn error(&self, offset: usize, problem: &str) -> EngineError {
+        let found = match self.data.get(offset) {
+            Some(&byte) if byte.is_ascii_graphic() => format!("'{}'", byte as char),
+            Some(&byte) => format!("byte 0x{byte:02X}"),
+            None => "end of data".to_string(),
+        };
+        EngineError::new(CreoVexError::InvalidParameter, format!("{problem} at offset {offset} (found {found})"))
+            .at_index(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::last_error_index;
+    use crate::test_support::{error_of, fails_with, take_bytes, take_strokes};
+
+    fn path(points: &[f32], precision: u32, use_beziers: bool) -> String {
+        let bytes = take_bytes(stroke_to_svg_path(points.as_ptr(), points.len(), precision, use_beziers));
+        assert_eq!(u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize, bytes.len() - 4);
+        String::from_utf8(bytes[4..].to_vec()).unwrap()
+    }
+
+    /**
+     * x/y of every point of every stroke svg_path_to_stroke reads from `d`
+     */
+    fn parse(d: &str, tolerance: f32) -> Vec<Vec<[f32; 2]>> {
+        let strokes = take_strokes(svg_path_to_stroke(d.as_ptr(), d.len(), tolerance), 3);
+        let points = |stroke: &Vec<f32>| -> Vec<[f32; 2]> {
+            assert!(stroke.chunks_exact(3).all(|point| point[2] == 1.0));
+            stroke.chunks_exact(3).map(|point| [point[0], point[1]]).collect()
+        };
+        strokes.iter().map(points).collect()
+    }
+
+    /**
+     * Error code and byte offset svg_path_to_stroke reports for `d`
+     */
+    fn parse_error(d: &str) -> (u32, i32) {
+        (error_of(svg_path_to_stroke(d.as_ptr(), d.len(), 0.1)), last_error_index())
+    }
+
+    fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3
+    }
+
+    /**
+     * Distance from `point` to the nearest segment of `polyline`
+     */
+    fn distance_to(polyline: &[[f32; 2]], point: [f32; 2]) -> f32 {
+        let segment = |a: [f32; 2], b: [f32; 2]| {
+            let (vx, vy) = (b[0] - a[0], b[1] - a[1]);
+            let t = (((point[0] - a[0]) * vx + (point[1] - a[1]) * vy) / (vx * vx + vy * vy).max(1e-12)).clamp(0.0, 1.0);
+            (point[0] - a[0] - t * vx).hypot(point[1] - a[1] - t * vy)
+        };
+        polyline.windows(2).map(|pair| segment(pair[0], pair[1])).fold(f32::MAX, f32::min)
+    }
+
+    #[test]
+    fn path_data_is_compact() {
+        let points = [10.0, 20.0, 1.0, 15.0, 17.0, 1.0, 17.0, 17.0, 1.0, 17.0, 19.5, 1.0];
+        assert_eq!(path(&points, 2, false), "M10 20l5-3h2v2.5");
+        assert_eq!(path(&[1.0, 2.0, 1.0], 2, false), "M1 2h0");
+        assert_eq!(path(&[0.5, 0.25, 1.0, 1.0, 0.5, 1.0, 1.5, 0.75, 1.0], 2, false), "M.5.25l.5.25.5.25");
+    }
+
+    #[test]
+    fn huge_coordinates_are_rejected_not_overflowed() {
+        // Alternating ±2^43 at precision 6 used to overflow the i64 deltas
+        let far = (1u64 << 43) as f32;
+        let points = [-far, 0.0, 1.0, far, 0.0, 1.0, -far, 5.0, 1.0];
+        for use_beziers in [false, true] {
+            let result = stroke_to_svg_path(points.as_ptr(), points.len(), 6, use_beziers);
+            assert!(fails_with(result, CreoVexError::InvalidParameter));
+        }
+        let extreme = [-3.0e38, 0.0, 1.0, 3.0e38, 0.0, 1.0];
+        assert!(fails_with(stroke_to_svg_path(extreme.as_ptr(), extreme.len(), 0, false), CreoVexError::InvalidParameter));
+
+        // The same stroke fits at precision 0
+        assert_eq!(path(&points, 0, false), "M-8796093022208 0h17592186044416l-17592186044416 5");
+    }
+
+    #[test]
+    fn numbers_and_commands_parse() {
+        assert_eq!(parse("M10 20l5-3h2v2.5", 0.1), vec![vec![[10.0, 20.0], [15.0, 17.0], [17.0, 17.0], [17.0, 19.5]]]);
+        assert_eq!(parse("M.5.25l.5.25.5.25", 0.1), vec![vec![[0.5, 0.25], [1.0, 0.5], [1.5, 0.75]]]);
+        assert_eq!(parse("M1e1,2E-1 L1.5e+1 -2e0", 0.1), vec![vec![[10.0, 0.2], [15.0, -2.0]]]);
+        assert_eq!(parse("M-1.5e-3-2.5E2l3e-1.5", 0.1), vec![vec![[-0.0015, -250.0], [0.2985, -249.5]]]);
+        assert!(parse("", 0.1).is_empty());
+        assert!(parse("  M1 1  ", 0.1).is_empty());
+
+        // Implicit lines after a moveto, and Z ending a subpath
+        assert_eq!(parse("m1 1 2 2 z m 1 0 1 1", 0.1), vec![vec![[1.0, 1.0], [3.0, 3.0], [1.0, 1.0]], vec![[2.0, 1.0], [3.0, 2.0]]]);
+        assert_eq!(parse("M0 0 L5 0 Z L0 5", 0.1), vec![vec![[0.0, 0.0], [5.0, 0.0], [0.0, 0.0]], vec![[0.0, 0.0], [0.0, 5.0]]]);
+        assert_eq!(parse("M0 0H5V5H0z", 0.1)[0].len(), 5);
+
+        // S after a line uses the pen as its first control point
+        assert_eq!(parse("M0 0L1 1S5 5 9 1", 0.1), parse("M0 0L1 1C1 1 5 5 9 1", 0.1));
+    }
+
+    #[test]
+    fn arcs_follow_their_flags() {
+        // Flags may run into each other and into the next number
+        for d in ["M0 0a5 5 0 0110 0", "M0 0a5,5,0,0,1,10,0", "M0 0 a 5 5 0 0 1 10 0", "M0 0A5 5 0 0 1 10 0"] {
+            let arc = &parse(d, 0.01)[0];
+            assert!(close(arc[0], [0.0, 0.0]) && close(arc[arc.len() - 1], [10.0, 0.0]), "{d}");
+            for point in arc {
+                assert!(((point[0] - 5.0).hypot(point[1]) - 5.0).abs() < 1e-3, "{d}: {point:?} is off the circle");
+                // Positive sweep from (0, 0) to (10, 0) passes through y < 0
+                assert!(point[1] <= 1e-4, "{d}: {point:?} is on the wrong side");
+            }
+            for pair in arc.windows(2) {
+                let middle = [(pair[0][0] + pair[1][0]) / 2.0, (pair[0][1] + pair[1][1]) / 2.0];
+                assert!(5.0 - (middle[0] - 5.0).hypot(middle[1]) <= 0.0101, "{d}: a chord strays past the tolerance");
+            }
+        }
+        assert!(parse("M0 0A5 5 0 0 0 10 0", 0.1)[0].iter().all(|point| point[1] >= -1e-4));
+
+        // The large arc flag picks the long way round
+        let small = parse("M0 0A10 10 0 0 1 10 0", 0.1)[0].len();
+        let large = parse("M0 0A10 10 0 1 1 10 0", 0.1)[0].len();
+        assert!(large > small * 3, "{large} points for the large arc, {small} for the small one");
+
+        // Radii too small to reach are scaled up (to a semicircle here); zero is a line
+        for point in &parse("M0 0A1 1 0 0 1 10 0", 0.01)[0] {
+            assert!(((point[0] - 5.0).hypot(point[1]) - 5.0).abs() < 1e-3);
+        }
+        assert_eq!(parse("M0 0A0 5 0 0 1 10 0", 0.1), vec![vec![[0.0, 0.0], [10.0, 0.0]]]);
+        let rotated = &parse("M0 0A8 3 30 1 0 6 4", 0.05)[0];
+        assert!(close(rotated[rotated.len() - 1], [6.0, 4.0]));
+    }
+
+    #[test]
+    fn curves_stay_within_tolerance() {
+        let flattened = &parse("M0 0C0 100 100 100 100 0S200 -100 200 0", 0.25)[0];
+        assert!(close(flattened[flattened.len() - 1], [200.0, 0.0]));
+        let cubic = |t: f32, c: [[f32; 2]; 4]| {
+            let u = 1.0 - t;
+            [0, 1].map(|k| u * u * u * c[0][k] + 3.0 * u * u * t * c[1][k] + 3.0 * u * t * t * c[2][k] + t * t * t * c[3][k])
+        };
+        // The S segment reflects the first curve's second control point
+        let curves = [[[0.0, 0.0], [0.0, 100.0], [100.0, 100.0], [100.0, 0.0]], [[100.0, 0.0], [100.0, -100.0], [200.0, -100.0], [200.0, 0.0]]];
+        for curve in curves {
+            for i in 0..=1000 {
+                let distance = distance_to(flattened, cubic(i as f32 / 1000.0, curve));
+                assert!(distance <= 0.26, "{distance} from the true curve");
+            }
+        }
+
+        // T reflects Q's control point to the other side
+        let wave = &parse("M0 0Q5 10 10 0T20 0", 0.01)[0];
+        assert!(wave.iter().any(|point| point[1] < -4.9) && wave.iter().any(|point| point[1] > 4.9));
+    }
+
+    #[test]
+    fn malformed_data_reports_its_offset() {
+        let bad = CreoVexError::InvalidParameter as u32;
+        let cases = [
+            ("L1 1", 0),
+            ("M1 1 L2 x", 8),
+            ("M1 1 L2", 7),
+            ("M1 1 A1 1 0 2 1 5 5", 12),
+            ("M1 1 L2 3e", 9),
+            ("M1 1 L2 1e999", 8),
+            ("M1 1 L. 3", 6),
+            ("M1 1 L2,,3", 8),
+            ("M1 1 X", 5),
+            ("M1 1 Z\u{e9}", 6),
+        ];
+        for (d, offset) in cases {
+            assert_eq!(parse_error(d), (bad, offset), "{d}");
+        }
+        assert!(fails_with(svg_path_to_stroke(std::ptr::null(), 3, 0.1), CreoVexError::NullInput));
+        assert!(fails_with(svg_path_to_stroke("M0 0".as_ptr(), 4, 0.0), CreoVexError::InvalidParameter));
+    }
+
+    #[test]
+    fn exported_paths_read_back() {
+        let points: Vec<f32> = (0..50)
+            .flat_map(|i| {
+                let angle = i as f32 * 0.1;
+                [100.0 + 50.0 * angle.cos(), 100.0 + 50.0 * angle.sin(), 1.0]
+            })
+            .collect();
+        let read = &parse(&path(&points, 3, false), 0.1)[0];
+        assert_eq!(read.len(), 50);
+        for (point, read) in points.chunks_exact(3).zip(read) {
+            assert!((point[0] - read[0]).abs() < 6e-4 && (point[1] - read[1]).abs() < 6e-4);
+        }
+
+        // Fitted curves pass within the fit tolerance of every point
+        let read = &parse(&path(&points, 3, true), 0.05)[0];
+        assert!(close(read[read.len() - 1], [points[147], points[148]]));
+        for point in points.chunks_exact(3) {
+            assert!(distance_to(read, [point[0], point[1]]) <= SVG_CURVE_MAX_ERROR + 0.05);
+        }
+    }
+}
@@ -0,0 +1,993 @@
+/*!
+ * Stroke tessellation (quad strip extrusion)
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Tessellation)
+ */
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::core::TessellationResult;
+use crate::error::{require_finite, require_non_negative, run_export, CreoVexError, EngineError, EngineResult};
+use crate::pressure::{CurveDescriptor, PressureCurve};
+use crate::validate::{with_float_validation, FloatValidation};
+use crate::{read_points, run_batch, serialize_mesh, serialize_records, serialize_strided_points, EngineBuffer};
+
+/**
+ * Maximum miter length as a multiple of the half-width
+ *
+ * Sharp turns are clamped to this so the strip never spikes outward.
+ */
+pub(crate) const MITER_LIMIT: f32 = 4.0;
+
+/**
+ * How the strip turns a corner between two segments
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinStyle {
+    /** Both sides meet at the miter point, its length clamped to the miter limit */
+    Miter = 0,
+    /** The outer side follows an arc fanned around the inner corner */
+    Round = 1,
+    /** The outer side is cut straight across between the segment offsets */
+    Bevel = 2,
+}
+
+/**
+ * How a stroke end is closed off
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapStyle {
+    /** Cut square at the endpoint (e.g. ends produced by erasing) */
+    Butt = 0,
+    /** Semicircle of the endpoint's half-width */
+    Round = 1,
+    /** Cut square one half-width past the endpoint */
+    Square = 2,
+}
+
+/**
+ * Most fan triangles a 180° round join or round cap may use
+ */
+pub(crate) const MAX_ROUND_SEGMENTS: u32 = 64;
+
+/**
+ * Join and cap settings for extrude_strip
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct StripOptions {
+    pub(crate) join: JoinStyle,
+    /** Longest miter (or inner corner) offset, in half-widths */
+    pub(crate) miter_limit: f32,
+    /** Fan triangles for a 180° round join or cap; smaller turns get proportionally fewer */
+    pub(crate) round_segments: u32,
+    pub(crate) start_cap: CapStyle,
+    pub(crate) end_cap: CapStyle,
+}
+
+impl StripOptions {
+    /**
+     * Validate the tessellate_stroke_ex arguments
+     *
+     * `miter_limit` must be finite and at least 1; `round_segments` is
+     * clamped to 1-MAX_ROUND_SEGMENTS.
+     */
+    pub(crate) fn from_ffi(
+        join: JoinStyle,
+        miter_limit: f32,
+        round_segments: u32,
+        start_cap: CapStyle,
+        end_cap: CapStyle,
+    ) -> EngineResult<Self> {
+        let miter_limit = require_finite("miter_limit", miter_limit)?;
+        if miter_limit < 1.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("miter_limit must be >= 1 (got {miter_limit})"),
+            ));
+        }
+        Ok(StripOptions {
+            join,
+            miter_limit,
+            round_segments: round_segments.clamp(1, MAX_ROUND_SEGMENTS),
+            start_cap,
+            end_cap,
+        })
+    }
+
+    /** tessellate_stroke's joins and caps */
+    pub(crate) const DEFAULT: StripOptions = StripOptions {
+        join: JoinStyle::Miter,
+        miter_limit: MITER_LIMIT,
+        round_segments: 8,
+        start_cap: CapStyle::Butt,
+        end_cap: CapStyle::Butt,
+    };
+}
+
+/**
+ * tessellate_stroke_ex joins and caps as one value, for APIs that take
+ * them together (StrokeCache::get_or_tessellate)
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug)]
+pub struct TessellationOptions {
+    pub join: JoinStyle,
+    /** Longest miter offset in half-widths (finite, at least 1) */
+    pub miter_limit: f32,
+    /** Fan triangles for a 180° round join or cap (clamped to 1-MAX_ROUND_SEGMENTS) */
+    pub round_segments: u32,
+    pub start_cap: CapStyle,
+    pub end_cap: CapStyle,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TessellationOptions {
+    /** Defaults: tessellate_stroke's miter joins and butt caps */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> TessellationOptions {
+        let StripOptions { join, miter_limit, round_segments, start_cap, end_cap } = StripOptions::DEFAULT;
+        TessellationOptions { join, miter_limit, round_segments, start_cap, end_cap }
+    }
+}
+
+impl Default for TessellationOptions {
+    fn default() -> Self {
+        TessellationOptions::new()
+    }
+}
+
+impl TessellationOptions {
+    /**
+     * Validated strip options (errors as StripOptions::from_ffi)
+     */
+    pub(crate) fn strip_options(&self) -> EngineResult<StripOptions> {
+        StripOptions::from_ffi(self.join, self.miter_limit, self.round_segments, self.start_cap, self.end_cap)
+    }
+}
+
+/**
+ * Tessellate stroke into a triangle strip
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Each point is extruded by a half-width of `base_width * pressure`.
+ *
+ * Output format:
+ * - 4 bytes: vertex count (u32)
+ * - N * 8 bytes: f32 pairs [x0, y0, x1, y1, ...]
+ *
+ * Vertices are in triangle-strip order: for every centerline point the
+ * left vertex is followed by the right vertex, so the buffer can be
+ * uploaded directly and drawn with TRIANGLE_STRIP.
+ *
+ * Consecutive duplicate points are skipped. Strokes with fewer than two
+ * distinct points produce an empty (vertex count 0) buffer.
+ *
+ * Equivalent to tessellate_stroke_ex with miter joins, MITER_LIMIT, and
+ * butt caps.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue/InvalidParameter
+ * for a NaN or negative base width.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn tessellate_stroke(points_ptr: *const f32, points_len: usize, base_width: f32) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", base_width)?;
+
+        let vertices = extrude_strip(points, base_width, &StripOptions::DEFAULT);
+        serialize_records((vertices.len() / 2) as u32, &vertices)
+    })
+}
+
+/**
+ * Tessellate many strokes in one call (document loading)
+ *
+ * Input: the batch container documented at read_batch (lib.rs). Each
+ * stroke is tessellated exactly like tessellate_stroke with the shared
+ * `base_width`.
+ *
+ * Output format: the batch result layout with vertex counts and f32
+ * [x, y] vertices, plus per-stroke status
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container and
+ * NonFiniteValue/InvalidParameter for a NaN or negative base width;
+ * invalid strokes fail individually.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn tessellate_strokes_batch(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    base_width: f32,
+    validation: Option<FloatValidation>,
+) -> EngineBuffer {
+    with_float_validation(validation, || {
+        let base_width = match require_non_negative("base_width", base_width) {
+            Ok(base_width) => base_width,
+            Err(error) => return run_export(|| Err(error)),
+        };
+
+        run_batch(batch_ptr, batch_len, 2, |points| Ok(extrude_strip(points, base_width, &StripOptions::DEFAULT)))
+    })
+}
+
+/**
+ * Tessellate stroke into a triangle strip with chosen joins and caps
+ *
+ * Input and output format as tessellate_stroke. Joins emit extra
+ * left/right pairs in which the inner corner vertex repeats, so the
+ * output stays a plain TRIANGLE_STRIP (the repeats form zero-area
+ * triangles):
+ * - Miter: one pair at the miter point, clamped to `miter_limit`
+ *   half-widths (tessellate_stroke's behavior)
+ * - Bevel: two pairs, cutting the outer corner straight across
+ * - Round: an arc of ceil(`round_segments` * turn / 180°) fan triangles,
+ *   at least one, so slight turns cost almost nothing
+ *
+ * For bevel and round joins the inner corner is the miter point clamped
+ * to `miter_limit`. A 180° reversal is capped round by round joins and
+ * left flat by the others. A join whose pressure is zero collapses to the
+ * centerline point. `round_segments` is clamped to 1-MAX_ROUND_SEGMENTS.
+ *
+ * `start_cap` and `end_cap` are set independently, so ends cut by the
+ * eraser can stay butt while natural ends are round. Round caps are a
+ * fan of ceil(`round_segments` / 2) pairs per end; square caps move the
+ * end pair one half-width outward. An end with zero pressure collapses
+ * to its point: no cap vertices are emitted for it.
+ *
+ * Errors: the read_points errors, NonFiniteValue/InvalidParameter for a
+ * NaN or negative base width, and the same for a NaN, infinite, or
+ * below-1 miter limit.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn tessellate_stroke_ex(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    join: JoinStyle,
+    miter_limit: f32,
+    round_segments: u32,
+    start_cap: CapStyle,
+    end_cap: CapStyle,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", base_width)?;
+        let options = StripOptions::from_ffi(join, miter_limit, round_segments, start_cap, end_cap)?;
+
+        let vertices = extrude_strip(points, base_width, &options);
+        serialize_records((vertices.len() / 2) as u32, &vertices)
+    })
+}
+
+/**
+ * Tessellate stroke into an indexed triangle list
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format (see serialize_mesh):
+ * - 4 bytes: vertex count V (u32)
+ * - 4 bytes: index count I (u32, three per triangle)
+ * - V * 8 bytes: f32 [x, y] per vertex
+ * - I * 4 bytes: u32 indices into the vertex list
+ *
+ * The triangles are those of the tessellate_stroke_ex strip with the
+ * same arguments, so several strokes can share one draw call. Vertices
+ * at the same position are stored once, which welds the quads of the
+ * stroke body to each other and to the join fans, and the zero-area
+ * triangles the strip uses to repeat inner corners are dropped. All
+ * triangles keep the strip's alternating winding corrected to one
+ * orientation (that of the strip's first triangle).
+ *
+ * Strokes with fewer than two distinct points give an empty mesh.
+ *
+ * Errors: as tessellate_stroke_ex.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn tessellate_stroke_indexed(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    join: JoinStyle,
+    miter_limit: f32,
+    round_segments: u32,
+    start_cap: CapStyle,
+    end_cap: CapStyle,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", base_width)?;
+        let options = StripOptions::from_ffi(join, miter_limit, round_segments, start_cap, end_cap)?;
+
+        let strip = extrude_strip(points, base_width, &options);
+        serialize_mesh(&TessellationResult::indexed_strip(&strip, 2))
+    })
+}
+
+/**
+ * Tessellate stroke into an indexed mesh with a feathered edge
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format (see serialize_mesh):
+ * - 4 bytes: vertex count V (u32)
+ * - 4 bytes: index count I (u32, three per triangle)
+ * - V * 12 bytes: interleaved f32 [x, y, alpha] per vertex (position at
+ *   byte offset 0, alpha at byte offset 8, byte stride 12)
+ * - I * 4 bytes: u32 indices into the vertex list
+ *
+ * The core is tessellate_stroke_indexed's mesh for the same joins and
+ * caps, with alpha 1. Around it runs a ring of quads (two triangles
+ * each) extruded `feather` canvas units outward from the core's
+ * boundary, whose inner vertices are the core's own boundary vertices
+ * (alpha 1) and whose outer vertices have alpha 0, so blending fades the
+ * edge without gaps. The boundary goes up the left side, around the end
+ * cap, back down the right side, and around the start cap, so the ring
+ * is continuous through joins and caps; at corners the outer vertex is
+ * pushed along the averaged normal, as a miter clamped to the miter
+ * limit. The feather is in canvas units: scale it by 1/zoom for a
+ * constant on-screen width. A feather of 0 gives the core alone.
+ *
+ * Core and ring triangles all wind like tessellate_stroke_indexed's.
+ * Strokes with fewer than two distinct points give an empty mesh.
+ *
+ * Errors: as tessellate_stroke_ex, plus NonFiniteValue/InvalidParameter
+ * for a NaN, infinite, or negative feather.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn tessellate_stroke_feathered(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    join: JoinStyle,
+    miter_limit: f32,
+    round_segments: u32,
+    start_cap: CapStyle,
+    end_cap: CapStyle,
+    feather: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", base_width)?;
+        let options = StripOptions::from_ffi(join, miter_limit, round_segments, start_cap, end_cap)?;
+        let feather = require_non_negative("feather", require_finite("feather", feather)?)?;
+
+        let strip = extrude_strip(points, base_width, &options);
+        let (core, remap) = weld_vertices(&strip, 2);
+        let mut indices = strip_triangles(&remap);
+        let mut vertices: Vec<f32> = core.chunks_exact(2).flat_map(|v| [v[0], v[1], 1.0]).collect();
+        if feather > 0.0 && !core.is_empty() {
+            feather_ring(&core, &remap, feather, options.miter_limit, &mut vertices, &mut indices);
+        }
+        serialize_mesh(&TessellationResult { vertices, floats_per_vertex: 3, indices })
+    })
+}
+
+/**
+ * Append the feather ring around the boundary of a strip (see
+ * tessellate_stroke_feathered)
+ *
+ * `core` and `remap` are the strip's weld_vertices result; core indices
+ * are those of the [x, y, alpha] `vertices` being extended.
+ */
+fn feather_ring(
+    core: &[f32],
+    remap: &[u32],
+    feather: f32,
+    miter_limit: f32,
+    vertices: &mut Vec<f32>,
+    indices: &mut Vec<u32>,
+) {
+    // Left side forward, then the right side back: interior on the right throughout
+    let mut boundary: Vec<u32> = remap.iter().step_by(2).copied().collect();
+    boundary.extend(remap.iter().skip(1).step_by(2).rev());
+    boundary.dedup();
+    if boundary.len() > 1 && boundary.first() == boundary.last() {
+        boundary.pop();
+    }
+
+    let count = boundary.len();
+    let point = |k: usize| {
+        let i = boundary[k % count] as usize;
+        (core[i * 2], core[i * 2 + 1])
+    };
+    let first_outer = (vertices.len() / 3) as u32;
+    for k in 0..count {
+        let (prev, here, next) = (point(k + count - 1), point(k), point(k + 1));
+        let (offset_x, offset_y) = ring_offset(prev, here, next, miter_limit);
+        vertices.extend_from_slice(&[here.0 + offset_x * feather, here.1 + offset_y * feather, 0.0]);
+    }
+    for k in 0..count {
+        let j = (k + 1) % count;
+        let (inner_k, inner_j) = (boundary[k], boundary[j]);
+        let (outer_k, outer_j) = (first_outer + k as u32, first_outer + j as u32);
+        indices.extend_from_slice(&[inner_k, inner_j, outer_j, inner_k, outer_j, outer_k]);
+    }
+}
+
+/**
+ * Outward offset, in feather widths, of boundary vertex `here`
+ *
+ * Edges have the interior on their right. A spike (the boundary turning
+ * straight back) is pushed on along the incoming edge.
+ */
+fn ring_offset(prev: (f32, f32), here: (f32, f32), next: (f32, f32), miter_limit: f32) -> (f32, f32) {
+    let unit = |dx: f32, dy: f32| {
+        let len = (dx * dx + dy * dy).sqrt();
+        (dx / len, dy / len)
+    };
+    let d0 = unit(here.0 - prev.0, here.1 - prev.1);
+    let d1 = unit(next.0 - here.0, next.1 - here.1);
+    let (n0, n1) = ((-d0.1, d0.0), (-d1.1, d1.0));
+    let (mx, my) = (n0.0 + n1.0, n0.1 + n1.1);
+    let m_len = (mx * mx + my * my).sqrt();
+    if m_len < 1e-6 {
+        return d0;
+    }
+    let m = (mx / m_len, my / m_len);
+    let scale = (1.0 / (m.0 * n0.0 + m.1 * n0.1)).min(miter_limit);
+    (m.0 * scale, m.1 * scale)
+}
+
+/**
+ * Tessellate stroke into a triangle strip with texture coordinates
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: vertex count (u32)
+ * - 4 bytes: floats per vertex (u32): 4 with `emit_uvs`, else 2
+ * - N * stride * 4 bytes: f32 [x, y, u, v] (or [x, y]) per vertex
+ *
+ * Vertices, joins, and caps are exactly those of tessellate_stroke_ex
+ * with the same arguments; only the header and the extra floats differ.
+ *
+ * U follows arc length, measured from the tip of the start cap: every
+ * vertex of a centerline point shares that point's distance, and cap
+ * vertices add their offset along the end direction, so U is continuous
+ * across joins and caps and never decreases along either side. It is
+ * divided by `uv_repeat_length` so the texture repeats every that many
+ * canvas units, or, when `uv_repeat_length` is 0, by the full painted
+ * length (caps included), stretching the texture once over the stroke.
+ *
+ * V is 0 on the left side and 1 on the right (left of the direction of
+ * travel); cap vertices interpolate between them by their position
+ * across the end, reaching 0.5 at a round cap's tip.
+ *
+ * Errors: as tessellate_stroke_ex, plus NonFiniteValue/InvalidParameter
+ * for a NaN, infinite, or negative repeat length.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn tessellate_stroke_uv(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    join: JoinStyle,
+    miter_limit: f32,
+    round_segments: u32,
+    start_cap: CapStyle,
+    end_cap: CapStyle,
+    emit_uvs: bool,
+    uv_repeat_length: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", base_width)?;
+        let options = StripOptions::from_ffi(join, miter_limit, round_segments, start_cap, end_cap)?;
+        let repeat = require_non_negative("uv_repeat_length", require_finite("uv_repeat_length", uv_repeat_length)?)?;
+
+        let (strip, painted_length) = extrude_strip_attributes(points, base_width, &options);
+        if !emit_uvs {
+            let positions: Vec<f32> = strip.chunks_exact(STRIP_ATTRIBUTE_FLOATS).flat_map(|v| [v[0], v[1]]).collect();
+            return serialize_strided_points(&positions, 2);
+        }
+
+        let scale = if repeat > 0.0 { repeat } else { painted_length };
+        let vertices: Vec<f32> = strip
+            .chunks_exact(STRIP_ATTRIBUTE_FLOATS)
+            .flat_map(|v| [v[0], v[1], if scale > 0.0 { v[2] / scale } else { v[2] }, v[3]])
+            .collect();
+        serialize_strided_points(&vertices, 4)
+    })
+}
+
+/**
+ * Tessellate stroke into a triangle strip with per-vertex alpha
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: vertex count N (u32)
+ * - 4 bytes: floats per vertex (u32, always 3)
+ * - N * 12 bytes: interleaved f32 [x, y, alpha] per vertex
+ *
+ * As a vertex attribute: position is 2 floats at byte offset 0, alpha 1
+ * float at byte offset 8, byte stride 12. Draw with TRIANGLE_STRIP.
+ *
+ * Alpha is the vertex's centerline pressure mapped through the curve
+ * (`curve`, `gamma`, and the LUT exactly as apply_pressure takes them),
+ * so it lies in [0, 1]. The geometry is tessellate_stroke's strip.
+ *
+ * With `edge_fade`, edge vertices get alpha 0 and the strip is split
+ * down the middle so interpolation fades from the curve value on the
+ * centerline to 0 at each side: every left/right pair of
+ * tessellate_stroke's strip becomes left, middle, right, emitted as the
+ * left half strip (left, middle per pair), two repeated middle vertices
+ * that stitch the halves with zero-area triangles, then the right half
+ * strip (middle, right per pair). That gives 4 * P + 2 vertices for P
+ * pairs, all triangles with tessellate_stroke's winding.
+ *
+ * Strokes with fewer than two distinct points give 0 vertices.
+ *
+ * Errors: the read_points errors, NonFiniteValue/InvalidParameter for a
+ * NaN or negative base width, and the apply_pressure curve errors.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn tessellate_stroke_alpha(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    curve: PressureCurve,
+    gamma: f32,
+    lut_ptr: *const f32,
+    lut_len: usize,
+    edge_fade: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let base_width = require_non_negative("base_width", base_width)?;
+        let curve = CurveDescriptor::from_ffi(curve, gamma, lut_ptr, lut_len)?;
+
+        let (strip, _) = extrude_strip_attributes(points, base_width, &StripOptions::DEFAULT);
+        let pairs: Vec<&[f32]> = strip.chunks_exact(2 * STRIP_ATTRIBUTE_FLOATS).collect();
+        let alpha = |pair: &[f32]| curve.evaluate(pair[4]);
+        let left = |pair: &[f32]| [pair[0], pair[1], 0.0];
+        let right = |pair: &[f32]| [pair[5], pair[6], 0.0];
+        let middle = |pair: &[f32]| [(pair[0] + pair[5]) * 0.5, (pair[1] + pair[6]) * 0.5, alpha(pair)];
+
+        let mut vertices = Vec::with_capacity((pairs.len() * 4 + 2) * 3);
+        if !edge_fade {
+            for pair in &pairs {
+                vertices.extend_from_slice(&[pair[0], pair[1], alpha(pair), pair[5], pair[6], alpha(pair)]);
+            }
+        } else if let (Some(first), Some(last)) = (pairs.first(), pairs.last()) {
+            for pair in &pairs {
+                vertices.extend(left(pair).into_iter().chain(middle(pair)));
+            }
+            vertices.extend(middle(last).into_iter().chain(middle(first)));
+            for pair in &pairs {
+                vertices.extend(middle(pair).into_iter().chain(right(pair)));
+            }
+        }
+        serialize_strided_points(&vertices, 3)
+    })
+}
+
+/**
+ * Extrude the centerline into left/right vertex pairs
+ *
+ * Interior points use the miter direction (bisector of the adjacent
+ * segment normals). A 180° reversal has no bisector, so the incoming
+ * segment normal is used instead; this keeps every normal finite.
+ * Bevel and round joins replace the outer vertex by several, and caps
+ * extend the end pairs (see tessellate_stroke_ex).
+ */
+pub(crate) fn extrude_strip(points: &[f32], base_width: f32, options: &StripOptions) -> Vec<f32> {
+    let (vertices, _) = extrude_strip_attributes(points, base_width, options);
+    vertices.chunks_exact(STRIP_ATTRIBUTE_FLOATS).flat_map(|v| [v[0], v[1]]).collect()
+}
+
+/**
+ * Floats per extrude_strip_attributes vertex: x, y, u, v, pressure
+ */
+pub(crate) const STRIP_ATTRIBUTE_FLOATS: usize = 5;
+
+/**
+ * extrude_strip with per-vertex attributes: [x, y, u, v, pressure]
+ *
+ * U is in canvas units from the tip of the start cap (see
+ * tessellate_stroke_uv); the painted length, the U of the end cap's tip,
+ * is returned alongside. Pressure is that of the vertex's centerline
+ * point, so both vertices of a pair share it.
+ */
+pub(crate) fn extrude_strip_attributes(points: &[f32], base_width: f32, options: &StripOptions) -> (Vec<f32>, f32) {
+    let centerline = distinct_points(points);
+    let count = centerline.len();
+    if count < 2 {
+        return (Vec::new(), 0.0);
+    }
+
+    let half_widths: Vec<f32> = centerline.iter().map(|point| base_width * point[2].max(0.0)).collect();
+    let cap_length = |cap: CapStyle, half_width: f32| if cap == CapStyle::Butt { 0.0 } else { half_width };
+    let mut walked = Vec::with_capacity(count);
+    let mut length = cap_length(options.start_cap, half_widths[0]) as f64;
+    walked.push(length as f32);
+    for pair in centerline.windows(2) {
+        length += distance_sq(pair[0], pair[1]).sqrt() as f64;
+        walked.push(length as f32);
+    }
+    let painted_length = (length + cap_length(options.end_cap, half_widths[count - 1]) as f64) as f32;
+
+    let mut vertices = Vec::with_capacity(count * 2 * STRIP_ATTRIBUTE_FLOATS);
+    let mut push_pair = |left: [f32; STRIP_ATTRIBUTE_FLOATS], right: [f32; STRIP_ATTRIBUTE_FLOATS]| {
+        vertices.extend_from_slice(&left);
+        vertices.extend_from_slice(&right);
+    };
+
+    for i in 0..count {
+        let [x, y, pressure] = centerline[i];
+        let half_width = half_widths[i];
+        let u = walked[i];
+        let at = |n: (f32, f32), scale: f32| (x + n.0 * half_width * scale, y + n.1 * half_width * scale);
+        let uv = |(vx, vy): (f32, f32), v: f32| [vx, vy, u, v, pressure];
+        // Cap vertices: U grows along the travel direction `t`, V runs across it
+        let cap_uv = |(vx, vy): (f32, f32), t: (f32, f32)| {
+            let (ox, oy) = (vx - x, vy - y);
+            let across = (oy * t.0 - ox * t.1) / half_width;
+            [vx, vy, u + ox * t.0 + oy * t.1, 0.5 - 0.5 * across, pressure]
+        };
+
+        let incoming = if i > 0 { Some(direction(centerline[i - 1], centerline[i])) } else { None };
+        let outgoing = if i + 1 < count { Some(direction(centerline[i], centerline[i + 1])) } else { None };
+
+        let (d0, d1) = match (incoming, outgoing) {
+            (Some(d0), Some(d1)) => (d0, d1),
+            _ if half_width == 0.0 => {
+                push_pair(uv((x, y), 0.0), uv((x, y), 1.0));
+                continue;
+            }
+            (None, Some(d)) => {
+                // Caps are built looking out of the stroke, so the start looks backwards
+                let cap = cap_pairs(options.start_cap, (-d.0, -d.1), options.round_segments);
+                for (left, right) in cap.into_iter().rev() {
+                    push_pair(cap_uv(at(right, 1.0), d), cap_uv(at(left, 1.0), d));
+                }
+                continue;
+            }
+            (Some(d), None) => {
+                for (left, right) in cap_pairs(options.end_cap, d, options.round_segments) {
+                    push_pair(cap_uv(at(left, 1.0), d), cap_uv(at(right, 1.0), d));
+                }
+                continue;
+            }
+            (None, None) => unreachable!("centerline has at least two points"),
+        };
+
+        let n0 = (-d0.1, d0.0);
+        let n1 = (-d1.1, d1.0);
+        let mx = n0.0 + n1.0;
+        let my = n0.1 + n1.1;
+        let m_len = (mx * mx + my * my).sqrt();
+
+        if half_width == 0.0 {
+            push_pair(uv((x, y), 0.0), uv((x, y), 1.0));
+            continue;
+        }
+
+        if m_len < 1e-6 {
+            // Full reversal: bisector vanishes
+            push_pair(uv(at(n0, 1.0), 0.0), uv(at(n0, -1.0), 1.0));
+            if options.join == JoinStyle::Round {
+                // Cap around the turning point on the left, back to the outgoing segment's sides
+                let arc = join_arc(n0, -std::f32::consts::PI, options.round_segments);
+                for &n in &arc[1..arc.len() - 1] {
+                    push_pair(uv(at(n, 1.0), 0.0), uv((x, y), 0.5));
+                }
+                push_pair(uv(at(n1, 1.0), 0.0), uv(at(n1, -1.0), 1.0));
+            }
+            continue;
+        }
+
+        let m = (mx / m_len, my / m_len);
+        let cos_half = m.0 * n0.0 + m.1 * n0.1;
+        let miter_scale = (1.0 / cos_half).min(options.miter_limit);
+        if options.join == JoinStyle::Miter {
+            push_pair(uv(at(m, miter_scale), 0.0), uv(at(m, -miter_scale), 1.0));
+            continue;
+        }
+
+        // Normals turn by the same signed angle as the directions; the outer side is opposite the turn
+        let turn = (n0.0 * n1.1 - n0.1 * n1.0).atan2(n0.0 * n1.0 + n0.1 * n1.1);
+        let outer_sign = if turn < 0.0 { 1.0 } else { -1.0 };
+        let inner = at(m, -outer_sign * miter_scale);
+        let (outer_v, inner_v) = if outer_sign > 0.0 { (0.0, 1.0) } else { (1.0, 0.0) };
+        let outer: Vec<(f32, f32)> = match options.join {
+            JoinStyle::Bevel => vec![n0, n1],
+            _ => join_arc(n0, turn, options.round_segments),
+        };
+        for n in outer {
+            let vertex = uv(at(n, outer_sign), outer_v);
+            if outer_sign > 0.0 {
+                push_pair(vertex, uv(inner, inner_v));
+            } else {
+                push_pair(uv(inner, inner_v), vertex);
+            }
+        }
+    }
+
+    (vertices, painted_length)
+}
+
+/**
+ * Convert a triangle strip of `floats_per_vertex`-wide vertices into
+ * deduplicated vertices plus three indices per triangle
+ *
+ * Vertices are equal when all their floats are (with -0.0 equal to 0.0).
+ * Triangles that reuse a vertex after deduplication are dropped; odd
+ * strip triangles are flipped so every triangle has the same winding.
+ */
+pub(crate) fn index_strip(strip: &[f32], floats_per_vertex: usize) -> (Vec<f32>, Vec<u32>) {
+    let (vertices, remap) = weld_vertices(strip, floats_per_vertex);
+    (vertices, strip_triangles(&remap))
+}
+
+/**
+ * Deduplicate vertices as index_strip does
+ *
+ * Returns the distinct vertices in first-use order and, for each input
+ * vertex, its index among them.
+ */
+pub(crate) fn weld_vertices(strip: &[f32], floats_per_vertex: usize) -> (Vec<f32>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut seen: BTreeMap<Vec<u32>, u32> = BTreeMap::new();
+    let remap: Vec<u32> = strip
+        .chunks_exact(floats_per_vertex)
+        .map(|vertex| {
+            let key = vertex.iter().map(|&value| (value + 0.0).to_bits()).collect();
+            *seen.entry(key).or_insert_with(|| {
+                vertices.extend_from_slice(vertex);
+                (vertices.len() / floats_per_vertex - 1) as u32
+            })
+        })
+        .collect();
+    (vertices, remap)
+}
+
+/**
+ * Indices of the triangles of a strip whose vertices were welded to
+ * `remap`, as index_strip emits them
+ */
+pub(crate) fn strip_triangles(remap: &[u32]) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(remap.len().saturating_sub(2) * 3);
+    for (k, window) in remap.windows(3).enumerate() {
+        let (a, b, c) = if k % 2 == 0 { (window[0], window[1], window[2]) } else { (window[1], window[0], window[2]) };
+        if a != b && b != c && a != c {
+            indices.extend_from_slice(&[a, b, c]);
+        }
+    }
+    indices
+}
+
+/**
+ * Offsets, in half-widths, of the left/right pairs closing an end whose
+ * outward direction is `out`, from the end's own pair outward
+ *
+ * "Left" is relative to `out`. Round caps end in a single tip, emitted as
+ * a repeated pair.
+ */
+fn cap_pairs(cap: CapStyle, out: (f32, f32), round_segments: u32) -> Vec<((f32, f32), (f32, f32))> {
+    let n = (-out.1, out.0);
+    match cap {
+        CapStyle::Butt => vec![(n, (-n.0, -n.1))],
+        CapStyle::Square => vec![((n.0 + out.0, n.1 + out.1), (out.0 - n.0, out.1 - n.1))],
+        CapStyle::Round => {
+            let steps = round_segments.div_ceil(2).max(1);
+            (0..=steps)
+                .map(|j| {
+                    let (sin, cos) = (std::f32::consts::FRAC_PI_2 * j as f32 / steps as f32).sin_cos();
+                    let left = (n.0 * cos + out.0 * sin, n.1 * cos + out.1 * sin);
+                    let right = (out.0 * sin - n.0 * cos, out.1 * sin - n.1 * cos);
+                    (left, right)
+                })
+                .collect()
+        }
+    }
+}
+
+/**
+ * Unit normals from `from` rotating by `angle`, both ends included, with
+ * ceil(`segments_per_half_turn` * |angle| / 180°) steps (at least one)
+ */
+fn join_arc(from: (f32, f32), angle: f32, segments_per_half_turn: u32) -> Vec<(f32, f32)> {
+    let steps = ((segments_per_half_turn as f32 * angle.abs() / std::f32::consts::PI).ceil() as usize).max(1);
+    (0..=steps)
+        .map(|j| {
+            let (sin, cos) = (angle * j as f32 / steps as f32).sin_cos();
+            (from.0 * cos - from.1 * sin, from.0 * sin + from.1 * cos)
+        })
+        .collect()
+}
+
+/**
+ * Collect points, dropping consecutive duplicates
+ *
+ * Duplicates have no direction and would produce NaN normals. A point
+ * counts as a duplicate when its squared distance to the previous kept
+ * point is zero in f32, which also catches separations that underflow.
+ */
+pub(crate) fn distinct_points(points: &[f32]) -> Vec<[f32; 3]> {
+    let mut result: Vec<[f32; 3]> = Vec::with_capacity(points.len() / 3);
+    for point in points.chunks_exact(3) {
+        let point = [point[0], point[1], point[2]];
+        match result.last() {
+            Some(last) if distance_sq(*last, point) == 0.0 => {}
+            _ => result.push(point),
+        }
+    }
+    result
+}
+
+fn distance_sq(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    dx * dx + dy * dy
+}
+
+/**
+ * Unit direction from a to b (points are guaranteed distinct)
+ */
+pub(crate) fn direction(a: [f32; 3], b: [f32; 3]) -> (f32, f32) {
+    let dx = b[0] - a[0];
+    let dy = b[1] - a[1];
+    let len = (dx * dx + dy * dy).sqrt();
+    (dx / len, dy / len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::test_support::{error_of, take_bytes, take_records};
+
+    /**
+     * Floats per vertex and the vertex data of a tessellate_stroke_uv result
+     */
+    fn take_uv_strip(buffer: EngineBuffer) -> (usize, Vec<f32>) {
+        let bytes = take_bytes(buffer);
+        let count = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let stride = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(bytes.len(), 8 + count * stride * 4);
+        (stride, bytes[8..].chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect())
+    }
+
+    fn uv_strip(points: &[f32], join: JoinStyle, cap: CapStyle, emit_uvs: bool, repeat: f32) -> (usize, Vec<f32>) {
+        take_uv_strip(tessellate_stroke_uv(points.as_ptr(), points.len(), 2.0, join, 4.0, 8, cap, cap, emit_uvs, repeat))
+    }
+
+    #[test]
+    fn u_never_decreases_along_either_side() {
+        let arc: Vec<f32> = (0..40)
+            .flat_map(|i| {
+                let a = i as f32 * 0.1;
+                [10.0 * a.cos(), 10.0 * a.sin(), 0.5 + 0.5 * a.sin().abs()]
+            })
+            .collect();
+        for join in [JoinStyle::Miter, JoinStyle::Round, JoinStyle::Bevel] {
+            for cap in [CapStyle::Butt, CapStyle::Round, CapStyle::Square] {
+                let (stride, vertices) = uv_strip(&arc, join, cap, true, 0.0);
+                assert_eq!(stride, 4);
+                // Positions are exactly tessellate_stroke_ex's
+                let (_, positions) = uv_strip(&arc, join, cap, false, 0.0);
+                let strip = take_records(tessellate_stroke_ex(arc.as_ptr(), arc.len(), 2.0, join, 4.0, 8, cap, cap), 2);
+                assert_eq!(positions, strip);
+                let xy: Vec<f32> = vertices.chunks_exact(4).flat_map(|v| [v[0], v[1]]).collect();
+                assert_eq!(xy, strip);
+
+                let vertices: Vec<&[f32]> = vertices.chunks_exact(4).collect();
+                for side in 0..2 {
+                    let us: Vec<f32> = vertices.iter().skip(side).step_by(2).map(|v| v[2]).collect();
+                    assert!(us.windows(2).all(|w| w[1] >= w[0] - 1e-5), "{join:?} {cap:?} side {side}: {us:?}");
+                }
+                // Repeat length 0 stretches U over 0-1 once
+                let (low, high) = vertices.iter().fold((f32::MAX, f32::MIN), |(low, high), v| (low.min(v[2]), high.max(v[2])));
+                assert!(low.abs() < 1e-5 && (high - 1.0).abs() < 1e-5, "{join:?} {cap:?}: U spans {low}..{high}");
+                assert!(vertices.iter().all(|v| (-1e-5..=1.0 + 1e-5).contains(&v[3])));
+            }
+        }
+    }
+
+    #[test]
+    fn u_repeats_every_repeat_length() {
+        let line = [0.0, 0.0, 1.0, 10.0, 0.0, 1.0];
+        let (_, vertices) = uv_strip(&line, JoinStyle::Miter, CapStyle::Butt, true, 4.0);
+        // Half-width 2 at full pressure: rungs at x = 0 and 10, left side first
+        assert_eq!(vertices, [0.0, 2.0, 0.0, 0.0, 0.0, -2.0, 0.0, 1.0, 10.0, 2.0, 2.5, 0.0, 10.0, -2.0, 2.5, 1.0]);
+
+        // U starts at the start cap's tip: with half-width 1 the caps add 1 unit at each end
+        let result = tessellate_stroke_uv(line.as_ptr(), 6, 1.0, JoinStyle::Miter, 4.0, 8, CapStyle::Square, CapStyle::Round, true, 1.0);
+        let (_, vertices) = take_uv_strip(result);
+        assert_eq!(vertices[2], 0.0);
+        assert_eq!(vertices[vertices.len() - 2], 12.0);
+
+        let result = tessellate_stroke_uv(line.as_ptr(), 6, 2.0, JoinStyle::Miter, 4.0, 8, CapStyle::Butt, CapStyle::Butt, true, -1.0);
+        assert_eq!(error_of(result), CreoVexError::InvalidParameter as u32);
+    }
+
+    /**
+     * Vertices and indices of a tessellate_stroke_indexed result
+     */
+    fn take_mesh(buffer: EngineBuffer) -> (Vec<[f32; 2]>, Vec<u32>) {
+        let bytes = take_bytes(buffer);
+        let vertex_count = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let index_count = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(bytes.len(), 8 + vertex_count * 8 + index_count * 4);
+        let (positions, indices) = bytes[8..].split_at(vertex_count * 8);
+        let vertices = positions.chunks_exact(8).map(|b| [0, 4].map(|i| f32::from_le_bytes(b[i..i + 4].try_into().unwrap())));
+        let indices = indices.chunks_exact(4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+        (vertices.collect(), indices.collect())
+    }
+
+    #[test]
+    fn indexed_meshes_weld_the_stroke_body() {
+        let stroke: Vec<f32> = (0..1000)
+            .flat_map(|i| {
+                let a = i as f32 * 0.01;
+                [100.0 * a.cos(), 50.0 * (2.0 * a).sin(), 0.6 + 0.4 * a.sin().abs()]
+            })
+            .collect();
+        let (cap, len) = (CapStyle::Round, stroke.len());
+        for join in [JoinStyle::Miter, JoinStyle::Round, JoinStyle::Bevel] {
+            let strip = take_records(tessellate_stroke_ex(stroke.as_ptr(), len, 3.0, join, 4.0, 8, cap, cap), 2);
+            let (vertices, indices) = take_mesh(tessellate_stroke_indexed(stroke.as_ptr(), len, 3.0, join, 4.0, 8, cap, cap));
+            assert_eq!(indices.len() % 3, 0);
+            assert!(indices.iter().all(|&index| (index as usize) < vertices.len()));
+
+            // Deduplicated: under half the vertices of the strip as a plain triangle list
+            let strip_vertices = strip.len() / 2;
+            let naive = (strip_vertices - 2) * 3;
+            assert!(vertices.len() * 2 < naive, "{join:?}: {} vertices against {naive}", vertices.len());
+            let mut seen = HashMap::new();
+            for vertex in &vertices {
+                assert!(seen.insert(vertex.map(f32::to_bits), ()).is_none(), "{join:?}: {vertex:?} is stored twice");
+            }
+
+            // Watertight: no edge has more than two triangles, and every rung
+            // of the strip's body is shared by the quads on both sides of it
+            let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+            for triangle in indices.chunks_exact(3) {
+                for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+                    *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+                }
+            }
+            assert!(edges.values().all(|&count| count <= 2), "{join:?}: an edge has more than two triangles");
+            let area = |t: &[u32]| {
+                let [a, b, c] = [0, 1, 2].map(|k| vertices[t[k] as usize]);
+                (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+            };
+            let orientation = area(&indices[..3]).signum();
+            assert!(indices.chunks_exact(3).all(|t| area(t) * orientation > 0.0), "{join:?}: triangles are wound both ways");
+            let index_of = |position: [f32; 2]| vertices.iter().position(|&vertex| vertex == position).unwrap() as u32;
+            for rung in strip.chunks_exact(4).skip(1).take(strip_vertices / 2 - 2) {
+                let (left, right) = ([rung[0], rung[1]], [rung[2], rung[3]]);
+                if left != right {
+                    let (a, b) = (index_of(left), index_of(right));
+                    assert_eq!(edges.get(&(a.min(b), a.max(b))), Some(&2), "{join:?}: rung {left:?}-{right:?} is open");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_dots_give_empty_meshes() {
+        let dot = [5.0f32, 5.0, 1.0];
+        let repeated = [5.0f32, 5.0, 1.0, 5.0, 5.0, 0.5];
+        for stroke in [&dot[..], &repeated[..]] {
+            let mesh = tessellate_stroke_indexed(stroke.as_ptr(), stroke.len(), 3.0, JoinStyle::Round, 4.0, 8, CapStyle::Round, CapStyle::Round);
+            assert_eq!(take_mesh(mesh), (vec![], vec![]));
+        }
+    }
+}
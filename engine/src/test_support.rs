@@ -0,0 +1,161 @@
+/*!
+ * Helpers for the unit tests
+ *
+ * Exports are tested through their FFI contract: inputs go in as
+ * pointer + length, and results are read back out of the EngineBuffer
+ * and freed again, as JS would.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md
+ */
+
+// Not every test module needs every helper
+#![allow(dead_code)]
+
+use crate::{free_buffer, CreoVexError, EngineBuffer};
+
+/**
+ * Bytes of a successful result, freeing its buffer
+ */
+pub(crate) fn take_bytes(buffer: EngineBuffer) -> Vec<u8> {
+    assert_eq!(buffer.error_code(), 0, "export failed: {}", crate::last_error_message());
+    let bytes = unsafe { std::slice::from_raw_parts(buffer.ptr(), buffer.byte_len()) }.to_vec();
+    free_buffer(buffer.ptr(), buffer.byte_len());
+    bytes
+}
+
+/**
+ * Payload of a serialize_records result (`record_floats` f32 values per
+ * record), freeing its buffer
+ */
+pub(crate) fn take_records(buffer: EngineBuffer, record_floats: usize) -> Vec<f32> {
+    let bytes = take_bytes(buffer);
+    let count = read_u32(&bytes, 0) as usize;
+    assert_eq!(bytes.len(), 4 + count * record_floats * 4, "record count does not match the buffer size");
+    floats(&bytes[4..])
+}
+
+/**
+ * Points of a serialize_points result, freeing its buffer
+ */
+pub(crate) fn take_points(buffer: EngineBuffer) -> Vec<f32> {
+    take_records(buffer, 3)
+}
+
+/**
+ * Payload of a serialize_records_f64 result (`record_values` f64 values
+ * per record), freeing its buffer
+ */
+pub(crate) fn take_records_f64(buffer: EngineBuffer, record_values: usize) -> Vec<f64> {
+    assert!((buffer.ptr() as usize).is_multiple_of(8), "f64 results are 8-byte aligned");
+    let bytes = take_bytes(buffer);
+    let count = read_u32(&bytes, 0) as usize;
+    assert_eq!(read_u32(&bytes, 4), 8, "element size");
+    assert_eq!(bytes.len(), 8 + count * record_values * 8, "record count does not match the buffer size");
+    bytes[8..].chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap())).collect()
+}
+
+/**
+ * Record lists of a serialize_packed result (no status array), freeing
+ * its buffer
+ */
+pub(crate) fn take_strokes(buffer: EngineBuffer, record_floats: usize) -> Vec<Vec<f32>> {
+    let bytes = take_bytes(buffer);
+    let (strokes, end) = unpack(&bytes, record_floats);
+    assert_eq!(end, bytes.len(), "trailing bytes after the last stroke");
+    strokes
+}
+
+/**
+ * Record lists and per-stroke statuses of a *_batch result, freeing its
+ * buffer
+ */
+pub(crate) fn take_batch(buffer: EngineBuffer, record_floats: usize) -> (Vec<Vec<f32>>, Vec<u32>) {
+    let bytes = take_bytes(buffer);
+    let (strokes, end) = unpack(&bytes, record_floats);
+    let statuses: Vec<u32> = (0..strokes.len()).map(|i| read_u32(&bytes, end + i * 4)).collect();
+    assert_eq!(end + statuses.len() * 4, bytes.len(), "status array does not end the buffer");
+    (strokes, statuses)
+}
+
+/**
+ * The error code of a failed result
+ */
+pub(crate) fn error_of(buffer: EngineBuffer) -> u32 {
+    assert!(buffer.ptr().is_null(), "expected a failure, got a {} byte result", buffer.byte_len());
+    buffer.error_code()
+}
+
+/**
+ * Whether a result failed with `code`
+ */
+pub(crate) fn fails_with(buffer: EngineBuffer, code: CreoVexError) -> bool {
+    error_of(buffer) == code as u32
+}
+
+/**
+ * Strokes of [x, y, p] points in the batch container layout
+ */
+pub(crate) fn pack_batch(strokes: &[Vec<f32>]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(strokes.len() as u32).to_le_bytes());
+    for stroke in strokes {
+        bytes.extend_from_slice(&((stroke.len() / 3) as u32).to_le_bytes());
+    }
+    for value in strokes.iter().flatten() {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+/**
+ * Points along y = amplitude * sin(x / period), one every `step` in x,
+ * with pressure 0.5
+ */
+pub(crate) fn sine_stroke(count: usize, step: f32, amplitude: f32, period: f32) -> Vec<f32> {
+    (0..count)
+        .flat_map(|i| {
+            let x = i as f32 * step;
+            [x, amplitude * (x / period).sin(), 0.5]
+        })
+        .collect()
+}
+
+/**
+ * Deterministic pseudo-random values in [-1, 1) (xorshift), so failures
+ * reproduce
+ */
+pub(crate) struct Noise(u64);
+
+impl Noise {
+    pub(crate) fn new(seed: u64) -> Self {
+        Noise(seed.max(1))
+    }
+
+    pub(crate) fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+fn unpack(bytes: &[u8], record_floats: usize) -> (Vec<Vec<f32>>, usize) {
+    let count = read_u32(bytes, 0) as usize;
+    let mut offset = 4 + count * 4;
+    let mut strokes = Vec::with_capacity(count);
+    for i in 0..count {
+        let len = read_u32(bytes, 4 + i * 4) as usize * record_floats * 4;
+        strokes.push(floats(&bytes[offset..offset + len]));
+        offset += len;
+    }
+    (strokes, offset)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn floats(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect()
+}
@@ -0,0 +1,239 @@
+/*!
+ * Thread pool for the batch exports
+ *
+ * Built with the `threads` cargo feature, smooth_strokes_batch,
+ * transform_strokes_batch, and tessellate_strokes_batch spread their
+ * strokes over the workers started by init_thread_pool. Every other
+ * build (the default), and a threads build before init_thread_pool or
+ * on wasm without shared memory, runs them sequentially with the same
+ * results.
+ *
+ * Ordering: every stroke has its result slot (its index in the batch)
+ * before any work starts, and the container is written from the slots in
+ * stroke order afterwards, so the output is byte-identical whatever the
+ * scheduling.
+ *
+ * wasm: the pool needs the atomics and bulk-memory target features (a
+ * std built for them) and a SharedArrayBuffer memory:
+ *
+ * ```text
+ * RUSTFLAGS="-C target-feature=+atomics,+bulk-memory,+mutable-globals" \
+ * cargo +nightly build --release --target wasm32-unknown-unknown \
+ * --features threads -Z build-std=panic_abort,std
+ * ```
+ *
+ * Browsers only hand out SharedArrayBuffer on cross-origin isolated
+ * pages (`self.crossOriginIsolated`), so the app must be served with
+ * - `Cross-Origin-Opener-Policy: same-origin`
+ * - `Cross-Origin-Embedder-Policy: require-corp` (or `credentialless`),
+ *   which also makes every cross-origin subresource need CORS or CORP
+ *   headers
+ *
+ * wasm cannot start threads itself: init_thread_pool(n) reserves n
+ * workers, and JS then starts n Web Workers that each instantiate the
+ * module on the same memory and call thread_pool_worker(), which never
+ * returns. A batch call waits for its strokes with Atomics.wait, which
+ * browsers forbid on the page's main thread: call the batch exports from
+ * a worker.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Stroke Processing)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/**
+ * Upper bound on pool workers
+ */
+pub(crate) const MAX_THREADS: usize = 64;
+
+/**
+ * Start (natively) or reserve (wasm) `threads` pool workers
+ *
+ * Returns the pool size: `threads` clamped to MAX_THREADS, or 0 when
+ * this build cannot run a pool (no `threads` feature, or wasm without
+ * the atomics target feature), in which case the batch exports stay
+ * sequential. The pool only grows: a later call with a smaller count
+ * returns the current size. On wasm, start that many workers calling
+ * thread_pool_worker() (see the module docs).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn init_thread_pool(threads: usize) -> usize {
+    let threads = threads.min(MAX_THREADS);
+    #[cfg(feature = "threads")]
+    if pool::SUPPORTED {
+        return pool::init(threads);
+    }
+    let _ = threads;
+    0
+}
+
+/**
+ * Number of pool workers running (0 while the batch exports are sequential)
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn thread_pool_size() -> usize {
+    #[cfg(feature = "threads")]
+    if pool::SUPPORTED {
+        return pool::running_workers();
+    }
+    0
+}
+
+/**
+ * Body of a pool Web Worker: runs batch work until the page closes
+ *
+ * Returns at once when every worker init_thread_pool reserved is
+ * already running, or when the build has no pool.
+ */
+#[cfg(feature = "threads")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn thread_pool_worker() {
+    if pool::SUPPORTED {
+        pool::work();
+    }
+}
+
+/**
+ * `process` applied to 0..count, in index order, on the pool when it
+ * runs (the calling thread takes part)
+ *
+ * A panic on a worker leaves its slot None; one on the calling thread
+ * propagates after the workers have let go of `process`.
+ */
+pub(crate) fn map_indexed<R: Send + Sync>(count: usize, process: impl Fn(usize) -> R + Sync) -> Vec<Option<R>> {
+    #[cfg(feature = "threads")]
+    if pool::SUPPORTED && count > 1 {
+        return pool::map_indexed(count, process);
+    }
+    (0..count).map(|index| Some(process(index))).collect()
+}
+
+#[cfg(feature = "threads")]
+mod pool {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+
+    /** Whether this target can share memory between threads */
+    pub(super) const SUPPORTED: bool = cfg!(any(not(target_arch = "wasm32"), target_feature = "atomics"));
+
+    /** Work posted to every worker, borrowed for one map_indexed call */
+    #[derive(Clone, Copy)]
+    struct Job(&'static (dyn Fn() + Sync));
+
+    struct State {
+        job: Option<Job>,
+        /** Bumped per job, so a worker runs each job at most once */
+        generation: u64,
+        /** Workers inside the current job */
+        busy: usize,
+        /** Workers reserved by init_thread_pool / running work() */
+        reserved: usize,
+        running: usize,
+    }
+
+    static STATE: Mutex<State> =
+        Mutex::new(State { job: None, generation: 0, busy: 0, reserved: 0, running: 0 });
+    /** A job was posted */
+    static POSTED: Condvar = Condvar::new();
+    /** A worker left a job */
+    static LEFT: Condvar = Condvar::new();
+
+    fn lock() -> MutexGuard<'static, State> {
+        STATE.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(super) fn init(threads: usize) -> usize {
+        let mut state = lock();
+        let added = threads.saturating_sub(state.reserved);
+        state.reserved += added;
+        let reserved = state.reserved;
+        drop(state);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        for _ in 0..added {
+            std::thread::Builder::new()
+                .name("creovox-engine".into())
+                .spawn(work)
+                .expect("spawn engine pool thread");
+        }
+        reserved
+    }
+
+    pub(super) fn running_workers() -> usize {
+        lock().running
+    }
+
+    pub(super) fn work() {
+        let mut state = lock();
+        if state.running == state.reserved {
+            return;
+        }
+        state.running += 1;
+        let mut seen = state.generation;
+        loop {
+            match state.job {
+                Some(job) if state.generation != seen => {
+                    seen = state.generation;
+                    state.busy += 1;
+                    drop(state);
+                    // The job records nothing for an index whose work panicked
+                    let _ = catch_unwind(AssertUnwindSafe(job.0));
+                    state = lock();
+                    state.busy -= 1;
+                    LEFT.notify_all();
+                }
+                _ => state = POSTED.wait(state).unwrap_or_else(PoisonError::into_inner),
+            }
+        }
+    }
+
+    /**
+     * Withdraws the job and waits until no worker is inside it, also when
+     * the calling thread unwinds, so the borrowed closure outlives its use
+     */
+    struct Withdraw;
+
+    impl Drop for Withdraw {
+        fn drop(&mut self) {
+            let mut state = lock();
+            state.job = None;
+            while state.busy > 0 {
+                state = LEFT.wait(state).unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+    }
+
+    pub(super) fn map_indexed<R: Send + Sync>(count: usize, process: impl Fn(usize) -> R + Sync) -> Vec<Option<R>> {
+        let slots: Vec<OnceLock<R>> = (0..count).map(|_| OnceLock::new()).collect();
+        let next = AtomicUsize::new(0);
+        let task = || loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            if index >= count {
+                break;
+            }
+            let _ = slots[index].set(process(index));
+        };
+
+        let mut state = lock();
+        if state.running == 0 || state.job.is_some() {
+            // No workers, or another call (or a worker) already owns the pool
+            drop(state);
+            task();
+        } else {
+            let task: &(dyn Fn() + Sync) = &task;
+            // Safety: Withdraw keeps `task` borrowed until every worker has left it
+            let task = unsafe { std::mem::transmute::<&(dyn Fn() + Sync), &'static (dyn Fn() + Sync)>(task) };
+            state.job = Some(Job(task));
+            state.generation += 1;
+            drop(state);
+            POSTED.notify_all();
+            let _withdraw = Withdraw;
+            task();
+        }
+
+        slots.into_iter().map(OnceLock::into_inner).collect()
+    }
+}
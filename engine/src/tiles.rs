@@ -0,0 +1,303 @@
+/*!
+ * Tile invalidation
+ *
+ * The renderer composites the canvas from fixed-size tiles and repaints
+ * only the dirty ones. These exports find the tiles a stroke's painted
+ * footprint touches, from the whole stroke or incrementally from the
+ * points a live stroke just gained.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Culling)
+ */
+
+use std::collections::BTreeSet;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{
+    require_finite, require_non_negative, require_positive, run_export, CreoVexError, EngineError, EngineResult,
+};
+use crate::{hand_off, output_buffer, read_points, EngineBuffer};
+
+/**
+ * Most tiles one call may report
+ *
+ * Guards against a tiny tile size or huge width turning one segment into
+ * billions of tiles; a million 256px tiles is a 256k x 256k px canvas.
+ */
+const MAX_DIRTY_TILES: usize = 1 << 20;
+
+/**
+ * Tile grid geometry and the width the footprint is measured with
+ */
+#[derive(Clone, Copy, Debug)]
+struct TileGrid {
+    base_width: f64,
+    tile_size: f64,
+    origin_x: f64,
+    origin_y: f64,
+}
+
+impl TileGrid {
+    fn from_ffi(base_width: f32, tile_size: f32, origin_x: f32, origin_y: f32) -> EngineResult<Self> {
+        Ok(TileGrid {
+            base_width: require_non_negative("base_width", require_finite("base_width", base_width)?)? as f64,
+            tile_size: require_positive("tile_size", require_finite("tile_size", tile_size)?)? as f64,
+            origin_x: require_finite("origin_x", origin_x)? as f64,
+            origin_y: require_finite("origin_y", origin_y)? as f64,
+        })
+    }
+
+    /**
+     * Column or row along one axis of the first tile whose far edge
+     * reaches `value` (`first`), or the last whose near edge does
+     */
+    fn tile_of(&self, value: f64, origin: f64, first: bool) -> EngineResult<i32> {
+        let cell = (value - origin) / self.tile_size;
+        let tile = if first { cell.ceil() - 1.0 } else { cell.floor() };
+        if tile < i32::MIN as f64 || tile > i32::MAX as f64 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                format!("coordinate {value} is outside the i32 tile range for tile_size {}", self.tile_size),
+            ));
+        }
+        Ok(tile as i32)
+    }
+
+    /**
+     * Add the tiles the segment's footprint touches: a capsule around a-b
+     * with radius base_width * the larger pressure (negative counts as 0)
+     *
+     * `a == b` gives the disc of a lone point.
+     */
+    fn add_segment(&self, a: &[f32], b: &[f32], tiles: &mut BTreeSet<(i32, i32)>) -> EngineResult<()> {
+        let (ax, ay, bx, by) = (a[0] as f64, a[1] as f64, b[0] as f64, b[1] as f64);
+        let radius = self.base_width * (a[2].max(b[2]).max(0.0) as f64);
+
+        let first_x = self.tile_of(ax.min(bx) - radius, self.origin_x, true)?;
+        let last_x = self.tile_of(ax.max(bx) + radius, self.origin_x, false)?;
+        let first_y = self.tile_of(ay.min(by) - radius, self.origin_y, true)?;
+        let last_y = self.tile_of(ay.max(by) + radius, self.origin_y, false)?;
+        let columns = (last_x as i64 - first_x as i64 + 1) as u64;
+        let rows = (last_y as i64 - first_y as i64 + 1) as u64;
+        if columns.saturating_mul(rows) > MAX_DIRTY_TILES as u64 {
+            return Err(too_many_tiles());
+        }
+
+        for tile_y in first_y..=last_y {
+            let min_y = self.origin_y + tile_y as f64 * self.tile_size;
+            for tile_x in first_x..=last_x {
+                let min_x = self.origin_x + tile_x as f64 * self.tile_size;
+                let rect = [min_x, min_y, min_x + self.tile_size, min_y + self.tile_size];
+                if segment_rect_distance_sq(ax, ay, bx, by, rect) <= radius * radius {
+                    tiles.insert((tile_y, tile_x));
+                }
+            }
+        }
+        if tiles.len() > MAX_DIRTY_TILES {
+            return Err(too_many_tiles());
+        }
+        Ok(())
+    }
+
+    /**
+     * Add the tiles of every segment of [x, y, p] points (the disc of a
+     * single point)
+     */
+    fn add_stroke(&self, points: &[f32], tiles: &mut BTreeSet<(i32, i32)>) -> EngineResult<()> {
+        if points.len() == 3 {
+            return self.add_segment(points, points, tiles);
+        }
+        for (a, b) in points.chunks_exact(3).zip(points.chunks_exact(3).skip(1)) {
+            self.add_segment(a, b, tiles)?;
+        }
+        Ok(())
+    }
+}
+
+fn too_many_tiles() -> EngineError {
+    EngineError::new(
+        CreoVexError::InvalidParameter,
+        format!("stroke footprint covers more than {MAX_DIRTY_TILES} tiles (tile_size too small?)"),
+    )
+}
+
+/**
+ * Find the tiles a stroke's painted footprint touches
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format:
+ * - 4 bytes: tile count N (u32)
+ * - N * 8 bytes: i32 [tile_x, tile_y] per tile, row by row (ascending
+ *   tile_y, then tile_x), each tile once
+ *
+ * Tile (tx, ty) covers [origin_x + tx * tile_size, origin_x + (tx + 1) *
+ * tile_size] and likewise in y; tiles left of or above the origin have
+ * negative indices. The footprint is that of round joins and caps: each
+ * segment counts as a capsule of radius `base_width` times the larger
+ * pressure of its two points (the half-width tessellate_stroke paints
+ * there), and a single point as its disc, which also covers bevel joins
+ * and butt caps. A tile is dirty when the footprint overlaps or touches
+ * it, so tiles reached only by the width or a cap are included. Miter
+ * joins on sharp turns and square caps can reach past the capsules (as
+ * for stroke_bounds).
+ *
+ * Errors: the read_points errors; NonFiniteValue/InvalidParameter for a
+ * NaN, infinite, or negative `base_width`, a `tile_size` that is not
+ * positive and finite, or a non-finite origin; InvalidParameter when the
+ * footprint covers more than MAX_DIRTY_TILES tiles or lies outside the
+ * i32 tile range.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn strokes_to_dirty_tiles(
+    points_ptr: *const f32,
+    points_len: usize,
+    base_width: f32,
+    tile_size: f32,
+    origin_x: f32,
+    origin_y: f32,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let grid = TileGrid::from_ffi(base_width, tile_size, origin_x, origin_y)?;
+
+        let mut tiles = BTreeSet::new();
+        grid.add_stroke(points, &mut tiles)?;
+        serialize_tiles(&tiles)
+    })
+}
+
+/**
+ * Incremental dirty tiles for a live stroke
+ *
+ * Feed each batch of newly appended points; every call reports only the
+ * tiles touched by the new ink: the segment joining the previous batch's
+ * last point to the new points, and the segments among them. Per call
+ * cost is proportional to the new points, and the union of all reports
+ * equals strokes_to_dirty_tiles for the whole stroke.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct DirtyTileTracker {
+    base_width: f32,
+    tile_size: f32,
+    origin_x: f32,
+    origin_y: f32,
+    /** Last point fed, where the next batch's first segment starts */
+    last: Option<[f32; 3]>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DirtyTileTracker {
+    /**
+     * Tracker for one stroke on the given grid (arguments as
+     * strokes_to_dirty_tiles; they are checked on every add_points)
+     */
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(base_width: f32, tile_size: f32, origin_x: f32, origin_y: f32) -> DirtyTileTracker {
+        DirtyTileTracker { base_width, tile_size, origin_x, origin_y, last: None }
+    }
+
+    /**
+     * Tiles touched by appending [x, y, p] points to the stroke
+     *
+     * Output format as strokes_to_dirty_tiles. The first batch of a
+     * stroke includes the first point's disc. On failure nothing is
+     * recorded, so the batch can be fixed and fed again.
+     *
+     * Errors: as strokes_to_dirty_tiles.
+     */
+    pub fn add_points(&mut self, points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+        run_export(|| {
+            let points = &read_points(points_ptr, points_len)?;
+            let grid = TileGrid::from_ffi(self.base_width, self.tile_size, self.origin_x, self.origin_y)?;
+
+            let mut tiles = BTreeSet::new();
+            if let Some(last) = self.last {
+                grid.add_segment(&last, &points[..3], &mut tiles)?;
+            }
+            grid.add_stroke(points, &mut tiles)?;
+            let end = points.len() - 3;
+            self.last = Some([points[end], points[end + 1], points[end + 2]]);
+            serialize_tiles(&tiles)
+        })
+    }
+
+    /**
+     * Forget the stroke so the next add_points starts a new one
+     */
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/**
+ * Tile set (keyed row first) in the strokes_to_dirty_tiles layout
+ */
+fn serialize_tiles(tiles: &BTreeSet<(i32, i32)>) -> EngineResult<EngineBuffer> {
+    let mut buffer = output_buffer(4 + tiles.len() * 8)?;
+    buffer.extend_from_slice(&(tiles.len() as u32).to_le_bytes());
+    for &(tile_y, tile_x) in tiles {
+        buffer.extend_from_slice(&tile_x.to_le_bytes());
+        buffer.extend_from_slice(&tile_y.to_le_bytes());
+    }
+    Ok(hand_off(buffer))
+}
+
+/**
+ * Squared distance between segment a-b and the rect [min_x, min_y,
+ * max_x, max_y] (0 when they meet)
+ */
+fn segment_rect_distance_sq(ax: f64, ay: f64, bx: f64, by: f64, rect: [f64; 4]) -> f64 {
+    let [min_x, min_y, max_x, max_y] = rect;
+    if segment_meets_rect(ax, ay, bx, by, rect) {
+        return 0.0;
+    }
+    let point_rect = |x: f64, y: f64| {
+        let dx = (min_x - x).max(x - max_x).max(0.0);
+        let dy = (min_y - y).max(y - max_y).max(0.0);
+        dx * dx + dy * dy
+    };
+    let corner_segment = |x: f64, y: f64| {
+        let (dx, dy) = (bx - ax, by - ay);
+        let length_sq = dx * dx + dy * dy;
+        let t = if length_sq > 0.0 { (((x - ax) * dx + (y - ay) * dy) / length_sq).clamp(0.0, 1.0) } else { 0.0 };
+        let (px, py) = (ax + dx * t - x, ay + dy * t - y);
+        px * px + py * py
+    };
+    [
+        point_rect(ax, ay),
+        point_rect(bx, by),
+        corner_segment(min_x, min_y),
+        corner_segment(max_x, min_y),
+        corner_segment(min_x, max_y),
+        corner_segment(max_x, max_y),
+    ]
+    .into_iter()
+    .fold(f64::INFINITY, f64::min)
+}
+
+/**
+ * Whether segment a-b overlaps or touches the rect (Liang-Barsky clip)
+ */
+fn segment_meets_rect(ax: f64, ay: f64, bx: f64, by: f64, rect: [f64; 4]) -> bool {
+    let [min_x, min_y, max_x, max_y] = rect;
+    let (dx, dy) = (bx - ax, by - ay);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [(-dx, ax - min_x), (dx, max_x - ax), (-dy, ay - min_y), (dy, max_y - ay)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    t0 <= t1
+}
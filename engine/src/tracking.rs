@@ -0,0 +1,177 @@
+/*!
+ * Allocation tracking for the FFI layer
+ *
+ * Debug aid for buffers JS forgets to free (or frees twice). While
+ * tracking is on, every buffer the engine hands out (alloc and every
+ * EngineBuffer result) is recorded with its size and origin until
+ * free_buffer releases it, and free_buffer checks each release against
+ * the registry. While it is off, alloc, hand_off, and free_buffer only
+ * test one flag.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Memory Ownership Rules)
+ */
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::panic::Location;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{record_error, CreoVexError, EngineError};
+use crate::pool::is_owned;
+
+/**
+ * Where a tracked buffer came from
+ */
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Origin {
+    /** alloc(), i.e. a buffer JS writes into */
+    Alloc,
+    /** An engine result, by the source location that serialized it */
+    Output(&'static Location<'static>),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Allocation {
+    size: usize,
+    origin: Origin,
+}
+
+thread_local! {
+    static TRACKING: Cell<bool> = const { Cell::new(false) };
+    /** Live tracked buffers by address */
+    static LIVE: RefCell<BTreeMap<usize, Allocation>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/**
+ * Whether allocation tracking is on (the only cost while it is off)
+ */
+#[inline(always)]
+pub(crate) fn tracking_enabled() -> bool {
+    TRACKING.with(Cell::get)
+}
+
+/**
+ * Record a buffer handed to JS
+ */
+#[cold]
+pub(crate) fn track_allocation(ptr: *mut u8, size: usize, origin: Origin) {
+    if !ptr.is_null() {
+        LIVE.with(|live| live.borrow_mut().insert(ptr as usize, Allocation { size, origin }));
+    }
+}
+
+/**
+ * Check a free_buffer call against the registry
+ *
+ * Returns whether the buffer may be freed. A pointer that is not live or
+ * a non-zero size that differs from the buffer's records InvalidFree and
+ * leaves the memory alone (and a mismatched buffer still live). Buffers
+ * handed out while tracking was off are let through untracked.
+ */
+#[cold]
+pub(crate) fn track_release(ptr: *mut u8, size: usize) -> bool {
+    if ptr.is_null() {
+        return true;
+    }
+    LIVE.with(|live| {
+        let mut live = live.borrow_mut();
+        let Some(allocation) = live.get(&(ptr as usize)) else {
+            if is_owned(ptr) {
+                return true;
+            }
+            record_error(EngineError::new(
+                CreoVexError::InvalidFree,
+                format!("free_buffer({:#x}, {size}): not a live engine buffer (already freed?)", ptr as usize),
+            ));
+            return false;
+        };
+        if size != 0 && allocation.size != size {
+            record_error(EngineError::new(
+                CreoVexError::InvalidFree,
+                format!(
+                    "free_buffer({:#x}, {size}): buffer from {} is {} bytes",
+                    ptr as usize,
+                    origin_tag(allocation.origin),
+                    allocation.size
+                ),
+            ));
+            return false;
+        }
+        live.remove(&(ptr as usize));
+        true
+    })
+}
+
+fn origin_tag(origin: Origin) -> String {
+    match origin {
+        Origin::Alloc => "alloc".to_string(),
+        Origin::Output(location) => format!("{}:{}", location.file(), location.line()),
+    }
+}
+
+/**
+ * Turn allocation tracking on or off
+ *
+ * Turn it on right after init_engine() to see every buffer: those
+ * handed out while tracking was off are freed normally but never
+ * counted. Turning tracking off forgets every recorded buffer.
+ *
+ * With tracking on, free_buffer and recycle_buffer report a pointer that
+ * is not live (double free) or a non-zero size that differs from the
+ * buffer's as CreoVexError::InvalidFree through last_error_code() /
+ * last_error_message(), and do not free the memory.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn set_allocation_tracking(enabled: bool) {
+    TRACKING.with(|tracking| tracking.set(enabled));
+    if !enabled {
+        LIVE.with(|live| live.borrow_mut().clear());
+    }
+}
+
+/**
+ * Whether allocation tracking is on
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn allocation_tracking() -> bool {
+    tracking_enabled()
+}
+
+/**
+ * Number of tracked buffers not yet freed (0 while tracking is off)
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn live_allocation_count() -> usize {
+    LIVE.with(|live| live.borrow().len())
+}
+
+/**
+ * Total size in bytes of the tracked buffers not yet freed
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn live_allocation_bytes() -> usize {
+    LIVE.with(|live| live.borrow().values().map(|allocation| allocation.size).sum())
+}
+
+/**
+ * Describe every tracked buffer not yet freed
+ *
+ * One line per buffer, in address order: "<ptr in hex> <size in bytes>
+ * <origin>", where the origin is "alloc" or the source file and line
+ * that produced the result (e.g. "src/smooth.rs:55"). Empty while
+ * tracking is off or nothing is outstanding.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn dump_allocations() -> String {
+    LIVE.with(|live| {
+        let mut dump = String::new();
+        for (&ptr, allocation) in live.borrow().iter() {
+            let _ = writeln!(dump, "{ptr:#x} {} {}", allocation.size, origin_tag(allocation.origin));
+        }
+        dump
+    })
+}
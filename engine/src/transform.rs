@@ -0,0 +1,643 @@
+/*!
+ * Affine stroke transforms
+ *
+ * Move/scale/rotate for selections, plus the reflections and rotations
+ * behind the symmetry tools. The in-place and _into variants write to the
+ * caller's buffer so interactive dragging allocates nothing per frame.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Editing)
+ */
+
+use alloc::format;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{require_finite, CreoVexError, EngineError, EngineResult};
+#[cfg(feature = "std")]
+use crate::error::{run_export, run_status};
+use crate::float::{math, Float};
+#[cfg(feature = "std")]
+use crate::smooth::{chaikin_uniform, DEFAULT_ITERATIONS};
+#[cfg(feature = "std")]
+use crate::validate::{float_validation, validate_floats, with_float_validation, FloatValidation};
+#[cfg(feature = "std")]
+use crate::{
+    batch_stroke_values, read_batch, read_points, require_capacity, run_batch, serialize_points, serialize_points_f64,
+    serialize_strokes, EngineBuffer,
+};
+
+/**
+ * Most copies radial_symmetry generates
+ *
+ * Beyond this the copies are closer than a degree apart and the per-frame
+ * output stops being cheap.
+ */
+pub(crate) const RADIAL_MAX_COPIES: u32 = 64;
+
+/**
+ * 2x3 affine matrix mapping (x, y) to (m00 x + m01 y + m02, m10 x + m11 y + m12)
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Affine<F = f32> {
+    pub(crate) m: [F; 6],
+}
+
+impl<F: Float> Affine<F> {
+    pub(crate) fn from_ffi(m00: F, m01: F, m02: F, m10: F, m11: F, m12: F) -> EngineResult<Self> {
+        Ok(Affine {
+            m: [
+                require_finite("m00", m00)?,
+                require_finite("m01", m01)?,
+                require_finite("m02", m02)?,
+                require_finite("m10", m10)?,
+                require_finite("m11", m11)?,
+                require_finite("m12", m12)?,
+            ],
+        })
+    }
+
+    /**
+     * Average linear scale sqrt(|det|): the uniform scale with the same area change
+     */
+    pub(crate) fn mean_scale(&self) -> F {
+        let [m00, m01, _, m10, m11, _] = self.m;
+        (m00 * m11 - m01 * m10).abs().sqrt()
+    }
+
+    /**
+     * Transform [x, y, p, ...] points in place, multiplying pressure by
+     * `pressure_scale`
+     */
+    pub(crate) fn apply(&self, points: &mut [F], stride: usize, pressure_scale: F) {
+        F::affine(points, stride, self.m, pressure_scale);
+    }
+
+    fn pressure_scale(&self, scale_pressure: bool) -> F {
+        if scale_pressure {
+            self.mean_scale()
+        } else {
+            F::ONE
+        }
+    }
+}
+
+impl Affine {
+    /**
+     * Reflection across the line through (ax, ay) and (bx, by)
+     */
+    pub(crate) fn mirror(ax: f32, ay: f32, bx: f32, by: f32) -> EngineResult<Self> {
+        let [ax, ay, bx, by] = [
+            require_finite("ax", ax)? as f64,
+            require_finite("ay", ay)? as f64,
+            require_finite("bx", bx)? as f64,
+            require_finite("by", by)? as f64,
+        ];
+        let (dx, dy) = (bx - ax, by - ay);
+        let length_sq = dx * dx + dy * dy;
+        if length_sq == 0.0 {
+            return Err(EngineError::new(
+                CreoVexError::InvalidParameter,
+                "mirror axis needs two distinct points",
+            ));
+        }
+
+        // Householder-style reflection 2 u u^T - I, then fix the axis point
+        let r00 = (dx * dx - dy * dy) / length_sq;
+        let r01 = 2.0 * dx * dy / length_sq;
+        let r11 = -r00;
+        let tx = ax - (r00 * ax + r01 * ay);
+        let ty = ay - (r01 * ax + r11 * ay);
+        Ok(Affine { m: [r00 as f32, r01 as f32, tx as f32, r01 as f32, r11 as f32, ty as f32] })
+    }
+
+    /**
+     * Rotation by `angle` radians (x toward y) about (cx, cy)
+     */
+    pub(crate) fn rotation(cx: f32, cy: f32, angle: f64) -> Self {
+        let (sin, cos) = math::sincos(angle);
+        let (cx, cy) = (cx as f64, cy as f64);
+        let tx = cx - (cos * cx - sin * cy);
+        let ty = cy - (sin * cx + cos * cy);
+        Affine { m: [cos as f32, -sin as f32, tx as f32, sin as f32, cos as f32, ty as f32] }
+    }
+
+    /**
+     * The transform applying `inner` first, then `self`
+     */
+    pub(crate) fn after(&self, inner: &Affine) -> Self {
+        let [a00, a01, a02, a10, a11, a12] = self.m;
+        let [b00, b01, b02, b10, b11, b12] = inner.m;
+        Affine {
+            m: [
+                a00 * b00 + a01 * b10,
+                a00 * b01 + a01 * b11,
+                a00 * b02 + a01 * b12 + a02,
+                a10 * b00 + a11 * b10,
+                a10 * b01 + a11 * b11,
+                a10 * b02 + a11 * b12 + a12,
+            ],
+        }
+    }
+}
+
+/**
+ * Scalar Affine::apply with matrix `m` (the reference for the SIMD kernel)
+ */
+pub(crate) fn affine_scalar<F: Float>(points: &mut [F], stride: usize, m: [F; 6], pressure_scale: F) {
+    let [m00, m01, m02, m10, m11, m12] = m;
+    for point in points.chunks_exact_mut(stride) {
+        let (x, y) = (point[0], point[1]);
+        point[0] = m00 * x + m01 * y + m02;
+        point[1] = m10 * x + m11 * y + m12;
+        point[2] *= pressure_scale;
+    }
+}
+
+/**
+ * Apply an affine transform to a stroke
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, transformed
+ *
+ * x/y are mapped through the 2x3 matrix [[m00, m01, m02], [m10, m11, m12]].
+ * Pressure is left alone unless `scale_pressure` is set, in which case it
+ * is multiplied by the matrix's mean scale sqrt(|m00 m11 - m01 m10|) so
+ * the pressure-scaled width keeps the stroke's visual weight.
+ *
+ * Errors: the read_points errors, plus NonFiniteValue for a NaN or
+ * infinite matrix entry.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn transform_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    m00: f32,
+    m01: f32,
+    m02: f32,
+    m10: f32,
+    m11: f32,
+    m12: f32,
+    scale_pressure: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        serialize_points(&transform_points(points, [m00, m01, m02, m10, m11, m12], scale_pressure)?)
+    })
+}
+
+/**
+ * transform_stroke on validated points (shared with Stroke::transform)
+ */
+pub(crate) fn transform_points(points: &[f32], m: [f32; 6], scale_pressure: bool) -> EngineResult<Vec<f32>> {
+    let [m00, m01, m02, m10, m11, m12] = m;
+    let affine = Affine::from_ffi(m00, m01, m02, m10, m11, m12)?;
+
+    let mut transformed = points.to_vec();
+    affine.apply(&mut transformed, 3, affine.pressure_scale(scale_pressure));
+    Ok(transformed)
+}
+
+/**
+ * Apply an affine transform to a stroke in f64 precision
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, ...] as f64 values
+ * Output format: see serialize_points_f64
+ *
+ * transform_stroke with an f64 matrix and f64 arithmetic, so moving a
+ * selection millions of units from the origin keeps sub-unit offsets.
+ *
+ * Errors: as transform_stroke.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn transform_stroke_f64(
+    points_ptr: *const f64,
+    points_len: usize,
+    m00: f64,
+    m01: f64,
+    m02: f64,
+    m10: f64,
+    m11: f64,
+    m12: f64,
+    scale_pressure: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let affine = Affine::from_ffi(m00, m01, m02, m10, m11, m12)?;
+
+        let mut transformed = points.to_vec();
+        affine.apply(&mut transformed, 3, affine.pressure_scale(scale_pressure));
+        serialize_points_f64(&transformed)
+    })
+}
+
+/**
+ * Apply an affine transform to a stroke buffer in place
+ *
+ * Same transform as transform_stroke, written back into `points_ptr`
+ * (e.g. a buffer from alloc reused across drag frames).
+ *
+ * Returns the number of points transformed, or a negative CreoVexError
+ * discriminant on failure (the buffer is untouched then). Under
+ * FloatValidation::Repair the repaired values are written back too.
+ *
+ * Errors: as transform_stroke.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn transform_stroke_in_place(
+    points_ptr: *mut f32,
+    points_len: usize,
+    m00: f32,
+    m01: f32,
+    m02: f32,
+    m10: f32,
+    m11: f32,
+    m12: f32,
+    scale_pressure: bool,
+) -> i32 {
+    run_status(|| {
+        let repaired = match read_points(points_ptr, points_len)? {
+            Cow::Owned(values) => Some(values),
+            Cow::Borrowed(_) => None,
+        };
+        let affine = Affine::from_ffi(m00, m01, m02, m10, m11, m12)?;
+
+        let points = unsafe { core::slice::from_raw_parts_mut(points_ptr, points_len) };
+        if let Some(values) = repaired {
+            points.copy_from_slice(&values);
+        }
+        affine.apply(points, 3, affine.pressure_scale(scale_pressure));
+        Ok(points_len / 3)
+    })
+}
+
+/**
+ * Apply one affine transform to every stroke of a batch
+ *
+ * Input format: the batch container (see read_batch)
+ * Output format: the batch result layout (transformed strokes plus
+ * per-stroke status)
+ *
+ * `validation` overrides the FloatValidation mode for this call; None
+ * (undefined from JS) keeps the one set with set_float_validation.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container and
+ * NonFiniteValue for a bad matrix; invalid strokes fail individually.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn transform_strokes_batch(
+    batch_ptr: *const u8,
+    batch_len: usize,
+    m00: f32,
+    m01: f32,
+    m02: f32,
+    m10: f32,
+    m11: f32,
+    m12: f32,
+    scale_pressure: bool,
+    validation: Option<FloatValidation>,
+) -> EngineBuffer {
+    with_float_validation(validation, || {
+        let affine = match Affine::from_ffi(m00, m01, m02, m10, m11, m12) {
+            Ok(affine) => affine,
+            Err(error) => return run_export(|| Err(error)),
+        };
+        let pressure_scale = affine.pressure_scale(scale_pressure);
+
+        run_batch(batch_ptr, batch_len, 3, |points| {
+            let mut transformed = points.to_vec();
+            affine.apply(&mut transformed, 3, pressure_scale);
+            Ok(transformed)
+        })
+    })
+}
+
+/**
+ * Apply one affine transform to every stroke of a batch, in place
+ *
+ * The container at `batch_ptr` keeps its layout; only the point values
+ * are rewritten.
+ *
+ * Returns the number of strokes in the batch, or a negative CreoVexError
+ * discriminant on failure (the buffer is untouched then). Under
+ * FloatValidation::Repair the repaired values are written back too.
+ *
+ * Errors: NullInput/InvalidLength for a malformed container, plus
+ * NonFiniteValue for a NaN or infinite matrix entry.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn transform_strokes_batch_in_place(
+    batch_ptr: *mut u8,
+    batch_len: usize,
+    m00: f32,
+    m01: f32,
+    m02: f32,
+    m10: f32,
+    m11: f32,
+    m12: f32,
+    scale_pressure: bool,
+) -> i32 {
+    run_status(|| {
+        let strokes = read_batch(batch_ptr, batch_len)?;
+        let stroke_count = strokes.len();
+        let mut repaired = Vec::new();
+        if float_validation() != FloatValidation::Passthrough {
+            let mut offset = 4 + stroke_count * 4;
+            for stroke in &strokes {
+                if let Cow::Owned(values) = validate_floats(&batch_stroke_values(stroke), 3)? {
+                    repaired.push((offset, values));
+                }
+                offset += stroke.len();
+            }
+        }
+        let affine = Affine::from_ffi(m00, m01, m02, m10, m11, m12)?;
+        let pressure_scale = affine.pressure_scale(scale_pressure);
+
+        // Point data runs from the end of the header to the end of the buffer
+        let bytes = unsafe { core::slice::from_raw_parts_mut(batch_ptr, batch_len) };
+        for (offset, values) in repaired {
+            for (field, value) in bytes[offset..].chunks_exact_mut(4).zip(values) {
+                field.copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        for point in bytes[4 + stroke_count * 4..].chunks_exact_mut(12) {
+            let mut values: [f32; 3] = core::array::from_fn(|c| {
+                let field = &point[c * 4..c * 4 + 4];
+                f32::from_le_bytes([field[0], field[1], field[2], field[3]])
+            });
+            affine.apply(&mut values, 3, pressure_scale);
+            for (c, value) in values.iter().enumerate() {
+                point[c * 4..c * 4 + 4].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        Ok(stroke_count)
+    })
+}
+
+/**
+ * Mirror a stroke across an arbitrary axis
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ * Output format: same structure, reflected
+ *
+ * Every point is reflected across the infinite line through (ax, ay) and
+ * (bx, by); pressure is kept. Point order is preserved unless `reverse`
+ * is set, which emits the points last to first so the mirrored stroke is
+ * drawn in the visually matching direction.
+ *
+ * Errors: the read_points errors, NonFiniteValue for a NaN or infinite
+ * axis coordinate, and InvalidParameter when the two axis points coincide.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn mirror_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    ax: f32,
+    ay: f32,
+    bx: f32,
+    by: f32,
+    reverse: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let mirror = Affine::mirror(ax, ay, bx, by)?;
+
+        serialize_points(&mirrored(points, &mirror, reverse))
+    })
+}
+
+/**
+ * Smooth a stroke and mirror the result in one call (symmetry preview)
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format: the batch container (see read_batch) holding two
+ * strokes, without a status array:
+ * - stroke 0: the input smoothed exactly as by smooth_stroke_v2
+ * - stroke 1: stroke 0 mirrored as by mirror_stroke
+ *
+ * The mirror is taken from the smoothed stroke, so both strokes always
+ * have the same point count.
+ *
+ * Errors: as mirror_stroke.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn smooth_and_mirror_stroke(
+    points_ptr: *const f32,
+    points_len: usize,
+    ax: f32,
+    ay: f32,
+    bx: f32,
+    by: f32,
+    reverse: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let mirror = Affine::mirror(ax, ay, bx, by)?;
+
+        let smoothed = chaikin_uniform(points, 3, DEFAULT_ITERATIONS as usize, 0.25);
+        let reflected = mirrored(&smoothed, &mirror, reverse);
+        serialize_strokes(&[smoothed, reflected])
+    })
+}
+
+/**
+ * Reflected copy of [x, y, p] points, optionally in reverse order
+ */
+fn mirrored(points: &[f32], mirror: &Affine, reverse: bool) -> Vec<f32> {
+    let mut result: Vec<f32> = if reverse {
+        points.chunks_exact(3).rev().flatten().copied().collect()
+    } else {
+        points.to_vec()
+    };
+    mirror.apply(&mut result, 3, 1.0);
+    result
+}
+
+/**
+ * Rotated (and optionally mirrored) copies of a stroke about a center
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]
+ *
+ * Output format: the batch container (see read_batch) without a status
+ * array. Copy k (0 to `copies` - 1) is the stroke rotated by k * 360 /
+ * `copies` degrees about (cx, cy), so copy 0 is the stroke itself. With
+ * `mirror_each` every copy is followed by its kaleidoscope twin: the
+ * stroke mirrored across the vertical line through the center, then
+ * rotated the same way (2 * `copies` strokes in total).
+ *
+ * Pressure passes through unchanged and point order is preserved.
+ *
+ * Errors: the read_points errors, NonFiniteValue for a NaN or infinite
+ * center, and InvalidParameter when `copies` is 0 or above
+ * RADIAL_MAX_COPIES.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn radial_symmetry(
+    points_ptr: *const f32,
+    points_len: usize,
+    cx: f32,
+    cy: f32,
+    copies: u32,
+    mirror_each: bool,
+) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let transforms = radial_transforms(cx, cy, copies, mirror_each)?;
+
+        let strokes: Vec<Vec<f32>> = transforms
+            .iter()
+            .map(|affine| {
+                let mut copy = points.to_vec();
+                affine.apply(&mut copy, 3, 1.0);
+                copy
+            })
+            .collect();
+        serialize_strokes(&strokes)
+    })
+}
+
+/**
+ * radial_symmetry into a caller-provided buffer
+ *
+ * Same output layout as radial_symmetry, written to `out_ptr` (typically
+ * a buffer from alloc reused on every pointermove) without building the
+ * copies in engine memory. Size it with radial_symmetry_output_size.
+ *
+ * Returns the number of bytes written, or a negative CreoVexError
+ * discriminant on failure. When the buffer is too small the result is
+ * -BufferTooSmall and, if `out_capacity_bytes` >= 4, the required byte
+ * count is stored as a u32 at `out_ptr`.
+ *
+ * Errors: as radial_symmetry, plus NullInput for a null `out_ptr`.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[allow(clippy::too_many_arguments)]
+pub fn radial_symmetry_into(
+    points_ptr: *const f32,
+    points_len: usize,
+    cx: f32,
+    cy: f32,
+    copies: u32,
+    mirror_each: bool,
+    out_ptr: *mut u8,
+    out_capacity_bytes: usize,
+) -> i32 {
+    run_status(|| {
+        let points = &read_points(points_ptr, points_len)?;
+        let transforms = radial_transforms(cx, cy, copies, mirror_each)?;
+        if out_ptr.is_null() {
+            return Err(EngineError::new(CreoVexError::NullInput, "out_ptr is null"));
+        }
+        let out = unsafe { core::slice::from_raw_parts_mut(out_ptr, out_capacity_bytes) };
+
+        let total_bytes = radial_symmetry_output_size(points_len, copies, mirror_each);
+        require_capacity(out, total_bytes)?;
+
+        let stroke_count = transforms.len();
+        let point_count = (points_len / 3) as u32;
+        out[..4].copy_from_slice(&(stroke_count as u32).to_le_bytes());
+        for count in out[4..4 + stroke_count * 4].chunks_exact_mut(4) {
+            count.copy_from_slice(&point_count.to_le_bytes());
+        }
+
+        let data = &mut out[4 + stroke_count * 4..total_bytes];
+        for (affine, copy) in transforms.iter().zip(data.chunks_exact_mut(points_len * 4)) {
+            for (point, bytes) in points.chunks_exact(3).zip(copy.chunks_exact_mut(12)) {
+                let mut values = [point[0], point[1], point[2]];
+                affine.apply(&mut values, 3, 1.0);
+                for (field, value) in bytes.chunks_exact_mut(4).zip(values) {
+                    field.copy_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        Ok(total_bytes)
+    })
+}
+
+/**
+ * Serialized size in bytes of radial_symmetry's output
+ *
+ * `points_len` counts f32 values (triplets) as for radial_symmetry.
+ * Exact for valid arguments; `copies` is not validated here. Sizes too
+ * large for usize saturate to usize::MAX, which no buffer can satisfy.
+ */
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn radial_symmetry_output_size(points_len: usize, copies: u32, mirror_each: bool) -> usize {
+    let strokes = (copies as usize).saturating_mul(if mirror_each { 2 } else { 1 });
+    (points_len / 3)
+        .checked_mul(12)
+        .and_then(|stroke_bytes| stroke_bytes.checked_add(4))
+        .and_then(|record_bytes| strokes.checked_mul(record_bytes))
+        .and_then(|bytes| bytes.checked_add(4))
+        .unwrap_or(usize::MAX)
+}
+
+/**
+ * One transform per output stroke of radial_symmetry, in output order
+ *
+ * sin/cos are evaluated once per copy here rather than per point.
+ */
+fn radial_transforms(cx: f32, cy: f32, copies: u32, mirror_each: bool) -> EngineResult<Vec<Affine>> {
+    let cx = require_finite("cx", cx)?;
+    let cy = require_finite("cy", cy)?;
+    if !(1..=RADIAL_MAX_COPIES).contains(&copies) {
+        return Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("copies must be 1-{RADIAL_MAX_COPIES} (got {copies})"),
+        ));
+    }
+
+    // Mirror across the vertical line x = cx
+    let mirror = Affine { m: [-1.0, 0.0, 2.0 * cx, 0.0, 1.0, 0.0] };
+    let step = core::f64::consts::TAU / copies as f64;
+    let mut transforms = Vec::with_capacity(copies as usize * 2);
+    for k in 0..copies {
+        let rotation = Affine::rotation(cx, cy, step * k as f64);
+        transforms.push(rotation);
+        if mirror_each {
+            transforms.push(rotation.after(&mirror));
+        }
+    }
+    Ok(transforms)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_support::take_records_f64;
+
+    #[test]
+    fn f64_transforms_keep_sub_unit_moves_ten_million_units_out() {
+        let far = [1.0e7 + 0.1, 1.0e7 - 0.3, 0.5, 1.0e7 + 2.7, 1.0e7 + 1.9, 1.0];
+        let moved = take_records_f64(transform_stroke_f64(far.as_ptr(), far.len(), 1.0, 0.0, 0.25, 0.0, 1.0, -0.125, false), 3);
+        let expected: Vec<f64> = far.chunks_exact(3).flat_map(|p| [p[0] + 0.25, p[1] - 0.125, p[2]]).collect();
+        assert_eq!(moved, expected);
+    }
+
+    #[test]
+    fn radial_output_size_saturates_instead_of_overflowing() {
+        assert_eq!(radial_symmetry_output_size(9, 4, true), 4 + 8 * (4 + 3 * 12));
+        assert_eq!(radial_symmetry_output_size(usize::MAX / 4, 64, true), usize::MAX);
+        assert_eq!(radial_symmetry_output_size(usize::MAX, u32::MAX, false), usize::MAX);
+    }
+}
@@ -0,0 +1,235 @@
+/*!
+ * Polygon triangulation for the fill tool
+ *
+ * A closed stroke becomes a filled shape by ear clipping its x/y ring.
+ * The result indexes the input points, so the fill reuses the stroke's
+ * own vertex data.
+ *
+ * Architecture: Engine Layer
+ * Authority: drawing_engine_architecture.md (Tessellation)
+ */
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{run_export, CreoVexError, EngineError, EngineResult};
+use crate::intersect::{self_intersections, INTERSECTION_EPSILON};
+use crate::{read_points, serialize_indices, EngineBuffer};
+
+/**
+ * Triangulate a closed stroke (simple polygon) by ear clipping
+ *
+ * Input format: [x0, y0, p0, x1, y1, p1, x2, y2, p2, ...]; the ring
+ * closes from the last point back to the first, and repeating the first
+ * point at the end is optional
+ *
+ * Output format:
+ * - 4 bytes: index count (u32, a multiple of 3)
+ * - N * 4 bytes: u32 point indices into the input, three per triangle
+ *
+ * Clockwise and counter-clockwise rings both work; every triangle is
+ * wound the same way as the ring. Repeated consecutive points are
+ * skipped, and a vertex on a straight run (collinear with its neighbors)
+ * is never the tip of a triangle, so no triangle has zero area.
+ *
+ * A ring that crosses or touches itself, including one that doubles back
+ * along an edge, is rejected instead of producing flipped or overlapping
+ * triangles.
+ *
+ * Errors: the read_points errors; TooFewPoints when the ring has fewer
+ * than 3 distinct points or they all lie on one line; InvalidParameter
+ * when it intersects itself.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn triangulate_polygon(points_ptr: *const f32, points_len: usize) -> EngineBuffer {
+    run_export(|| {
+        let points = &read_points(points_ptr, points_len)?;
+
+        let indices: Vec<u32> = ear_clip(points, 3)?.into_iter().map(|i| i as u32).collect();
+        serialize_indices(&indices)
+    })
+}
+
+/**
+ * Triangles of the simple polygon whose ring is `points` (`stride`
+ * floats per point, x/y read), as point indices into `points`
+ */
+pub(crate) fn ear_clip(points: &[f32], stride: usize) -> EngineResult<Vec<usize>> {
+    let position = |i: usize| &points[i * stride..i * stride + 2];
+
+    // Distinct ring vertices, by input point index
+    let mut ring: Vec<usize> = Vec::with_capacity(points.len() / stride);
+    for index in 0..points.len() / stride {
+        if ring.last().is_none_or(|&last| position(last) != position(index)) {
+            ring.push(index);
+        }
+    }
+    while ring.len() > 1 && position(ring[0]) == position(ring[ring.len() - 1]) {
+        ring.pop();
+    }
+    if ring.len() < 3 {
+        return Err(EngineError::new(
+            CreoVexError::TooFewPoints,
+            format!("polygon needs at least 3 distinct points (got {})", ring.len()),
+        ));
+    }
+
+    let xy: Vec<[f64; 2]> = ring.iter().map(|&i| [position(i)[0] as f64, position(i)[1] as f64]).collect();
+    // Checked first: a ring along one line would otherwise report overlapping edges
+    let along = sub(xy[1], xy[0]);
+    if xy.iter().all(|&vertex| cross(sub(vertex, xy[0]), along) == 0.0) {
+        return Err(EngineError::new(CreoVexError::TooFewPoints, "polygon points all lie on one line"));
+    }
+    require_simple(&xy)?;
+
+    let doubled_area: f64 = (1..xy.len() - 1).map(|i| cross(sub(xy[i], xy[0]), sub(xy[i + 1], xy[0]))).sum();
+    if doubled_area == 0.0 {
+        return Err(EngineError::new(CreoVexError::TooFewPoints, "polygon has no area"));
+    }
+
+    let triangles = EarClipper::new(xy, doubled_area.signum()).run();
+    Ok(triangles.into_iter().map(|corner| ring[corner]).collect())
+}
+
+/**
+ * Reject a ring that crosses or touches itself
+ */
+fn require_simple(xy: &[[f64; 2]]) -> EngineResult<()> {
+    let mut closed: Vec<f32> = Vec::with_capacity((xy.len() + 1) * 2);
+    for vertex in xy.iter().chain(xy.first()) {
+        closed.extend_from_slice(&[vertex[0] as f32, vertex[1] as f32]);
+    }
+
+    // The first and last edges share the ring's start vertex
+    let last = xy.len() - 1;
+    let epsilon_sq = INTERSECTION_EPSILON * INTERSECTION_EPSILON;
+    let crossing = self_intersections(&closed, 2).into_iter().find(|hit| {
+        let dx = hit.x - xy[0][0];
+        let dy = hit.y - xy[0][1];
+        !(hit.seg_a == 0 && hit.seg_b == last && dx * dx + dy * dy < epsilon_sq)
+    });
+    match crossing {
+        Some(hit) => Err(EngineError::new(
+            CreoVexError::InvalidParameter,
+            format!("polygon intersects itself at ({}, {})", hit.x, hit.y),
+        )),
+        None => Ok(()),
+    }
+}
+
+/**
+ * Doubly linked ring of the vertices not yet clipped
+ */
+struct EarClipper {
+    xy: Vec<[f64; 2]>,
+    /** +1 for a counter-clockwise ring (y up), -1 for clockwise */
+    orientation: f64,
+    prev: Vec<usize>,
+    next: Vec<usize>,
+    /** Reflex or collinear: could block an ear */
+    blocking: Vec<bool>,
+}
+
+impl EarClipper {
+    fn new(xy: Vec<[f64; 2]>, orientation: f64) -> Self {
+        let count = xy.len();
+        let mut clipper = EarClipper {
+            xy,
+            orientation,
+            prev: (0..count).map(|i| (i + count - 1) % count).collect(),
+            next: (0..count).map(|i| (i + 1) % count).collect(),
+            blocking: vec![false; count],
+        };
+        for i in 0..count {
+            clipper.blocking[i] = clipper.turn(i) <= 0.0;
+        }
+        clipper
+    }
+
+    /**
+     * Turn at `i` relative to the ring's orientation: positive convex,
+     * negative reflex, 0 collinear
+     */
+    fn turn(&self, i: usize) -> f64 {
+        let (a, b, c) = (self.xy[self.prev[i]], self.xy[i], self.xy[self.next[i]]);
+        cross(sub(b, a), sub(c, b)) * self.orientation
+    }
+
+    fn unlink(&mut self, i: usize) {
+        let (p, n) = (self.prev[i], self.next[i]);
+        self.next[p] = n;
+        self.prev[n] = p;
+        self.blocking[p] = self.turn(p) <= 0.0;
+        self.blocking[n] = self.turn(n) <= 0.0;
+    }
+
+    /**
+     * Whether clipping convex vertex `i` leaves no remaining vertex on or
+     * inside its triangle
+     */
+    fn is_ear(&self, i: usize) -> bool {
+        let (a, b, c) = (self.xy[self.prev[i]], self.xy[i], self.xy[self.next[i]]);
+        let inside = |p: [f64; 2]| {
+            cross(sub(b, a), sub(p, a)) * self.orientation >= 0.0
+                && cross(sub(c, b), sub(p, b)) * self.orientation >= 0.0
+                && cross(sub(a, c), sub(p, c)) * self.orientation >= 0.0
+        };
+
+        let mut w = self.next[self.next[i]];
+        while w != self.prev[i] {
+            if self.blocking[w] && inside(self.xy[w]) {
+                return false;
+            }
+            w = self.next[w];
+        }
+        true
+    }
+
+    /**
+     * Clip ears until one triangle is left; returns corner indices into
+     * `xy`, three per triangle
+     */
+    fn run(mut self) -> Vec<usize> {
+        let mut triangles = Vec::with_capacity((self.xy.len() - 2) * 3);
+        let mut remaining = self.xy.len();
+        let mut v = 0;
+        let mut stalled = 0;
+
+        while remaining > 3 {
+            let turn = self.turn(v);
+            let next = self.next[v];
+            if turn == 0.0 {
+                // Straight run: the vertex adds nothing to the area
+                self.unlink(v);
+            } else if turn > 0.0 && (self.is_ear(v) || stalled >= remaining) {
+                // A full lap without an ear only happens through rounding
+                // on near-degenerate input; clip the convex vertex anyway
+                triangles.extend_from_slice(&[self.prev[v], v, next]);
+                self.unlink(v);
+            } else {
+                v = next;
+                stalled += 1;
+                if stalled > 2 * remaining {
+                    break;
+                }
+                continue;
+            }
+            remaining -= 1;
+            stalled = 0;
+            v = next;
+        }
+
+        if remaining == 3 && self.turn(v) != 0.0 {
+            triangles.extend_from_slice(&[self.prev[v], v, self.next[v]]);
+        }
+        triangles
+    }
+}
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn cross(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
@@ -0,0 +1,474 @@
+/*!
+ * Float payload validation
+ *
+ * One NaN coordinate from a faulty input path spreads through smoothing
+ * and tessellation until the whole output is NaN and the canvas draws
+ * nothing. Every export reads its point buffers through read_points /
+ * read_strided_points, which apply the FloatValidation mode set here, so
+ * all of them treat bad input the same way.
+ *
+ * The mode is per thread (std builds): each Web Worker running the
+ * engine starts at Reject and needs its own set_float_validation call.
+ * The batch and `_js` exports take an explicit mode argument instead.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Error Handling)
+ */
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::{AtomicU8, Ordering};
+#[cfg(feature = "std")]
+use std::cell::Cell;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{CreoVexError, EngineError, EngineResult};
+use crate::float::Float;
+
+/**
+ * How exports treat NaN and infinite values in point buffers
+ *
+ * Subnormal values are finite and always pass. Under Passthrough every
+ * algorithm still terminates and reports no Internal error, but its
+ * output for the affected points is unspecified (NaN usually spreads).
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatValidation {
+    /** No check: values reach the algorithm as given */
+    Passthrough = 0,
+    /**
+     * Fail with NonFiniteValue; last_error_index() gives the f32 offset
+     * of the first bad value (the default)
+     */
+    Reject = 1,
+    /**
+     * Replace each bad value by interpolating the same channel of the
+     * nearest finite points before and after it (the nearest one at the
+     * ends); the caller's buffer is not modified unless the export works
+     * in place
+     */
+    Repair = 2,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static FLOAT_VALIDATION: Cell<FloatValidation> = const { Cell::new(FloatValidation::Reject) };
+}
+
+/** no_std builds have no engine threads: one mode for the whole engine */
+#[cfg(not(feature = "std"))]
+static FLOAT_VALIDATION: ModeCell = ModeCell(AtomicU8::new(FloatValidation::Reject as u8));
+
+/**
+ * The thread-local's Cell interface over an atomic (no_std builds)
+ */
+#[cfg(not(feature = "std"))]
+struct ModeCell(AtomicU8);
+
+#[cfg(not(feature = "std"))]
+impl ModeCell {
+    fn with<R>(&self, f: impl FnOnce(&ModeCell) -> R) -> R {
+        f(self)
+    }
+
+    fn set(&self, mode: FloatValidation) {
+        self.0.store(mode as u8, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> FloatValidation {
+        match self.0.load(Ordering::Relaxed) {
+            0 => FloatValidation::Passthrough,
+            2 => FloatValidation::Repair,
+            _ => FloatValidation::Reject,
+        }
+    }
+}
+
+/**
+ * Set how every export treats NaN/infinite input values from now on
+ *
+ * Applies to all point and batch buffers (and shape records) read on
+ * the calling thread only; the default is Reject. Other threads and Web
+ * Workers keep their own mode, so set it in every worker that calls the
+ * engine, or pass the `validation` argument of the batch and `_js`
+ * exports. Scalar parameters are checked by each export regardless of
+ * the mode.
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn set_float_validation(mode: FloatValidation) {
+    FLOAT_VALIDATION.with(|current| current.set(mode));
+}
+
+/**
+ * The mode set with set_float_validation
+ */
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn float_validation() -> FloatValidation {
+    FLOAT_VALIDATION.with(|current| current.get())
+}
+
+/**
+ * Run `body` under `mode`, restoring the thread's mode afterwards
+ *
+ * Backs the `validation` argument of the batch and `_js` exports; None
+ * runs `body` under the mode set with set_float_validation.
+ */
+pub(crate) fn with_float_validation<R>(mode: Option<FloatValidation>, body: impl FnOnce() -> R) -> R {
+    /** Puts the previous mode back, even when `body` unwinds */
+    struct Restore(FloatValidation);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            set_float_validation(self.0);
+        }
+    }
+
+    let Some(mode) = mode else {
+        return body();
+    };
+    let _restore = Restore(float_validation());
+    set_float_validation(mode);
+    body()
+}
+
+/**
+ * Apply the current FloatValidation mode to `stride`-wide points
+ *
+ * Borrows `values` unless Repair had something to replace. Errors:
+ * NonFiniteValue (with the index) under Reject, or under Repair when a
+ * channel has no finite value to interpolate from.
+ */
+pub(crate) fn validate_floats<F: Float>(values: &[F], stride: usize) -> EngineResult<Cow<'_, [F]>> {
+    let mode = float_validation();
+    if mode == FloatValidation::Passthrough {
+        return Ok(Cow::Borrowed(values));
+    }
+    let Some(first_bad) = values.iter().position(|value| !value.is_finite()) else {
+        return Ok(Cow::Borrowed(values));
+    };
+    let describe = |index: usize| format!("value {index} (point {}, channel {})", index / stride, index % stride);
+
+    if mode == FloatValidation::Reject {
+        return Err(EngineError::new(
+            CreoVexError::NonFiniteValue,
+            format!("{} is {}", describe(first_bad), values[first_bad]),
+        )
+        .at_index(first_bad));
+    }
+
+    let mut repaired = values.to_vec();
+    let count = values.len() / stride;
+    for channel in 0..stride {
+        let finite: Vec<usize> = (0..count).filter(|&i| values[i * stride + channel].is_finite()).collect();
+        if finite.len() == count {
+            continue;
+        }
+        if finite.is_empty() {
+            return Err(EngineError::new(
+                CreoVexError::NonFiniteValue,
+                format!("channel {channel} has no finite value to repair {} from", describe(channel)),
+            )
+            .at_index(channel));
+        }
+
+        let at = |i: usize| values[i * stride + channel];
+        for i in (0..count).filter(|&i| !at(i).is_finite()) {
+            let after = finite.partition_point(|&j| j < i);
+            repaired[i * stride + channel] = match (after.checked_sub(1).map(|k| finite[k]), finite.get(after)) {
+                (Some(before), Some(&next)) => {
+                    let t = F::from_usize(i - before) / F::from_usize(next - before);
+                    at(before) + (at(next) - at(before)) * t
+                }
+                (Some(before), None) => at(before),
+                (None, Some(&next)) => at(next),
+                (None, None) => unreachable!("channel has a finite value"),
+            };
+        }
+    }
+    Ok(Cow::Owned(repaired))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_support::{fails_with, pack_batch, sine_stroke, take_batch, take_points};
+    use crate::*;
+
+    /** Points with a bad value at every kind of position */
+    fn poisoned_strokes() -> Vec<Vec<f32>> {
+        let clean = sine_stroke(12, 3.0, 8.0, 4.0);
+        let mut strokes = Vec::new();
+        for (index, value) in [(0, f32::NAN), (13, f32::NAN), (17, f32::INFINITY), (34, f32::NEG_INFINITY), (5, f32::NAN)] {
+            let mut stroke = clean.clone();
+            stroke[index] = value;
+            strokes.push(stroke);
+        }
+        strokes.push(vec![f32::NAN; 12 * 3]);
+        strokes.push(vec![f32::NAN, 1.0, 0.5]);
+        // Finite, but every difference overflows f32
+        strokes.push((0..12).flat_map(|i| [if i % 2 == 0 { -3.0e38 } else { 3.0e38 }, i as f32 * 3.0e37, 0.5]).collect());
+        strokes
+    }
+
+    /** The export returned (no hang) without a caught panic; frees any result */
+    fn survives(name: &str, buffer: EngineBuffer) {
+        assert_ne!(buffer.error_code(), CreoVexError::Internal as u32, "{name}: {}", last_error_message());
+        if !buffer.ptr().is_null() {
+            free_buffer(buffer.ptr(), buffer.byte_len());
+        }
+    }
+
+    fn survives_status(name: &str, status: i32) {
+        assert_ne!(status, CreoVexError::Internal as i32, "{name}: {}", last_error_message());
+    }
+
+    /** For exports returning a plain value: no caught panic recorded */
+    fn survives_value<T>(name: &str, _value: T) {
+        assert_ne!(last_error_code(), CreoVexError::Internal as u32, "{name}: {}", last_error_message());
+    }
+
+    #[test]
+    fn reject_is_the_default() {
+        assert_eq!(float_validation(), FloatValidation::Reject);
+        let points = [0.0, 0.0, 1.0, f32::NAN, 1.0, 1.0, 2.0, 2.0, 1.0];
+        assert!(fails_with(smooth_stroke_v2(points.as_ptr(), points.len()), CreoVexError::NonFiniteValue));
+        assert_eq!(last_error_index(), 3);
+    }
+
+    #[test]
+    fn reject_reports_the_first_bad_index() {
+        set_float_validation(FloatValidation::Reject);
+        for (index, value) in [(4, f32::NAN), (0, f32::INFINITY), (8, f32::NEG_INFINITY)] {
+            let mut points = [0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 1.0];
+            points[index] = value;
+            points[7] = f32::NAN;
+            assert!(fails_with(smooth_stroke_v2(points.as_ptr(), points.len()), CreoVexError::NonFiniteValue));
+            assert_eq!(last_error_index(), index.min(7) as i32);
+        }
+    }
+
+    #[test]
+    fn subnormal_values_pass_every_mode() {
+        let tiny = f32::MIN_POSITIVE / 4.0;
+        assert!(tiny > 0.0 && !tiny.is_normal());
+        let points = [tiny, 0.0, tiny, 1.0, tiny, 1.0, 2.0, 2.0, -tiny];
+        for mode in [FloatValidation::Reject, FloatValidation::Repair, FloatValidation::Passthrough] {
+            set_float_validation(mode);
+            let reversed = take_points(reverse_stroke(points.as_ptr(), points.len()));
+            assert_eq!(reversed, [2.0, 2.0, -tiny, 1.0, tiny, 1.0, tiny, 0.0, tiny]);
+        }
+    }
+
+    #[test]
+    fn repair_interpolates_neighbours() {
+        set_float_validation(FloatValidation::Repair);
+        let points = [0.0, 0.0, 0.25, f32::NAN, 3.0, f32::INFINITY, 4.0, 6.0, 0.75, 6.0, f32::NEG_INFINITY, 0.8];
+        let repaired = validate_floats(&points, 3).unwrap();
+        assert_eq!(*repaired, [0.0, 0.0, 0.25, 2.0, 3.0, 0.5, 4.0, 6.0, 0.75, 6.0, 6.0, 0.8]);
+
+        let hopeless = [f32::NAN, 0.0, 1.0, f32::NAN, 1.0, 1.0];
+        assert!(validate_floats(&hopeless, 3).is_err());
+    }
+
+    #[test]
+    fn the_validation_argument_overrides_the_thread_mode_for_one_call() {
+        set_float_validation(FloatValidation::Reject);
+        let mut stroke = sine_stroke(6, 3.0, 8.0, 4.0);
+        stroke[4] = f32::NAN;
+        assert!(smooth_stroke_js(&stroke, None).is_empty());
+        assert_eq!(last_error_code(), CreoVexError::NonFiniteValue as u32);
+
+        let repaired = smooth_stroke_js(&stroke, Some(FloatValidation::Repair));
+        assert!(!repaired.is_empty() && repaired.iter().all(|value| value.is_finite()));
+        assert_eq!(float_validation(), FloatValidation::Reject);
+
+        let batch = pack_batch(&[stroke.clone()]);
+        let (_, statuses) = take_batch(smooth_strokes_batch(batch.as_ptr(), batch.len(), None), 3);
+        assert_eq!(statuses, [CreoVexError::NonFiniteValue as u32]);
+        for mode in [FloatValidation::Repair, FloatValidation::Passthrough] {
+            let (_, statuses) = take_batch(smooth_strokes_batch(batch.as_ptr(), batch.len(), Some(mode)), 3);
+            assert_eq!(statuses, [0]);
+        }
+
+        // And the other way round: Reject for one call under a Repair thread
+        set_float_validation(FloatValidation::Repair);
+        assert!(smooth_stroke_js(&stroke, Some(FloatValidation::Reject)).is_empty());
+        assert_eq!(float_validation(), FloatValidation::Repair);
+        set_float_validation(FloatValidation::Reject);
+    }
+
+    #[test]
+    fn text_inputs_with_overflowing_numbers_terminate() {
+        set_float_validation(FloatValidation::Passthrough);
+        for d in ["M0 0L1e39 0A1e39 1e39 0 1 1 -1e39 5Q1e308 0 0 0Z", "M0 0C1e38 1e38 -1e38 -1e38 3e38 0", "M1e999 0l1 1"] {
+            survives("svg_path_to_stroke", svg_path_to_stroke(d.as_ptr(), d.len(), 0.25));
+        }
+        let ink = "<ink><trace>0 0, 1e39 5, -1e39 2e39, 3 'nan</trace><trace>1e38 1e38 \"1e38 1e38 * *</trace></ink>";
+        survives("parse_inkml_traces", parse_inkml_traces(ink.as_ptr(), ink.len()));
+    }
+
+    #[test]
+    fn passthrough_terminates_without_panicking() {
+        set_float_validation(FloatValidation::Passthrough);
+        let clean = sine_stroke(12, 3.0, 8.0, 4.0);
+        let eraser = [10.0, 0.0, 1.0, 10.0, 10.0, 1.0];
+        let pattern = [4.0f32, 2.0];
+        let cuts = [5.0f32, 20.0];
+        let levels = [0.5f32, 2.0];
+        let anchors = [3u32, 7];
+        let mut raster = vec![0u8; 16 * 16 * 4];
+
+        for stroke in &poisoned_strokes() {
+            let (p, n) = (stroke.as_ptr(), stroke.len());
+            let (c, cn) = (clean.as_ptr(), clean.len());
+            survives_value("stroke_length", stroke_length(p, n));
+            survives("build_arclength_table", build_arclength_table(p, n));
+            survives("point_at_length", point_at_length(p, n, 10.0));
+            survives("trim_stroke", trim_stroke(p, n, 2.0, -3.0));
+            survives("fit_curve", fit_curve(p, n, 0.5));
+            survives("stroke_bounds", stroke_bounds(p, n, 4.0));
+            survives("convex_hull", convex_hull(p, n));
+            survives("detect_corners", detect_corners(p, n, 120.0));
+            survives("smooth_stroke_one_euro", smooth_stroke_one_euro(p, n, 1.0, 0.1));
+            survives("smooth_stroke_ema", smooth_stroke_ema(p, n, 0.5, true, true));
+            survives("stabilize_stroke_kalman", stabilize_stroke_kalman(p, n, 1.0, 1.0));
+            survives("constrain_to_line", constrain_to_line(p, n, 1.0, 0.0, true));
+            survives("stroke_geometry", stroke_geometry(p, n));
+            survives_value("hash_stroke", hash_stroke(p, n));
+            survives_value("strokes_equal", strokes_equal(p, n, c, cn, 0.1));
+            survives("find_self_intersections", find_self_intersections(p, n));
+            survives("intersect_strokes", intersect_strokes(p, n, c, cn));
+            survives("reverse_stroke", reverse_stroke(p, n));
+            survives("join_strokes", join_strokes(p, n, c, cn, f32::MAX, BridgeMode::Smooth));
+            survives("morph_strokes", morph_strokes(p, n, c, cn, 0.5, true));
+            survives("morph_sequence", morph_sequence(p, n, c, cn, 3, true));
+            survives("stroke_outline", stroke_outline(p, n, 4.0));
+            survives("stroke_outline_ex", stroke_outline_ex(p, n, 4.0, CapStyle::Round, CapStyle::Square));
+            survives("predict_points", predict_points(p, n, 16.0, 4));
+            survives("apply_pressure", apply_pressure(p, n, PressureCurve::Gamma, 2.0, std::ptr::null(), 0));
+            survives("apply_taper", apply_taper(p, n, 10.0, 10.0, TaperProfile::Linear));
+            survives("smooth_pressure", smooth_pressure(p, n, 3.0, PressureSmoothing::MovingAverage, true));
+            survives(
+                "normalize_pressure",
+                normalize_pressure(p, n, PressureNormalization::MinMax, false, 0.0, 1.0, std::ptr::null(), 0),
+            );
+            survives(
+                "synthesize_pressure_from_velocity",
+                synthesize_pressure_from_velocity(p, n, false, 0.2, 1.0, 0.5),
+            );
+            survives("quantize_stroke", quantize_stroke(p, n, 0.0, -10.0, 40.0, 10.0));
+            survives("compress_stroke", compress_stroke(p, n));
+            survives("nearest_point_on_stroke", nearest_point_on_stroke(p, n, 5.0, 5.0));
+            survives_value("hit_test_stroke", hit_test_stroke(p, n, 5.0, 5.0, 4.0));
+            survives_status("stroke_in_polygon", stroke_in_polygon(p, n, c, cn, LassoMode::Contained));
+            survives("stroke_sdf", stroke_sdf(p, n, 4.0, 0.0, -10.0, 16, 16, 2.0));
+            survives_status(
+                "rasterize_stroke",
+                rasterize_stroke(p, n, 4.0, 0.0, -10.0, 16, 16, 2.0, raster.as_mut_ptr(), raster.len()),
+            );
+            survives("resample_stroke", resample_stroke(p, n, 1.0));
+            survives("resample_to_count", resample_to_count(p, n, 20));
+            survives("roughen_stroke", roughen_stroke(p, n, 1.0, 0.5, 7));
+            survives("recognize_shape", recognize_shape(p, n, 0.2));
+            survives("simplify_stroke", simplify_stroke(p, n, 0.5, 120.0));
+            survives("simplify_stroke_vw", simplify_stroke_vw(p, n, 0.5, 120.0));
+            survives("simplify_between_anchors", simplify_between_anchors(p, n, anchors.as_ptr(), anchors.len(), 0.5));
+            for method in [SimplifyMethod::Rdp, SimplifyMethod::VisvalingamWhyatt] {
+                survives("simplify_stroke_to_count", simplify_stroke_to_count(p, n, 4, method));
+            }
+            survives("generate_lod", generate_lod(p, n, levels.as_ptr(), levels.len()));
+            survives("smooth_stroke_v2", smooth_stroke_v2(p, n));
+            survives("smooth_stroke_ex", smooth_stroke_ex(p, n, 3, 0.5, 120.0, 0.7, true));
+            survives("smooth_stroke_closed", smooth_stroke_closed(p, n, 2, 1.0));
+            survives("smooth_stroke_adaptive", smooth_stroke_adaptive(p, n, 10.0, 4, 1000));
+            survives("smooth_stroke_bspline", smooth_stroke_bspline(p, n, 4));
+            survives("interpolate_stroke_catmull_rom", interpolate_stroke_catmull_rom(p, n, 4, 0.5));
+            survives("split_stroke", split_stroke(p, n, cuts.as_ptr(), cuts.len(), true));
+            survives("dash_stroke", dash_stroke(p, n, pattern.as_ptr(), pattern.len(), 0.0));
+            survives("erase_stroke", erase_stroke(p, n, eraser.as_ptr(), eraser.len(), 2.0));
+            survives("compute_stamps", compute_stamps(p, n, 2.0, 0.1, 0.1, 3, true));
+            survives("compute_scatter", compute_scatter(p, n, 0.5, 2.0, 0.1, 3));
+            survives("stroke_to_svg_path", stroke_to_svg_path(p, n, 2, true));
+            survives("tessellate_stroke", tessellate_stroke(p, n, 4.0));
+            for join in [JoinStyle::Miter, JoinStyle::Round, JoinStyle::Bevel] {
+                survives(
+                    "tessellate_stroke_ex",
+                    tessellate_stroke_ex(p, n, 4.0, join, 4.0, 6, CapStyle::Round, CapStyle::Square),
+                );
+                survives(
+                    "tessellate_stroke_indexed",
+                    tessellate_stroke_indexed(p, n, 4.0, join, 4.0, 6, CapStyle::Round, CapStyle::Butt),
+                );
+            }
+            survives(
+                "tessellate_stroke_uv",
+                tessellate_stroke_uv(p, n, 4.0, JoinStyle::Round, 4.0, 6, CapStyle::Round, CapStyle::Round, true, 0.0),
+            );
+            survives("triangulate_polygon", triangulate_polygon(p, n));
+            survives("strokes_to_dirty_tiles", strokes_to_dirty_tiles(p, n, 4.0, 256.0, 0.0, 0.0));
+            survives("transform_stroke", transform_stroke(p, n, 2.0, 0.0, 1.0, 0.0, 2.0, 1.0, true));
+            survives("mirror_stroke", mirror_stroke(p, n, 0.0, 0.0, 1.0, 1.0, true));
+            survives("smooth_and_mirror_stroke", smooth_and_mirror_stroke(p, n, 0.0, 0.0, 1.0, 1.0, true));
+            survives("radial_symmetry", radial_symmetry(p, n, 0.0, 0.0, 6, true));
+
+            survives("reverse_stroke_n", reverse_stroke_n(p, n, 3));
+            survives("resample_to_count_n", resample_to_count_n(p, n, 20, 3));
+            survives("smooth_stroke_n", smooth_stroke_n(p, n, 3));
+            survives("split_stroke_at_params", split_stroke_at_params(p, n, cuts.as_ptr(), 1, false));
+            survives("nearest_points_on_stroke", nearest_points_on_stroke(p, n, c, 6));
+            survives("stroke_dirty_rect", stroke_dirty_rect(p, n, c, cn, 4.0));
+            survives(
+                "tessellate_stroke_feathered",
+                tessellate_stroke_feathered(p, n, 4.0, JoinStyle::Round, 4.0, 6, CapStyle::Round, CapStyle::Round, 1.0),
+            );
+            survives(
+                "tessellate_stroke_alpha",
+                tessellate_stroke_alpha(p, n, 4.0, PressureCurve::Gamma, 1.5, std::ptr::null(), 0, true),
+            );
+            survives_status("stroke_in_polygon (poisoned polygon)", stroke_in_polygon(c, cn, p, n, LassoMode::Contained));
+            survives("erase_stroke (poisoned eraser)", erase_stroke(c, cn, p, n, 2.0));
+            survives("intersect_strokes (poisoned b)", intersect_strokes(c, cn, p, n));
+            if n % 4 == 0 {
+                survives("smooth_stroke_timed", smooth_stroke_timed(p, n, 2, 1.0));
+            }
+            let mut copy = stroke.clone();
+            survives_status(
+                "transform_stroke_in_place",
+                transform_stroke_in_place(copy.as_mut_ptr(), n, 2.0, 0.0, 1.0, 0.0, 2.0, 1.0, true),
+            );
+            let wide: Vec<f64> = stroke.iter().map(|&value| value as f64).collect();
+            survives("smooth_stroke_f64", smooth_stroke_f64(wide.as_ptr(), wide.len()));
+            survives("simplify_stroke_f64", simplify_stroke_f64(wide.as_ptr(), wide.len(), 0.5, 120.0));
+            survives("stroke_bounds_f64", stroke_bounds_f64(wide.as_ptr(), wide.len(), 4.0));
+
+            let mut builder = StrokeBuilder::new(&StrokeBuilderOptions::new());
+            survives_status("StrokeBuilder::add_points", builder.add_points(p, n));
+            survives("StrokeBuilder::finish", builder.finish());
+            let mut tiles = DirtyTileTracker::new(4.0, 256.0, 0.0, 0.0);
+            survives("DirtyTileTracker::add_points", tiles.add_points(p, n));
+            let mut cache = StrokeCache::new(1 << 20);
+            let handle = cache.store(p, n);
+            survives("StrokeCache::simplify", cache.simplify(handle, 0.5, 0.0));
+
+            let batch = pack_batch(&[stroke.clone(), clean.clone()]);
+            let (b, bn) = (batch.as_ptr(), batch.len());
+            survives("smooth_strokes_batch", smooth_strokes_batch(b, bn, None));
+            survives("tessellate_strokes_batch", tessellate_strokes_batch(b, bn, 4.0, None));
+            survives("average_strokes", average_strokes(b, bn, true));
+            survives("hash_strokes_batch", hash_strokes_batch(b, bn, None));
+            survives("convex_hull_batch", convex_hull_batch(b, bn, None));
+            survives("cull_strokes", cull_strokes(b, bn, 0.0, 0.0, 10.0, 10.0, 1.0));
+            survives("hit_test_strokes_batch", hit_test_strokes_batch(b, bn, 5.0, 5.0, 4.0, None));
+            survives("strokes_in_polygon_batch", strokes_in_polygon_batch(b, bn, c, cn, LassoMode::Contained, None));
+            survives("strokes_to_svg_paths_batch", strokes_to_svg_paths_batch(b, bn, 2, true, None));
+            survives("quantize_strokes_batch", quantize_strokes_batch(b, bn, 0.0, -10.0, 40.0, 10.0, None));
+            survives("serialize_document", serialize_document(b, bn, std::ptr::null(), 0));
+            survives("polygon_boolean", polygon_boolean(b, bn, b, bn, BooleanOp::Union));
+        }
+    }
+}
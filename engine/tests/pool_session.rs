@@ -0,0 +1,114 @@
+/*!
+ * Allocation churn of a simulated drawing session
+ *
+ * Replays what the live stroke path does every frame for 60 seconds:
+ * copy the newest input into an alloc buffer, smooth and tessellate it,
+ * and hand every buffer back. Recycling them through the pool should
+ * allocate engine buffers an order of magnitude less often than freeing
+ * them. Scratch space inside the exports is not pooled, so the heap as a
+ * whole only saves those buffer allocations.
+ *
+ * Architecture: Engine Layer
+ * Authority: ffi_contract.md (Memory Ownership Rules)
+ */
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use creovox_engine::{
+    alloc, free_buffer, pool_stats, recycle_buffer, smooth_stroke_v2, tessellate_stroke, trim_pool,
+};
+
+/**
+ * The system allocator, counting allocations made on the current thread
+ */
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const FRAMES_PER_SECOND: usize = 60;
+const SESSION_SECONDS: usize = 60;
+/** Input points per frame (a 120 Hz stylus) */
+const POINTS_PER_FRAME: usize = 2;
+/** Frames per stroke, then a frame with the pen up */
+const STROKE_FRAMES: usize = 90;
+/** Points the live preview re-smooths every frame */
+const LIVE_TAIL_POINTS: usize = 48;
+
+/**
+ * Engine buffer and heap allocations made over the session
+ */
+#[derive(Debug)]
+struct Session {
+    buffers: usize,
+    heap: usize,
+}
+
+/**
+ * Run the session, handing buffers back with `release`
+ */
+fn run_session(release: fn(*mut u8, usize)) -> Session {
+    trim_pool();
+    let misses = pool_stats().misses;
+    let heap = ALLOCATIONS.with(Cell::get);
+
+    let mut stroke: Vec<f32> = Vec::with_capacity(STROKE_FRAMES * POINTS_PER_FRAME * 3);
+    for frame in 0..FRAMES_PER_SECOND * SESSION_SECONDS {
+        if frame % (STROKE_FRAMES + 1) == STROKE_FRAMES {
+            stroke.clear();
+            continue;
+        }
+        for _ in 0..POINTS_PER_FRAME {
+            let t = stroke.len() as f32 / 3.0;
+            stroke.extend_from_slice(&[t * 1.5, 40.0 * (t / 25.0).sin(), 0.4 + 0.3 * (t / 40.0).cos()]);
+        }
+        let tail = &stroke[stroke.len().saturating_sub(LIVE_TAIL_POINTS * 3)..];
+
+        // JS writes the tail into engine memory, as it would into wasm memory
+        let input_bytes = tail.len() * 4;
+        let input = alloc(input_bytes);
+        assert!(!input.is_null());
+        unsafe { std::ptr::copy_nonoverlapping(tail.as_ptr().cast::<u8>(), input, input_bytes) };
+
+        let smoothed = smooth_stroke_v2(input.cast::<f32>(), tail.len());
+        let mesh = tessellate_stroke(input.cast::<f32>(), tail.len(), 6.0);
+        release(input, input_bytes);
+        for result in [smoothed, mesh] {
+            assert_eq!(result.error_code(), 0);
+            release(result.ptr(), result.byte_len());
+        }
+    }
+
+    Session { buffers: pool_stats().misses - misses, heap: ALLOCATIONS.with(Cell::get) - heap }
+}
+
+#[test]
+fn recycling_cuts_buffer_allocations_tenfold() {
+    let freeing = run_session(free_buffer);
+    let recycling = run_session(recycle_buffer);
+
+    // Every frame with the pen down allocates three buffers when they are freed
+    let drawn = (0..FRAMES_PER_SECOND * SESSION_SECONDS).filter(|frame| frame % (STROKE_FRAMES + 1) != STROKE_FRAMES).count();
+    assert_eq!(freeing.buffers, 3 * drawn);
+    assert!(recycling.buffers * 10 <= freeing.buffers, "{recycling:?} recycling against {freeing:?} freeing");
+    // ... and the heap saves nearly all of them: the pool's own bookkeeping
+    // (a list per bucket) allocates a few dozen times at most
+    let saved = freeing.buffers - recycling.buffers;
+    assert!((freeing.heap - recycling.heap) * 100 >= saved * 99, "{recycling:?} recycling against {freeing:?} freeing");
+}